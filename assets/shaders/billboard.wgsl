@@ -0,0 +1,68 @@
+#import bevy_pbr::mesh_functions::{get_model_matrix, mesh_position_local_to_world}
+#import bevy_pbr::mesh_view_bindings::view
+#import voxel_fog::{VoxelFog, apply_fog, faded_out}
+
+@group(1) @binding(0) var<uniform> size: f32;
+@group(1) @binding(1) var circle_texture: texture_2d<f32>;
+@group(1) @binding(2) var circle_sampler: sampler;
+@group(1) @binding(3) var<uniform> fog: VoxelFog;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    // Every corner of a quad shares its voxel center
+    @location(0) center: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    // Direction the voxel is most open to, shared by the quad
+    @location(3) normal: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) world_position: vec3<f32>,
+    @location(3) normal: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_center = mesh_position_local_to_world(
+        get_model_matrix(vertex.instance_index),
+        vec4<f32>(vertex.center, 1.0),
+    );
+
+    let corner = vec2<f32>(vertex.uv.x - 0.5, 0.5 - vertex.uv.y) * size;
+#ifdef SPLATS
+    // Lie in the plane of the surface normal, with any in-plane axes
+    let normal = normalize(vertex.normal);
+    let helper = select(vec3<f32>(0.0, 1.0, 0.0), vec3<f32>(1.0, 0.0, 0.0), abs(normal.y) > 0.9);
+    let right = normalize(cross(helper, normal));
+    let up = cross(normal, right);
+#else
+    // Columns of the camera transform are its world space axes
+    let right = view.view[0].xyz;
+    let up = view.view[1].xyz;
+#endif
+    let world_position = world_center.xyz + right * corner.x + up * corner.y;
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(world_position, 1.0);
+    out.uv = vertex.uv;
+    out.color = vertex.color;
+    out.world_position = world_position;
+    out.normal = vertex.normal;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = length(in.world_position - view.world_position);
+    let mask = textureSample(circle_texture, circle_sampler, in.uv);
+    if mask.a < 0.1 || faded_out(fog, distance, in.clip_position.xy) {
+        discard;
+    }
+    // Sky lighting: voxels open to the top are brightest, ones only open below darkest
+    let light = mix(0.6, 1.0, normalize(in.normal).y * 0.5 + 0.5);
+    return apply_fog(fog, vec4<f32>(in.color.rgb * light, in.color.a), distance);
+}
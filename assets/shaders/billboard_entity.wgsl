@@ -0,0 +1,49 @@
+#import bevy_pbr::mesh_functions::get_model_matrix
+#import bevy_pbr::mesh_view_bindings::view
+#import voxel_fog::{VoxelFog, apply_fog, faded_out}
+
+@group(1) @binding(0) var<uniform> color: vec4<f32>;
+@group(1) @binding(1) var circle_texture: texture_2d<f32>;
+@group(1) @binding(2) var circle_sampler: sampler;
+@group(1) @binding(3) var<uniform> fog: VoxelFog;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    // Unit quad corner in [-0.5, 0.5]
+    @location(0) position: vec3<f32>,
+    @location(1) uv: vec2<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let model = get_model_matrix(vertex.instance_index);
+    let center = (model * vec4<f32>(0.0, 0.0, 0.0, 1.0)).xyz;
+    let scale = length(model[0].xyz);
+
+    // Columns of the camera transform are its world space axes
+    let camera_right = view.view[0].xyz;
+    let camera_up = view.view[1].xyz;
+    let world_position = center + (camera_right * vertex.position.x + camera_up * vertex.position.y) * scale;
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(world_position, 1.0);
+    out.uv = vertex.uv;
+    out.world_position = world_position;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = length(in.world_position - view.world_position);
+    let mask = textureSample(circle_texture, circle_sampler, in.uv);
+    if mask.a < 0.1 || faded_out(fog, distance, in.clip_position.xy) {
+        discard;
+    }
+    return apply_fog(fog, color, distance);
+}
@@ -0,0 +1,48 @@
+#import bevy_pbr::mesh_functions::{get_model_matrix, mesh_position_local_to_world}
+#import bevy_pbr::mesh_view_bindings::view
+
+@group(1) @binding(0) var<uniform> point_size: f32;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    // Every corner of a quad shares its voxel center
+    @location(0) center: vec3<f32>,
+#ifdef POINT_QUADS
+    @location(1) uv: vec2<f32>,
+#endif
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_center = mesh_position_local_to_world(
+        get_model_matrix(vertex.instance_index),
+        vec4<f32>(vertex.center, 1.0),
+    );
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * world_center;
+
+#ifdef POINT_QUADS
+    // Offset in clip space so the quad shrinks with distance like the voxel would,
+    // but never below two pixels so far voxels don't drop out
+    let projected = vec2<f32>(view.projection[0][0], view.projection[1][1]) * point_size;
+    let min_size = 4.0 / view.viewport.zw * out.clip_position.w;
+    let size = max(projected, min_size);
+    let corner = vec2<f32>(vertex.uv.x - 0.5, 0.5 - vertex.uv.y);
+    out.clip_position = vec4<f32>(out.clip_position.xy + corner * size, out.clip_position.zw);
+#endif
+
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
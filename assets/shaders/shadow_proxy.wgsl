@@ -0,0 +1,7 @@
+// Shadow proxies only belong in shadow maps, which are drawn by the prepass
+// shaders. In the main pass they leave nothing behind.
+@fragment
+fn fragment() -> @location(0) vec4<f32> {
+    discard;
+    return vec4<f32>(0.0);
+}
@@ -0,0 +1,16 @@
+#import bevy_pbr::forward_io::VertexOutput
+#import bevy_pbr::mesh_view_bindings::view
+
+@group(1) @binding(0) var<uniform> zenith: vec4<f32>;
+@group(1) @binding(1) var<uniform> horizon: vec4<f32>;
+@group(1) @binding(2) var<uniform> ground: vec4<f32>;
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    let direction = normalize(in.world_position.xyz - view.world_position);
+    // The square root keeps the horizon band narrow
+    if direction.y >= 0.0 {
+        return mix(horizon, zenith, sqrt(direction.y));
+    }
+    return mix(horizon, ground, sqrt(-direction.y));
+}
@@ -0,0 +1,46 @@
+#define_import_path voxel_fog
+
+struct VoxelFog {
+    color: vec4<f32>,
+    fog_start: f32,
+    fog_end: f32,
+    fade_start: f32,
+    fade_end: f32,
+};
+
+struct LodFade {
+    progress: f32,
+    outgoing: u32,
+};
+
+// Blends toward the fog color between fog_start and fog_end
+fn apply_fog(fog: VoxelFog, color: vec4<f32>, distance: f32) -> vec4<f32> {
+    let amount = clamp((distance - fog.fog_start) / max(fog.fog_end - fog.fog_start, 0.0001), 0.0, 1.0);
+    return vec4<f32>(mix(color.rgb, fog.color.rgb, amount), color.a);
+}
+
+// 4x4 ordered dither threshold for the pixel, between 0 and 1
+fn dither_threshold(frag_coord: vec2<f32>) -> f32 {
+    var bayer = array<f32, 16>(
+        0.0, 8.0, 2.0, 10.0,
+        12.0, 4.0, 14.0, 6.0,
+        3.0, 11.0, 1.0, 9.0,
+        15.0, 7.0, 13.0, 5.0,
+    );
+    let cell = vec2<u32>(frag_coord) % 4u;
+    return (bayer[cell.y * 4u + cell.x] + 0.5) / 16.0;
+}
+
+// Ordered dithering across the fade band, so chunks dissolve near the render
+// distance without needing alpha blending
+fn faded_out(fog: VoxelFog, distance: f32, frag_coord: vec2<f32>) -> bool {
+    let visibility = 1.0 - clamp((distance - fog.fade_start) / max(fog.fade_end - fog.fade_start, 0.0001), 0.0, 1.0);
+    return visibility < dither_threshold(frag_coord);
+}
+
+// The incoming mesh draws the pixels below progress and the outgoing one the rest,
+// so the two levels of detail never overlap or leave gaps
+fn lod_faded_out(fade: LodFade, frag_coord: vec2<f32>) -> bool {
+    let drawn = dither_threshold(frag_coord) < fade.progress;
+    return drawn == (fade.outgoing != 0u);
+}
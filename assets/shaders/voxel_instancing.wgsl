@@ -0,0 +1,37 @@
+#import bevy_pbr::mesh_view_bindings::view
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) normal: vec3<f32>,
+    @location(2) uv: vec2<f32>,
+
+    // xyz: world space voxel center, w: edge length
+    @location(8) i_pos_scale: vec4<f32>,
+    @location(9) i_color: u32,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+};
+
+const LIGHT_DIRECTION: vec3<f32> = vec3<f32>(0.36, 0.8, 0.48);
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_position = vertex.position * vertex.i_pos_scale.w + vertex.i_pos_scale.xyz;
+    let color = unpack4x8unorm(vertex.i_color);
+
+    // Cheap fixed-direction shading so cube faces stay distinguishable
+    let light = 0.6 + 0.4 * max(dot(vertex.normal, LIGHT_DIRECTION), 0.0);
+
+    var out: VertexOutput;
+    out.clip_position = view.view_proj * vec4<f32>(world_position, 1.0);
+    out.color = vec4<f32>(color.rgb * light, color.a);
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
@@ -0,0 +1,34 @@
+#import bevy_pbr::{
+    pbr_fragment::pbr_input_from_standard_material,
+    pbr_functions::{alpha_discard, apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT,
+    forward_io::{VertexOutput, FragmentOutput},
+    mesh_view_bindings::view,
+}
+#import voxel_fog::{VoxelFog, LodFade, apply_fog, faded_out, lod_faded_out}
+
+@group(1) @binding(100) var<uniform> fog: VoxelFog;
+@group(1) @binding(101) var<uniform> lod_fade: LodFade;
+
+// StandardMaterial lighting with voxel fog, the distance fade and the LOD cross-fade on top
+@fragment
+fn fragment(in: VertexOutput, @builtin(front_facing) is_front: bool) -> FragmentOutput {
+    let distance = length(in.world_position.xyz - view.world_position);
+    if faded_out(fog, distance, in.position.xy) || lod_faded_out(lod_fade, in.position.xy) {
+        discard;
+    }
+
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+    var out: FragmentOutput;
+    // Impostors are unlit, their snapshot already carries the lighting
+    if (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = apply_fog(fog, out.color, distance);
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+    return out;
+}
@@ -0,0 +1,17 @@
+// Chunk [2, 0, -1] captured with Ctrl+Q; decode with VoxelChunk::from_fixture
+pub const CHUNK_2_0_M1: &[u8] = &[
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0x02, 0x00, 0x00, 0x00,
+    0x02, 0x00, 0x00, 0x00, 0x00, 0x3f, 0x00, 0x00, 0x00, 0x3f, 0xb8, 0x1e, 0x05, 0x3f, 0x00, 0x00,
+    0x80, 0x3f, 0x00, 0x00, 0x9a, 0x99, 0x19, 0x3e, 0x33, 0x33, 0xb3, 0x3e, 0xcd, 0xcc, 0x4c, 0x3f,
+    0xcd, 0xcc, 0x0c, 0x3f, 0x00, 0x00, 0x14, 0x00, 0x00, 0x00, 0x44, 0x04, 0x00, 0x00, 0x03, 0x00,
+    0x02, 0x00, 0x0d, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x0d, 0x00, 0x00, 0x00, 0x03, 0x00,
+    0x02, 0x00, 0xdd, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x0d, 0x00, 0x00, 0x00, 0x03, 0x00,
+    0x02, 0x00, 0x01, 0x00, 0x03, 0x00, 0x0c, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0xdd, 0x00,
+    0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x0d, 0x00, 0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x0d, 0x00,
+    0x00, 0x00, 0x03, 0x00, 0x02, 0x00, 0x99, 0x09, 0x00, 0x00, 0x13, 0x00, 0x00, 0x00, 0x44, 0x04,
+    0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0d, 0x00,
+    0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0xdd, 0x00, 0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0d, 0x00,
+    0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0xdd, 0x00,
+    0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0d, 0x00, 0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x0d, 0x00,
+    0x0f, 0x00, 0x03, 0x00, 0x00, 0x00, 0x99, 0x09, 0x0f, 0x00,
+];
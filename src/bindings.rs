@@ -0,0 +1,362 @@
+// src/bindings.rs
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+
+pub struct KeyBindingsPlugin;
+
+impl Plugin for KeyBindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyBindings>();
+    }
+}
+
+// Everything the app reads from the keyboard or mouse buttons
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    StrafeLeft,
+    StrafeRight,
+    Ascend,
+    Descend,
+    Jump,
+    Sprint,
+    // Slows movement for precise positioning
+    Precision,
+    // Held while scrolling to change mouse sensitivity instead of speed
+    AdjustSensitivity,
+    SwitchCameraMode,
+    // Opens and closes the top-down map view
+    ToggleMap,
+    // Frames the chunk under the crosshair
+    FocusChunk,
+    ToggleCollision,
+    // Held to narrow the field of view
+    Zoom,
+    LockCursor,
+    ReleaseCursor,
+    OrbitRotate,
+    OrbitPan,
+    ToggleDiagnostics,
+    CycleRenderMode,
+    ToggleChunkBounds,
+    CycleDemoScene,
+    ToggleWireframe,
+    ToggleAtlas,
+    // Held with the culling toggles below, so plain number keys stay free
+    DebugModifier,
+    ToggleFrustumCulling,
+    ToggleDistanceCulling,
+    ToggleVoxelOcclusion,
+    ToggleChunkOcclusion,
+    // Freezes the view culling and LOD work from, so the camera can fly outside it
+    ToggleFrozenView,
+    // Held with a bookmark key to save the camera pose there instead of returning to it
+    SaveBookmarkModifier,
+    Bookmark1,
+    Bookmark2,
+    Bookmark3,
+    Bookmark4,
+    // Starts and stops recording a camera path
+    RecordPath,
+    PlayPath,
+    SavePath,
+    LoadPath,
+    Screenshot,
+    // Editing with the cursor locked
+    ApplyBrush,
+    BrushSmaller,
+    BrushLarger,
+    CycleBrushShape,
+    CycleBrushMode,
+    // Choose the first nine palette colors
+    Palette1,
+    Palette2,
+    Palette3,
+    Palette4,
+    Palette5,
+    Palette6,
+    Palette7,
+    Palette8,
+    Palette9,
+    // Takes the color under the crosshair
+    Eyedropper,
+    // Held with the undo, redo and clipboard keys
+    EditModifier,
+    Undo,
+    Redo,
+    // Enters and leaves box selection
+    ToggleSelection,
+    SelectCorner,
+    CancelSelection,
+    DeleteSelection,
+    FillSelection,
+    HollowSelection,
+    Copy,
+    Cut,
+    Paste,
+    SaveClipboard,
+    LoadClipboard,
+    // In paste mode
+    RotatePaste,
+    CommitPaste,
+    CancelPaste,
+    // Held for the second click of a line to draw a rectangle instead
+    RectangleModifier,
+    CancelLine,
+    // Saves the selection as a prefab
+    SavePrefab,
+    NextPrefab,
+    PreviousPrefab,
+    // Starts and stops stamping the active prefab
+    Stamp,
+    ToggleStampOverlap,
+    // With the edit modifier, exports the selection or targeted chunk as .vox
+    ExportVox,
+    // With the edit modifier, exports the selection or targeted chunk as .glb
+    ExportGltf,
+    // With the edit modifier, exports the selection or targeted chunk as .obj
+    ExportObj,
+    // With the edit modifier, exports the visible voxels as a .ply point cloud
+    ExportPly,
+    // With the edit modifier, exports the selection or targeted chunk as a .wvox model
+    ExportWvox,
+    // Held with quick-save and quick-load, which share F5 and F9
+    WorldSaveModifier,
+    QuickSave,
+    QuickLoad,
+    // Stops a running point cloud import
+    CancelImport,
+    // With the edit modifier, writes the targeted chunk as a Rust test fixture
+    CaptureFixture,
+    // Pins the targeted chunk in the chunk inspector, or unpins it
+    PinInspectedChunk,
+    // Opens or closes the debug console
+    ToggleConsole,
+}
+
+impl Action {
+    // Bookmark keys in slot order
+    pub const BOOKMARKS: [Action; 4] = [Action::Bookmark1, Action::Bookmark2, Action::Bookmark3, Action::Bookmark4];
+    // Palette keys in slot order
+    pub const PALETTE: [Action; 9] = [
+        Action::Palette1,
+        Action::Palette2,
+        Action::Palette3,
+        Action::Palette4,
+        Action::Palette5,
+        Action::Palette6,
+        Action::Palette7,
+        Action::Palette8,
+        Action::Palette9,
+    ];
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+}
+
+impl From<KeyCode> for Binding {
+    fn from(key: KeyCode) -> Self {
+        Binding::Key(key)
+    }
+}
+
+impl From<MouseButton> for Binding {
+    fn from(button: MouseButton) -> Self {
+        Binding::Mouse(button)
+    }
+}
+
+// Inputs bound to each action. An action can have several, and one with none
+// never fires.
+#[derive(Resource, Clone, Debug)]
+pub struct KeyBindings {
+    bindings: HashMap<Action, Vec<Binding>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        let mut bindings = Self {
+            bindings: HashMap::default(),
+        };
+        let defaults: [(Action, &[Binding]); 84] = [
+            (Action::MoveForward, &[Binding::Key(KeyCode::W)]),
+            (Action::MoveBack, &[Binding::Key(KeyCode::S)]),
+            (Action::StrafeLeft, &[Binding::Key(KeyCode::A)]),
+            (Action::StrafeRight, &[Binding::Key(KeyCode::D)]),
+            (Action::Ascend, &[Binding::Key(KeyCode::Space)]),
+            (Action::Descend, &[Binding::Key(KeyCode::ShiftLeft)]),
+            (Action::Jump, &[Binding::Key(KeyCode::Space)]),
+            (Action::Sprint, &[Binding::Key(KeyCode::ControlLeft)]),
+            (Action::Precision, &[Binding::Key(KeyCode::AltLeft), Binding::Key(KeyCode::AltRight)]),
+            (Action::AdjustSensitivity, &[Binding::Key(KeyCode::ControlLeft), Binding::Key(KeyCode::ControlRight)]),
+            (Action::SwitchCameraMode, &[Binding::Key(KeyCode::Tab)]),
+            (Action::ToggleMap, &[Binding::Key(KeyCode::M)]),
+            (Action::FocusChunk, &[Binding::Key(KeyCode::F)]),
+            (Action::ToggleCollision, &[Binding::Key(KeyCode::N)]),
+            // Without the edit modifier, which copies the selection on the same key
+            (Action::Zoom, &[Binding::Key(KeyCode::C)]),
+            (Action::LockCursor, &[Binding::Mouse(MouseButton::Left)]),
+            (Action::ReleaseCursor, &[Binding::Key(KeyCode::Escape)]),
+            (Action::OrbitRotate, &[Binding::Mouse(MouseButton::Left)]),
+            (Action::OrbitPan, &[Binding::Mouse(MouseButton::Middle)]),
+            (Action::CycleRenderMode, &[Binding::Key(KeyCode::F1)]),
+            (Action::ToggleDiagnostics, &[Binding::Key(KeyCode::F3)]),
+            (Action::ToggleChunkBounds, &[Binding::Key(KeyCode::F4)]),
+            (Action::CycleDemoScene, &[Binding::Key(KeyCode::F5)]),
+            (Action::ToggleWireframe, &[Binding::Key(KeyCode::F6)]),
+            (Action::ToggleAtlas, &[Binding::Key(KeyCode::F7)]),
+            (Action::DebugModifier, &[Binding::Key(KeyCode::ControlLeft), Binding::Key(KeyCode::ControlRight)]),
+            (Action::ToggleFrustumCulling, &[Binding::Key(KeyCode::Key1)]),
+            (Action::ToggleDistanceCulling, &[Binding::Key(KeyCode::Key2)]),
+            (Action::ToggleVoxelOcclusion, &[Binding::Key(KeyCode::Key3)]),
+            (Action::ToggleChunkOcclusion, &[Binding::Key(KeyCode::Key4)]),
+            (Action::ToggleFrozenView, &[Binding::Key(KeyCode::Key5)]),
+            // F1-F4 would be the natural bookmark keys, but they cycle render modes, save
+            // camera paths, toggle diagnostics and show chunk bounds, and F5-F8 and F12
+            // are taken too. Bookmarks sit on F9-F11 and Home instead, saved with Ctrl.
+            // F9 also quick-loads with Shift, so bookmarks ignore it while Shift is held.
+            (Action::SaveBookmarkModifier, &[Binding::Key(KeyCode::ControlLeft), Binding::Key(KeyCode::ControlRight)]),
+            (Action::Bookmark1, &[Binding::Key(KeyCode::F9)]),
+            (Action::Bookmark2, &[Binding::Key(KeyCode::F10)]),
+            (Action::Bookmark3, &[Binding::Key(KeyCode::F11)]),
+            (Action::Bookmark4, &[Binding::Key(KeyCode::Home)]),
+            (Action::RecordPath, &[Binding::Key(KeyCode::R)]),
+            (Action::PlayPath, &[Binding::Key(KeyCode::P)]),
+            (Action::SavePath, &[Binding::Key(KeyCode::F2)]),
+            (Action::LoadPath, &[Binding::Key(KeyCode::F8)]),
+            (Action::Screenshot, &[Binding::Key(KeyCode::F12)]),
+            (Action::ApplyBrush, &[Binding::Mouse(MouseButton::Left)]),
+            (Action::BrushSmaller, &[Binding::Key(KeyCode::BracketLeft)]),
+            (Action::BrushLarger, &[Binding::Key(KeyCode::BracketRight)]),
+            (Action::CycleBrushShape, &[Binding::Key(KeyCode::Backslash)]),
+            (Action::CycleBrushMode, &[Binding::Key(KeyCode::Apostrophe)]),
+            // Without the debug modifier, which turns 1-5 into culling toggles
+            (Action::Palette1, &[Binding::Key(KeyCode::Key1)]),
+            (Action::Palette2, &[Binding::Key(KeyCode::Key2)]),
+            (Action::Palette3, &[Binding::Key(KeyCode::Key3)]),
+            (Action::Palette4, &[Binding::Key(KeyCode::Key4)]),
+            (Action::Palette5, &[Binding::Key(KeyCode::Key5)]),
+            (Action::Palette6, &[Binding::Key(KeyCode::Key6)]),
+            (Action::Palette7, &[Binding::Key(KeyCode::Key7)]),
+            (Action::Palette8, &[Binding::Key(KeyCode::Key8)]),
+            (Action::Palette9, &[Binding::Key(KeyCode::Key9)]),
+            // Shares middle mouse with orbit panning, which only applies in orbit mode
+            (Action::Eyedropper, &[Binding::Mouse(MouseButton::Middle)]),
+            (Action::EditModifier, &[Binding::Key(KeyCode::ControlLeft), Binding::Key(KeyCode::ControlRight)]),
+            (Action::Undo, &[Binding::Key(KeyCode::Z)]),
+            (Action::Redo, &[Binding::Key(KeyCode::Y)]),
+            (Action::ToggleSelection, &[Binding::Key(KeyCode::B)]),
+            (Action::SelectCorner, &[Binding::Mouse(MouseButton::Left)]),
+            // Escape and F are shared with releasing the cursor and framing a chunk;
+            // the selection consumes them while it's in use
+            (Action::CancelSelection, &[Binding::Key(KeyCode::Escape)]),
+            (Action::DeleteSelection, &[Binding::Key(KeyCode::Delete)]),
+            (Action::FillSelection, &[Binding::Key(KeyCode::F)]),
+            (Action::HollowSelection, &[Binding::Key(KeyCode::H)]),
+            (Action::Copy, &[Binding::Key(KeyCode::C)]),
+            (Action::Cut, &[Binding::Key(KeyCode::X)]),
+            (Action::Paste, &[Binding::Key(KeyCode::V)]),
+            // Ctrl+S would also move backwards
+            (Action::SaveClipboard, &[Binding::Key(KeyCode::K)]),
+            (Action::LoadClipboard, &[Binding::Key(KeyCode::L)]),
+            // R and Escape are consumed in paste mode, so they don't also record a
+            // camera path or release the cursor
+            (Action::RotatePaste, &[Binding::Key(KeyCode::R)]),
+            (Action::CommitPaste, &[Binding::Mouse(MouseButton::Left)]),
+            (Action::CancelPaste, &[Binding::Key(KeyCode::Escape)]),
+            (Action::RectangleModifier, &[Binding::Key(KeyCode::AltLeft), Binding::Key(KeyCode::AltRight)]),
+            (Action::CancelLine, &[Binding::Key(KeyCode::Escape)]),
+            (Action::SavePrefab, &[Binding::Key(KeyCode::Insert)]),
+            (Action::NextPrefab, &[Binding::Key(KeyCode::Period)]),
+            (Action::PreviousPrefab, &[Binding::Key(KeyCode::Comma)]),
+            (Action::Stamp, &[Binding::Key(KeyCode::G)]),
+            (Action::ToggleStampOverlap, &[Binding::Key(KeyCode::O)]),
+            (Action::ExportVox, &[Binding::Key(KeyCode::E)]),
+            (Action::ExportGltf, &[Binding::Key(KeyCode::J)]),
+            (Action::ExportObj, &[Binding::Key(KeyCode::T)]),
+            (Action::ExportPly, &[Binding::Key(KeyCode::U)]),
+            (Action::ExportWvox, &[Binding::Key(KeyCode::I)]),
+            (Action::WorldSaveModifier, &[Binding::Key(KeyCode::ShiftLeft), Binding::Key(KeyCode::ShiftRight)]),
+            (Action::QuickSave, &[Binding::Key(KeyCode::F5)]),
+            (Action::QuickLoad, &[Binding::Key(KeyCode::F9)]),
+            (Action::CancelImport, &[Binding::Key(KeyCode::Escape)]),
+            (Action::CaptureFixture, &[Binding::Key(KeyCode::Q)]),
+            // Without the edit modifier, which exports .wvox on the same key
+            (Action::PinInspectedChunk, &[Binding::Key(KeyCode::I)]),
+            (Action::ToggleConsole, &[Binding::Key(KeyCode::Grave)]),
+        ];
+        for (action, inputs) in defaults {
+            bindings.bindings.insert(action, inputs.to_vec());
+        }
+        bindings
+    }
+}
+
+impl KeyBindings {
+    // Replaces whatever the action was bound to
+    pub fn set(&mut self, action: Action, binding: impl Into<Binding>) {
+        self.bindings.insert(action, vec![binding.into()]);
+    }
+
+    // Binds another input alongside the existing ones
+    pub fn add(&mut self, action: Action, binding: impl Into<Binding>) {
+        let binding = binding.into();
+        let inputs = self.bindings.entry(action).or_default();
+        if !inputs.contains(&binding) {
+            inputs.push(binding);
+        }
+    }
+
+    pub fn unbind(&mut self, action: Action) {
+        self.bindings.remove(&action);
+    }
+
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Action, &[Binding])> {
+        self.bindings.iter().map(|(action, inputs)| (*action, inputs.as_slice()))
+    }
+
+    // Whether the action was just pressed, clearing the press so systems later in
+    // the frame don't also act on the same input
+    pub fn consume(&self, action: Action, keyboard: &mut Input<KeyCode>, mouse: &mut Input<MouseButton>) -> bool {
+        let mut pressed = false;
+        for binding in self.get(action) {
+            pressed |= match binding {
+                Binding::Key(key) => keyboard.clear_just_pressed(*key),
+                Binding::Mouse(button) => mouse.clear_just_pressed(*button),
+            };
+        }
+        pressed
+    }
+}
+
+// Reads actions through the current bindings
+#[derive(SystemParam)]
+pub struct ActionInput<'w> {
+    bindings: Res<'w, KeyBindings>,
+    keyboard: Res<'w, Input<KeyCode>>,
+    mouse: Res<'w, Input<MouseButton>>,
+}
+
+impl ActionInput<'_> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| match binding {
+            Binding::Key(key) => self.keyboard.pressed(*key),
+            Binding::Mouse(button) => self.mouse.pressed(*button),
+        })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| match binding {
+            Binding::Key(key) => self.keyboard.just_pressed(*key),
+            Binding::Mouse(button) => self.mouse.just_pressed(*button),
+        })
+    }
+}
@@ -0,0 +1,87 @@
+// src/bytes.rs
+// Why a ByteReader couldn't read a value. Each format turns this into its own
+// error, so `?` works on reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteError {
+    EndsEarly,
+    NotUtf8,
+}
+
+impl ByteError {
+    pub fn reason(self) -> &'static str {
+        match self {
+            ByteError::EndsEarly => "file ends early",
+            ByteError::NotUtf8 => "string is not UTF-8",
+        }
+    }
+}
+
+// Reads little-endian values, failing on data that ends early. Shared by the
+// world save and .wvox models.
+pub struct ByteReader<'a> {
+    pub bytes: &'a [u8],
+}
+
+impl<'a> ByteReader<'a> {
+    pub fn take(&mut self, count: usize) -> Result<&'a [u8], ByteError> {
+        if self.bytes.len() < count {
+            return Err(ByteError::EndsEarly);
+        }
+        let (taken, rest) = self.bytes.split_at(count);
+        self.bytes = rest;
+        Ok(taken)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], ByteError> {
+        Ok(self.take(N)?.try_into().unwrap_or([0; N]))
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ByteError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, ByteError> {
+        self.array().map(u16::from_le_bytes)
+    }
+
+    pub fn u32(&mut self) -> Result<u32, ByteError> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    pub fn i32(&mut self) -> Result<i32, ByteError> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    pub fn f32(&mut self) -> Result<f32, ByteError> {
+        self.array().map(f32::from_le_bytes)
+    }
+
+    // A u32 length, then that many bytes of UTF-8
+    pub fn string(&mut self) -> Result<String, ByteError> {
+        let length = self.u32()? as usize;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| ByteError::NotUtf8)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_little_endian_values_until_the_data_ends() {
+        let mut reader = ByteReader {
+            bytes: &[1, 2, 0, 0xff, 0xff, 0xff, 0xff, 2, 0, 0, 0, b'h', b'i', 7],
+        };
+        assert_eq!(reader.u16(), Ok(2 << 8 | 1));
+        assert_eq!(reader.u8(), Ok(0));
+        assert_eq!(reader.i32(), Ok(-1));
+        assert_eq!(reader.string(), Ok("hi".to_string()));
+        assert_eq!(reader.u32(), Err(ByteError::EndsEarly));
+        // A failed read leaves the rest in place
+        assert_eq!(reader.bytes, [7]);
+
+        let mut reader = ByteReader { bytes: &[1, 0, 0, 0, 0xff] };
+        assert_eq!(reader.string(), Err(ByteError::NotUtf8));
+    }
+}
@@ -1,43 +1,314 @@
 // src/camera.rs
 use bevy::{
     prelude::*,
-    input::mouse::MouseMotion,
-    window::CursorGrabMode,
+    input::mouse::{MouseMotion, MouseScrollUnit, MouseWheel},
+    render::{camera::ScalingMode, primitives::Aabb},
+    window::{CursorGrabMode, WindowFocused},
 };
+use crate::bindings::{Action, ActionInput};
+use crate::voxel::{RayFilter, VoxelWorld, CHUNK_SIZE};
+use crate::voxel_types::VoxelRenderSettings;
+
+mod path;
+
+pub use path::{camera_path_controls, CameraPath, CameraPathPlugin, PathAutoplay, PathKeyframe, PathState};
+use path::camera_path_idle;
 
 pub struct CameraPlugin;
 
 impl Plugin for CameraPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<CameraState>()
+            .init_resource::<CameraFeedback>()
+            .init_resource::<CameraBookmarks>()
+            .init_resource::<CameraFlight>()
+            .add_plugins(CameraPathPlugin)
             .add_systems(Startup, setup_camera)
             .add_systems(Update, (
-                camera_controller,
-                toggle_cursor_lock,
+                update_camera_fov,
+                // Path playback drives the camera on its own
+                (camera_controller, camera_bookmarks, focus_targeted_chunk, advance_camera_flight)
+                    .chain()
+                    .run_if(camera_path_idle),
+                toggle_cursor_lock.before(camera_controller),
             ));
     }
 }
 
 #[derive(Resource, Default)]
-struct CameraState {
+pub struct CameraState {
     cursor_locked: bool,
+    // The window lost focus while locked, so the grab comes back with focus
+    relock_on_focus: bool,
+    // Frames of mouse motion still to drop, since the first after locking can carry
+    // a jump from the cursor warping to the center
+    ignore_motion_frames: u8,
+}
+
+impl CameraState {
+    pub fn cursor_locked(&self) -> bool {
+        self.cursor_locked
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CameraSetting {
+    Speed,
+    Sensitivity,
+    // Slot index, counted from 0
+    BookmarkSaved(usize),
+    BookmarkRecalled(usize),
+}
+
+// Last setting changed from the scroll wheel or bookmark keys and when, in elapsed
+// seconds, so the overlay can show it for a moment
+#[derive(Resource, Default)]
+pub struct CameraFeedback {
+    pub last_change: Option<(CameraSetting, f64)>,
+}
+
+// Where the camera is and which way it looks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CameraPose {
+    pub translation: Vec3,
+    pub yaw: f32,
+    pub pitch: f32,
+}
+
+impl CameraPose {
+    fn from_transform(transform: &Transform) -> Self {
+        let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        Self {
+            translation: transform.translation,
+            yaw,
+            pitch,
+        }
+    }
+
+    fn rotation(&self) -> Quat {
+        Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+    }
+}
+
+// Camera poses saved with Ctrl and a bookmark key, returned to with the key alone
+#[derive(Resource)]
+pub struct CameraBookmarks {
+    pub slots: [Option<CameraPose>; 4],
+    // Seconds to fly back to a bookmark; 0 teleports
+    pub fly_time: f32,
+}
+
+impl Default for CameraBookmarks {
+    fn default() -> Self {
+        Self {
+            slots: [None; 4],
+            fly_time: 0.3,
+        }
+    }
+}
+
+// A fly-to in progress for bookmarks and chunk focus
+#[derive(Resource, Default)]
+struct CameraFlight {
+    active: Option<Flight>,
+}
+
+struct Flight {
+    from_translation: Vec3,
+    from_rotation: Quat,
+    to: CameraPose,
+    elapsed: f32,
+    // Seconds the whole flight takes; 0 teleports
+    duration: f32,
+}
+
+impl CameraFlight {
+    fn start(&mut self, from: &Transform, to: CameraPose, duration: f32) {
+        self.active = Some(Flight {
+            from_translation: from.translation,
+            from_rotation: from.rotation,
+            to,
+            elapsed: 0.0,
+            duration,
+        });
+    }
+}
+
+// Keys that take the camera back from a flight
+const MOVEMENT_ACTIONS: [Action; 6] = [
+    Action::MoveForward,
+    Action::MoveBack,
+    Action::StrafeLeft,
+    Action::StrafeRight,
+    Action::Ascend,
+    Action::Descend,
+];
+// Seconds the focus command takes to frame a chunk
+const FOCUS_FLY_TIME: f32 = 0.5;
+
+// Frames of mouse motion dropped after the cursor locks
+const LOCK_SETTLE_FRAMES: u8 = 2;
+// Orbit distance never goes below this, so zooming in can't pass through the focus
+const MIN_ORBIT_DISTANCE: f32 = 0.5;
+// Fraction of the orbit distance each scroll line zooms by
+const ORBIT_ZOOM_STEP: f32 = 0.1;
+// Pixels per scroll line for touchpads, which report pixels
+const PIXELS_PER_LINE: f32 = 16.0;
+// Fly speed and mouse sensitivity scale by these per scroll line
+const SPEED_SCROLL_STEP: f32 = 1.15;
+const SENSITIVITY_SCROLL_STEP: f32 = 1.1;
+const MIN_SENSITIVITY: f32 = 0.0002;
+const MAX_SENSITIVITY: f32 = 0.02;
+// Range fov_degrees is kept in
+const MIN_FOV_DEGREES: f32 = 30.0;
+const MAX_FOV_DEGREES: f32 = 120.0;
+// Radius of the sphere the camera collides as, in voxels
+const COLLISION_RADIUS: f32 = 0.4;
+// Walking body in voxels: half its width, and how far it reaches above the eye
+const BODY_HALF_WIDTH: f32 = 0.3;
+const BODY_HEAD_ROOM: f32 = 0.1;
+// Map view: pan speed in screen pixels per second, the range its scale is kept
+// in, in world units per pixel, and how far below the camera it still draws
+const MAP_PAN_PIXELS_PER_SECOND: f32 = 600.0;
+const MIN_MAP_SCALE: f32 = 0.01;
+const MAX_MAP_SCALE: f32 = 16.0;
+const MAP_DEPTH: f32 = 1024.0;
+// Longest substep of a walking move, in voxels, so falls can't pass through floors
+const WALK_SUBSTEP: f32 = 0.25;
+// Voxels below the feet searched for ground before a fall counts as out of the world
+const FALL_OUT_DEPTH: i32 = 256;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CameraMode {
+    // Free flight with WASD, looking around with the mouse
+    #[default]
+    Fly,
+    // Circles a focus point: drag to rotate, scroll to zoom, middle mouse to pan
+    Orbit,
+    // On foot: gravity, WASD along the ground, Space to jump, one voxel step-ups
+    Walk,
+    // Orthographic top-down view toggled with M: WASD pans, scroll zooms
+    Map,
+}
+
+// What the map view replaced, put back exactly when it closes
+struct MapReturn {
+    transform: Transform,
+    mode: CameraMode,
+    projection: Projection,
 }
 
 #[derive(Component)]
 pub struct CameraController {
     pub speed: f32,
+    // Range the scroll wheel keeps speed in
+    pub min_speed: f32,
+    pub max_speed: f32,
+    // Sprint multiplies speed by this
+    pub sprint_multiplier: f32,
+    // Precision divides speed by this for careful moves
+    pub precision_divisor: f32,
+    // Speed the fly camera moved at this frame, with modifiers applied
+    pub effective_speed: f32,
+    // Double-tapping forward within double_tap_window seconds multiplies fly speed
+    // by dash_multiplier until forward is released. Off by default.
+    pub double_tap_dash: bool,
+    pub double_tap_window: f32,
+    pub dash_multiplier: f32,
+    // Elapsed seconds of the last forward press, and whether a dash is on
+    last_forward_press: Option<f64>,
+    dashing: bool,
+    // Scale fly speed by height above the ground: 1 at altitude_reference voxels,
+    // proportionally above and below within altitude_scale_range. Off by default.
+    pub altitude_speed_scaling: bool,
+    pub altitude_reference: f32,
+    pub altitude_scale_range: (f32, f32),
+    // Time constants in seconds for easing the fly velocity toward the input, while
+    // speeding up and while slowing down. 0 reacts instantly.
+    pub acceleration_time: f32,
+    pub damping_time: f32,
+    // Time constant for easing the fly camera's view toward yaw and pitch; 0 is instant
+    pub look_smoothing: f32,
+    velocity: Vec3,
+    // Yaw and pitch currently shown, trailing the targets above; None after a snap
+    smoothed_look: Option<Vec2>,
+    // Stop the fly camera at solid voxels instead of passing through; toggled with N
+    pub collision: bool,
     pub sensitivity: f32,
     pub pitch: f32,
     pub yaw: f32,
+    pub mode: CameraMode,
+    // Point the orbit camera circles and looks at
+    pub focus: Vec3,
+    // From the focus to the orbit camera
+    pub distance: f32,
+    // Walk mode, in voxels and seconds: eye above the feet, ground speed, upward
+    // speed of a jump and downward acceleration
+    pub eye_height: f32,
+    pub walk_speed: f32,
+    pub jump_impulse: f32,
+    pub gravity: f32,
+    grounded: bool,
+    // Eye position when last standing, where a fall out of the world respawns
+    last_ground: Option<Vec3>,
+    // Vertical field of view, clamped to 30-120 degrees
+    pub fov_degrees: f32,
+    // Field of view while the zoom key is held, and the time constant for easing
+    // toward it and back
+    pub zoom_fov_degrees: f32,
+    pub zoom_time: f32,
+    // Field of view on the projection right now, None until first applied
+    current_fov: Option<f32>,
+    // Map view height above the position it opened at, and its scale in world
+    // units per pixel
+    pub map_height: f32,
+    pub map_scale: f32,
+    map_return: Option<MapReturn>,
 }
 
 impl Default for CameraController {
     fn default() -> Self {
         Self {
             speed: 10.0,
+            min_speed: 1.0,
+            max_speed: 500.0,
+            sprint_multiplier: 4.0,
+            precision_divisor: 4.0,
+            effective_speed: 10.0,
+            double_tap_dash: false,
+            double_tap_window: 0.25,
+            dash_multiplier: 10.0,
+            last_forward_press: None,
+            dashing: false,
+            altitude_speed_scaling: false,
+            altitude_reference: 20.0,
+            altitude_scale_range: (0.25, 20.0),
+            acceleration_time: 0.08,
+            damping_time: 0.12,
+            look_smoothing: 0.02,
+            velocity: Vec3::ZERO,
+            smoothed_look: None,
+            collision: false,
             sensitivity: 0.002,
             pitch: 0.0,
             yaw: 0.0,
+            mode: CameraMode::Fly,
+            focus: Vec3::ZERO,
+            distance: 20.0,
+            eye_height: 1.7,
+            walk_speed: 5.0,
+            jump_impulse: 8.0,
+            gravity: 25.0,
+            grounded: false,
+            last_ground: None,
+            // Bevy's default perspective
+            fov_degrees: 45.0,
+            zoom_fov_degrees: 20.0,
+            zoom_time: 0.08,
+            current_fov: None,
+            map_height: 200.0,
+            map_scale: 0.25,
+            map_return: None,
         }
     }
 }
@@ -53,79 +324,786 @@ fn setup_camera(mut commands: Commands) {
     ));
 }
 
+#[allow(clippy::too_many_arguments)]
 fn camera_controller(
     time: Res<Time>,
-    camera_state: Res<CameraState>,
+    mut camera_state: ResMut<CameraState>,
+    mut feedback: ResMut<CameraFeedback>,
     mut mouse_motion: EventReader<MouseMotion>,
-    keyboard: Res<Input<KeyCode>>,
+    mut mouse_wheel: EventReader<MouseWheel>,
+    input: ActionInput,
+    settings: Res<VoxelRenderSettings>,
+    world: VoxelWorld,
+    mut query: Query<(&mut Transform, &mut CameraController, &mut Projection), With<Camera>>,
+) {
+    let mut motion: Vec2 = mouse_motion.read().map(|ev| ev.delta).sum();
+    if camera_state.ignore_motion_frames > 0 {
+        camera_state.ignore_motion_frames -= 1;
+        motion = Vec2::ZERO;
+    }
+    let scroll: f32 = mouse_wheel
+        .read()
+        .map(|ev| match ev.unit {
+            MouseScrollUnit::Line => ev.y,
+            MouseScrollUnit::Pixel => ev.y / PIXELS_PER_LINE,
+        })
+        .sum();
+
+    for (mut transform, mut controller, mut projection) in query.iter_mut() {
+        if input.just_pressed(Action::ToggleMap) {
+            toggle_map(&mut transform, &mut controller, &mut projection);
+        }
+        if input.just_pressed(Action::SwitchCameraMode) && controller.mode != CameraMode::Map {
+            switch_camera_mode(&transform, &mut controller);
+        }
+        if input.just_pressed(Action::ToggleCollision) {
+            controller.collision = !controller.collision;
+            info!("Camera collision: {}", if controller.collision { "collide" } else { "noclip" });
+        }
+
+        match controller.mode {
+            CameraMode::Fly => {
+                if scroll != 0.0 {
+                    let setting = adjust_with_scroll(&mut controller, scroll, &input);
+                    feedback.last_change = Some((setting, time.elapsed_seconds_f64()));
+                }
+                let speed_scale = altitude_speed_scale(&world, transform.translation, settings.voxel_size, &controller);
+                let delta =
+                    fly_camera(&time, &camera_state, motion, &input, speed_scale, &mut transform, &mut controller);
+                if delta == Vec3::ZERO {
+                    continue;
+                }
+                if controller.collision {
+                    let voxel_size = settings.voxel_size;
+                    let (center, blocked) =
+                        sweep_sphere(&world, transform.translation, delta, COLLISION_RADIUS * voxel_size, voxel_size);
+                    transform.translation = center;
+                    // Sliding keeps the unblocked part of the velocity
+                    controller.velocity = Vec3::select(blocked, Vec3::ZERO, controller.velocity);
+                } else {
+                    transform.translation += delta;
+                }
+            }
+            CameraMode::Orbit => orbit_camera(&camera_state, motion, scroll, &input, &mut transform, &mut controller),
+            CameraMode::Walk => walk_camera(
+                &time,
+                &camera_state,
+                motion,
+                &input,
+                &world,
+                settings.voxel_size,
+                &mut transform,
+                &mut controller,
+            ),
+            CameraMode::Map => map_camera(&time, scroll, &input, &mut transform, &mut controller, &mut projection),
+        }
+    }
+}
+
+// Saves the camera pose into a bookmark slot, or flies back to one
+fn camera_bookmarks(
+    time: Res<Time>,
+    input: ActionInput,
+    mut bookmarks: ResMut<CameraBookmarks>,
+    mut flight: ResMut<CameraFlight>,
+    mut feedback: ResMut<CameraFeedback>,
+    query: Query<(&Transform, &CameraController), With<Camera>>,
+) {
+    let Ok((transform, controller)) = query.get_single() else {
+        return;
+    };
+    // Bookmarks hold perspective poses
+    if controller.mode == CameraMode::Map {
+        return;
+    }
+    // Shift+F9 quick-loads the world instead
+    if input.pressed(Action::WorldSaveModifier) {
+        return;
+    }
+    let Some(slot) = Action::BOOKMARKS.iter().position(|action| input.just_pressed(*action)) else {
+        return;
+    };
+
+    let now = time.elapsed_seconds_f64();
+    if input.pressed(Action::SaveBookmarkModifier) {
+        bookmarks.slots[slot] = Some(CameraPose::from_transform(transform));
+        feedback.last_change = Some((CameraSetting::BookmarkSaved(slot), now));
+    } else if let Some(to) = bookmarks.slots[slot] {
+        flight.start(transform, to, bookmarks.fly_time);
+        feedback.last_change = Some((CameraSetting::BookmarkRecalled(slot), now));
+    }
+}
+
+// Frames the chunk under the crosshair: flies to where its whole box fits the view,
+// looking at its center, or in orbit mode moves the focus there
+fn focus_targeted_chunk(
+    input: ActionInput,
+    settings: Res<VoxelRenderSettings>,
+    world: VoxelWorld,
+    mut flight: ResMut<CameraFlight>,
+    mut query: Query<(&Transform, &mut CameraController, &Projection), With<Camera>>,
+) {
+    if !input.just_pressed(Action::FocusChunk) {
+        return;
+    }
+    let Ok((transform, mut controller, projection)) = query.get_single_mut() else {
+        return;
+    };
+    let Projection::Perspective(perspective) = projection else {
+        return;
+    };
+
+    // Further than the crosshair pick reaches, so distant chunks can be framed
+    let Some(hit) = world.raycast(transform.translation, transform.forward(), settings.render_distance, RayFilter::default()) else {
+        return;
+    };
+    let chunk = hit.chunk;
+    let extent = CHUNK_SIZE as f32 * settings.voxel_size;
+    let center = (chunk.as_vec3() + 0.5) * extent;
+
+    // Fit the chunk's bounding sphere inside the narrower of the two view angles
+    let radius = extent * 3f32.sqrt() / 2.0;
+    let half_vertical = perspective.fov / 2.0;
+    let half_horizontal = (half_vertical.tan() * perspective.aspect_ratio).atan();
+    let distance = radius / half_vertical.min(half_horizontal).sin();
+
+    info!("Focusing chunk {}", chunk);
+    if controller.mode == CameraMode::Orbit {
+        controller.focus = center;
+        controller.distance = distance;
+        return;
+    }
+    let direction = (center - transform.translation).normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return;
+    }
+    let framed = Transform::from_translation(center - direction * distance).looking_to(direction, Vec3::Y);
+    flight.start(transform, CameraPose::from_transform(&framed), FOCUS_FLY_TIME);
+}
+
+// Moves the camera along a flight started by a bookmark or focus. Runs after the
+// controller so the flight overrides whatever the current mode did this frame, and
+// any movement key hands control back where the camera is.
+fn advance_camera_flight(
+    time: Res<Time>,
+    input: ActionInput,
+    mut flight: ResMut<CameraFlight>,
     mut query: Query<(&mut Transform, &mut CameraController), With<Camera>>,
 ) {
-    for (mut transform, mut controller) in query.iter_mut() {
-        // Mouse look (only when cursor is locked)
-        if camera_state.cursor_locked {
-            for ev in mouse_motion.read() {
-                controller.pitch -= ev.delta.y * controller.sensitivity;
-                controller.yaw -= ev.delta.x * controller.sensitivity;
+    let Some(active) = flight.active.as_mut() else {
+        return;
+    };
+    let Ok((mut transform, mut controller)) = query.get_single_mut() else {
+        return;
+    };
+    if MOVEMENT_ACTIONS.iter().any(|action| input.pressed(*action)) {
+        flight.active = None;
+        resume_from_transform(&transform, &mut controller);
+        return;
+    }
+
+    active.elapsed += time.delta_seconds();
+    let t = if active.duration <= 0.0 { 1.0 } else { (active.elapsed / active.duration).min(1.0) };
+    // Smoothstep, so the flight starts and stops gently
+    let s = t * t * (3.0 - 2.0 * t);
+    transform.translation = active.from_translation.lerp(active.to.translation, s);
+    transform.rotation = active.from_rotation.slerp(active.to.rotation(), s);
+
+    // Hold the controller on the destination so it picks up from there
+    controller.yaw = active.to.yaw;
+    controller.pitch = active.to.pitch;
+    controller.smoothed_look = None;
+    controller.velocity = Vec3::ZERO;
+    controller.grounded = false;
+    if t >= 1.0 {
+        if controller.mode == CameraMode::Orbit {
+            controller.focus = transform.translation + transform.forward() * controller.distance;
+        }
+        flight.active = None;
+    }
+}
+
+// Hands the camera back where something else left it, with the controller's view
+// and orbit focus matching
+pub(super) fn resume_from_transform(transform: &Transform, controller: &mut CameraController) {
+    let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    controller.yaw = yaw;
+    controller.pitch = pitch;
+    controller.smoothed_look = None;
+    controller.velocity = Vec3::ZERO;
+    controller.grounded = false;
+    if controller.mode == CameraMode::Orbit {
+        controller.focus = transform.translation + transform.forward() * controller.distance;
+    }
+}
+
+// Eases the projection toward the zoom or normal field of view, writing it only
+// when it changes
+fn update_camera_fov(
+    time: Res<Time>,
+    input: ActionInput,
+    mut query: Query<(&mut Projection, &mut CameraController)>,
+) {
+    for (mut projection, mut controller) in query.iter_mut() {
+        // The map view is orthographic and the perspective comes back as it was
+        if controller.mode == CameraMode::Map {
+            continue;
+        }
+        // Ctrl+C copies the selection, so C only zooms without the edit modifier
+        let zooming = input.pressed(Action::Zoom) && !input.pressed(Action::EditModifier);
+        let target = if zooming {
+            controller.zoom_fov_degrees
+        } else {
+            controller.fov_degrees.clamp(MIN_FOV_DEGREES, MAX_FOV_DEGREES)
+        };
+        let fov = match controller.current_fov {
+            Some(current) => {
+                let eased = current + (target - current) * ease(controller.zoom_time, time.delta_seconds());
+                // Snap the tail of the ease so the projection stops changing
+                if (eased - target).abs() < 0.01 { target } else { eased }
             }
-            
-            // Clamp pitch to prevent camera flipping
-            controller.pitch = controller.pitch.clamp(-1.5, 1.5);
-            
-            // Apply rotation
-            let rotation = Quat::from_euler(EulerRot::YXZ, controller.yaw, controller.pitch, 0.0);
-            transform.rotation = rotation;
+            None => target,
+        };
+        if controller.current_fov == Some(fov) {
+            continue;
+        }
+        controller.current_fov = Some(fov);
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = fov.to_radians();
+        }
+    }
+}
+
+// Cycles Fly, Orbit and Walk. All modes share yaw and pitch, so switching keeps the
+// view direction. Orbit picks the focus straight ahead at the current orbit distance.
+fn switch_camera_mode(transform: &Transform, controller: &mut CameraController) {
+    let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+    controller.yaw = yaw;
+    controller.pitch = pitch;
+    controller.smoothed_look = None;
+    controller.velocity = Vec3::ZERO;
+    controller.mode = match controller.mode {
+        CameraMode::Fly => {
+            controller.focus = transform.translation + transform.forward() * controller.distance;
+            CameraMode::Orbit
+        }
+        CameraMode::Orbit => CameraMode::Walk,
+        CameraMode::Walk | CameraMode::Map => CameraMode::Fly,
+    };
+    controller.grounded = false;
+    info!("Camera mode: {:?}", controller.mode);
+}
+
+// Opens the map above the camera looking straight down with -Z up the screen, or
+// closes it and puts back the pose, mode and projection it replaced
+fn toggle_map(transform: &mut Transform, controller: &mut CameraController, projection: &mut Projection) {
+    if let Some(previous) = controller.map_return.take() {
+        *transform = previous.transform;
+        *projection = previous.projection;
+        controller.mode = previous.mode;
+        controller.smoothed_look = None;
+        controller.velocity = Vec3::ZERO;
+        controller.grounded = false;
+        info!("Camera mode: {:?}", controller.mode);
+        return;
+    }
+
+    controller.map_return = Some(MapReturn {
+        transform: *transform,
+        mode: controller.mode,
+        projection: projection.clone(),
+    });
+    controller.mode = CameraMode::Map;
+    controller.velocity = Vec3::ZERO;
+    let above = transform.translation + Vec3::Y * controller.map_height;
+    *transform = Transform::from_translation(above).looking_to(Vec3::NEG_Y, Vec3::NEG_Z);
+    *projection = Projection::Orthographic(OrthographicProjection {
+        near: 0.0,
+        far: controller.map_height + MAP_DEPTH,
+        scale: controller.map_scale,
+        scaling_mode: ScalingMode::WindowSize(1.0),
+        ..default()
+    });
+    info!("Camera mode: {:?}", controller.mode);
+}
+
+// Pans over the ground with WASD at a screen speed that holds at any zoom, and
+// scales the view with the scroll wheel
+fn map_camera(
+    time: &Time,
+    scroll: f32,
+    input: &ActionInput,
+    transform: &mut Transform,
+    controller: &mut CameraController,
+    projection: &mut Projection,
+) {
+    let mut direction = Vec3::ZERO;
+    if input.pressed(Action::MoveForward) {
+        direction += Vec3::NEG_Z;
+    }
+    if input.pressed(Action::MoveBack) {
+        direction += Vec3::Z;
+    }
+    if input.pressed(Action::StrafeLeft) {
+        direction += Vec3::NEG_X;
+    }
+    if input.pressed(Action::StrafeRight) {
+        direction += Vec3::X;
+    }
+    if direction != Vec3::ZERO {
+        let speed = modified_speed(MAP_PAN_PIXELS_PER_SECOND * controller.map_scale, input, controller);
+        transform.translation += direction.normalize() * speed * time.delta_seconds();
+    }
+
+    if scroll != 0.0 {
+        controller.map_scale =
+            (controller.map_scale * (1.0 - scroll * ORBIT_ZOOM_STEP)).clamp(MIN_MAP_SCALE, MAX_MAP_SCALE);
+        if let Projection::Orthographic(orthographic) = projection {
+            orthographic.scale = controller.map_scale;
         }
+    }
+}
+
+// Fraction of the way to move toward a target this frame for exponential easing
+// with the given time constant, independent of frame rate
+fn ease(time_constant: f32, dt: f32) -> f32 {
+    if time_constant <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-dt / time_constant).exp()
+    }
+}
+
+// Scrolling scales fly speed, or mouse sensitivity while AdjustSensitivity (Ctrl)
+// is held. Steps are multiplicative so they feel the same at any speed.
+fn adjust_with_scroll(controller: &mut CameraController, scroll: f32, input: &ActionInput) -> CameraSetting {
+    if input.pressed(Action::AdjustSensitivity) {
+        controller.sensitivity =
+            (controller.sensitivity * SENSITIVITY_SCROLL_STEP.powf(scroll)).clamp(MIN_SENSITIVITY, MAX_SENSITIVITY);
+        CameraSetting::Sensitivity
+    } else {
+        controller.speed =
+            (controller.speed * SPEED_SCROLL_STEP.powf(scroll)).clamp(controller.min_speed, controller.max_speed);
+        CameraSetting::Speed
+    }
+}
+
+fn look(controller: &mut CameraController, motion: Vec2) {
+    // Narrower views turn slower, so aiming while zoomed isn't twitchy
+    let fov = controller.fov_degrees.clamp(MIN_FOV_DEGREES, MAX_FOV_DEGREES);
+    let sensitivity = controller.sensitivity * controller.current_fov.map_or(1.0, |current| (current / fov).min(1.0));
+    controller.pitch -= motion.y * sensitivity;
+    controller.yaw -= motion.x * sensitivity;
+    // Clamp pitch to prevent camera flipping
+    controller.pitch = controller.pitch.clamp(-1.5, 1.5);
+}
+
+// Turns the camera and returns how far it wants to move this frame, which the
+// caller applies with or without collision
+fn fly_camera(
+    time: &Time,
+    camera_state: &CameraState,
+    motion: Vec2,
+    input: &ActionInput,
+    speed_scale: f32,
+    transform: &mut Transform,
+    controller: &mut CameraController,
+) -> Vec3 {
+    let dt = time.delta_seconds();
+    mouse_look(camera_state, motion, dt, transform, controller);
+
+    // Keyboard movement
+    let mut velocity = Vec3::ZERO;
+    let forward = transform.forward();
+    let right = transform.right();
+    let up = Vec3::Y;
+
+    // Get movement input
+    if input.pressed(Action::MoveForward) {
+        velocity += forward;
+    }
+    if input.pressed(Action::MoveBack) {
+        velocity += -forward;
+    }
+    if input.pressed(Action::StrafeLeft) {
+        velocity += -right;
+    }
+    if input.pressed(Action::StrafeRight) {
+        velocity += right;
+    }
+    if input.pressed(Action::Ascend) {
+        velocity += up;
+    }
+    if input.pressed(Action::Descend) {
+        velocity += -up;
+    }
+
+    update_dash(time, input, controller);
+
+    // Modifiers scale the scroll-adjusted base speed
+    let mut speed = modified_speed(controller.speed, input, controller) * speed_scale;
+    if controller.dashing {
+        speed *= controller.dash_multiplier;
+    }
+    controller.effective_speed = speed;
+
+    // Ease toward the input velocity
+    let target = velocity.normalize_or_zero() * speed;
+    controller.velocity = eased_velocity(controller, controller.velocity, target, dt);
+
+    controller.velocity * dt
+}
+
+// Mouse look (only when cursor is locked)
+fn mouse_look(
+    camera_state: &CameraState,
+    motion: Vec2,
+    dt: f32,
+    transform: &mut Transform,
+    controller: &mut CameraController,
+) {
+    if !camera_state.cursor_locked {
+        return;
+    }
+    look(controller, motion);
+
+    // Apply rotation
+    let target = Vec2::new(controller.yaw, controller.pitch);
+    let look = match controller.smoothed_look {
+        Some(current) => current.lerp(target, ease(controller.look_smoothing, dt)),
+        None => target,
+    };
+    controller.smoothed_look = Some(look);
+    transform.rotation = Quat::from_euler(EulerRot::YXZ, look.x, look.y, 0.0);
+}
+
+// Starts a dash on the second of two quick forward presses and ends it when
+// forward is released
+fn update_dash(time: &Time, input: &ActionInput, controller: &mut CameraController) {
+    if !input.pressed(Action::MoveForward) {
+        controller.dashing = false;
+    }
+    if !input.just_pressed(Action::MoveForward) {
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    let double_tap = controller
+        .last_forward_press
+        .is_some_and(|last| now - last <= controller.double_tap_window as f64);
+    controller.dashing = controller.double_tap_dash && double_tap;
+    // A third quick press doesn't count as another double tap
+    controller.last_forward_press = if double_tap { None } else { Some(now) };
+}
+
+// Speed factor from the camera's height above the terrain surface of its column, 1
+// when altitude scaling is off. Under the surface, as in a cave, it's the lowest;
+// over a column with no surface, the highest.
+fn altitude_speed_scale(world: &VoxelWorld, eye: Vec3, voxel_size: f32, controller: &CameraController) -> f32 {
+    let (min_scale, max_scale) = controller.altitude_scale_range;
+    if !controller.altitude_speed_scaling {
+        return 1.0;
+    }
+    let Some(surface) = world.surface_height_at(eye.x, eye.z, RayFilter::default()) else {
+        return max_scale;
+    };
+    // In voxels
+    let altitude = (eye.y - surface) / voxel_size;
+    (altitude / controller.altitude_reference.max(f32::EPSILON)).clamp(min_scale, max_scale)
+}
+
+fn modified_speed(base: f32, input: &ActionInput, controller: &CameraController) -> f32 {
+    let mut speed = base;
+    if input.pressed(Action::Sprint) {
+        speed *= controller.sprint_multiplier;
+    }
+    if input.pressed(Action::Precision) {
+        speed /= controller.precision_divisor.max(f32::EPSILON);
+    }
+    speed
+}
+
+// Eases current toward target with the acceleration or damping time constant
+fn eased_velocity(controller: &CameraController, current: Vec3, target: Vec3, dt: f32) -> Vec3 {
+    let time_constant = if target.length_squared() >= current.length_squared() {
+        controller.acceleration_time
+    } else {
+        controller.damping_time
+    };
+    let velocity = current.lerp(target, ease(time_constant, dt));
+    if velocity.length_squared() < 1e-6 {
+        Vec3::ZERO
+    } else {
+        velocity
+    }
+}
+
+// Moves on foot: horizontal input eases like flying, gravity and jumps drive the
+// vertical speed, and the body collides as a box from the feet to just above the eye
+#[allow(clippy::too_many_arguments)]
+fn walk_camera(
+    time: &Time,
+    camera_state: &CameraState,
+    motion: Vec2,
+    input: &ActionInput,
+    world: &VoxelWorld,
+    voxel_size: f32,
+    transform: &mut Transform,
+    controller: &mut CameraController,
+) {
+    let dt = time.delta_seconds();
+    mouse_look(camera_state, motion, dt, transform, controller);
+
+    let body = WalkBody::new(controller, voxel_size);
+    let eye = transform.translation;
+    // A sliver below the feet counts as standing
+    controller.grounded = body.overlaps(world, eye - Vec3::Y * 0.05 * voxel_size);
+    if controller.grounded {
+        controller.last_ground = Some(eye);
+    }
 
-        // Keyboard movement
-        let mut velocity = Vec3::ZERO;
-        let forward = transform.forward();
-        let right = transform.right();
-        let up = Vec3::Y;
+    let forward = transform.forward().reject_from(Vec3::Y).normalize_or_zero();
+    let right = transform.right().reject_from(Vec3::Y).normalize_or_zero();
+    let mut wish = Vec3::ZERO;
+    if input.pressed(Action::MoveForward) {
+        wish += forward;
+    }
+    if input.pressed(Action::MoveBack) {
+        wish -= forward;
+    }
+    if input.pressed(Action::StrafeLeft) {
+        wish -= right;
+    }
+    if input.pressed(Action::StrafeRight) {
+        wish += right;
+    }
+
+    let speed = modified_speed(controller.walk_speed * voxel_size, input, controller);
+    controller.effective_speed = speed;
+    let horizontal = Vec3::new(controller.velocity.x, 0.0, controller.velocity.z);
+    let horizontal = eased_velocity(controller, horizontal, wish.normalize_or_zero() * speed, dt);
 
-        // Get movement input
-        if keyboard.pressed(KeyCode::W) {
-            velocity += forward;
+    let mut vertical = controller.velocity.y;
+    if controller.grounded {
+        vertical = vertical.max(0.0);
+        if input.just_pressed(Action::Jump) {
+            vertical = controller.jump_impulse * voxel_size;
         }
-        if keyboard.pressed(KeyCode::S) {
-            velocity += -forward;
+    }
+    vertical -= controller.gravity * voxel_size * dt;
+    controller.velocity = horizontal + Vec3::Y * vertical;
+
+    let (eye, blocked) = body.sweep(world, eye, controller.velocity * dt, controller.grounded);
+    controller.velocity = Vec3::select(blocked, Vec3::ZERO, controller.velocity);
+    transform.translation = eye;
+
+    // Nothing to land on below: put the camera back where it last stood, or on the
+    // surface of its column, or hand over to flying if there's neither
+    let feet = ((eye.y - controller.eye_height * voxel_size) / voxel_size).floor() as i32;
+    let column = (eye / voxel_size).floor().as_ivec3();
+    let ground_below = world.ground_below(IVec3::new(column.x, feet, column.z), FALL_OUT_DEPTH).is_some();
+    if controller.velocity.y < 0.0 && !ground_below {
+        controller.velocity = Vec3::ZERO;
+        match controller.last_ground {
+            Some(last_ground) => transform.translation = last_ground,
+            None => match world.surface_height_at(eye.x, eye.z, RayFilter::default()) {
+                Some(surface) => transform.translation.y = surface + controller.eye_height * voxel_size,
+                None => {
+                    controller.mode = CameraMode::Fly;
+                    info!("Camera mode: {:?} (no ground to walk on)", controller.mode);
+                }
+            },
         }
-        if keyboard.pressed(KeyCode::A) {
-            velocity += -right;
+    }
+}
+
+// Box around a walking camera, as offsets from the eye
+struct WalkBody {
+    min: Vec3,
+    max: Vec3,
+    voxel_size: f32,
+}
+
+impl WalkBody {
+    fn new(controller: &CameraController, voxel_size: f32) -> Self {
+        let half_width = BODY_HALF_WIDTH * voxel_size;
+        Self {
+            min: Vec3::new(-half_width, -controller.eye_height * voxel_size, -half_width),
+            max: Vec3::new(half_width, BODY_HEAD_ROOM * voxel_size, half_width),
+            voxel_size,
         }
-        if keyboard.pressed(KeyCode::D) {
-            velocity += right;
+    }
+
+    fn overlaps(&self, world: &VoxelWorld, eye: Vec3) -> bool {
+        world.overlaps_solid(&Aabb::from_min_max(eye + self.min, eye + self.max))
+    }
+
+    // Same axis-by-axis slide as sweep_sphere. A blocked horizontal step on the
+    // ground is retried one voxel up, so single voxel ledges can be walked onto.
+    fn sweep(&self, world: &VoxelWorld, eye: Vec3, delta: Vec3, grounded: bool) -> (Vec3, BVec3) {
+        if self.overlaps(world, eye) {
+            return (eye + delta, BVec3::FALSE);
         }
-        if keyboard.pressed(KeyCode::Space) {
-            velocity += up;
+
+        let substep = WALK_SUBSTEP * self.voxel_size;
+        let steps = (delta.abs().max_element() / substep).ceil().max(1.0) as u32;
+        let step = delta / steps as f32;
+        let step_up = Vec3::Y * self.voxel_size;
+        let mut eye = eye;
+        let mut blocked = [false; 3];
+        for _ in 0..steps {
+            // Vertical first, so a landing is known before stepping sideways
+            for axis in [1, 0, 2] {
+                if blocked[axis] || step[axis] == 0.0 {
+                    continue;
+                }
+                let mut next = eye;
+                next[axis] += step[axis];
+                if !self.overlaps(world, next) {
+                    eye = next;
+                } else if axis != 1
+                    && grounded
+                    && !self.overlaps(world, eye + step_up)
+                    && !self.overlaps(world, next + step_up)
+                {
+                    eye = next + step_up;
+                } else {
+                    blocked[axis] = true;
+                }
+            }
         }
-        if keyboard.pressed(KeyCode::ShiftLeft) {
-            velocity += -up;
+        (eye, BVec3::from(blocked))
+    }
+}
+
+// Moves a sphere by delta one axis at a time, in steps no longer than its radius so
+// fast moves can't tunnel through a voxel. An axis that would overlap a solid voxel
+// stops and the others carry on, sliding along the surface. Returns the new center
+// and the axes that were blocked.
+fn sweep_sphere(world: &VoxelWorld, center: Vec3, delta: Vec3, radius: f32, voxel_size: f32) -> (Vec3, BVec3) {
+    // Already inside rock, e.g. after switching collision on; move freely until clear
+    if sphere_overlaps(world, center, radius, voxel_size) {
+        return (center + delta, BVec3::FALSE);
+    }
+
+    let steps = (delta.abs().max_element() / radius).ceil().max(1.0) as u32;
+    let step = delta / steps as f32;
+    let mut center = center;
+    let mut blocked = [false; 3];
+    for _ in 0..steps {
+        for axis in 0..3 {
+            if blocked[axis] || step[axis] == 0.0 {
+                continue;
+            }
+            let mut next = center;
+            next[axis] += step[axis];
+            if sphere_overlaps(world, next, radius, voxel_size) {
+                blocked[axis] = true;
+            } else {
+                center = next;
+            }
         }
+    }
+    (center, BVec3::from(blocked))
+}
 
-        // Apply movement
-        if velocity != Vec3::ZERO {
-            transform.translation += velocity.normalize() * controller.speed * time.delta_seconds();
+fn sphere_overlaps(world: &VoxelWorld, center: Vec3, radius: f32, voxel_size: f32) -> bool {
+    let min = ((center - radius) / voxel_size).floor().as_ivec3();
+    let max = ((center + radius) / voxel_size).floor().as_ivec3();
+    for x in min.x..=max.x {
+        for y in min.y..=max.y {
+            for z in min.z..=max.z {
+                let cell = IVec3::new(x, y, z);
+                if !world.is_solid(cell) {
+                    continue;
+                }
+                let cell_min = cell.as_vec3() * voxel_size;
+                let closest = center.clamp(cell_min, cell_min + Vec3::splat(voxel_size));
+                if closest.distance_squared(center) < radius * radius {
+                    return true;
+                }
+            }
         }
     }
+    false
 }
 
-fn toggle_cursor_lock(
+// Rotates while the cursor is locked or the left button drags, pans the focus in
+// the view plane with the middle button, and zooms with the scroll wheel
+fn orbit_camera(
+    camera_state: &CameraState,
+    motion: Vec2,
+    scroll: f32,
+    input: &ActionInput,
+    transform: &mut Transform,
+    controller: &mut CameraController,
+) {
+    if input.pressed(Action::OrbitPan) {
+        // Scaled by distance so the focus keeps up with the cursor at any zoom
+        let pan = (transform.up() * motion.y - transform.right() * motion.x) * controller.sensitivity * controller.distance;
+        controller.focus += pan;
+    } else if camera_state.cursor_locked || input.pressed(Action::OrbitRotate) {
+        look(controller, motion);
+    }
+
+    controller.distance = (controller.distance * (1.0 - scroll * ORBIT_ZOOM_STEP)).max(MIN_ORBIT_DISTANCE);
+
+    let rotation = Quat::from_euler(EulerRot::YXZ, controller.yaw, controller.pitch, 0.0);
+    // Forward is -Z, so the camera sits behind the focus along +Z
+    transform.rotation = rotation;
+    transform.translation = controller.focus + rotation * Vec3::Z * controller.distance;
+}
+
+// Left click locks the cursor unless it lands on UI, Escape releases it. Losing
+// window focus releases the grab, and regaining it restores the lock.
+pub fn toggle_cursor_lock(
     mut camera_state: ResMut<CameraState>,
     mut windows: Query<&mut Window>,
-    keyboard: Res<Input<KeyCode>>,
-    mouse: Res<Input<MouseButton>>,
+    mut focus_events: EventReader<WindowFocused>,
+    interactions: Query<&Interaction>,
+    input: ActionInput,
 ) {
-    let mut window = windows.single_mut();
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
 
-    if keyboard.just_pressed(KeyCode::Escape) {
-        camera_state.cursor_locked = false;
-        window.cursor.grab_mode = CursorGrabMode::None;
-        window.cursor.visible = true;
+    for event in focus_events.read() {
+        if !event.focused && camera_state.cursor_locked {
+            set_cursor_lock(&mut window, &mut camera_state, false);
+            camera_state.relock_on_focus = true;
+        } else if event.focused && camera_state.relock_on_focus {
+            set_cursor_lock(&mut window, &mut camera_state, true);
+        }
+    }
+
+    if input.just_pressed(Action::ReleaseCursor) {
+        set_cursor_lock(&mut window, &mut camera_state, false);
+    }
+
+    let over_ui = interactions.iter().any(|interaction| *interaction != Interaction::None);
+    if input.just_pressed(Action::LockCursor) && !camera_state.cursor_locked && !over_ui {
+        set_cursor_lock(&mut window, &mut camera_state, true);
+    }
+}
+
+// For overlays that take the keyboard, such as the console
+pub fn release_cursor(window: &mut Window, camera_state: &mut CameraState) {
+    if camera_state.cursor_locked {
+        set_cursor_lock(window, camera_state, false);
     }
+}
 
-    if mouse.just_pressed(MouseButton::Left) && !camera_state.cursor_locked {
-        camera_state.cursor_locked = true;
+fn set_cursor_lock(window: &mut Window, camera_state: &mut CameraState, locked: bool) {
+    camera_state.cursor_locked = locked;
+    camera_state.relock_on_focus = false;
+    if locked {
+        // Start from the center so the first motion after locking is small
+        let center = Vec2::new(window.width(), window.height()) / 2.0;
+        window.set_cursor_position(Some(center));
         window.cursor.grab_mode = CursorGrabMode::Locked;
         window.cursor.visible = false;
+        camera_state.ignore_motion_frames = LOCK_SETTLE_FRAMES;
+    } else {
+        window.cursor.grab_mode = CursorGrabMode::None;
+        window.cursor.visible = true;
     }
-}
\ No newline at end of file
+}
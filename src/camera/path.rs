@@ -0,0 +1,325 @@
+// src/camera/path.rs
+use bevy::{app::AppExit, prelude::*};
+use serde::{Deserialize, Serialize};
+use std::{
+    fmt,
+    ops::{Add, Mul, Sub},
+};
+use super::{resume_from_transform, CameraController, CameraMode};
+use crate::bindings::{Action, ActionInput};
+use crate::generation::GenerationProgress;
+use crate::voxel::VoxelChunk;
+
+const PATH_FILE: &str = "camera_path.ron";
+
+pub struct CameraPathPlugin;
+
+impl Plugin for CameraPathPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CameraPath>()
+            .add_systems(Update, (camera_path_controls, autoplay_camera_path, record_camera_path, play_camera_path).chain());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PathKeyframe {
+    pub translation: [f32; 3],
+    pub rotation: [f32; 4],
+}
+
+impl PathKeyframe {
+    fn from_transform(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation.to_array(),
+            rotation: transform.rotation.to_array(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PathState {
+    #[default]
+    Idle,
+    Recording,
+    Playing,
+}
+
+// A recorded flight: R starts and stops recording, P plays it back, F2 saves it to
+// camera_path.ron and F8 loads it
+#[derive(Resource, Serialize, Deserialize)]
+pub struct CameraPath {
+    pub keyframes: Vec<PathKeyframe>,
+    // Seconds between recorded keyframes
+    pub sample_interval: f32,
+    // Seconds playback takes from the first keyframe to the last
+    pub duration: f32,
+    // Playback advances by this many seconds each frame instead of by frame time,
+    // so every run shows the same frames regardless of frame rate. None follows
+    // the clock.
+    pub fixed_step: Option<f32>,
+    #[serde(skip)]
+    state: PathState,
+    // Seconds into the recording or playback
+    #[serde(skip)]
+    elapsed: f32,
+    #[serde(skip)]
+    since_sample: f32,
+}
+
+impl Default for CameraPath {
+    fn default() -> Self {
+        Self {
+            keyframes: Vec::new(),
+            sample_interval: 0.25,
+            duration: 0.0,
+            fixed_step: Some(1.0 / 60.0),
+            state: PathState::Idle,
+            elapsed: 0.0,
+            since_sample: 0.0,
+        }
+    }
+}
+
+impl CameraPath {
+    pub fn state(&self) -> PathState {
+        self.state
+    }
+
+    // Starts playback from the first keyframe, for scripted runs
+    pub fn play(&mut self) {
+        self.state = PathState::Playing;
+        self.elapsed = 0.0;
+    }
+
+    // Seconds into playback, for logs that line up with the path
+    pub fn playback_time(&self) -> Option<f32> {
+        (self.state == PathState::Playing).then_some(self.elapsed)
+    }
+
+    // Camera transform at a time into playback. Positions and rotations follow
+    // uniform Catmull-Rom splines through the keyframes, with the ends repeated.
+    pub fn sample(&self, time: f32) -> Option<Transform> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        if last == 0 || self.duration <= 0.0 {
+            let key = self.keyframes[if time <= 0.0 { 0 } else { last }];
+            return Some(keyframe_transform(&key));
+        }
+        let position = (time / self.duration).clamp(0.0, 1.0) * last as f32;
+        let segment = (position.floor() as usize).min(last - 1);
+        let t = position - segment as f32;
+        let key = |index: isize| self.keyframes[index.clamp(0, last as isize) as usize];
+        let i = segment as isize;
+        let (k0, k1, k2, k3) = (key(i - 1), key(i), key(i + 1), key(i + 2));
+
+        let translation = catmull_rom(
+            Vec3::from(k0.translation),
+            Vec3::from(k1.translation),
+            Vec3::from(k2.translation),
+            Vec3::from(k3.translation),
+            t,
+        );
+        // Keep neighbouring quaternions in one hemisphere so the spline takes the
+        // short way round, then renormalize the blend
+        let q1 = Vec4::from(k1.rotation);
+        let align = |q: [f32; 4]| {
+            let q = Vec4::from(q);
+            if q.dot(q1) < 0.0 { -q } else { q }
+        };
+        let rotation = catmull_rom(align(k0.rotation), q1, align(k2.rotation), align(k3.rotation), t);
+        let rotation = Quat::from_vec4(rotation).normalize();
+
+        Some(Transform::from_translation(translation).with_rotation(rotation))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), CameraPathError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(CameraPathError::Serialize)?;
+        std::fs::write(path, text).map_err(CameraPathError::Io)
+    }
+
+    pub fn load(path: &str) -> Result<Self, CameraPathError> {
+        let bytes = std::fs::read(path).map_err(CameraPathError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(CameraPathError::Parse)
+    }
+}
+
+#[derive(Debug)]
+pub enum CameraPathError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for CameraPathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CameraPathError::Io(err) => write!(f, "could not access camera path: {}", err),
+            CameraPathError::Parse(err) => write!(f, "could not parse camera path: {}", err),
+            CameraPathError::Serialize(err) => write!(f, "could not write camera path: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for CameraPathError {}
+
+// Set by --play-path: plays the loaded path once the world has generated, then
+// exits, so a benchmark run flies the same route every time
+#[derive(Resource, Default)]
+pub struct PathAutoplay {
+    started: bool,
+}
+
+// Run condition for the manual camera systems, which stand aside during playback
+pub fn camera_path_idle(path: Res<CameraPath>) -> bool {
+    path.state != PathState::Playing
+}
+
+fn keyframe_transform(key: &PathKeyframe) -> Transform {
+    Transform::from_translation(Vec3::from(key.translation)).with_rotation(Quat::from_array(key.rotation))
+}
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+pub fn camera_path_controls(
+    input: ActionInput,
+    mut path: ResMut<CameraPath>,
+    mut query: Query<(&Transform, &mut CameraController), With<Camera>>,
+) {
+    if input.just_pressed(Action::RecordPath) {
+        match path.state {
+            PathState::Recording => {
+                path.state = PathState::Idle;
+                path.duration = (path.keyframes.len().saturating_sub(1)) as f32 * path.sample_interval;
+                info!("Recorded camera path: {} keyframes, {:.1}s", path.keyframes.len(), path.duration);
+            }
+            PathState::Idle => {
+                path.keyframes.clear();
+                path.state = PathState::Recording;
+                // The first frame of recording samples straight away
+                path.since_sample = path.sample_interval;
+                info!("Recording camera path");
+            }
+            PathState::Playing => {}
+        }
+    }
+
+    let in_map = query.get_single().is_ok_and(|(_, controller)| controller.mode == CameraMode::Map);
+    if input.just_pressed(Action::PlayPath) {
+        match path.state {
+            // Paths hold perspective poses, so the map view doesn't play them
+            PathState::Idle if !path.keyframes.is_empty() && !in_map => {
+                path.state = PathState::Playing;
+                path.elapsed = 0.0;
+                info!("Playing camera path over {:.1}s", path.duration);
+            }
+            PathState::Playing => {
+                path.state = PathState::Idle;
+                if let Ok((transform, mut controller)) = query.get_single_mut() {
+                    resume_from_transform(transform, &mut controller);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if path.state != PathState::Idle {
+        return;
+    }
+    if input.just_pressed(Action::SavePath) {
+        match path.save(PATH_FILE) {
+            Ok(()) => info!("Saved camera path to {}", PATH_FILE),
+            Err(err) => warn!("{}", err),
+        }
+    }
+    if input.just_pressed(Action::LoadPath) {
+        match CameraPath::load(PATH_FILE) {
+            Ok(loaded) => {
+                info!("Loaded camera path with {} keyframes from {}", loaded.keyframes.len(), PATH_FILE);
+                *path = loaded;
+            }
+            Err(err) => warn!("{}", err),
+        }
+    }
+}
+
+fn autoplay_camera_path(
+    autoplay: Option<ResMut<PathAutoplay>>,
+    progress: Res<GenerationProgress>,
+    chunks: Query<(), With<VoxelChunk>>,
+    mut path: ResMut<CameraPath>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(mut autoplay) = autoplay else {
+        return;
+    };
+    if autoplay.started {
+        if path.state == PathState::Idle {
+            info!("Scripted flythrough finished; exiting");
+            exit.send(AppExit);
+        }
+        return;
+    }
+    if chunks.is_empty() || !progress.is_complete() || path.keyframes.is_empty() {
+        return;
+    }
+    autoplay.started = true;
+    path.play();
+    info!("Playing scripted flythrough over {:.1}s", path.duration);
+}
+
+fn record_camera_path(
+    time: Res<Time>,
+    mut path: ResMut<CameraPath>,
+    query: Query<&Transform, With<CameraController>>,
+) {
+    if path.state != PathState::Recording {
+        return;
+    }
+    let Ok(transform) = query.get_single() else {
+        return;
+    };
+    path.since_sample += time.delta_seconds();
+    if path.since_sample >= path.sample_interval {
+        path.since_sample -= path.sample_interval;
+        // A long frame shouldn't queue a burst of samples at one pose
+        path.since_sample = path.since_sample.min(path.sample_interval);
+        path.keyframes.push(PathKeyframe::from_transform(transform));
+    }
+}
+
+fn play_camera_path(
+    time: Res<Time>,
+    mut path: ResMut<CameraPath>,
+    mut query: Query<(&mut Transform, &mut CameraController), With<Camera>>,
+) {
+    if path.state != PathState::Playing {
+        return;
+    }
+    let Ok((mut transform, mut controller)) = query.get_single_mut() else {
+        return;
+    };
+    let Some(pose) = path.sample(path.elapsed) else {
+        path.state = PathState::Idle;
+        return;
+    };
+    *transform = pose;
+
+    if path.elapsed >= path.duration {
+        path.state = PathState::Idle;
+        resume_from_transform(&transform, &mut controller);
+        info!("Camera path finished");
+        return;
+    }
+    path.elapsed += path.fixed_step.unwrap_or_else(|| time.delta_seconds());
+}
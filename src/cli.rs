@@ -0,0 +1,263 @@
+// src/cli.rs
+use bevy::prelude::*;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::camera::{CameraPath, PathAutoplay};
+use crate::diagnostics::{BenchmarkRun, BenchmarkScenario, StatsLog};
+use crate::generation::{DemoScene, GenerationSettings, Heightmap, PointCloudImporter, WorldSaver};
+use crate::voxel::StartupModels;
+
+pub const USAGE: &str = "\
+Usage: bevy_voxel [options]
+
+Options:
+  --seed <u64>                  Terrain seed
+  --generator <name>            flat, noise, cube, sphere, torus, menger,
+                                staircase or heightmap:<path>
+  --render-distance <f32>       Render distance in world units
+  --world-size <x,y,z>          Chunks to generate along each axis
+  --headless                    Run without a window
+  --load <save name>            Load saves/<save name>/world.bin at startup
+  --settings <path>             Settings file to use instead of settings.ron
+  --import-pointcloud <path>    Voxelize a .ply, .xyz, .txt or .pts point cloud
+  --point-voxel-size <f32>      Point cloud units per voxel (default 0.1)
+  --import-qb <path>            Import a Qubicle .qb model
+  --spawn-model <path>          Place a .wvox model from assets/ at the origin;
+                                it reloads when the file changes
+  --log-stats <path>            Append a CSV row of performance stats every second
+  --play-path <path>            Fly a saved camera path once the world has
+                                generated, then exit
+  --benchmark <scenario>        Run benchmarks/<scenario>.ron without a window,
+                                print a report and exit nonzero on failure
+  --help                        Show this message";
+
+#[derive(Clone, Debug)]
+pub enum GeneratorArg {
+    Scene(DemoScene),
+    Heightmap(String),
+}
+
+// Options given on the command line. Values that are set override the settings
+// file and the generation defaults.
+#[derive(Clone, Debug, Default)]
+pub struct CliArgs {
+    pub seed: Option<u64>,
+    pub generator: Option<GeneratorArg>,
+    pub render_distance: Option<f32>,
+    pub world_size: Option<IVec3>,
+    pub headless: bool,
+    pub load: Option<String>,
+    pub settings: Option<String>,
+    pub import_pointcloud: Option<String>,
+    pub point_voxel_size: Option<f32>,
+    pub import_qb: Option<String>,
+    pub spawn_model: Option<String>,
+    pub log_stats: Option<String>,
+    pub play_path: Option<String>,
+    pub benchmark: Option<String>,
+    pub help: bool,
+}
+
+impl CliArgs {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut parsed = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| args.next().ok_or_else(|| format!("{} needs a value", name));
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = value("--seed")?;
+                    parsed.seed = Some(seed.parse().map_err(|_| format!("invalid seed '{}'", seed))?);
+                }
+                "--generator" => parsed.generator = Some(parse_generator(&value("--generator")?)?),
+                "--render-distance" => {
+                    let distance = value("--render-distance")?;
+                    match distance.parse::<f32>() {
+                        Ok(distance) if distance > 0.0 && distance.is_finite() => parsed.render_distance = Some(distance),
+                        _ => return Err(format!("invalid render distance '{}'", distance)),
+                    }
+                }
+                "--world-size" => parsed.world_size = Some(parse_world_size(&value("--world-size")?)?),
+                "--headless" => parsed.headless = true,
+                "--load" => parsed.load = Some(value("--load")?),
+                "--settings" => parsed.settings = Some(value("--settings")?),
+                "--import-pointcloud" => parsed.import_pointcloud = Some(value("--import-pointcloud")?),
+                "--import-qb" => parsed.import_qb = Some(value("--import-qb")?),
+                "--spawn-model" => parsed.spawn_model = Some(value("--spawn-model")?),
+                "--log-stats" => parsed.log_stats = Some(value("--log-stats")?),
+                "--play-path" => parsed.play_path = Some(value("--play-path")?),
+                "--benchmark" => parsed.benchmark = Some(value("--benchmark")?),
+                "--point-voxel-size" => {
+                    let size = value("--point-voxel-size")?;
+                    match size.parse::<f32>() {
+                        Ok(size) if size > 0.0 && size.is_finite() => parsed.point_voxel_size = Some(size),
+                        _ => return Err(format!("invalid point voxel size '{}'", size)),
+                    }
+                }
+                "--help" | "-h" => parsed.help = true,
+                other => return Err(format!("unknown argument '{}'", other)),
+            }
+        }
+        Ok(parsed)
+    }
+
+    // Generation settings, the world save to start from, a file to import, a
+    // model to spawn and a benchmark run. Fails on a heightmap or camera path that
+    // can't be read or a save, import or model that doesn't exist.
+    pub fn insert_resources(&self, app: &mut App) -> Result<(), String> {
+        // A benchmark scenario fills in what the command line leaves out
+        let scenario = match &self.benchmark {
+            Some(name) => Some(BenchmarkScenario::load(name).map_err(|err| format!("{}: {}", BenchmarkScenario::path(name).display(), err))?),
+            None => None,
+        };
+        let scenario_generator = match scenario.as_ref().filter(|_| self.generator.is_none()) {
+            Some(scenario) => Some(parse_generator(&scenario.generator)?),
+            None => None,
+        };
+        let seed = self.seed.or(scenario.as_ref().and_then(|scenario| scenario.seed));
+        let world_size = self.world_size.or(scenario.as_ref().and_then(|scenario| scenario.world_size.map(IVec3::from)));
+
+        let mut generation = GenerationSettings::default();
+        if let Some(seed) = seed {
+            // Generators take 32-bit seeds; fold the high half in so it still counts
+            generation.seed = (seed ^ (seed >> 32)) as u32;
+        }
+        match self.generator.as_ref().or(scenario_generator.as_ref()) {
+            Some(GeneratorArg::Scene(scene)) => generation.scene = *scene,
+            Some(GeneratorArg::Heightmap(path)) => {
+                let heightmap = Heightmap::load(path).map_err(|err| format!("{}: {}", path, err))?;
+                generation.heightmap = Some(Arc::new(heightmap));
+                generation.scene = DemoScene::Heightmap;
+            }
+            None => {}
+        }
+        generation.world_size = world_size;
+        app.insert_resource(generation);
+
+        let saver = match &self.load {
+            Some(name) => {
+                let saver = WorldSaver::named(name.clone());
+                if !saver.path().exists() {
+                    return Err(format!("no save named '{}' at {}", name, saver.path().display()));
+                }
+                saver
+            }
+            None => {
+                let mut saver = WorldSaver::default();
+                // A generated world was asked for, so the quick-save doesn't replace it
+                saver.load_at_startup = self.seed.is_none() && self.generator.is_none() && self.world_size.is_none() && scenario.is_none();
+                saver
+            }
+        };
+        app.insert_resource(saver);
+
+        let mut importer = PointCloudImporter::default();
+        if let Some(size) = self.point_voxel_size {
+            importer.voxel_size = size;
+        }
+        if let Some(path) = &self.import_pointcloud {
+            if !Path::new(path).is_file() {
+                return Err(format!("no point cloud at {}", path));
+            }
+            importer.pending = Some(PathBuf::from(path));
+        }
+        if let Some(path) = &self.import_qb {
+            if !Path::new(path).is_file() {
+                return Err(format!("no Qubicle model at {}", path));
+            }
+            importer.pending = Some(PathBuf::from(path));
+        }
+        app.insert_resource(importer);
+
+        if let Some(path) = &self.spawn_model {
+            if !Path::new("assets").join(path).is_file() {
+                return Err(format!("no model at assets/{}", path));
+            }
+            app.insert_resource(StartupModels(vec![(path.clone(), IVec3::ZERO)]));
+        }
+
+        if let Some(path) = &self.log_stats {
+            app.insert_resource(StatsLog::new(PathBuf::from(path)));
+        }
+        if let Some(path) = &self.play_path {
+            let camera_path = CameraPath::load(path).map_err(|err| format!("{}: {}", path, err))?;
+            if camera_path.keyframes.is_empty() {
+                return Err(format!("camera path {} has no keyframes", path));
+            }
+            app.insert_resource(camera_path).init_resource::<PathAutoplay>();
+        }
+        if let (Some(name), Some(scenario)) = (&self.benchmark, scenario) {
+            if let Some(path) = &scenario.camera_path {
+                let camera_path = CameraPath::load(path).map_err(|err| format!("{}: {}", path, err))?;
+                app.insert_resource(camera_path);
+            }
+            app.insert_resource(BenchmarkRun::new(name.clone(), scenario));
+        }
+        Ok(())
+    }
+}
+
+fn parse_generator(value: &str) -> Result<GeneratorArg, String> {
+    if let Some(path) = value.strip_prefix("heightmap:") {
+        if path.is_empty() {
+            return Err(String::from("heightmap needs a path, as in heightmap:<path>"));
+        }
+        return Ok(GeneratorArg::Heightmap(path.to_string()));
+    }
+    match value {
+        "flat" => Ok(GeneratorArg::Scene(DemoScene::Flat)),
+        "noise" => Ok(GeneratorArg::Scene(DemoScene::Terrain)),
+        "cube" => Ok(GeneratorArg::Scene(DemoScene::CubeGrid)),
+        "sphere" => Ok(GeneratorArg::Scene(DemoScene::Sphere)),
+        "torus" => Ok(GeneratorArg::Scene(DemoScene::Torus)),
+        "menger" => Ok(GeneratorArg::Scene(DemoScene::MengerSponge)),
+        "staircase" => Ok(GeneratorArg::Scene(DemoScene::Staircase)),
+        _ => Err(format!("unknown generator '{}'", value)),
+    }
+}
+
+fn parse_world_size(value: &str) -> Result<IVec3, String> {
+    let axes: Vec<i32> = value
+        .split(',')
+        .map(|axis| axis.trim().parse::<i32>().ok().filter(|axis| *axis > 0))
+        .collect::<Option<_>>()
+        .ok_or_else(|| format!("invalid world size '{}'", value))?;
+    match axes.as_slice() {
+        [x, y, z] => Ok(IVec3::new(*x, *y, *z)),
+        _ => Err(format!("world size '{}' needs three axes, as in 8,2,8", value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn demo_scenes_are_generators() {
+        for (name, scene) in [
+            ("flat", DemoScene::Flat),
+            ("noise", DemoScene::Terrain),
+            ("cube", DemoScene::CubeGrid),
+            ("sphere", DemoScene::Sphere),
+            ("torus", DemoScene::Torus),
+            ("menger", DemoScene::MengerSponge),
+            ("staircase", DemoScene::Staircase),
+        ] {
+            assert!(matches!(parse_generator(name), Ok(GeneratorArg::Scene(parsed)) if parsed == scene), "{}", name);
+        }
+        assert!(matches!(parse_generator("heightmap:hills.png"), Ok(GeneratorArg::Heightmap(path)) if path == "hills.png"));
+        assert!(parse_generator("heightmap:").is_err());
+        assert!(parse_generator("sponge").is_err());
+    }
+
+    #[test]
+    fn benchmark_scenarios_name_known_generators() {
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("benchmarks");
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            let bytes = std::fs::read(&path).unwrap();
+            let scenario: BenchmarkScenario = ron::de::from_bytes(&bytes).unwrap();
+            assert!(parse_generator(&scenario.generator).is_ok(), "{}: {}", path.display(), scenario.generator);
+        }
+    }
+}
@@ -0,0 +1,379 @@
+// src/console.rs
+use bevy::{
+    ecs::system::SystemState,
+    input::{mouse::MouseWheel, InputSystem},
+    prelude::*,
+    window::{PrimaryWindow, ReceivedCharacter},
+};
+use std::collections::{BTreeMap, VecDeque};
+use std::sync::Arc;
+use crate::bindings::{Action, KeyBindings};
+use crate::camera::{release_cursor, CameraController, CameraMode, CameraState};
+use crate::diagnostics::{body_text_style, OverlaySettings};
+use crate::editing::PaintState;
+use crate::picking::TargetedVoxel;
+use crate::voxel::{EditOp, VoxelEditor, VoxelEdits, WorldRegion};
+use crate::voxel_types::{VoxelRenderSettings, KIND_PLAIN};
+
+// Lines kept in the scrollback, and lines shown at once
+const MAX_SCROLLBACK: usize = 200;
+const VISIBLE_LINES: usize = 16;
+// Largest sphere or cube the spawn command builds
+const MAX_SPAWN_SIZE: i32 = 64;
+// Largest radius, in voxels, the explode command clears
+const MAX_EXPLOSION_RADIUS: i32 = 32;
+
+// Shared so a handler can run while the registry stays in the world
+pub type CommandHandler = Arc<dyn Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync>;
+
+struct ConsoleCommand {
+    usage: &'static str,
+    handler: CommandHandler,
+}
+
+// Commands the console runs, by name. Handlers get the world and the words after
+// the name, and return the text to print; an Err is printed as an error.
+#[derive(Resource, Default)]
+pub struct ConsoleCommands {
+    commands: BTreeMap<&'static str, ConsoleCommand>,
+}
+
+impl ConsoleCommands {
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        handler: impl Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync + 'static,
+    ) {
+        self.commands.insert(name, ConsoleCommand {
+            usage,
+            handler: Arc::new(handler),
+        });
+    }
+}
+
+// Lets plugins register commands in build, whichever is added first
+pub trait RegisterConsoleCommand {
+    fn register_console_command(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        handler: impl Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self;
+}
+
+impl RegisterConsoleCommand for App {
+    fn register_console_command(
+        &mut self,
+        name: &'static str,
+        usage: &'static str,
+        handler: impl Fn(&mut World, &[&str]) -> Result<String, String> + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        self.world.resource_mut::<ConsoleCommands>().register(name, usage, handler);
+        self
+    }
+}
+
+// Drop-down console toggled with the backtick. While it's open it takes all
+// keyboard and mouse button input and the scroll wheel, so nothing else reacts to
+// typing, and the cursor is released.
+#[derive(Resource, Default)]
+pub struct Console {
+    pub open: bool,
+    input: String,
+    scrollback: VecDeque<String>,
+    // Lines scrolled back from the newest
+    scroll: usize,
+    // Submitted lines, oldest first, and the one Up and Down are on
+    history: Vec<String>,
+    history_index: Option<usize>,
+    // Submitted but not yet run
+    pending: Vec<String>,
+}
+
+impl Console {
+    pub fn print(&mut self, text: &str) {
+        for line in text.lines() {
+            if self.scrollback.len() == MAX_SCROLLBACK {
+                self.scrollback.pop_front();
+            }
+            self.scrollback.push_back(line.to_string());
+        }
+        self.scroll = 0;
+    }
+}
+
+pub struct ConsolePlugin;
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Console>()
+            .init_resource::<ConsoleCommands>()
+            .add_systems(Startup, setup_console)
+            .add_systems(PreUpdate, capture_console_input.after(InputSystem))
+            .add_systems(Update, (run_console_commands, update_console_text).chain())
+            .register_console_command("help", "help: lists the commands", help)
+            .register_console_command("clear", "clear: empties the scrollback", |world, _| {
+                world.resource_mut::<Console>().scrollback.clear();
+                Ok(String::new())
+            })
+            .register_console_command("tp", "tp <x> <y> <z>: moves the camera to a world position", teleport)
+            .register_console_command("set", "set <setting> <value>: changes a render or camera setting", set_setting)
+            .register_console_command(
+                "spawn",
+                "spawn <sphere|cube> <size>: builds a shape at the targeted voxel in the active color",
+                spawn_shape,
+            )
+            .register_console_command("explode", "explode <radius>: clears a sphere of voxels at the targeted voxel", explode);
+    }
+}
+
+#[derive(Component)]
+struct ConsolePanel;
+
+#[derive(Component)]
+struct ConsoleText;
+
+fn setup_console(mut commands: Commands) {
+    commands
+        .spawn((NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                padding: UiRect::all(Val::Px(8.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            visibility: Visibility::Hidden,
+            // Above the diagnostics overlay
+            z_index: ZIndex::Global(10),
+            ..default()
+        }, ConsolePanel))
+        .with_children(|parent| {
+            parent.spawn((TextBundle::from_section("", body_text_style()), ConsoleText));
+        });
+}
+
+// Runs before anything reads input, then clears what it saw while open
+#[allow(clippy::too_many_arguments)]
+fn capture_console_input(
+    bindings: Res<KeyBindings>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut characters: ResMut<Events<ReceivedCharacter>>,
+    mut wheel: ResMut<Events<MouseWheel>>,
+    mut console: ResMut<Console>,
+    mut camera_state: ResMut<CameraState>,
+    mut windows: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    if bindings.consume(Action::ToggleConsole, &mut keyboard, &mut mouse) {
+        console.open = !console.open;
+        if console.open {
+            if let Ok(mut window) = windows.get_single_mut() {
+                release_cursor(&mut window, &mut camera_state);
+            }
+        }
+    }
+    if !console.open {
+        return;
+    }
+
+    for event in characters.drain() {
+        if !event.char.is_control() && event.char != '`' {
+            console.input.push(event.char);
+        }
+    }
+    let scrolled: f32 = wheel.drain().map(|event| event.y).sum();
+    if scrolled != 0.0 {
+        let max_scroll = console.scrollback.len().saturating_sub(VISIBLE_LINES);
+        let scroll = console.scroll as i64 + scrolled.signum() as i64 * 3;
+        console.scroll = scroll.clamp(0, max_scroll as i64) as usize;
+    }
+    if keyboard.just_pressed(KeyCode::Back) {
+        console.input.pop();
+    }
+    if keyboard.just_pressed(KeyCode::Up) && !console.history.is_empty() {
+        let index = console.history_index.map_or(console.history.len() - 1, |index| index.saturating_sub(1));
+        console.input = console.history[index].clone();
+        console.history_index = Some(index);
+    }
+    if keyboard.just_pressed(KeyCode::Down) {
+        if let Some(index) = console.history_index {
+            let next = index + 1;
+            console.history_index = (next < console.history.len()).then_some(next);
+            console.input = console.history.get(next).cloned().unwrap_or_default();
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Return) {
+        let line = std::mem::take(&mut console.input);
+        console.history_index = None;
+        if !line.trim().is_empty() {
+            console.history.push(line.clone());
+            console.pending.push(line);
+        }
+    }
+    if keyboard.just_pressed(KeyCode::Escape) {
+        console.open = false;
+    }
+    keyboard.reset_all();
+    mouse.reset_all();
+}
+
+fn run_console_commands(world: &mut World) {
+    let lines = std::mem::take(&mut world.resource_mut::<Console>().pending);
+    for line in lines {
+        world.resource_mut::<Console>().print(&format!("> {}", line));
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let handler = world.resource::<ConsoleCommands>().commands.get(words[0]).map(|command| command.handler.clone());
+        let output = match handler {
+            Some(handler) => handler(world, &words[1..]),
+            None => Err(format!("unknown command '{}'; try help", words[0])),
+        };
+        let mut console = world.resource_mut::<Console>();
+        match output {
+            Ok(text) => console.print(&text),
+            Err(err) => console.print(&format!("error: {}", err)),
+        }
+    }
+}
+
+fn update_console_text(
+    console: Res<Console>,
+    mut panel: Query<&mut Visibility, With<ConsolePanel>>,
+    mut text: Query<&mut Text, With<ConsoleText>>,
+) {
+    if !console.is_changed() {
+        return;
+    }
+    let target = if console.open { Visibility::Inherited } else { Visibility::Hidden };
+    for mut visibility in &mut panel {
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+    for mut text in &mut text {
+        let end = console.scrollback.len() - console.scroll;
+        let start = end.saturating_sub(VISIBLE_LINES);
+        let mut value: String = console.scrollback.range(start..end).map(|line| format!("{}\n", line)).collect();
+        value.push_str(&format!("> {}_", console.input));
+        text.sections[0].value = value;
+    }
+}
+
+fn help(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let commands = world.resource::<ConsoleCommands>();
+    Ok(commands.commands.values().map(|command| command.usage).collect::<Vec<_>>().join("\n"))
+}
+
+fn parse<T: std::str::FromStr>(word: Option<&&str>, what: &str) -> Result<T, String> {
+    let word = word.ok_or_else(|| format!("missing {}", what))?;
+    word.parse().map_err(|_| format!("invalid {} '{}'", what, word))
+}
+
+fn teleport(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let position = Vec3::new(parse(args.first(), "x")?, parse(args.get(1), "y")?, parse(args.get(2), "z")?);
+    let mut cameras = world.query_filtered::<(&mut Transform, &mut CameraController), With<Camera>>();
+    let (mut transform, mut controller) = cameras.get_single_mut(world).map_err(|_| "no camera")?;
+    match controller.mode {
+        // The orbit camera follows its focus
+        CameraMode::Orbit => controller.focus = position,
+        _ => transform.translation = position,
+    }
+    Ok(format!("Moved to {:.1} {:.1} {:.1}", position.x, position.y, position.z))
+}
+
+fn set_setting(world: &mut World, args: &[&str]) -> Result<String, String> {
+    const SETTINGS: &str = "render_distance, ao_strength, light_ambient, fog_start, fade_band, shadow_distance, speed, fov, overlay_scale, overlay_opacity";
+    let Some(name) = args.first() else {
+        return Ok(format!("Settings: {}", SETTINGS));
+    };
+    let value: f32 = parse(args.get(1), "value")?;
+    if !value.is_finite() || value < 0.0 {
+        return Err(format!("{} must be a positive number", name));
+    }
+    if matches!(*name, "speed" | "fov") {
+        let mut cameras = world.query_filtered::<&mut CameraController, With<Camera>>();
+        let mut controller = cameras.get_single_mut(world).map_err(|_| "no camera")?;
+        match *name {
+            "speed" => controller.speed = value.clamp(controller.min_speed, controller.max_speed),
+            _ => controller.fov_degrees = value,
+        }
+        return Ok(format!("{} = {}", name, value));
+    }
+    if matches!(*name, "overlay_scale" | "overlay_opacity") {
+        let mut overlay = world.resource_mut::<OverlaySettings>();
+        match *name {
+            "overlay_scale" => overlay.scale = value,
+            _ => overlay.background_opacity = value.min(1.0),
+        }
+        return Ok(format!("{} = {}", name, value));
+    }
+    let mut settings = world.resource_mut::<VoxelRenderSettings>();
+    match *name {
+        "render_distance" => settings.render_distance = value,
+        "ao_strength" => settings.ao_strength = value,
+        "light_ambient" => settings.light_ambient = value.min(1.0),
+        "fog_start" => settings.fog_start = value,
+        "fade_band" => settings.fade_band = value,
+        "shadow_distance" => settings.shadow_distance = value,
+        other => return Err(format!("unknown setting '{}'; settings are {}", other, SETTINGS)),
+    }
+    Ok(format!("{} = {}", name, value))
+}
+
+// Centered on the targeted voxel, as one undo step
+fn spawn_shape(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let shape = *args.first().ok_or("missing shape")?;
+    let size: i32 = parse(args.get(1), "size")?;
+    if !(1..=MAX_SPAWN_SIZE).contains(&size) {
+        return Err(format!("size must be 1 to {}", MAX_SPAWN_SIZE));
+    }
+    let center = world.resource::<TargetedVoxel>().hit.map(|hit| hit.world).ok_or("no voxel targeted")?;
+    let color = world.resource::<PaintState>().active;
+    let half = size / 2;
+    let mut edits = VoxelEdits::default();
+    for x in -half..=half {
+        for y in -half..=half {
+            for z in -half..=half {
+                let offset = IVec3::new(x, y, z);
+                let inside = match shape {
+                    "sphere" => offset.length_squared() <= half * half,
+                    "cube" => true,
+                    other => return Err(format!("unknown shape '{}'; use sphere or cube", other)),
+                };
+                if inside {
+                    edits.push(center + offset, EditOp::Place { color, kind: KIND_PLAIN });
+                }
+            }
+        }
+    }
+    let voxels = edits.len();
+    let mut state = SystemState::<VoxelEditor>::new(world);
+    let chunks = state.get_mut(world).apply(edits);
+    state.apply(world);
+    Ok(format!("Placed a {} of {} voxels in {} chunks", shape, voxels, chunks))
+}
+
+// Erases every voxel within radius of the targeted one, as one undo step
+fn explode(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let radius: i32 = parse(args.first(), "radius")?;
+    if !(1..=MAX_EXPLOSION_RADIUS).contains(&radius) {
+        return Err(format!("radius must be 1 to {}", MAX_EXPLOSION_RADIUS));
+    }
+    let center = world.resource::<TargetedVoxel>().hit.map(|hit| hit.world).ok_or("no voxel targeted")?;
+    let region = WorldRegion {
+        min: center - IVec3::splat(radius),
+        max: center + IVec3::splat(radius),
+    };
+    let mut removed = 0;
+    let mut state = SystemState::<VoxelEditor>::new(world);
+    let chunks = state.get_mut(world).modify_region(region, |cell, voxel| {
+        let inside = (cell - center).length_squared() <= radius * radius;
+        removed += usize::from(inside && voxel.is_some());
+        inside.then_some(EditOp::Erase)
+    });
+    state.apply(world);
+    Ok(format!("Removed {} voxels in {} chunks", removed, chunks))
+}
@@ -1,9 +1,62 @@
 // src/diagnostics.rs
 use bevy::{
     diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin},
+    ecs::entity::Entities,
     prelude::*,
+    ui::UiSystem,
+    utils::HashMap,
 };
-use crate::voxel::VoxelChunk;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::bindings::{Action, ActionInput};
+use crate::camera::{CameraController, CameraFeedback, CameraMode, CameraSetting};
+use crate::console::RegisterConsoleCommand;
+use crate::editing::EditFeedback;
+use crate::generation::{PointCloudImporter, SaveStatus, WorldCommand, WorldSaver};
+use crate::picking::TargetedVoxel;
+use crate::render::{
+    BillboardMarker, BillboardMaterialCache, BillboardStats, FacingBillboardMaterial, MeshingStats, VoxelMeshMaterial,
+};
+use crate::screenshot::ScreenshotState;
+use crate::voxel::{ChunkSpatialIndex, CullReason, CullingStats, SystemTimings, VoxelChunk};
+use crate::voxel_types::{RenderMode, Voxel, VoxelRenderSettings};
+
+mod benchmark;
+mod stats_log;
+pub use benchmark::{BenchmarkOutcome, BenchmarkRun, BenchmarkScenario};
+pub use stats_log::StatsLog;
+use benchmark::run_benchmark;
+use stats_log::write_stats_log;
+
+const CROSSHAIR_SIZE: f32 = 12.0;
+const CROSSHAIR_THICKNESS: f32 = 2.0;
+
+// How long a scroll wheel change stays on screen
+const CAMERA_FEEDBACK_SECONDS: f64 = 1.5;
+const EDIT_WARNING_SECONDS: f64 = 4.0;
+
+// Frames after a mode switch left out of its timing, while the new mode builds up
+const MODE_SETTLE_FRAMES: u32 = 30;
+
+// Entity and asset counts are refreshed this often, since counting isn't free
+const COUNT_INTERVAL_SECS: f64 = 1.0;
+// Refreshes in a row with the camera still and asset counts growing each time
+// before a leak is suspected
+const LEAK_SAMPLES: usize = 30;
+
+// Seconds of frame times behind the percentiles
+const FRAME_WINDOW_SECS: f64 = 10.0;
+
+// Slowest systems listed in the extended overlay
+const TIMING_ENTRIES: usize = 8;
+
+// Frame time graph, one bar per frame, newest on the right
+const GRAPH_FRAMES: usize = 240;
+const GRAPH_HEIGHT: f32 = 80.0;
+// Frame time at the top of the graph; longer frames are clipped
+const GRAPH_MAX_MS: f64 = 50.0;
+// Reference lines, at 60 and 30 FPS
+const GRAPH_TARGET_MS: [f64; 2] = [1000.0 / 60.0, 1000.0 / 30.0];
 
 pub struct DiagnosticsPlugin;
 
@@ -11,75 +64,448 @@ impl Plugin for DiagnosticsPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(FrameTimeDiagnosticsPlugin::default())
             .init_resource::<PerformanceStats>()
-            .add_systems(Startup, setup_diagnostics)
-            .add_systems(Update, (
+            .init_resource::<RenderModeTimings>()
+            .init_resource::<ChunkInspector>()
+            .init_resource::<OverlaySettings>()
+            .add_systems(Startup, (setup_diagnostics, setup_crosshair, setup_frame_graph))
+            .add_systems(Update, (toggle_diagnostics, pin_inspected_chunk))
+            // Per-frame counters are complete once Update is done
+            .add_systems(PostUpdate, (
                 update_performance_stats,
+                update_frame_time_window,
+                update_lod_distribution,
+                update_entity_counts,
+                record_render_mode_timing,
+                apply_overlay_settings,
                 update_diagnostics_text,
-            ).chain());
+                update_target_text,
+                update_camera_feedback_text,
+                sync_diagnostics_visibility,
+                update_frame_graph,
+                // Ahead of the log, which flushes when the benchmark exits
+                run_benchmark,
+                write_stats_log,
+            ).chain().before(UiSystem::Layout))
+            .register_console_command("stats", "stats: prints frame, chunk and entity counts", stats_command);
     }
 }
 
+fn stats_command(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let stats = world.resource::<PerformanceStats>();
+    Ok(format!(
+        "FPS {:.1} ({:.2}ms)\n{} chunks loaded, {} visible\n{} voxels loaded, {} rendered\n{} entities, {} meshes, {} materials",
+        stats.fps,
+        stats.frame_time,
+        stats.loaded_chunks,
+        stats.visible_chunks,
+        stats.loaded_voxels,
+        stats.voxels_rendered,
+        stats.entities,
+        stats.meshes,
+        stats.materials(),
+    ))
+}
+
 #[derive(Resource, Default)]
 struct PerformanceStats {
+    render_mode: Option<RenderMode>,
     voxels_rendered: usize,
+    // Visible voxels before LOD reduction
+    voxels_full_detail: usize,
     visible_chunks: usize,
+    // Every loaded chunk, drawn or not
+    loaded_chunks: usize,
+    loaded_voxels: usize,
+    culling: CullingStats,
+    meshes_rebuilt: usize,
+    meshes_queued: usize,
+    meshes_building: usize,
+    meshes_ready: usize,
+    cached_materials: usize,
+    material_hit_rate: f64,
+    billboards_spawned: usize,
+    billboards_despawned: usize,
     camera_position: Vec3,
+    camera_speed: f32,
+    camera_mode: String,
     frame_time: f64,
     fps: f64,
+    // Unsmoothed frame times in milliseconds, oldest first
+    frame_times: VecDeque<f64>,
+    frame_window: FrameTimeWindow,
+    // Drawn chunks at each LOD level, by level, and chunks drawn as impostors
+    lod_chunks: Vec<usize>,
+    impostor_chunks: usize,
+    // Refreshed every COUNT_INTERVAL_SECS
+    entities: usize,
+    billboard_entities: usize,
+    mesh_entities: usize,
+    meshes: usize,
+    standard_materials: usize,
+    billboard_materials: usize,
+    voxel_mesh_materials: usize,
+}
+
+impl PerformanceStats {
+    fn materials(&self) -> usize {
+        self.standard_materials + self.billboard_materials + self.voxel_mesh_materials
+    }
+}
+
+// Frame times over the last FRAME_WINDOW_SECS, kept in arrival order to expire
+// them and sorted for the percentiles; both are a binary search and a shift
+#[derive(Default)]
+struct FrameTimeWindow {
+    // Elapsed seconds and milliseconds, oldest first
+    arrivals: VecDeque<(f64, f64)>,
+    sorted: Vec<f64>,
+}
+
+impl FrameTimeWindow {
+    fn push(&mut self, now: f64, milliseconds: f64) {
+        while let Some(&(at, old)) = self.arrivals.front() {
+            if now - at <= FRAME_WINDOW_SECS {
+                break;
+            }
+            self.arrivals.pop_front();
+            let index = self.sorted.partition_point(|value| *value < old);
+            self.sorted.remove(index);
+        }
+        let index = self.sorted.partition_point(|value| *value < milliseconds);
+        self.sorted.insert(index, milliseconds);
+        self.arrivals.push_back((now, milliseconds));
+    }
+
+    fn clear(&mut self) {
+        self.arrivals.clear();
+        self.sorted.clear();
+    }
+
+    // Nearest rank, 0 while empty
+    fn percentile(&self, fraction: f64) -> f64 {
+        let rank = ((self.sorted.len() as f64 * fraction).ceil() as usize).clamp(1, self.sorted.len().max(1));
+        self.sorted.get(rank - 1).copied().unwrap_or(0.0)
+    }
+
+    fn max(&self) -> f64 {
+        self.sorted.last().copied().unwrap_or(0.0)
+    }
+}
+
+// Chunk described in the extended overlay: the pinned one, or else the targeted one
+#[derive(Resource, Default)]
+struct ChunkInspector {
+    pinned: Option<IVec3>,
+}
+
+// Asset counts from recent refreshes, for spotting leaks on a static scene
+#[derive(Default)]
+struct AssetGrowth {
+    last_count: f64,
+    camera_position: Vec3,
+    // Mesh and material counts, oldest first
+    samples: VecDeque<(usize, usize)>,
+    warned: bool,
+}
+
+// Frame time averaged over every settled frame spent in each render mode, kept
+// across switches so the modes can be compared side by side
+#[derive(Resource, Default)]
+struct RenderModeTimings {
+    modes: HashMap<RenderMode, ModeTiming>,
+    // Mode of the previous frame, and frames left before the current one is sampled
+    last_mode: Option<RenderMode>,
+    settle_frames: u32,
+}
+
+#[derive(Default, Clone, Copy)]
+struct ModeTiming {
+    frames: u32,
+    total_seconds: f64,
+}
+
+impl ModeTiming {
+    fn average_ms(&self) -> f64 {
+        if self.frames == 0 {
+            0.0
+        } else {
+            self.total_seconds * 1000.0 / self.frames as f64
+        }
+    }
 }
 
 #[derive(Component)]
 struct DiagnosticsText;
 
+// Frame time graph, shown with the extended diagnostics
+#[derive(Component)]
+struct FrameGraph;
+
+// One bar of the frame graph, counted from the left
+#[derive(Component)]
+struct FrameGraphBar(usize);
+
+// Readout of the voxel under the crosshair
+#[derive(Component)]
+struct TargetText;
+
+// Camera speed or sensitivity, shown for a moment after the scroll wheel changes it
+#[derive(Component)]
+struct CameraFeedbackText;
+
+// Shared text styles so other overlays match the diagnostics panel
+pub fn header_text_style() -> TextStyle {
+    TextStyle {
+        font_size: 20.0,
+        color: Color::WHITE,
+        ..default()
+    }
+}
+
+pub fn body_text_style() -> TextStyle {
+    TextStyle {
+        font_size: 18.0,
+        color: Color::YELLOW,
+        ..default()
+    }
+}
+
+// Where the diagnostics text sits on screen
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum OverlayAnchor {
+    #[default]
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+// Layout of the diagnostics text, applied whenever it changes
+#[derive(Resource, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    // Multiplies text sizes and margins, for high-DPI displays
+    pub scale: f32,
+    pub anchor: OverlayAnchor,
+    // Opacity of the panel behind the text; 0 leaves it out
+    pub background_opacity: f32,
+    // FPS below these turns yellow, then red
+    pub fps_warning: f64,
+    pub fps_critical: f64,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            anchor: OverlayAnchor::TopLeft,
+            background_opacity: 0.0,
+            fps_warning: 60.0,
+            fps_critical: 30.0,
+        }
+    }
+}
+
+impl OverlaySettings {
+    fn fps_color(&self, fps: f64) -> Color {
+        if fps < self.fps_critical {
+            Color::RED
+        } else if fps < self.fps_warning {
+            Color::YELLOW
+        } else {
+            Color::GREEN
+        }
+    }
+}
+
+fn apply_overlay_settings(
+    overlay: Res<OverlaySettings>,
+    mut query: Query<(&mut Style, &mut Text, &mut BackgroundColor), With<DiagnosticsText>>,
+) {
+    if !overlay.is_changed() {
+        return;
+    }
+    let scale = overlay.scale.clamp(0.25, 8.0);
+    let margin = Val::Px(10.0 * scale);
+    let (top, bottom, left, right) = match overlay.anchor {
+        OverlayAnchor::TopLeft => (margin, Val::Auto, margin, Val::Auto),
+        OverlayAnchor::TopRight => (margin, Val::Auto, Val::Auto, margin),
+        OverlayAnchor::BottomLeft => (Val::Auto, margin, margin, Val::Auto),
+        OverlayAnchor::BottomRight => (Val::Auto, margin, Val::Auto, margin),
+    };
+    for (mut style, mut text, mut background) in &mut query {
+        style.top = top;
+        style.bottom = bottom;
+        style.left = left;
+        style.right = right;
+        style.padding = UiRect::all(Val::Px(6.0 * scale));
+        for (index, section) in text.sections.iter_mut().enumerate() {
+            let base = if index == 0 { header_text_style() } else { body_text_style() };
+            section.style.font_size = base.font_size * scale;
+        }
+        *background = Color::rgba(0.0, 0.0, 0.0, overlay.background_opacity.clamp(0.0, 1.0)).into();
+    }
+}
+
 fn setup_diagnostics(mut commands: Commands) {
     // Spawn diagnostics text overlay
     commands.spawn((
+        // Header, render mode, FPS in its threshold color, then the rest; placed,
+        // sized and backed by apply_overlay_settings
         TextBundle::from_sections([
-            TextSection::new(
-                "Diagnostics\n",
-                TextStyle {
-                    font_size: 20.0,
-                    color: Color::WHITE,
-                    ..default()
-                },
-            ),
-            TextSection::from_style(TextStyle {
-                font_size: 18.0,
-                color: Color::YELLOW,
-                ..default()
-            }),
+            TextSection::new("Diagnostics\n", header_text_style()),
+            TextSection::from_style(body_text_style()),
+            TextSection::from_style(body_text_style()),
+            TextSection::from_style(body_text_style()),
         ])
         .with_style(Style {
             position_type: PositionType::Absolute,
-            top: Val::Px(10.0),
-            left: Val::Px(10.0),
             ..default()
         }),
         DiagnosticsText,
     ));
+
+    commands.spawn((
+        TextBundle::from_section("", body_text_style()).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            left: Val::Px(10.0),
+            ..default()
+        }),
+        TargetText,
+    ));
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(40.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", header_text_style()),
+                CameraFeedbackText,
+            ));
+        });
+}
+
+// Two bars crossing at the screen center, where picking casts its ray
+fn setup_crosshair(mut commands: Commands) {
+    let bar = |width: f32, height: f32| NodeBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            width: Val::Px(width),
+            height: Val::Px(height),
+            ..default()
+        },
+        background_color: Color::rgba(1.0, 1.0, 1.0, 0.8).into(),
+        ..default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(bar(CROSSHAIR_SIZE, CROSSHAIR_THICKNESS));
+            parent.spawn(bar(CROSSHAIR_THICKNESS, CROSSHAIR_SIZE));
+        });
 }
 
+fn toggle_diagnostics(
+    input: ActionInput,
+    mut settings: ResMut<VoxelRenderSettings>,
+) {
+    if !input.just_pressed(Action::ToggleDiagnostics) {
+        return;
+    }
+    // Hidden, then compact, then extended
+    let (show, extended) = match (settings.show_diagnostics, settings.extended_diagnostics) {
+        (false, _) => (true, false),
+        (true, false) => (true, true),
+        (true, true) => (false, false),
+    };
+    settings.show_diagnostics = show;
+    settings.extended_diagnostics = extended;
+}
+
+fn pin_inspected_chunk(input: ActionInput, target: Res<TargetedVoxel>, mut inspector: ResMut<ChunkInspector>) {
+    if !input.just_pressed(Action::PinInspectedChunk) || input.pressed(Action::EditModifier) {
+        return;
+    }
+    inspector.pinned = match (inspector.pinned, target.hit) {
+        (Some(position), _) => {
+            info!("Unpinned chunk {}", position);
+            None
+        }
+        (None, Some(hit)) => {
+            info!("Pinned chunk {}", hit.chunk);
+            Some(hit.chunk)
+        }
+        (None, None) => None,
+    };
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_performance_stats(
     mut stats: ResMut<PerformanceStats>,
     diagnostics: Res<DiagnosticsStore>,
-    chunks: Query<&VoxelChunk>,
-    camera: Query<&Transform, With<Camera>>,
+    chunks: Query<(&VoxelChunk, &ViewVisibility)>,
+    camera: Query<(&Transform, &CameraController)>,
+    meshing_stats: Res<MeshingStats>,
+    material_cache: Res<BillboardMaterialCache>,
+    billboard_stats: Res<BillboardStats>,
+    settings: Res<VoxelRenderSettings>,
+    culling_stats: Res<CullingStats>,
 ) {
+    stats.render_mode = Some(settings.render_mode);
+
     // Update voxel count
-    stats.voxels_rendered = chunks
-        .iter()
-        .filter(|chunk| chunk.visible)
+    // Bevy's view visibility, from the previous frame's visibility pass
+    let visible_chunks = || chunks.iter().filter(|(_, visibility)| visibility.get()).map(|(chunk, _)| chunk);
+    stats.voxels_rendered = visible_chunks()
+        .map(|chunk| chunk.lod_voxel_count)
+        .sum();
+    stats.voxels_full_detail = visible_chunks()
         .map(|chunk| chunk.voxels.len())
         .sum();
     
-    stats.visible_chunks = chunks
-        .iter()
-        .filter(|chunk| chunk.visible)
-        .count();
+    stats.visible_chunks = visible_chunks().count();
+    stats.loaded_chunks = chunks.iter().len();
+    stats.loaded_voxels = chunks.iter().map(|(chunk, _)| chunk.voxels.len() + chunk.hidden_voxels.len()).sum();
+    stats.culling = culling_stats.clone();
+
+    stats.meshes_rebuilt = meshing_stats.meshes_rebuilt;
+    stats.meshes_queued = meshing_stats.queued;
+    stats.meshes_building = meshing_stats.building;
+    stats.meshes_ready = meshing_stats.ready;
+    stats.cached_materials = material_cache.len();
+    stats.material_hit_rate = material_cache.hit_rate();
+    stats.billboards_spawned = billboard_stats.spawned;
+    stats.billboards_despawned = billboard_stats.despawned;
     
     // Update camera position
-    if let Ok(camera_transform) = camera.get_single() {
+    if let Ok((camera_transform, controller)) = camera.get_single() {
         stats.camera_position = camera_transform.translation;
+        stats.camera_speed = controller.effective_speed;
+        stats.camera_mode = match controller.mode {
+            CameraMode::Fly if controller.collision => String::from("Fly (collide)"),
+            CameraMode::Fly => String::from("Fly (noclip)"),
+            mode => format!("{:?}", mode),
+        };
     }
     
     // Update FPS and frame time
@@ -93,23 +519,484 @@ fn update_performance_stats(
         if let Some(frame_time_value) = frame_time.smoothed() {
             stats.frame_time = frame_time_value;
         }
+        if let Some(latest) = frame_time.value() {
+            if stats.frame_times.len() == GRAPH_FRAMES {
+                stats.frame_times.pop_front();
+            }
+            stats.frame_times.push_back(latest);
+        }
+    }
+}
+
+// Starts over when the world is cleared, regenerated or loaded, so spikes from
+// the old world don't count against the new one
+fn update_frame_time_window(
+    time: Res<Time>,
+    mut world_commands: EventReader<WorldCommand>,
+    mut stats: ResMut<PerformanceStats>,
+) {
+    if world_commands.read().count() > 0 {
+        stats.frame_window.clear();
+    }
+    stats.frame_window.push(time.elapsed_seconds_f64(), time.delta_seconds_f64() * 1000.0);
+}
+
+fn update_lod_distribution(mut stats: ResMut<PerformanceStats>, chunks: Query<&VoxelChunk>) {
+    let mut lod_chunks = Vec::new();
+    let mut impostor_chunks = 0;
+    for chunk in &chunks {
+        match chunk.cull_reason {
+            None => {
+                if lod_chunks.len() <= chunk.lod_level {
+                    lod_chunks.resize(chunk.lod_level + 1, 0);
+                }
+                lod_chunks[chunk.lod_level] += 1;
+            }
+            Some(CullReason::Impostor) => impostor_chunks += 1,
+            Some(_) => {}
+        }
+    }
+    if stats.lod_chunks != lod_chunks || stats.impostor_chunks != impostor_chunks {
+        stats.lod_chunks = lod_chunks;
+        stats.impostor_chunks = impostor_chunks;
+    }
+}
+
+fn lod_distribution_text(stats: &PerformanceStats) -> String {
+    let levels: Vec<String> = stats.lod_chunks.iter().enumerate().map(|(level, count)| format!("LOD {}: {}", level, count)).collect();
+    let levels = if levels.is_empty() { String::from("no chunks drawn") } else { levels.join(", ") };
+    format!("Chunk LODs: {}, impostors: {}\n", levels, stats.impostor_chunks)
+}
+
+// A warning is logged once when mesh or material counts keep growing while the
+// camera stands still, which points to a leak
+#[allow(clippy::too_many_arguments)]
+fn update_entity_counts(
+    time: Res<Time>,
+    mut stats: ResMut<PerformanceStats>,
+    mut growth: Local<AssetGrowth>,
+    entities: &Entities,
+    billboards: Query<(), With<BillboardMarker>>,
+    mesh_entities: Query<(), With<Handle<Mesh>>>,
+    meshes: Res<Assets<Mesh>>,
+    standard_materials: Res<Assets<StandardMaterial>>,
+    billboard_materials: Res<Assets<FacingBillboardMaterial>>,
+    voxel_mesh_materials: Res<Assets<VoxelMeshMaterial>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    if now - growth.last_count < COUNT_INTERVAL_SECS {
+        return;
+    }
+    growth.last_count = now;
+    stats.entities = entities.len() as usize;
+    stats.billboard_entities = billboards.iter().len();
+    stats.mesh_entities = mesh_entities.iter().len();
+    stats.meshes = meshes.len();
+    stats.standard_materials = standard_materials.len();
+    stats.billboard_materials = billboard_materials.len();
+    stats.voxel_mesh_materials = voxel_mesh_materials.len();
+
+    if stats.camera_position != growth.camera_position {
+        growth.camera_position = stats.camera_position;
+        growth.samples.clear();
+        growth.warned = false;
+    }
+    if growth.samples.len() > LEAK_SAMPLES {
+        growth.samples.pop_front();
+    }
+    growth.samples.push_back((stats.meshes, stats.materials()));
+    if growth.warned || growth.samples.len() <= LEAK_SAMPLES {
+        return;
+    }
+    let grew = |count: fn(&(usize, usize)) -> usize| {
+        growth.samples.iter().zip(growth.samples.iter().skip(1)).all(|(before, after)| count(after) > count(before))
+    };
+    let (meshes_grew, materials_grew) = (grew(|sample| sample.0), grew(|sample| sample.1));
+    if meshes_grew || materials_grew {
+        warn!(
+            "Asset counts grew for {} seconds with the camera still ({} meshes, {} materials); something may be leaking",
+            LEAK_SAMPLES as f64 * COUNT_INTERVAL_SECS,
+            stats.meshes,
+            stats.materials(),
+        );
+        growth.warned = true;
+    }
+}
+
+// Frames are only sampled once a switch has settled and no meshes are pending, so
+// the rebuild after a switch doesn't count against the new mode
+fn record_render_mode_timing(
+    time: Res<Time>,
+    stats: Res<PerformanceStats>,
+    mut timings: ResMut<RenderModeTimings>,
+) {
+    let Some(mode) = stats.render_mode else {
+        return;
+    };
+    if timings.last_mode != Some(mode) {
+        timings.last_mode = Some(mode);
+        timings.settle_frames = MODE_SETTLE_FRAMES;
+        return;
+    }
+    if timings.settle_frames > 0 {
+        timings.settle_frames -= 1;
+        return;
+    }
+    if stats.meshes_queued + stats.meshes_building + stats.meshes_ready > 0 {
+        return;
+    }
+
+    let timing = timings.modes.entry(mode).or_default();
+    timing.frames += 1;
+    timing.total_seconds += time.delta_seconds_f64();
+}
+
+fn render_mode_timing_text(stats: &PerformanceStats, timings: &RenderModeTimings) -> String {
+    let mut text = String::from("Frame Time by Mode:\n");
+    for mode in RenderMode::ALL {
+        let marker = if stats.render_mode == Some(mode) { ">" } else { " " };
+        match timings.modes.get(&mode) {
+            Some(timing) => text.push_str(&format!(
+                "{} {:?}: {:.2}ms ({} frames)\n",
+                marker,
+                mode,
+                timing.average_ms(),
+                timing.frames,
+            )),
+            None => text.push_str(&format!("{} {:?}: -\n", marker, mode)),
+        }
     }
+    text
 }
 
+fn chunk_inspector_text(position: Option<IVec3>, pinned: bool, chunk: Option<&VoxelChunk>) -> String {
+    let Some(position) = position else {
+        return String::from("Chunk Inspector: nothing targeted\n");
+    };
+    let mut text = format!(
+        "Chunk Inspector ({}): {} {} {}\n",
+        if pinned { "pinned, I unpins" } else { "I pins" },
+        position.x,
+        position.y,
+        position.z,
+    );
+    let Some(chunk) = chunk else {
+        text.push_str("  Not loaded\n");
+        return text;
+    };
+    let state = match chunk.cull_reason {
+        Some(reason) => format!("Hidden ({:?})", reason),
+        None => String::from("Visible"),
+    };
+    text.push_str(&format!(
+        "  Version: {}\n  Voxels: {} ({} exposed, {} hidden)\n  LOD: {} ({} voxels drawn)\n  State: {}\n  Memory: {:.1} KB\n  Last Rebuild: {:.2}ms\n",
+        chunk.version,
+        chunk.voxels.len() + chunk.hidden_voxels.len(),
+        chunk.voxels.len(),
+        chunk.hidden_voxels.len(),
+        chunk.lod_level,
+        chunk.lod_voxel_count,
+        state,
+        chunk.memory_bytes() as f64 / 1024.0,
+        chunk.rebuild_ms,
+    ));
+    text
+}
+
+#[allow(clippy::too_many_arguments)]
 fn update_diagnostics_text(
     stats: Res<PerformanceStats>,
+    timings: Res<RenderModeTimings>,
+    settings: Res<VoxelRenderSettings>,
+    system_timings: Res<SystemTimings>,
+    target: Res<TargetedVoxel>,
+    inspector: Res<ChunkInspector>,
+    index: Res<ChunkSpatialIndex>,
+    chunks: Query<&VoxelChunk>,
+    overlay: Res<OverlaySettings>,
     mut query: Query<&mut Text, With<DiagnosticsText>>,
 ) {
+    let mode = format!("Render Mode: {}\n", stats.render_mode.map_or_else(String::new, |mode| format!("{:?}", mode)));
+    let fps = format!("FPS: {:.1}\n", stats.fps);
+    let mut value = format!(
+        "Frame Time: {:.2}ms (p50 {:.1}, p95 {:.1}, p99 {:.1}, max {:.1})\nVoxels Rendered: {} (of {})\nVisible Chunks: {}\nCamera Pos: {:.1} {:.1} {:.1}\nCamera Mode: {}\n",
+        stats.frame_time,
+        stats.frame_window.percentile(0.5),
+        stats.frame_window.percentile(0.95),
+        stats.frame_window.percentile(0.99),
+        stats.frame_window.max(),
+        stats.voxels_rendered,
+        stats.voxels_full_detail,
+        stats.visible_chunks,
+        stats.camera_position.x,
+        stats.camera_position.y,
+        stats.camera_position.z,
+        stats.camera_mode,
+    );
+    if settings.extended_diagnostics {
+        // Voxel lists only; meshes and GPU buffers aren't counted
+        let voxel_megabytes = (stats.loaded_voxels * std::mem::size_of::<Voxel>()) as f64 / (1024.0 * 1024.0);
+        value.push_str(&format!(
+            "Entities: {} ({} billboards, {} with meshes)\nAssets: {} meshes, {} materials ({} standard, {} billboard, {} voxel mesh)\nCulled: {} of {} chunks ({} distance, {} frustum, {} occluded, {} impostors)\nVoxel Culling: {} -> {} | Entities +{} -{}\nMeshes Rebuilt: {}\nMeshing: {} queued, {} building, {} ready\nMaterial Cache: {} ({:.1}% hits)\nBillboards Spawned/Despawned: {}/{}\nVoxel Memory: {:.1} MB ({} voxels in {} chunks)\nCamera Speed: {:.1}\n{}",
+            stats.entities,
+            stats.billboard_entities,
+            stats.mesh_entities,
+            stats.meshes,
+            stats.materials(),
+            stats.standard_materials,
+            stats.billboard_materials,
+            stats.voxel_mesh_materials,
+            stats.culling.culled_distance + stats.culling.culled_frustum + stats.culling.culled_occluded,
+            stats.culling.chunks_tested,
+            stats.culling.culled_distance,
+            stats.culling.culled_frustum,
+            stats.culling.culled_occluded,
+            stats.culling.impostors,
+            stats.culling.voxels_before_culling,
+            stats.culling.voxels_after_culling,
+            stats.culling.entities_spawned,
+            stats.culling.entities_despawned,
+            stats.meshes_rebuilt,
+            stats.meshes_queued,
+            stats.meshes_building,
+            stats.meshes_ready,
+            stats.cached_materials,
+            stats.material_hit_rate * 100.0,
+            stats.billboards_spawned,
+            stats.billboards_despawned,
+            voxel_megabytes,
+            stats.loaded_voxels,
+            stats.loaded_chunks,
+            stats.camera_speed,
+            render_mode_timing_text(&stats, &timings),
+        ));
+        value.push_str(&lod_distribution_text(&stats));
+        // Billboard mode draws an entity per voxel, the others meshes per chunk
+        let drawing = match stats.render_mode {
+            Some(RenderMode::Billboards) => format!("{} billboard entities", stats.billboard_entities),
+            _ => format!("{} mesh entities", stats.mesh_entities),
+        };
+        value.push_str(&format!("Drawing: {}\n", drawing));
+        value.push_str("System Times:\n");
+        for (name, ms) in system_timings.slowest(TIMING_ENTRIES) {
+            value.push_str(&format!("  {}: {:.2}ms\n", name, ms));
+        }
+        let inspected = inspector.pinned.or(target.hit.map(|hit| hit.chunk));
+        let chunk = inspected.and_then(|position| index.chunk_at(position)).and_then(|entity| chunks.get(entity).ok());
+        value.push_str(&chunk_inspector_text(inspected, inspector.pinned.is_some(), chunk));
+    }
     for mut text in &mut query {
-        text.sections[1].value = format!(
-            "FPS: {:.1}\nFrame Time: {:.2}ms\nVoxels Rendered: {}\nVisible Chunks: {}\nCamera Pos: {:.1} {:.1} {:.1}\n",
-            stats.fps,
-            stats.frame_time,
-            stats.voxels_rendered,
-            stats.visible_chunks,
-            stats.camera_position.x,
-            stats.camera_position.y,
-            stats.camera_position.z,
-        );
+        text.sections[1].value.clone_from(&mode);
+        text.sections[2].value.clone_from(&fps);
+        text.sections[2].style.color = overlay.fps_color(stats.fps);
+        text.sections[3].value.clone_from(&value);
+    }
+}
+
+fn update_target_text(
+    target: Res<TargetedVoxel>,
+    mut query: Query<&mut Text, With<TargetText>>,
+) {
+    let value = match target.hit {
+        Some(hit) => {
+            let [r, g, b, _] = target.color.as_rgba_u8();
+            format!(
+                "Target: {} {} {} (chunk {} {} {}, local {} {} {}) #{:02X}{:02X}{:02X} at {:.1}",
+                hit.world.x,
+                hit.world.y,
+                hit.world.z,
+                hit.chunk.x,
+                hit.chunk.y,
+                hit.chunk.z,
+                hit.local.x,
+                hit.local.y,
+                hit.local.z,
+                r,
+                g,
+                b,
+                hit.distance,
+            )
+        }
+        None => String::from("Target: none"),
+    };
+    for mut text in &mut query {
+        text.sections[0].value.clone_from(&value);
+    }
+}
+
+// Shown regardless of the diagnostics toggle, since it answers the scroll,
+// bookmark or screenshot keys, edits, world saves and imports directly. The newest
+// message wins.
+#[allow(clippy::too_many_arguments)]
+fn update_camera_feedback_text(
+    time: Res<Time>,
+    feedback: Res<CameraFeedback>,
+    screenshot: Res<ScreenshotState>,
+    edits: Res<EditFeedback>,
+    saver: Res<WorldSaver>,
+    importer: Res<PointCloudImporter>,
+    camera: Query<&CameraController>,
+    mut query: Query<(&mut Text, &mut Visibility), With<CameraFeedbackText>>,
+) {
+    let now = time.elapsed_seconds_f64();
+    let camera_message = feedback.last_change.and_then(|(setting, at)| {
+        let text = match (setting, camera.get_single()) {
+            (CameraSetting::Speed, Ok(controller)) => format!("Speed: {:.1}", controller.speed),
+            (CameraSetting::Sensitivity, Ok(controller)) => format!("Sensitivity: {:.4}", controller.sensitivity),
+            (CameraSetting::BookmarkSaved(slot), _) => format!("Bookmark {} saved", slot + 1),
+            (CameraSetting::BookmarkRecalled(slot), _) => format!("Bookmark {}", slot + 1),
+            _ => return None,
+        };
+        (now - at < CAMERA_FEEDBACK_SECONDS).then_some((text, at))
+    });
+    let saved = screenshot
+        .last_saved
+        .as_ref()
+        .filter(|(_, at)| now - at < CAMERA_FEEDBACK_SECONDS)
+        .map(|(path, at)| (format!("Saved {}", path), *at));
+    let warning = edits
+        .last_warning
+        .as_ref()
+        .filter(|(_, at)| now - at < EDIT_WARNING_SECONDS)
+        .map(|(warning, at)| (format!("Warning: {}", warning), *at));
+    // Saving stays up until the save finishes
+    let world_save = saver
+        .status
+        .filter(|(status, at)| *status == SaveStatus::Saving || now - at < CAMERA_FEEDBACK_SECONDS)
+        .map(|(status, at)| {
+            let text = match status {
+                SaveStatus::Saving => "Saving…",
+                SaveStatus::Saved => "Saved",
+                SaveStatus::Failed => "Save failed",
+            };
+            (text.to_string(), at)
+        });
+    // Stays up, as the newest message, until the import finishes
+    let import = importer
+        .progress()
+        .map(|fraction| (format!("Importing point cloud: {:.0}% (Esc cancels)", fraction * 100.0), now));
+    let value = [camera_message, saved, warning, world_save, import]
+        .into_iter()
+        .flatten()
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map_or_else(String::new, |(text, _)| text);
+
+    for (mut text, mut visibility) in &mut query {
+        let target = if value.is_empty() { Visibility::Hidden } else { Visibility::Inherited };
+        if *visibility != target {
+            *visibility = target;
+        }
+        if text.sections[0].value != value {
+            text.sections[0].value.clone_from(&value);
+        }
+    }
+}
+
+fn sync_diagnostics_visibility(
+    settings: Res<VoxelRenderSettings>,
+    mut query: Query<&mut Visibility, Or<(With<DiagnosticsText>, With<TargetText>)>>,
+) {
+    let target = if settings.show_diagnostics { Visibility::Inherited } else { Visibility::Hidden };
+    for mut visibility in &mut query {
+        if *visibility != target {
+            *visibility = target;
+        }
     }
-}
\ No newline at end of file
+}
+
+fn frame_time_color(ms: f64) -> Color {
+    if ms <= GRAPH_TARGET_MS[0] {
+        Color::GREEN
+    } else if ms <= GRAPH_TARGET_MS[1] {
+        Color::YELLOW
+    } else {
+        Color::RED
+    }
+}
+
+fn graph_height(ms: f64) -> f32 {
+    (ms / GRAPH_MAX_MS).min(1.0) as f32 * GRAPH_HEIGHT
+}
+
+// Bars stand on the bottom edge, under lines marking the reference frame times
+fn setup_frame_graph(mut commands: Commands) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(10.0),
+                    right: Val::Px(10.0),
+                    width: Val::Px(GRAPH_FRAMES as f32),
+                    height: Val::Px(GRAPH_HEIGHT),
+                    align_items: AlignItems::FlexEnd,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                visibility: Visibility::Hidden,
+                ..default()
+            },
+            FrameGraph,
+        ))
+        .with_children(|parent| {
+            for index in 0..GRAPH_FRAMES {
+                parent.spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Px(1.0),
+                            height: Val::Px(0.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    FrameGraphBar(index),
+                ));
+            }
+            for ms in GRAPH_TARGET_MS {
+                parent.spawn(NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        bottom: Val::Px(graph_height(ms)),
+                        width: Val::Percent(100.0),
+                        height: Val::Px(1.0),
+                        ..default()
+                    },
+                    background_color: frame_time_color(ms).with_a(0.6).into(),
+                    ..default()
+                });
+            }
+        });
+}
+
+// Left alone while hidden, so it costs nothing then
+fn update_frame_graph(
+    stats: Res<PerformanceStats>,
+    settings: Res<VoxelRenderSettings>,
+    mut graph: Query<&mut Visibility, With<FrameGraph>>,
+    mut bars: Query<(&FrameGraphBar, &mut Style, &mut BackgroundColor)>,
+) {
+    let shown = settings.show_diagnostics && settings.extended_diagnostics;
+    let target = if shown { Visibility::Inherited } else { Visibility::Hidden };
+    for mut visibility in &mut graph {
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+    if !shown {
+        return;
+    }
+
+    // Fewer frames than bars leaves the oldest bars empty
+    let empty = GRAPH_FRAMES - stats.frame_times.len();
+    for (bar, mut style, mut background) in &mut bars {
+        let ms = bar.0.checked_sub(empty).and_then(|index| stats.frame_times.get(index)).copied();
+        let height = Val::Px(ms.map_or(0.0, graph_height));
+        if style.height != height {
+            style.height = height;
+        }
+        let color = ms.map_or(Color::NONE, frame_time_color);
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
@@ -0,0 +1,249 @@
+// src/diagnostics/benchmark.rs
+use bevy::{app::AppExit, prelude::*};
+use serde::Deserialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use crate::camera::CameraPath;
+use crate::generation::GenerationProgress;
+use crate::voxel::VoxelChunk;
+use super::PerformanceStats;
+
+const SCENARIO_DIRECTORY: &str = "benchmarks";
+// Each frame counts as this much simulated time, the camera path's default fixed
+// step, so a scenario runs the same number of frames however fast they are
+const SIMULATED_FRAME_SECS: f32 = 1.0 / 60.0;
+
+// A scripted run, read from benchmarks/<name>.ron
+#[derive(Deserialize, Clone, Debug)]
+pub struct BenchmarkScenario {
+    // As for --generator
+    pub generator: String,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub world_size: Option<[i32; 3]>,
+    // Camera path played once the world has generated; the camera stays put without one
+    #[serde(default)]
+    pub camera_path: Option<String>,
+    // Simulated seconds measured after generation
+    pub duration_secs: f32,
+    #[serde(default)]
+    pub thresholds: BenchmarkThresholds,
+}
+
+// Limits that fail the run when exceeded; unset ones aren't checked
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct BenchmarkThresholds {
+    pub max_average_ms: Option<f64>,
+    pub max_p95_ms: Option<f64>,
+    pub max_p99_ms: Option<f64>,
+    pub max_memory_mb: Option<f64>,
+}
+
+impl BenchmarkScenario {
+    pub fn path(name: &str) -> PathBuf {
+        PathBuf::from(SCENARIO_DIRECTORY).join(format!("{}.ron", name))
+    }
+
+    pub fn load(name: &str) -> Result<Self, BenchmarkError> {
+        let bytes = std::fs::read(Self::path(name)).map_err(BenchmarkError::Io)?;
+        let scenario: Self = ron::de::from_bytes(&bytes).map_err(BenchmarkError::Parse)?;
+        if !(scenario.duration_secs > 0.0 && scenario.duration_secs.is_finite()) {
+            return Err(BenchmarkError::InvalidDuration);
+        }
+        Ok(scenario)
+    }
+}
+
+#[derive(Debug)]
+pub enum BenchmarkError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    InvalidDuration,
+}
+
+impl fmt::Display for BenchmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BenchmarkError::Io(err) => write!(f, "could not read benchmark scenario: {}", err),
+            BenchmarkError::Parse(err) => write!(f, "could not parse benchmark scenario: {}", err),
+            BenchmarkError::InvalidDuration => write!(f, "benchmark duration must be a positive number of seconds"),
+        }
+    }
+}
+
+impl std::error::Error for BenchmarkError {}
+
+// A run's result, shared with main. App::run hands the app to the runner, so
+// main can't read BenchmarkRun once it returns.
+#[derive(Clone, Default)]
+pub struct BenchmarkOutcome(Arc<AtomicU8>);
+
+impl BenchmarkOutcome {
+    const UNFINISHED: u8 = 0;
+    const PASSED: u8 = 1;
+    const FAILED: u8 = 2;
+
+    // None until the run is over
+    pub fn passed(&self) -> Option<bool> {
+        match self.0.load(Ordering::Acquire) {
+            Self::UNFINISHED => None,
+            outcome => Some(outcome == Self::PASSED),
+        }
+    }
+
+    fn set(&self, passed: bool) {
+        self.0.store(if passed { Self::PASSED } else { Self::FAILED }, Ordering::Release);
+    }
+
+    // A failed or unfinished run exits nonzero
+    pub fn exit_code(&self) -> i32 {
+        if self.passed() == Some(true) { 0 } else { 1 }
+    }
+}
+
+// Set by --benchmark. Measures every frame from the end of generation until the
+// scenario's duration has been simulated, then prints a report and exits; main
+// turns a failed or unfinished run into a nonzero exit code through the outcome.
+#[derive(Resource)]
+pub struct BenchmarkRun {
+    pub name: String,
+    pub scenario: BenchmarkScenario,
+    pub outcome: BenchmarkOutcome,
+    started: bool,
+    // Milliseconds per measured frame
+    frame_times: Vec<f64>,
+    peak_memory_bytes: usize,
+    voxels_rendered_total: usize,
+}
+
+impl BenchmarkRun {
+    pub fn new(name: String, scenario: BenchmarkScenario) -> Self {
+        Self {
+            name,
+            scenario,
+            outcome: BenchmarkOutcome::default(),
+            started: false,
+            frame_times: Vec::new(),
+            peak_memory_bytes: 0,
+            voxels_rendered_total: 0,
+        }
+    }
+
+    // Prints the report and returns whether every threshold held
+    fn report(&self, chunks_generated: usize) -> bool {
+        let mut sorted = self.frame_times.clone();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |fraction: f64| {
+            let rank = ((sorted.len() as f64 * fraction).ceil() as usize).clamp(1, sorted.len().max(1));
+            sorted.get(rank - 1).copied().unwrap_or(0.0)
+        };
+        let frames = sorted.len().max(1);
+        let average = sorted.iter().sum::<f64>() / frames as f64;
+        let (p50, p95, p99) = (percentile(0.5), percentile(0.95), percentile(0.99));
+        let max = sorted.last().copied().unwrap_or(0.0);
+        let memory_mb = self.peak_memory_bytes as f64 / (1024.0 * 1024.0);
+
+        println!("Benchmark {}: {} frames over {:.1} simulated seconds", self.name, sorted.len(), self.scenario.duration_secs);
+        println!("  frame time: average {:.2}ms, p50 {:.2}ms, p95 {:.2}ms, p99 {:.2}ms, max {:.2}ms", average, p50, p95, p99, max);
+        println!("  peak chunk memory: {:.1} MB", memory_mb);
+        println!("  chunks generated: {}", chunks_generated);
+        println!("  voxels rendered: {} per frame on average", self.voxels_rendered_total / frames);
+
+        let thresholds = &self.scenario.thresholds;
+        let checks = [
+            ("average frame time", average, thresholds.max_average_ms, "ms"),
+            ("p95 frame time", p95, thresholds.max_p95_ms, "ms"),
+            ("p99 frame time", p99, thresholds.max_p99_ms, "ms"),
+            ("peak chunk memory", memory_mb, thresholds.max_memory_mb, " MB"),
+        ];
+        let mut passed = true;
+        for (name, value, limit, unit) in checks {
+            if let Some(limit) = limit.filter(|limit| value > *limit) {
+                println!("  FAIL: {} {:.2}{} is over {:.2}{}", name, value, unit, limit, unit);
+                passed = false;
+            }
+        }
+        println!("  {}", if passed { "PASS" } else { "FAIL" });
+        passed
+    }
+}
+
+pub(super) fn run_benchmark(
+    time: Res<Time>,
+    run: Option<ResMut<BenchmarkRun>>,
+    progress: Res<GenerationProgress>,
+    stats: Res<PerformanceStats>,
+    chunks: Query<&VoxelChunk>,
+    mut camera_path: ResMut<CameraPath>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let Some(mut run) = run else {
+        return;
+    };
+    if run.outcome.passed().is_some() {
+        return;
+    }
+    if !run.started {
+        if chunks.is_empty() || !progress.is_complete() {
+            return;
+        }
+        run.started = true;
+        if !camera_path.keyframes.is_empty() {
+            camera_path.play();
+        }
+        info!("Benchmark {} started after {:.1}s of generation", run.name, progress.elapsed);
+        return;
+    }
+
+    run.frame_times.push(time.delta_seconds_f64() * 1000.0);
+    let memory = chunks.iter().map(VoxelChunk::memory_bytes).sum::<usize>();
+    run.peak_memory_bytes = run.peak_memory_bytes.max(memory);
+    run.voxels_rendered_total += stats.voxels_rendered;
+    if run.frame_times.len() as f32 * SIMULATED_FRAME_SECS < run.scenario.duration_secs {
+        return;
+    }
+    let passed = run.report(progress.completed);
+    run.outcome.set(passed);
+    exit.send(AppExit);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs a scenario against an already generated world until it exits, the way
+    // main does, and returns what main would see
+    fn run_scenario(name: &str) -> BenchmarkOutcome {
+        let run = BenchmarkRun::new(name.to_string(), BenchmarkScenario::load(name).unwrap());
+        let outcome = run.outcome.clone();
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<GenerationProgress>()
+            .init_resource::<PerformanceStats>()
+            .init_resource::<CameraPath>()
+            .insert_resource(run)
+            .add_systems(Update, run_benchmark);
+        app.world.spawn(VoxelChunk::new(IVec3::ZERO, Vec::new()));
+        app.run();
+        outcome
+    }
+
+    #[test]
+    fn impossible_threshold_fails_the_run_with_exit_code_one() {
+        let outcome = run_scenario("impossible");
+        assert_eq!(outcome.passed(), Some(false));
+        assert_eq!(outcome.exit_code(), 1);
+    }
+
+    #[test]
+    fn unfinished_run_exits_nonzero() {
+        let outcome = BenchmarkOutcome::default();
+        assert_eq!(outcome.passed(), None);
+        assert_eq!(outcome.exit_code(), 1);
+        outcome.set(true);
+        assert_eq!(outcome.exit_code(), 0);
+    }
+}
@@ -0,0 +1,124 @@
+// src/diagnostics/stats_log.rs
+use bevy::{app::AppExit, prelude::*};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use crate::camera::CameraPath;
+use crate::generation::GenerationProgress;
+use crate::voxel::VoxelChunk;
+use super::PerformanceStats;
+
+// Rows between flushes, so a crash loses at most this many seconds
+const FLUSH_ROWS: usize = 10;
+const ROW_INTERVAL_SECS: f64 = 1.0;
+
+const HEADER: &str = "elapsed_secs,path_secs,fps,frame_ms_p50,frame_ms_p95,frame_ms_p99,frame_ms_max,\
+voxels_rendered,voxels_full_detail,voxels_loaded,visible_chunks,loaded_chunks,\
+culled_distance,culled_frustum,culled_occluded,impostors,chunk_memory_bytes,entities,meshes,materials,\
+meshes_queued,meshes_building,meshes_ready,chunks_generating";
+
+// Appends a CSV row of performance numbers every second to the file given with
+// --log-stats. A new or empty file gets a header first. Frame time percentiles
+// cover the overlay's rolling window. path_secs is the camera path playback time,
+// so runs of the same --play-path line up row by row.
+#[derive(Resource)]
+pub struct StatsLog {
+    pub path: PathBuf,
+    writer: Option<BufWriter<File>>,
+    last_row: f64,
+    unflushed: usize,
+}
+
+impl StatsLog {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            writer: None,
+            last_row: 0.0,
+            unflushed: 0,
+        }
+    }
+
+    fn open(&mut self) -> std::io::Result<&mut BufWriter<File>> {
+        if self.writer.is_none() {
+            let file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+            let empty = file.metadata()?.len() == 0;
+            let mut writer = BufWriter::new(file);
+            if empty {
+                writeln!(writer, "{}", HEADER)?;
+            }
+            self.writer = Some(writer);
+        }
+        Ok(self.writer.as_mut().unwrap())
+    }
+
+    fn flush(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            if let Err(err) = writer.flush() {
+                warn!("Could not write {}: {}", self.path.display(), err);
+            }
+        }
+        self.unflushed = 0;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(super) fn write_stats_log(
+    mut commands: Commands,
+    time: Res<Time>,
+    log: Option<ResMut<StatsLog>>,
+    mut exit: EventReader<AppExit>,
+    stats: Res<PerformanceStats>,
+    progress: Res<GenerationProgress>,
+    camera_path: Res<CameraPath>,
+    chunks: Query<&VoxelChunk>,
+) {
+    let Some(mut log) = log else {
+        return;
+    };
+    let exiting = exit.read().count() > 0;
+    let now = time.elapsed_seconds_f64();
+    if now - log.last_row >= ROW_INTERVAL_SECS {
+        log.last_row = now;
+        let row = format!(
+            "{:.3},{},{:.1},{:.2},{:.2},{:.2},{:.2},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            now,
+            camera_path.playback_time().map_or_else(String::new, |secs| format!("{:.3}", secs)),
+            stats.fps,
+            stats.frame_window.percentile(0.5),
+            stats.frame_window.percentile(0.95),
+            stats.frame_window.percentile(0.99),
+            stats.frame_window.max(),
+            stats.voxels_rendered,
+            stats.voxels_full_detail,
+            stats.loaded_voxels,
+            stats.visible_chunks,
+            stats.loaded_chunks,
+            stats.culling.culled_distance,
+            stats.culling.culled_frustum,
+            stats.culling.culled_occluded,
+            stats.culling.impostors,
+            chunks.iter().map(VoxelChunk::memory_bytes).sum::<usize>(),
+            stats.entities,
+            stats.meshes,
+            stats.materials(),
+            stats.meshes_queued,
+            stats.meshes_building,
+            stats.meshes_ready,
+            progress.requested.saturating_sub(progress.completed),
+        );
+        let written = log.open().and_then(|writer| writeln!(writer, "{}", row));
+        match written {
+            Ok(()) => log.unflushed += 1,
+            Err(err) => {
+                // One warning, not one a second
+                warn!("Could not write {}: {}; stats logging stopped", log.path.display(), err);
+                commands.remove_resource::<StatsLog>();
+                return;
+            }
+        }
+    }
+    if exiting || log.unflushed >= FLUSH_ROWS {
+        log.flush();
+    }
+}
@@ -0,0 +1,335 @@
+// src/editing.rs
+use bevy::prelude::*;
+use crate::bindings::{Action, ActionInput, KeyBindings};
+use crate::camera::{toggle_cursor_lock, CameraState};
+use crate::diagnostics::body_text_style;
+use crate::picking::TargetedVoxel;
+use crate::voxel::{process_dirty_chunks, shapes, EditOp, VoxelEditor, VoxelEdits, VoxelWorld};
+use crate::voxel_types::VoxelRenderSettings;
+use crate::voxel_types::KIND_PLAIN;
+
+mod clipboard;
+mod palette;
+mod prefab;
+mod sculpt;
+mod selection;
+
+pub use clipboard::{ClipboardPlugin, PasteSource, PasteState, VoxelClipboard};
+pub use palette::{PaintState, PalettePlugin};
+pub use prefab::{PrefabLibrary, PrefabPlugin, StampOverlap};
+pub use sculpt::{SculptPlugin, SculptSettings};
+pub use selection::{SelectionBox, SelectionPlugin, SelectionState};
+use selection::MAX_SELECTION_VOLUME;
+
+const MIN_BRUSH_RADIUS: u32 = 1;
+const MAX_BRUSH_RADIUS: u32 = 16;
+// Line previews draw at most this many cells
+const MAX_LINE_PREVIEW_CELLS: usize = 2048;
+
+pub struct EditingPlugin;
+
+impl Plugin for EditingPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((PalettePlugin, SelectionPlugin, ClipboardPlugin, PrefabPlugin, SculptPlugin))
+            .init_resource::<BrushSettings>()
+            .init_resource::<EditFeedback>()
+            .add_systems(Startup, setup_brush_text)
+            .add_systems(Update, (
+                adjust_brush,
+                cancel_line.before(toggle_cursor_lock),
+                // Before the cursor lock sees the click, so the click that locks the
+                // cursor doesn't also edit
+                apply_brush.before(toggle_cursor_lock).before(process_dirty_chunks),
+                undo_edits.before(process_dirty_chunks),
+                draw_line_preview,
+                update_brush_text,
+            ).chain());
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrushShape {
+    Cube,
+    Sphere,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BrushMode {
+    // Fills cells against the face under the crosshair
+    Place,
+    Erase,
+    // Recolors filled cells
+    Paint,
+    // Recolors the connected voxels sharing the targeted voxel's color
+    Fill,
+    // Places a line from the first click to the second, or a rectangle with Alt held
+    Line,
+    // Held to build up or dig down the surface; see SculptSettings
+    Raise,
+    Lower,
+}
+
+// Brush applied with a left click while the cursor is locked, in the PaintState
+// color. [ and ] change the radius, \ the shape and ' the mode.
+#[derive(Resource)]
+pub struct BrushSettings {
+    pub shape: BrushShape,
+    // In voxels, 1 to 16; radius 1 is a single voxel
+    pub radius: u32,
+    pub mode: BrushMode,
+    // Most voxels one fill recolors
+    pub fill_cap: usize,
+    // First click of a line, waiting for the second
+    line_start: Option<IVec3>,
+}
+
+impl Default for BrushSettings {
+    fn default() -> Self {
+        Self {
+            shape: BrushShape::Cube,
+            radius: 1,
+            mode: BrushMode::Place,
+            fill_cap: 100_000,
+            line_start: None,
+        }
+    }
+}
+
+impl BrushSettings {
+    // Cells the brush covers, as offsets from its center. The sphere keeps cells
+    // whose center is within radius - 0.5 of the center cell's, compared in whole
+    // numbers so it comes out the same along every axis and direction.
+    pub fn offsets(&self) -> impl Iterator<Item = IVec3> + '_ {
+        let reach = self.radius.clamp(MIN_BRUSH_RADIUS, MAX_BRUSH_RADIUS) as i32 - 1;
+        let limit = (2 * reach + 1).pow(2);
+        (-reach..=reach)
+            .flat_map(move |x| (-reach..=reach).flat_map(move |y| (-reach..=reach).map(move |z| IVec3::new(x, y, z))))
+            .filter(move |offset| match self.shape {
+                BrushShape::Cube => true,
+                BrushShape::Sphere => 4 * offset.length_squared() <= limit,
+            })
+    }
+}
+
+// Latest edit warning and when it was raised, in elapsed seconds, for the overlay
+#[derive(Resource, Default)]
+pub struct EditFeedback {
+    pub last_warning: Option<(String, f64)>,
+}
+
+#[derive(Component)]
+struct BrushText;
+
+fn adjust_brush(input: ActionInput, mut brush: ResMut<BrushSettings>) {
+    if input.just_pressed(Action::BrushSmaller) && brush.radius > MIN_BRUSH_RADIUS {
+        brush.radius -= 1;
+    }
+    if input.just_pressed(Action::BrushLarger) && brush.radius < MAX_BRUSH_RADIUS {
+        brush.radius += 1;
+    }
+    if input.just_pressed(Action::CycleBrushShape) {
+        brush.shape = match brush.shape {
+            BrushShape::Cube => BrushShape::Sphere,
+            BrushShape::Sphere => BrushShape::Cube,
+        };
+    }
+    if input.just_pressed(Action::CycleBrushMode) {
+        brush.mode = match brush.mode {
+            BrushMode::Place => BrushMode::Erase,
+            BrushMode::Erase => BrushMode::Paint,
+            BrushMode::Paint => BrushMode::Fill,
+            BrushMode::Fill => BrushMode::Line,
+            BrushMode::Line => BrushMode::Raise,
+            BrushMode::Raise => BrushMode::Lower,
+            BrushMode::Lower => BrushMode::Place,
+        };
+        brush.line_start = None;
+    }
+}
+
+// Escape drops a started line instead of releasing the cursor
+fn cancel_line(
+    bindings: Res<KeyBindings>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut brush: ResMut<BrushSettings>,
+) {
+    if brush.line_start.is_some() && bindings.consume(Action::CancelLine, &mut keyboard, &mut mouse) {
+        brush.line_start = None;
+    }
+}
+
+fn line_cells(start: IVec3, end: IVec3, rectangle: bool) -> Vec<IVec3> {
+    if rectangle { shapes::plane(start, end) } else { shapes::line(start, end) }
+}
+
+// Applies the brush around the targeted voxel as one batch, so each chunk it
+// reaches is rebuilt once
+#[allow(clippy::too_many_arguments)]
+fn apply_brush(
+    time: Res<Time>,
+    input: ActionInput,
+    camera_state: Res<CameraState>,
+    mut brush: ResMut<BrushSettings>,
+    paint: Res<PaintState>,
+    selection: Res<SelectionState>,
+    target: Res<TargetedVoxel>,
+    mut feedback: ResMut<EditFeedback>,
+    mut world: ParamSet<(VoxelWorld, VoxelEditor)>,
+) {
+    // Clicks set selection corners while selecting
+    if !input.just_pressed(Action::ApplyBrush) || !camera_state.cursor_locked() || selection.active {
+        return;
+    }
+    let Some(hit) = target.hit else {
+        return;
+    };
+
+    let place = EditOp::Place {
+        color: paint.active,
+        kind: KIND_PLAIN,
+    };
+    let mut edits = VoxelEdits::default();
+    match brush.mode {
+        BrushMode::Fill => {
+            if target.color == paint.active {
+                return;
+            }
+            let Some(fill) = world.p0().flood_fill_color(hit.world, brush.fill_cap) else {
+                return;
+            };
+            if fill.capped {
+                let warning = format!("fill stopped at {} voxels", brush.fill_cap);
+                warn!("Flood {}", warning);
+                feedback.last_warning = Some((warning, time.elapsed_seconds_f64()));
+            }
+            for cell in fill.cells {
+                edits.push(cell, EditOp::Paint(paint.active));
+            }
+        }
+        BrushMode::Line => {
+            let end = hit.world + hit.normal;
+            let Some(start) = brush.line_start.take() else {
+                brush.line_start = Some(end);
+                return;
+            };
+            let rectangle = input.pressed(Action::RectangleModifier);
+            if rectangle && shapes::plane_area(start, end) > MAX_SELECTION_VOLUME {
+                let warning = format!("rectangle is larger than {} voxels", MAX_SELECTION_VOLUME);
+                warn!("The {}", warning);
+                feedback.last_warning = Some((warning, time.elapsed_seconds_f64()));
+                return;
+            }
+            for cell in line_cells(start, end, rectangle) {
+                edits.push(cell, place);
+            }
+        }
+        // Applied while held, by sculpt_terrain
+        BrushMode::Raise | BrushMode::Lower => return,
+        BrushMode::Place | BrushMode::Erase | BrushMode::Paint => {
+            let (center, op) = match brush.mode {
+                BrushMode::Erase => (hit.world, EditOp::Erase),
+                BrushMode::Paint => (hit.world, EditOp::Paint(paint.active)),
+                _ => (hit.world + hit.normal, place),
+            };
+            for offset in brush.offsets() {
+                edits.push(center + offset, op);
+            }
+        }
+    }
+    let cells = edits.len();
+    let chunks = world.p1().apply(edits);
+    if chunks > 0 {
+        info!("Brush {:?}: {} cells across {} chunks", brush.mode, cells, chunks);
+    }
+}
+
+// Ctrl+Z and Ctrl+Y step back and forth through brush and selection edits
+fn undo_edits(input: ActionInput, mut editor: VoxelEditor) {
+    if !input.pressed(Action::EditModifier) {
+        return;
+    }
+    if input.just_pressed(Action::Undo) && !editor.undo() {
+        info!("Nothing to undo");
+    }
+    if input.just_pressed(Action::Redo) && !editor.redo() {
+        info!("Nothing to redo");
+    }
+}
+
+// Outlines the cells a line or rectangle would fill, from its first click to the
+// targeted cell
+fn draw_line_preview(
+    mut gizmos: Gizmos,
+    input: ActionInput,
+    settings: Res<VoxelRenderSettings>,
+    brush: Res<BrushSettings>,
+    paint: Res<PaintState>,
+    target: Res<TargetedVoxel>,
+) {
+    let (Some(start), Some(hit)) = (brush.line_start, target.hit) else {
+        return;
+    };
+    let end = hit.world + hit.normal;
+    let rectangle = input.pressed(Action::RectangleModifier);
+    if rectangle && shapes::plane_area(start, end) > MAX_LINE_PREVIEW_CELLS as i64 {
+        let min = start.min(end).as_vec3() * settings.voxel_size;
+        let max = (start.max(end) + IVec3::ONE).as_vec3() * settings.voxel_size;
+        gizmos.cuboid(Transform::from_translation((min + max) / 2.0).with_scale(max - min), paint.active);
+        return;
+    }
+    for cell in line_cells(start, end, rectangle).into_iter().take(MAX_LINE_PREVIEW_CELLS) {
+        let center = (cell.as_vec3() + Vec3::splat(0.5)) * settings.voxel_size;
+        gizmos.cuboid(
+            Transform::from_translation(center).with_scale(Vec3::splat(settings.voxel_size)),
+            paint.active,
+        );
+    }
+}
+
+fn setup_brush_text(mut commands: Commands) {
+    commands.spawn((
+        TextBundle::from_section("", body_text_style()).with_style(Style {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(10.0),
+            right: Val::Px(10.0),
+            ..default()
+        }),
+        BrushText,
+    ));
+}
+
+fn update_brush_text(
+    brush: Res<BrushSettings>,
+    selection: Res<SelectionState>,
+    paste: Res<PasteState>,
+    library: Res<PrefabLibrary>,
+    mut query: Query<&mut Text, With<BrushText>>,
+) {
+    if !brush.is_changed() && !selection.is_changed() && !paste.is_changed() && !library.is_changed() {
+        return;
+    }
+    let turned = paste.quarter_turns as u32 * 90;
+    let value = match (selection.active, selection.selection) {
+        _ if paste.active && paste.source == PasteSource::Prefab => format!(
+            "Stamp: {} ({:?}), turned {} degrees",
+            library.active().map_or("none", |prefab| prefab.name.as_str()),
+            library.overlap,
+            turned,
+        ),
+        _ if paste.active => format!("Paste: turned {} degrees, R to turn", turned),
+        (true, Some(selected)) => {
+            let size = selected.size();
+            format!("Selection: {} x {} x {}", size.x, size.y, size.z)
+        }
+        (true, None) => String::from("Selection: click two corners"),
+        (false, _) if brush.mode == BrushMode::Line => match brush.line_start {
+            Some(_) => String::from("Line: click the end, Alt for a rectangle"),
+            None => String::from("Line: click the start"),
+        },
+        (false, _) => format!("Brush: {:?} {:?}, radius {}", brush.mode, brush.shape, brush.radius),
+    };
+    for mut text in &mut query {
+        text.sections[0].value.clone_from(&value);
+    }
+}
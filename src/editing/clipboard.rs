@@ -0,0 +1,397 @@
+// src/editing/clipboard.rs
+use bevy::{prelude::*, utils::HashMap};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use crate::bindings::{Action, ActionInput, KeyBindings};
+use crate::camera::{camera_path_controls, toggle_cursor_lock, CameraState};
+use crate::picking::TargetedVoxel;
+use crate::voxel::{process_dirty_chunks, split_world, EditOp, VoxelEditor, VoxelEdits, VoxelWorld, CHUNK_SIZE};
+use crate::voxel_types::VoxelRenderSettings;
+use super::selection::{cancel_selection, pick_selection_corner, MAX_SELECTION_VOLUME};
+use super::{apply_brush, cancel_line, PrefabLibrary, SelectionBox, SelectionState, StampOverlap};
+
+const CLIPBOARD_FILE: &str = "clipboard.ron";
+// The preview leaves out voxels past this; its outline still covers the whole region
+const MAX_PREVIEW_VOXELS: usize = 4096;
+const PREVIEW_ALPHA: f32 = 0.4;
+const PREVIEW_OUTLINE_COLOR: Color = Color::rgb(0.3, 1.0, 0.6);
+
+pub struct ClipboardPlugin;
+
+impl Plugin for ClipboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<VoxelClipboard>()
+            .init_resource::<PasteState>()
+            .add_systems(Startup, setup_paste_preview)
+            .add_systems(Update, (
+                clipboard_controls,
+                paste_controls,
+                update_paste_preview,
+            ).chain()
+                // Paste mode takes Escape, R and the click before the selection, brush,
+                // cursor lock and camera path recording see them
+                .before(cancel_selection)
+                .before(pick_selection_corner)
+                .before(cancel_line)
+                .before(apply_brush)
+                .before(toggle_cursor_lock)
+                .before(camera_path_controls)
+                .before(process_dirty_chunks));
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct ClipboardVoxel {
+    // From the region's min corner
+    pub offset: [i32; 3],
+    // Index into the clipboard palette
+    pub color: u32,
+    pub kind: u16,
+}
+
+// Voxels copied out of a box selection, with each color stored once. Ctrl+C copies,
+// Ctrl+X cuts and Ctrl+V pastes; Ctrl+K saves it to clipboard.ron and Ctrl+L loads it.
+#[derive(Resource, Default, Serialize, Deserialize)]
+pub struct VoxelClipboard {
+    pub size: [i32; 3],
+    // sRGBA
+    pub palette: Vec<[f32; 4]>,
+    pub voxels: Vec<ClipboardVoxel>,
+}
+
+impl VoxelClipboard {
+    // Voxels inside the box from the loaded chunks. Solid cells a generator left
+    // without a voxel have no color to copy and are skipped.
+    pub fn copy(world: &VoxelWorld, selection: SelectionBox) -> Self {
+        let mut clipboard = Self {
+            size: selection.size().to_array(),
+            ..default()
+        };
+        // Keyed by bit pattern, since floats don't hash
+        let mut palette_index: HashMap<[u32; 4], u32> = HashMap::default();
+        let (min_chunk, _) = split_world(selection.min);
+        let (max_chunk, _) = split_world(selection.max);
+        for x in min_chunk.x..=max_chunk.x {
+            for y in min_chunk.y..=max_chunk.y {
+                for z in min_chunk.z..=max_chunk.z {
+                    let Some(chunk) = world.chunk(IVec3::new(x, y, z)) else {
+                        continue;
+                    };
+                    let chunk_origin = chunk.position * CHUNK_SIZE;
+                    for voxel in chunk.voxels.iter().chain(chunk.hidden_voxels.iter()) {
+                        let cell = chunk_origin + voxel.position.as_ivec3();
+                        if cell.cmplt(selection.min).any() || cell.cmpgt(selection.max).any() {
+                            continue;
+                        }
+                        let rgba = voxel.color.as_rgba_f32();
+                        let color = *palette_index.entry(rgba.map(f32::to_bits)).or_insert_with(|| {
+                            clipboard.palette.push(rgba);
+                            (clipboard.palette.len() - 1) as u32
+                        });
+                        clipboard.voxels.push(ClipboardVoxel {
+                            offset: (cell - selection.min).to_array(),
+                            color,
+                            kind: voxel.kind,
+                        });
+                    }
+                }
+            }
+        }
+        clipboard
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.voxels.is_empty()
+    }
+
+    pub fn color(&self, voxel: &ClipboardVoxel) -> Option<Color> {
+        let [r, g, b, a] = *self.palette.get(voxel.color as usize)?;
+        Some(Color::rgba(r, g, b, a))
+    }
+
+    // Size of the region after turning it a number of quarter turns about Y
+    pub fn turned_size(&self, quarter_turns: u8) -> IVec3 {
+        let size = IVec3::from(self.size);
+        if quarter_turns % 2 == 0 { size } else { IVec3::new(size.z, size.y, size.x) }
+    }
+
+    // Voxels with their offsets from the min corner of the turned region
+    pub fn turned(&self, quarter_turns: u8) -> impl Iterator<Item = (IVec3, &ClipboardVoxel)> + '_ {
+        let size = IVec3::from(self.size);
+        self.voxels.iter().map(move |voxel| {
+            let (mut offset, mut turned_size) = (IVec3::from(voxel.offset), size);
+            for _ in 0..quarter_turns % 4 {
+                offset = IVec3::new(turned_size.z - 1 - offset.z, offset.y, offset.x);
+                turned_size = IVec3::new(turned_size.z, turned_size.y, turned_size.x);
+            }
+            (offset, voxel)
+        })
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), ClipboardError> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(ClipboardError::Serialize)?;
+        std::fs::write(path, text).map_err(ClipboardError::Io)
+    }
+
+    pub fn load(path: &str) -> Result<Self, ClipboardError> {
+        let bytes = std::fs::read(path).map_err(ClipboardError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(ClipboardError::Parse)
+    }
+}
+
+#[derive(Debug)]
+pub enum ClipboardError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for ClipboardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClipboardError::Io(err) => write!(f, "could not access clipboard file: {}", err),
+            ClipboardError::Parse(err) => write!(f, "could not parse clipboard file: {}", err),
+            ClipboardError::Serialize(err) => write!(f, "could not write clipboard file: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ClipboardError {}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum PasteSource {
+    #[default]
+    Clipboard,
+    // The library's active prefab
+    Prefab,
+}
+
+// Paste mode, entered with Ctrl+V or G for a prefab: a preview follows the
+// targeted face, R turns it and a click pastes it. Escape leaves.
+#[derive(Resource, Default)]
+pub struct PasteState {
+    pub active: bool,
+    pub source: PasteSource,
+    // Quarter turns about Y
+    pub quarter_turns: u8,
+    // Where the turned region's min corner lands, None with nothing targeted
+    origin: Option<IVec3>,
+}
+
+#[derive(Component, Default)]
+struct PastePreview {
+    // Source and quarter turns the preview voxels were spawned with
+    built: Option<(PasteSource, u8)>,
+}
+
+fn paste_region<'a>(
+    source: PasteSource,
+    clipboard: &'a VoxelClipboard,
+    library: &'a PrefabLibrary,
+) -> Option<&'a VoxelClipboard> {
+    match source {
+        PasteSource::Clipboard => Some(clipboard),
+        PasteSource::Prefab => library.active().map(|prefab| &prefab.voxels),
+    }
+}
+
+fn clipboard_controls(
+    input: ActionInput,
+    selection: Res<SelectionState>,
+    mut clipboard: ResMut<VoxelClipboard>,
+    mut paste: ResMut<PasteState>,
+    mut world: ParamSet<(VoxelWorld, VoxelEditor)>,
+) {
+    if !input.pressed(Action::EditModifier) {
+        return;
+    }
+
+    let cut = input.just_pressed(Action::Cut);
+    let selected = selection.selection.filter(|_| selection.active);
+    if let Some(selected) = selected.filter(|_| cut || input.just_pressed(Action::Copy)) {
+        *clipboard = VoxelClipboard::copy(&world.p0(), selected);
+        info!("Copied {} voxels", clipboard.voxels.len());
+        if cut && selected.volume() <= MAX_SELECTION_VOLUME {
+            // The whole box, so cells without a stored voxel are cleared too
+            let mut edits = VoxelEdits::default();
+            for cell in selected.cells() {
+                edits.push(cell, EditOp::Erase);
+            }
+            world.p1().apply(edits);
+        } else if cut {
+            warn!("Selection of {} voxels is too large to cut; copied only", selected.volume());
+        }
+    }
+
+    if input.just_pressed(Action::Paste) {
+        if clipboard.is_empty() {
+            info!("Clipboard is empty");
+        } else {
+            *paste = PasteState {
+                active: true,
+                ..default()
+            };
+        }
+    }
+
+    if input.just_pressed(Action::SaveClipboard) {
+        match clipboard.save(CLIPBOARD_FILE) {
+            Ok(()) => info!("Saved clipboard to {}", CLIPBOARD_FILE),
+            Err(err) => warn!("{}", err),
+        }
+    }
+    if input.just_pressed(Action::LoadClipboard) {
+        match VoxelClipboard::load(CLIPBOARD_FILE) {
+            Ok(loaded) => {
+                info!("Loaded {} voxels from {}", loaded.voxels.len(), CLIPBOARD_FILE);
+                *clipboard = loaded;
+            }
+            Err(err) => warn!("{}", err),
+        }
+    }
+}
+
+// Pastes the whole clipboard or prefab as one undo step, spawning chunks where
+// needed
+#[allow(clippy::too_many_arguments)]
+fn paste_controls(
+    bindings: Res<KeyBindings>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    camera_state: Res<CameraState>,
+    target: Res<TargetedVoxel>,
+    clipboard: Res<VoxelClipboard>,
+    library: Res<PrefabLibrary>,
+    mut paste: ResMut<PasteState>,
+    mut editor: VoxelEditor,
+) {
+    if !paste.active {
+        return;
+    }
+    if bindings.consume(Action::CancelPaste, &mut keyboard, &mut mouse) {
+        *paste = PasteState::default();
+        return;
+    }
+    if bindings.consume(Action::RotatePaste, &mut keyboard, &mut mouse) {
+        paste.quarter_turns = (paste.quarter_turns + 1) % 4;
+    }
+
+    let Some(region) = paste_region(paste.source, &clipboard, &library) else {
+        *paste = PasteState::default();
+        return;
+    };
+
+    // Against the targeted face like a placed voxel, centered on the footprint
+    let size = region.turned_size(paste.quarter_turns);
+    let origin = target.hit.map(|hit| hit.world + hit.normal - IVec3::new(size.x / 2, 0, size.z / 2));
+    if paste.origin != origin {
+        paste.origin = origin;
+    }
+
+    // A click with the cursor free locks it instead
+    let Some(origin) = origin.filter(|_| camera_state.cursor_locked()) else {
+        return;
+    };
+    if !bindings.consume(Action::CommitPaste, &mut keyboard, &mut mouse) {
+        return;
+    }
+    let fill_empty = paste.source == PasteSource::Prefab && library.overlap == StampOverlap::FillEmpty;
+    let mut edits = VoxelEdits::default();
+    for (offset, voxel) in region.turned(paste.quarter_turns) {
+        let Some(color) = region.color(voxel) else {
+            continue;
+        };
+        let kind = voxel.kind;
+        let op = if fill_empty { EditOp::PlaceEmpty { color, kind } } else { EditOp::Place { color, kind } };
+        edits.push(origin + offset, op);
+    }
+    let cells = edits.len();
+    let chunks = editor.apply(edits);
+    info!("Pasted {} voxels across {} chunks", cells, chunks);
+    // Stamping stays on for the next stamp
+    if paste.source == PasteSource::Clipboard {
+        *paste = PasteState::default();
+    }
+}
+
+fn setup_paste_preview(mut commands: Commands) {
+    commands.spawn((
+        SpatialBundle {
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        PastePreview::default(),
+    ));
+}
+
+// Translucent copies of the pasted voxels at the paste position, respawned when
+// what's pasted or its rotation changes
+#[allow(clippy::too_many_arguments)]
+fn update_paste_preview(
+    mut commands: Commands,
+    mut gizmos: Gizmos,
+    settings: Res<VoxelRenderSettings>,
+    clipboard: Res<VoxelClipboard>,
+    library: Res<PrefabLibrary>,
+    paste: Res<PasteState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut query: Query<(Entity, &mut PastePreview, &mut Transform, &mut Visibility)>,
+) {
+    let Ok((entity, mut preview, mut transform, mut visibility)) = query.get_single_mut() else {
+        return;
+    };
+    let origin = paste.origin.filter(|_| paste.active);
+    let wanted = if origin.is_some() { Visibility::Visible } else { Visibility::Hidden };
+    if *visibility != wanted {
+        *visibility = wanted;
+    }
+    let (Some(origin), Some(region)) = (origin, paste_region(paste.source, &clipboard, &library)) else {
+        return;
+    };
+
+    let voxel_size = settings.voxel_size;
+    let turns = paste.quarter_turns;
+    let built = Some((paste.source, turns));
+    if clipboard.is_changed() || library.is_changed() || preview.built != built {
+        preview.built = built;
+        commands.entity(entity).despawn_descendants();
+        let mesh = meshes.add(Mesh::from(shape::Cube { size: voxel_size }));
+        let palette: Vec<Handle<StandardMaterial>> = region
+            .palette
+            .iter()
+            .map(|&[r, g, b, _]| {
+                materials.add(StandardMaterial {
+                    base_color: Color::rgba(r, g, b, PREVIEW_ALPHA),
+                    alpha_mode: AlphaMode::Blend,
+                    unlit: true,
+                    ..default()
+                })
+            })
+            .collect();
+        commands.entity(entity).with_children(|parent| {
+            for (offset, voxel) in region.turned(turns).take(MAX_PREVIEW_VOXELS) {
+                let Some(material) = palette.get(voxel.color as usize) else {
+                    continue;
+                };
+                parent.spawn(PbrBundle {
+                    mesh: mesh.clone(),
+                    material: material.clone(),
+                    transform: Transform::from_translation((offset.as_vec3() + Vec3::splat(0.5)) * voxel_size),
+                    ..default()
+                });
+            }
+        });
+    }
+
+    let translation = origin.as_vec3() * voxel_size;
+    if transform.translation != translation {
+        transform.translation = translation;
+    }
+    let extent = region.turned_size(turns).as_vec3() * voxel_size;
+    gizmos.cuboid(
+        Transform::from_translation(translation + extent / 2.0).with_scale(extent),
+        PREVIEW_OUTLINE_COLOR,
+    );
+}
@@ -0,0 +1,311 @@
+// src/editing/palette.rs
+use bevy::prelude::*;
+use serde::Deserialize;
+use std::fmt;
+use crate::bindings::{Action, ActionInput};
+use crate::camera::{CameraController, CameraMode, CameraState};
+use crate::picking::TargetedVoxel;
+
+const PALETTE_FILE: &str = "assets/palette.ron";
+const PALETTE_SLOTS: usize = 16;
+const PALETTE_COLUMNS: u16 = 8;
+const SWATCH_SIZE: f32 = 22.0;
+const SWATCH_GAP: f32 = 4.0;
+const SWATCH_BORDER: f32 = 2.0;
+const ACTIVE_SWATCH_SIZE: f32 = 12.0;
+// From the crosshair's center to the active color swatch's left edge
+const ACTIVE_SWATCH_OFFSET: f32 = 16.0;
+const EMPTY_SLOT_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.4);
+const SLOT_BORDER_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+
+// Used when palette.ron is missing or can't be read
+const DEFAULT_PALETTE: [[f32; 3]; 10] = [
+    [0.6, 0.6, 0.6],
+    [0.95, 0.95, 0.95],
+    [0.15, 0.15, 0.15],
+    [0.45, 0.32, 0.2],
+    [0.3, 0.6, 0.25],
+    [0.76, 0.7, 0.5],
+    [0.8, 0.2, 0.15],
+    [0.95, 0.75, 0.2],
+    [0.2, 0.4, 0.8],
+    [0.55, 0.3, 0.7],
+];
+
+pub struct PalettePlugin;
+
+impl Plugin for PalettePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PaintState>()
+            .add_systems(Startup, (load_palette, setup_palette_ui).chain())
+            .add_systems(Update, (
+                select_palette_slot,
+                click_palette_slot,
+                eyedropper,
+                update_palette_ui,
+            ).chain());
+    }
+}
+
+// Color used for placing and painting, and the palette it's chosen from. Number
+// keys and clicks pick a slot; middle click takes the color under the crosshair.
+#[derive(Resource)]
+pub struct PaintState {
+    // None is an empty slot, filled by the next new color eyedropped
+    pub palette: Vec<Option<Color>>,
+    pub active: Color,
+    // Palette slot holding the active color, if any
+    pub selected: Option<usize>,
+}
+
+impl Default for PaintState {
+    fn default() -> Self {
+        let mut palette: Vec<Option<Color>> =
+            DEFAULT_PALETTE.iter().map(|&[r, g, b]| Some(Color::rgb(r, g, b))).collect();
+        palette.resize(PALETTE_SLOTS, None);
+        Self {
+            active: palette[0].unwrap_or(Color::WHITE),
+            palette,
+            selected: Some(0),
+        }
+    }
+}
+
+impl PaintState {
+    // Makes a filled slot's color active. Returns false for empty or missing slots.
+    pub fn select(&mut self, slot: usize) -> bool {
+        let Some(Some(color)) = self.palette.get(slot).copied() else {
+            return false;
+        };
+        self.active = color;
+        self.selected = Some(slot);
+        true
+    }
+
+    // Makes a color active, selecting the slot that already holds it or filling
+    // the first empty one. With the palette full it's active without a slot.
+    pub fn pick(&mut self, color: Color) {
+        self.active = color;
+        self.selected = self.palette.iter().position(|slot| *slot == Some(color)).or_else(|| {
+            let empty = self.palette.iter().position(Option::is_none)?;
+            self.palette[empty] = Some(color);
+            Some(empty)
+        });
+    }
+}
+
+// Slots are sRGB colors in [0, 1], or None for an empty slot
+#[derive(Deserialize)]
+struct PaletteFile {
+    colors: Vec<Option<[f32; 3]>>,
+}
+
+#[derive(Debug)]
+pub enum PaletteError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::Io(err) => write!(f, "could not read palette: {}", err),
+            PaletteError::Parse(err) => write!(f, "could not parse palette: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for PaletteError {}
+
+// Reads a palette, padding it with empty slots or dropping those past the last
+fn read_palette(path: &str) -> Result<Vec<Option<Color>>, PaletteError> {
+    let bytes = std::fs::read(path).map_err(PaletteError::Io)?;
+    let file: PaletteFile = ron::de::from_bytes(&bytes).map_err(PaletteError::Parse)?;
+    if file.colors.len() > PALETTE_SLOTS {
+        warn!("{} has {} colors; keeping the first {}", path, file.colors.len(), PALETTE_SLOTS);
+    }
+    let mut palette: Vec<Option<Color>> = file
+        .colors
+        .into_iter()
+        .take(PALETTE_SLOTS)
+        .map(|color| color.map(|[r, g, b]| Color::rgb(r, g, b)))
+        .collect();
+    palette.resize(PALETTE_SLOTS, None);
+    Ok(palette)
+}
+
+#[derive(Component)]
+struct PaletteSlot(usize);
+
+#[derive(Component)]
+struct ActiveColorSwatch;
+
+fn load_palette(mut paint: ResMut<PaintState>) {
+    match read_palette(PALETTE_FILE) {
+        Ok(palette) => {
+            info!("Loaded {} palette colors from {}", palette.iter().flatten().count(), PALETTE_FILE);
+            paint.palette = palette;
+            // A palette with no colors keeps the default active color
+            let first = paint.palette.iter().position(Option::is_some);
+            if !first.is_some_and(|slot| paint.select(slot)) {
+                paint.selected = None;
+            }
+        }
+        Err(err) => warn!("{}; using the default palette", err),
+    }
+}
+
+fn setup_palette_ui(mut commands: Commands, paint: Res<PaintState>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                bottom: Val::Px(10.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        display: Display::Grid,
+                        grid_template_columns: RepeatedGridTrack::px(PALETTE_COLUMNS, SWATCH_SIZE),
+                        row_gap: Val::Px(SWATCH_GAP),
+                        column_gap: Val::Px(SWATCH_GAP),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|grid| {
+                    for (slot, color) in paint.palette.iter().enumerate() {
+                        grid.spawn((
+                            ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(SWATCH_SIZE),
+                                    height: Val::Px(SWATCH_SIZE),
+                                    border: UiRect::all(Val::Px(SWATCH_BORDER)),
+                                    ..default()
+                                },
+                                background_color: color.unwrap_or(EMPTY_SLOT_COLOR).into(),
+                                border_color: slot_border(paint.selected == Some(slot)),
+                                ..default()
+                            },
+                            PaletteSlot(slot),
+                        ));
+                    }
+                });
+        });
+
+    // Centered like the crosshair. The margin counts towards the centered width,
+    // which puts the swatch's left edge the offset right of center.
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Px(ACTIVE_SWATCH_SIZE),
+                        height: Val::Px(ACTIVE_SWATCH_SIZE),
+                        margin: UiRect::left(Val::Px(2.0 * ACTIVE_SWATCH_OFFSET + ACTIVE_SWATCH_SIZE)),
+                        border: UiRect::all(Val::Px(1.0)),
+                        ..default()
+                    },
+                    background_color: paint.active.into(),
+                    border_color: SLOT_BORDER_COLOR.into(),
+                    ..default()
+                },
+                ActiveColorSwatch,
+            ));
+        });
+}
+
+fn slot_border(selected: bool) -> BorderColor {
+    if selected { Color::WHITE.into() } else { SLOT_BORDER_COLOR.into() }
+}
+
+fn select_palette_slot(input: ActionInput, mut paint: ResMut<PaintState>) {
+    // Ctrl with the number keys toggles culling instead
+    if input.pressed(Action::DebugModifier) {
+        return;
+    }
+    for (slot, action) in Action::PALETTE.into_iter().enumerate() {
+        if input.just_pressed(action) && paint.selected != Some(slot) {
+            paint.select(slot);
+        }
+    }
+}
+
+fn click_palette_slot(
+    query: Query<(&Interaction, &PaletteSlot), Changed<Interaction>>,
+    mut paint: ResMut<PaintState>,
+) {
+    for (interaction, slot) in &query {
+        if *interaction == Interaction::Pressed && paint.selected != Some(slot.0) {
+            paint.select(slot.0);
+        }
+    }
+}
+
+// Takes the color of the voxel under the crosshair, adding it to the palette if
+// it's new
+fn eyedropper(
+    input: ActionInput,
+    camera_state: Res<CameraState>,
+    target: Res<TargetedVoxel>,
+    controller: Query<&CameraController>,
+    mut paint: ResMut<PaintState>,
+) {
+    if !input.just_pressed(Action::Eyedropper) || !camera_state.cursor_locked() {
+        return;
+    }
+    // Middle drag pans the orbit camera
+    if controller.get_single().is_ok_and(|controller| controller.mode == CameraMode::Orbit) {
+        return;
+    }
+    if target.hit.is_none() {
+        return;
+    }
+    paint.pick(target.color);
+    match paint.selected {
+        Some(slot) => info!("Picked color into palette slot {}", slot + 1),
+        None => info!("Picked color; the palette is full"),
+    }
+}
+
+fn update_palette_ui(
+    paint: Res<PaintState>,
+    mut slots: Query<(&PaletteSlot, &mut BackgroundColor, &mut BorderColor)>,
+    mut active: Query<&mut BackgroundColor, (With<ActiveColorSwatch>, Without<PaletteSlot>)>,
+) {
+    if !paint.is_changed() {
+        return;
+    }
+    for (slot, mut background, mut border) in &mut slots {
+        let color = paint.palette.get(slot.0).copied().flatten().unwrap_or(EMPTY_SLOT_COLOR);
+        if background.0 != color {
+            background.0 = color;
+        }
+        let wanted = slot_border(paint.selected == Some(slot.0));
+        if border.0 != wanted.0 {
+            *border = wanted;
+        }
+    }
+    for mut background in &mut active {
+        if background.0 != paint.active {
+            background.0 = paint.active;
+        }
+    }
+}
@@ -0,0 +1,146 @@
+// src/editing/prefab.rs
+use bevy::prelude::*;
+use std::path::Path;
+use crate::bindings::{Action, ActionInput};
+use crate::voxel::VoxelWorld;
+use super::{PasteSource, PasteState, SelectionState, VoxelClipboard};
+
+// Prefabs are clipboard files, one per prefab, named after the file
+const PREFAB_DIRECTORY: &str = "assets/prefabs";
+
+pub struct PrefabPlugin;
+
+impl Plugin for PrefabPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PrefabLibrary>()
+            .add_systems(Startup, load_prefabs)
+            .add_systems(Update, prefab_controls);
+    }
+}
+
+// What a stamp does to cells that already hold voxels
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum StampOverlap {
+    #[default]
+    Replace,
+    // Only fills empty cells, leaving existing voxels alone
+    FillEmpty,
+}
+
+pub struct Prefab {
+    pub name: String,
+    pub voxels: VoxelClipboard,
+}
+
+// Saved regions to stamp around the world. Insert saves the selection as a new
+// prefab, comma and period choose one, G starts stamping it and O switches the
+// overlap mode. Stamping works like pasting but stays on after each click.
+#[derive(Resource, Default)]
+pub struct PrefabLibrary {
+    pub prefabs: Vec<Prefab>,
+    pub active: usize,
+    pub overlap: StampOverlap,
+}
+
+impl PrefabLibrary {
+    pub fn active(&self) -> Option<&Prefab> {
+        self.prefabs.get(self.active)
+    }
+}
+
+fn load_prefabs(mut library: ResMut<PrefabLibrary>) {
+    let entries = match std::fs::read_dir(PREFAB_DIRECTORY) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+        Err(err) => {
+            warn!("Could not read {}: {}", PREFAB_DIRECTORY, err);
+            return;
+        }
+    };
+    for path in entries.flatten().map(|entry| entry.path()) {
+        if path.extension().and_then(|extension| extension.to_str()) != Some("ron") {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()).map(String::from) else {
+            continue;
+        };
+        match VoxelClipboard::load(&path.to_string_lossy()) {
+            Ok(voxels) => library.prefabs.push(Prefab { name, voxels }),
+            Err(err) => warn!("Skipping prefab {}: {}", name, err),
+        }
+    }
+    library.prefabs.sort_by(|a, b| a.name.cmp(&b.name));
+    info!("Loaded {} prefabs from {}", library.prefabs.len(), PREFAB_DIRECTORY);
+}
+
+fn prefab_controls(
+    input: ActionInput,
+    selection: Res<SelectionState>,
+    world: VoxelWorld,
+    mut library: ResMut<PrefabLibrary>,
+    mut paste: ResMut<PasteState>,
+) {
+    let selected = selection.selection.filter(|_| selection.active);
+    if let Some(selected) = selected.filter(|_| input.just_pressed(Action::SavePrefab)) {
+        let voxels = VoxelClipboard::copy(&world, selected);
+        if voxels.is_empty() {
+            info!("Selection is empty; no prefab saved");
+        } else {
+            save_prefab(&mut library, voxels);
+        }
+    }
+
+    let count = library.prefabs.len();
+    if count > 0 && input.just_pressed(Action::NextPrefab) {
+        library.active = (library.active + 1) % count;
+    }
+    if count > 0 && input.just_pressed(Action::PreviousPrefab) {
+        library.active = (library.active + count - 1) % count;
+    }
+    if input.just_pressed(Action::ToggleStampOverlap) {
+        library.overlap = match library.overlap {
+            StampOverlap::Replace => StampOverlap::FillEmpty,
+            StampOverlap::FillEmpty => StampOverlap::Replace,
+        };
+    }
+
+    if input.just_pressed(Action::Stamp) {
+        if paste.active && paste.source == PasteSource::Prefab {
+            *paste = PasteState::default();
+        } else if count == 0 {
+            info!("No prefabs in {}", PREFAB_DIRECTORY);
+        } else {
+            *paste = PasteState {
+                active: true,
+                source: PasteSource::Prefab,
+                ..default()
+            };
+        }
+    }
+}
+
+// Writes the voxels under the first free prefab-N name and makes them active
+fn save_prefab(library: &mut PrefabLibrary, voxels: VoxelClipboard) {
+    if let Err(err) = std::fs::create_dir_all(PREFAB_DIRECTORY) {
+        warn!("Could not create {}: {}", PREFAB_DIRECTORY, err);
+        return;
+    }
+    let Some((name, path)) = (1..)
+        .map(|n| {
+            let name = format!("prefab-{}", n);
+            let path = format!("{}/{}.ron", PREFAB_DIRECTORY, name);
+            (name, path)
+        })
+        .find(|(name, path)| !Path::new(path).exists() && library.prefabs.iter().all(|prefab| prefab.name != *name))
+    else {
+        return;
+    };
+    match voxels.save(&path) {
+        Ok(()) => {
+            info!("Saved prefab {} with {} voxels", name, voxels.voxels.len());
+            library.prefabs.push(Prefab { name, voxels });
+            library.active = library.prefabs.len() - 1;
+        }
+        Err(err) => warn!("{}", err),
+    }
+}
@@ -0,0 +1,155 @@
+// src/editing/sculpt.rs
+use bevy::{prelude::*, utils::HashMap};
+use crate::bindings::{Action, ActionInput};
+use crate::camera::{toggle_cursor_lock, CameraState};
+use crate::picking::TargetedVoxel;
+use crate::voxel::{process_dirty_chunks, EditOp, VoxelEditor, VoxelEdits, VoxelWorld};
+use super::{BrushMode, BrushSettings, PasteState, SelectionState};
+
+// How far above and below the targeted voxel columns are searched for their surface
+const SURFACE_SEARCH_HEIGHT: i32 = 64;
+
+pub struct SculptPlugin;
+
+impl Plugin for SculptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SculptSettings>()
+            .init_resource::<SculptStroke>()
+            .add_systems(Update, sculpt_terrain.before(toggle_cursor_lock).before(process_dirty_chunks));
+    }
+}
+
+// Raise and lower brushes, held down to build mounds or dig hollows within the
+// brush radius. Columns move less towards the edge of the brush, and each stroke
+// is one undo step.
+#[derive(Resource)]
+pub struct SculptSettings {
+    // Ticks per second while the button is held
+    pub rate: f32,
+    // Voxels per tick at the brush center
+    pub strength: f32,
+    // Lowest and highest cells sculpting may fill or clear
+    pub min_height: i32,
+    pub max_height: i32,
+}
+
+impl Default for SculptSettings {
+    fn default() -> Self {
+        Self {
+            rate: 10.0,
+            strength: 1.0,
+            min_height: 0,
+            max_height: 255,
+        }
+    }
+}
+
+// Progress through the current stroke, reset when the button is released
+#[derive(Resource, Default)]
+struct SculptStroke {
+    since_tick: f32,
+    // Some tick of the stroke has changed voxels, so later ticks join its undo step
+    edited: bool,
+    // Fractions of a voxel each column has built up but not yet moved, by world x and z
+    carry: HashMap<IVec2, f32>,
+}
+
+// Smooth falloff from 1 at the center column to 0 at the brush edge
+fn falloff(offset: IVec2, radius: f32) -> f32 {
+    let t = (offset.as_vec2().length() / radius).min(1.0);
+    (1.0 - t * t).powi(2)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sculpt_terrain(
+    time: Res<Time>,
+    input: ActionInput,
+    camera_state: Res<CameraState>,
+    brush: Res<BrushSettings>,
+    settings: Res<SculptSettings>,
+    selection: Res<SelectionState>,
+    paste: Res<PasteState>,
+    target: Res<TargetedVoxel>,
+    mut stroke: ResMut<SculptStroke>,
+    mut world: ParamSet<(VoxelWorld, VoxelEditor)>,
+) {
+    let raise = match brush.mode {
+        BrushMode::Raise => true,
+        BrushMode::Lower => false,
+        _ => return,
+    };
+    let held = input.pressed(Action::ApplyBrush) && camera_state.cursor_locked() && !selection.active && !paste.active;
+    if !held {
+        if stroke.edited || !stroke.carry.is_empty() || stroke.since_tick != 0.0 {
+            *stroke = SculptStroke::default();
+        }
+        return;
+    }
+    // The first tick lands on the press itself
+    if input.just_pressed(Action::ApplyBrush) {
+        stroke.since_tick = 1.0 / settings.rate;
+    } else {
+        stroke.since_tick += time.delta_seconds();
+    }
+    if stroke.since_tick < 1.0 / settings.rate {
+        return;
+    }
+    // A long frame doesn't queue up extra ticks
+    stroke.since_tick = 0.0;
+    let Some(hit) = target.hit else {
+        return;
+    };
+
+    let radius = brush.radius.max(1) as f32;
+    let reach = brush.radius.max(1) as i32 - 1;
+    let limit = (2 * reach + 1).pow(2);
+    let mut edits = VoxelEdits::default();
+    {
+        let voxel_world = world.p0();
+        for x in -reach..=reach {
+            for z in -reach..=reach {
+                let offset = IVec2::new(x, z);
+                // Same whole-number circle as the sphere brush
+                if 4 * offset.length_squared() > limit {
+                    continue;
+                }
+                let carry = stroke.carry.entry(IVec2::new(hit.world.x + x, hit.world.z + z)).or_default();
+                *carry += falloff(offset, radius) * settings.strength;
+                let steps = carry.floor() as i32;
+                if steps == 0 {
+                    continue;
+                }
+                *carry -= steps as f32;
+
+                let probe = IVec3::new(hit.world.x + x, hit.world.y + SURFACE_SEARCH_HEIGHT, hit.world.z + z);
+                let Some(top) = voxel_world.ground_below(probe, 2 * SURFACE_SEARCH_HEIGHT) else {
+                    continue;
+                };
+                let surface = IVec3::new(probe.x, top, probe.z);
+                if raise {
+                    // The new voxels copy the surface; a solid cell without a voxel has
+                    // nothing to copy
+                    let Some(voxel) = voxel_world.voxel(surface) else {
+                        continue;
+                    };
+                    let op = EditOp::Place {
+                        color: voxel.color,
+                        kind: voxel.kind,
+                    };
+                    for y in (top + 1..=top + steps).filter(|y| *y <= settings.max_height) {
+                        edits.push(IVec3::new(surface.x, y, surface.z), op);
+                    }
+                } else {
+                    for y in (top - steps + 1..=top).filter(|y| *y >= settings.min_height) {
+                        edits.push(IVec3::new(surface.x, y, surface.z), EditOp::Erase);
+                    }
+                }
+            }
+        }
+    }
+    if edits.is_empty() {
+        return;
+    }
+    let changed = if stroke.edited { world.p1().apply_continuing(edits) } else { world.p1().apply(edits) };
+    stroke.edited |= changed > 0;
+}
@@ -0,0 +1,264 @@
+// src/editing/selection.rs
+use bevy::prelude::*;
+use std::path::Path;
+use crate::bindings::{Action, ActionInput, KeyBindings};
+use crate::camera::{toggle_cursor_lock, CameraState};
+use crate::picking::TargetedVoxel;
+use crate::render::{export_gltf, export_obj, HighlightedRegion};
+use crate::screenshot::timestamp;
+use crate::voxel::{export_ply, export_vox, export_wvox, process_dirty_chunks, split_world, EditOp, VoxelEditor, VoxelEdits, VoxelWorld, WorldRegion};
+use crate::voxel_types::KIND_PLAIN;
+use super::PaintState;
+
+// Where exports are written, relative to the working directory
+const EXPORT_DIRECTORY: &str = "exports";
+
+// Largest box an operation edits at once, in voxels
+pub(super) const MAX_SELECTION_VOLUME: i64 = 1 << 21;
+
+pub struct SelectionPlugin;
+
+impl Plugin for SelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SelectionState>()
+            .add_systems(Update, (
+                // Before the cursor lock and chunk framing, which share Escape and F
+                cancel_selection,
+                toggle_selection,
+                pick_selection_corner,
+                apply_selection_operation,
+                export_selection,
+                update_selection_highlight,
+            ).chain().before(toggle_cursor_lock).before(process_dirty_chunks));
+    }
+}
+
+// Axis-aligned box of world voxel coordinates, both corners inclusive
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SelectionBox {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl SelectionBox {
+    pub fn from_corners(a: IVec3, b: IVec3) -> Self {
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    pub fn size(&self) -> IVec3 {
+        self.max - self.min + IVec3::ONE
+    }
+
+    pub fn volume(&self) -> i64 {
+        let size = self.size();
+        size.x as i64 * size.y as i64 * size.z as i64
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = IVec3> {
+        let (min, max) = (self.min, self.max);
+        (min.x..=max.x).flat_map(move |x| (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z))))
+    }
+
+    // Inside the one-voxel shell
+    fn is_interior(&self, cell: IVec3) -> bool {
+        cell.cmpgt(self.min).all() && cell.cmplt(self.max).all()
+    }
+}
+
+// Box selection, toggled with B. With the cursor locked, two clicks on voxels set
+// opposite corners; Delete then clears the box, F fills it with the active color
+// and H hollows it out. Ctrl+E, Ctrl+J, Ctrl+T, Ctrl+U and Ctrl+I export it as
+// .vox, .glb, .obj, .ply and .wvox files. Escape cancels.
+#[derive(Resource, Default)]
+pub struct SelectionState {
+    pub active: bool,
+    // First corner, waiting for the second click
+    anchor: Option<IVec3>,
+    pub selection: Option<SelectionBox>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum SelectionOp {
+    Delete,
+    Fill,
+    // Erases everything but the outer layer
+    Hollow,
+}
+
+pub(super) fn cancel_selection(
+    bindings: Res<KeyBindings>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    mut state: ResMut<SelectionState>,
+) {
+    // Consumed so the same Escape doesn't also release the cursor
+    if state.active && bindings.consume(Action::CancelSelection, &mut keyboard, &mut mouse) {
+        *state = SelectionState::default();
+        info!("Selection cancelled");
+    }
+}
+
+fn toggle_selection(input: ActionInput, mut state: ResMut<SelectionState>) {
+    if !input.just_pressed(Action::ToggleSelection) {
+        return;
+    }
+    if state.active {
+        *state = SelectionState::default();
+    } else {
+        state.active = true;
+    }
+}
+
+pub(super) fn pick_selection_corner(
+    input: ActionInput,
+    camera_state: Res<CameraState>,
+    target: Res<TargetedVoxel>,
+    mut state: ResMut<SelectionState>,
+) {
+    if !state.active || !input.just_pressed(Action::SelectCorner) || !camera_state.cursor_locked() {
+        return;
+    }
+    let Some(hit) = target.hit else {
+        return;
+    };
+    match state.anchor.take() {
+        // A third click starts over
+        None => {
+            state.anchor = Some(hit.world);
+            state.selection = None;
+        }
+        Some(anchor) => {
+            let selection = SelectionBox::from_corners(anchor, hit.world);
+            let size = selection.size();
+            info!("Selected {} x {} x {} voxels", size.x, size.y, size.z);
+            state.selection = Some(selection);
+        }
+    }
+}
+
+// Applies an operation to the whole box as one undo step
+fn apply_selection_operation(
+    bindings: Res<KeyBindings>,
+    mut keyboard: ResMut<Input<KeyCode>>,
+    mut mouse: ResMut<Input<MouseButton>>,
+    state: Res<SelectionState>,
+    paint: Res<PaintState>,
+    mut editor: VoxelEditor,
+) {
+    let Some(selection) = state.selection.filter(|_| state.active) else {
+        return;
+    };
+    let op = [
+        (Action::DeleteSelection, SelectionOp::Delete),
+        (Action::FillSelection, SelectionOp::Fill),
+        (Action::HollowSelection, SelectionOp::Hollow),
+    ]
+    .into_iter()
+    .find(|(action, _)| bindings.consume(*action, &mut keyboard, &mut mouse))
+    .map(|(_, op)| op);
+    let Some(op) = op else {
+        return;
+    };
+    if selection.volume() > MAX_SELECTION_VOLUME {
+        warn!("Selection of {} voxels is larger than {}; not applying {:?}", selection.volume(), MAX_SELECTION_VOLUME, op);
+        return;
+    }
+
+    let place = EditOp::Place {
+        color: paint.active,
+        kind: KIND_PLAIN,
+    };
+    let mut edits = VoxelEdits::default();
+    for cell in selection.cells() {
+        match op {
+            SelectionOp::Delete => edits.push(cell, EditOp::Erase),
+            SelectionOp::Fill => edits.push(cell, place),
+            SelectionOp::Hollow if selection.is_interior(cell) => edits.push(cell, EditOp::Erase),
+            SelectionOp::Hollow => {}
+        }
+    }
+    let chunks = editor.apply(edits);
+    info!("{:?} selection: {} chunks changed", op, chunks);
+}
+
+// Export actions, each held with the edit modifier
+const EXPORT_FORMATS: [(Action, &str); 5] = [
+    (Action::ExportVox, "vox"),
+    (Action::ExportGltf, "glb"),
+    (Action::ExportObj, "obj"),
+    (Action::ExportPly, "ply"),
+    (Action::ExportWvox, "wvox"),
+];
+
+// Writes the selection, or with none the targeted chunk, to a timestamped file in
+// the format of the export action pressed
+fn export_selection(
+    input: ActionInput,
+    state: Res<SelectionState>,
+    target: Res<TargetedVoxel>,
+    world: VoxelWorld,
+) {
+    if !input.pressed(Action::EditModifier) {
+        return;
+    }
+    let Some((_, extension)) = EXPORT_FORMATS.into_iter().find(|(action, _)| input.just_pressed(*action)) else {
+        return;
+    };
+    let region = match (state.selection.filter(|_| state.active), target.hit) {
+        (Some(selection), _) => WorldRegion {
+            min: selection.min,
+            max: selection.max,
+        },
+        (None, Some(hit)) => WorldRegion::chunk(split_world(hit.world).0),
+        (None, None) => {
+            info!("Nothing selected or targeted to export");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::create_dir_all(EXPORT_DIRECTORY) {
+        warn!("Could not create {}: {}", EXPORT_DIRECTORY, err);
+        return;
+    }
+    let path = format!("{}/export-{}.{}", EXPORT_DIRECTORY, timestamp(), extension);
+    let file = Path::new(&path);
+    let result = match extension {
+        "vox" => export_vox(&world, region, file).map_err(|err| err.to_string()),
+        "glb" => export_gltf(&world, region, file).map_err(|err| err.to_string()),
+        "obj" => export_obj(&world, region, file).map_err(|err| err.to_string()),
+        "wvox" => export_wvox(&world, region, file).map_err(|err| err.to_string()),
+        _ => export_ply(&world, region, file).map_err(|err| err.to_string()),
+    };
+    match result {
+        Ok(()) => info!("Exported {}", path),
+        Err(err) => warn!("{}", err),
+    }
+}
+
+// Shows the box, or while the second corner is pending, the box out to the
+// targeted voxel
+fn update_selection_highlight(
+    mut commands: Commands,
+    state: Res<SelectionState>,
+    target: Res<TargetedVoxel>,
+    region: Option<Res<HighlightedRegion>>,
+) {
+    let shown = state.selection.or_else(|| {
+        let anchor = state.anchor?;
+        Some(SelectionBox::from_corners(anchor, target.hit.map_or(anchor, |hit| hit.world)))
+    });
+    match shown {
+        Some(selection) => {
+            if region.map_or(true, |region| region.min != selection.min || region.max != selection.max) {
+                commands.insert_resource(HighlightedRegion {
+                    min: selection.min,
+                    max: selection.max,
+                });
+            }
+        }
+        None if region.is_some() => commands.remove_resource::<HighlightedRegion>(),
+        None => {}
+    }
+}
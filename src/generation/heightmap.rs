@@ -0,0 +1,79 @@
+// src/generation/heightmap.rs
+use bevy::prelude::*;
+use bevy::render::{
+    render_resource::TextureFormat,
+    texture::{CompressedImageFormats, ImageSampler, ImageType, TextureError, TextureFormatPixelInfo},
+};
+use std::fmt;
+use std::path::Path;
+
+// Grayscale image read as terrain heights, one pixel per voxel column, centered
+// on the origin. Brighter is higher.
+pub struct Heightmap {
+    pub width: i32,
+    pub depth: i32,
+    // Heights in [0, 255], row by row along z
+    samples: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum HeightmapError {
+    Io(std::io::Error),
+    Decode(TextureError),
+}
+
+impl fmt::Display for HeightmapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HeightmapError::Io(err) => write!(f, "could not read heightmap: {}", err),
+            HeightmapError::Decode(err) => write!(f, "could not decode heightmap: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for HeightmapError {}
+
+impl Heightmap {
+    pub fn load(path: &str) -> Result<Self, HeightmapError> {
+        let bytes = std::fs::read(path).map_err(HeightmapError::Io)?;
+        let extension = Path::new(path).extension().and_then(|extension| extension.to_str()).unwrap_or("png");
+        let image = Image::from_buffer(
+            &bytes,
+            ImageType::Extension(extension),
+            CompressedImageFormats::NONE,
+            false,
+            ImageSampler::Default,
+        )
+        .map_err(HeightmapError::Decode)?;
+
+        let format = image.texture_descriptor.format;
+        let stride = format.pixel_size();
+        // The first channel's most significant byte; 16-bit channels are little-endian
+        let offset = match format {
+            TextureFormat::R16Uint | TextureFormat::R16Unorm | TextureFormat::Rgba16Uint | TextureFormat::Rgba16Unorm => 1,
+            _ => 0,
+        };
+        let samples = image.data.chunks_exact(stride).map(|pixel| pixel[offset]).collect();
+        let size = image.size();
+        Ok(Self {
+            width: size.x as i32,
+            depth: size.y as i32,
+            samples,
+        })
+    }
+
+    // Height in [0, 1] of the column at world x and z, None outside the image
+    pub fn sample(&self, x: i32, z: i32) -> Option<f32> {
+        let (column, row) = (x + self.width / 2, z + self.depth / 2);
+        if !(0..self.width).contains(&column) || !(0..self.depth).contains(&row) {
+            return None;
+        }
+        Some(self.samples[(row * self.width + column) as usize] as f32 / 255.0)
+    }
+
+    // World x and z range the image covers, min inclusive and max exclusive
+    pub fn bounds(&self) -> (IVec2, IVec2) {
+        let min = IVec2::new(-self.width / 2, -self.depth / 2);
+        (min, min + IVec2::new(self.width, self.depth))
+    }
+}
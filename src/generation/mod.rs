@@ -0,0 +1,450 @@
+use bevy::{
+    prelude::*,
+    tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
+    utils::HashSet,
+};
+use std::sync::Arc;
+use crate::bindings::{Action, ActionInput};
+use crate::console::RegisterConsoleCommand;
+use crate::diagnostics::{body_text_style, header_text_style};
+use crate::triggers::TriggerRegion;
+use crate::voxel::{VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::VoxelRenderSettings;
+
+mod heightmap;
+mod pointcloud;
+mod save;
+mod shapes;
+mod terrain;
+mod terrain_config;
+pub use heightmap::{Heightmap, HeightmapError};
+pub use pointcloud::{PointCloudError, PointCloudImportPlugin, PointCloudImporter};
+pub use save::{decode_fixture, ChunkStore, Compression, SaveError, SaveSettings, SaveStatus, WorldSavePlugin, WorldSaver};
+pub use shapes::{GlassPoolGenerator, GradientCubeGenerator, MengerSpongeGenerator, SphereGenerator, StaircaseGenerator, TorusGenerator};
+pub use terrain::{FlatGenerator, TerrainGenerator};
+pub use terrain_config::{TerrainConfig, TerrainConfigPlugin};
+
+pub struct GenerationPlugin;
+
+impl Plugin for GenerationPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GenerationProgress>()
+            .init_resource::<GenerationSettings>()
+            .add_event::<WorldCommand>()
+            .add_plugins((TerrainConfigPlugin, WorldSavePlugin, PointCloudImportPlugin))
+            .add_systems(Startup, (
+                log_configuration,
+                request_initial_chunks,
+                setup_progress_overlay,
+            ))
+            .add_systems(Update, (
+                cycle_demo_scene,
+                handle_world_commands,
+                poll_generation_tasks,
+                update_progress_overlay,
+            ).chain())
+            .register_console_command("seed", "seed [n]: shows the seed, or sets it and regenerates", seed_command)
+            .register_console_command("regen", "regen: generates the world again", |world, _| {
+                world.send_event(WorldCommand::Regenerate);
+                Ok("Regenerating".to_string())
+            });
+    }
+}
+
+fn seed_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let Some(word) = args.first() else {
+        return Ok(format!("Seed {}", world.resource::<GenerationSettings>().seed));
+    };
+    let seed: u32 = word.parse().map_err(|_| format!("invalid seed '{}'", word))?;
+    world.resource_mut::<GenerationSettings>().seed = seed;
+    world.send_event(WorldCommand::Regenerate);
+    Ok(format!("Seed {}; regenerating", seed))
+}
+
+// Produces the voxel contents of a world, one chunk at a time
+pub trait WorldGenerator: Send + Sync + 'static {
+    fn name(&self) -> &'static str;
+
+    // Chunks to generate when the world is (re)built
+    fn chunk_positions(&self, settings: &VoxelRenderSettings) -> Vec<IVec3>;
+
+    // Runs on the async compute pool, so it must not touch the ECS
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DemoScene {
+    CubeGrid,
+    Flat,
+    Terrain,
+    Sphere,
+    Torus,
+    MengerSponge,
+    Staircase,
+    GlassPool,
+    // Terrain from GenerationSettings::heightmap, skipped while there is none
+    Heightmap,
+}
+
+impl DemoScene {
+    pub const ALL: [DemoScene; 9] = [
+        DemoScene::CubeGrid,
+        DemoScene::Flat,
+        DemoScene::Terrain,
+        DemoScene::Sphere,
+        DemoScene::Torus,
+        DemoScene::MengerSponge,
+        DemoScene::Staircase,
+        DemoScene::GlassPool,
+        DemoScene::Heightmap,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            DemoScene::CubeGrid => DemoScene::Flat,
+            DemoScene::Flat => DemoScene::Terrain,
+            DemoScene::Terrain => DemoScene::Sphere,
+            DemoScene::Sphere => DemoScene::Torus,
+            DemoScene::Torus => DemoScene::MengerSponge,
+            DemoScene::MengerSponge => DemoScene::Staircase,
+            DemoScene::Staircase => DemoScene::GlassPool,
+            DemoScene::GlassPool => DemoScene::Heightmap,
+            DemoScene::Heightmap => DemoScene::CubeGrid,
+        }
+    }
+
+    pub fn build(self, settings: &GenerationSettings) -> Arc<dyn WorldGenerator> {
+        let size = settings.shape_size;
+        match self {
+            DemoScene::CubeGrid => Arc::new(GradientCubeGenerator),
+            DemoScene::Flat => Arc::new(FlatGenerator { height: 8 }),
+            DemoScene::Terrain => Arc::new(TerrainGenerator {
+                seed: settings.seed,
+                surface_only: settings.surface_only,
+                config: settings.terrain.clone(),
+                heightmap: None,
+            }),
+            DemoScene::Heightmap => Arc::new(TerrainGenerator {
+                seed: settings.seed,
+                surface_only: settings.surface_only,
+                config: settings.terrain.clone(),
+                heightmap: settings.heightmap.clone(),
+            }),
+            DemoScene::Sphere => Arc::new(SphereGenerator {
+                radius: size as f32 / 2.0,
+            }),
+            DemoScene::Torus => Arc::new(TorusGenerator {
+                major_radius: size as f32 * 0.35,
+                minor_radius: size as f32 * 0.15,
+            }),
+            DemoScene::MengerSponge => Arc::new(MengerSpongeGenerator::fitting(size)),
+            DemoScene::Staircase => Arc::new(StaircaseGenerator {
+                steps: size / 3,
+                tread: 3,
+                width: 12,
+            }),
+            DemoScene::GlassPool => Arc::new(GlassPoolGenerator { size: 32 }),
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct GenerationSettings {
+    pub scene: DemoScene,
+    // Width of the demo shapes in voxels; may span several chunks
+    pub shape_size: i32,
+    pub seed: u32,
+    // Terrain emits only exposed voxels instead of filling and culling
+    pub surface_only: bool,
+    pub terrain: TerrainConfig,
+    pub heightmap: Option<Arc<Heightmap>>,
+    // Chunks to generate along each axis in place of the generator's own set,
+    // centered on the origin horizontally and starting at y = 0
+    pub world_size: Option<IVec3>,
+}
+
+impl Default for GenerationSettings {
+    fn default() -> Self {
+        Self {
+            scene: DemoScene::CubeGrid,
+            shape_size: 81,
+            seed: 0,
+            surface_only: true,
+            terrain: TerrainConfig::default(),
+            heightmap: None,
+            world_size: None,
+        }
+    }
+}
+
+impl GenerationSettings {
+    pub fn generator(&self) -> Arc<dyn WorldGenerator> {
+        self.scene.build(self)
+    }
+}
+
+#[derive(Event, Clone, Copy, Debug)]
+pub enum WorldCommand {
+    // Despawn every chunk and cancel pending generation
+    Clear,
+    // Clear, then generate the world again from the current settings
+    Regenerate,
+}
+
+// Tracks how far the initial world load has come
+#[derive(Resource, Default)]
+pub struct GenerationProgress {
+    pub requested: usize,
+    pub completed: usize,
+    pub started_at: f32,
+    pub elapsed: f32,
+}
+
+impl GenerationProgress {
+    pub fn is_complete(&self) -> bool {
+        self.completed >= self.requested
+    }
+
+    pub fn fraction(&self) -> f32 {
+        if self.requested == 0 {
+            return 1.0;
+        }
+        self.completed as f32 / self.requested as f32
+    }
+
+    // Estimated seconds remaining, extrapolated from the average time per chunk so far
+    pub fn eta(&self) -> Option<f32> {
+        if self.completed == 0 || self.is_complete() {
+            return None;
+        }
+        let per_chunk = self.elapsed / self.completed as f32;
+        Some(per_chunk * (self.requested - self.completed) as f32)
+    }
+}
+
+// Chunk data being generated on the async compute pool
+#[derive(Component)]
+pub struct ChunkGenerationTask(Task<VoxelChunk>);
+
+#[derive(Component)]
+struct ProgressText;
+
+// Chunk positions whose center lies within render distance of the origin
+pub fn chunks_within_render_distance(settings: &VoxelRenderSettings) -> Vec<IVec3> {
+    let chunk_world_size = CHUNK_SIZE as f32 * settings.voxel_size;
+    let radius = (settings.render_distance / chunk_world_size).ceil() as i32;
+
+    let mut positions = Vec::new();
+    for x in -radius..=radius {
+        for z in -radius..=radius {
+            let position = IVec3::new(x, 0, z);
+            let center = (position.as_vec3() + Vec3::splat(0.5)) * chunk_world_size;
+            if Vec2::new(center.x, center.z).length() <= settings.render_distance {
+                positions.push(position);
+            }
+        }
+    }
+    positions
+}
+
+fn chunks_in_world_size(size: IVec3) -> Vec<IVec3> {
+    let min = IVec3::new(-size.x / 2, 0, -size.z / 2);
+    let max = min + size;
+    (min.x..max.x)
+        .flat_map(|x| (min.y..max.y).flat_map(move |y| (min.z..max.z).map(move |z| IVec3::new(x, y, z))))
+        .collect()
+}
+
+fn log_configuration(generation_settings: Res<GenerationSettings>, settings: Res<VoxelRenderSettings>) {
+    info!(
+        "Generator {} (seed {}), chunk size {}, render mode {:?}, render distance {}",
+        generation_settings.generator().name(),
+        generation_settings.seed,
+        CHUNK_SIZE,
+        settings.render_mode,
+        settings.render_distance,
+    );
+}
+
+fn request_initial_chunks(mut world_commands: EventWriter<WorldCommand>) {
+    world_commands.send(WorldCommand::Regenerate);
+}
+
+fn cycle_demo_scene(
+    input: ActionInput,
+    mut generation_settings: ResMut<GenerationSettings>,
+    mut world_commands: EventWriter<WorldCommand>,
+) {
+    // Shift+F5 quick-saves instead
+    if input.just_pressed(Action::CycleDemoScene) && !input.pressed(Action::WorldSaveModifier) {
+        generation_settings.scene = generation_settings.scene.next();
+        if generation_settings.scene == DemoScene::Heightmap && generation_settings.heightmap.is_none() {
+            generation_settings.scene = generation_settings.scene.next();
+        }
+        info!("Switching demo scene to {:?}", generation_settings.scene);
+        world_commands.send(WorldCommand::Regenerate);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn handle_world_commands(
+    mut commands: Commands,
+    mut world_commands: EventReader<WorldCommand>,
+    mut progress: ResMut<GenerationProgress>,
+    mut saver: ResMut<WorldSaver>,
+    mut store: ResMut<ChunkStore>,
+    generation_settings: Res<GenerationSettings>,
+    settings: Res<VoxelRenderSettings>,
+    chunks: Query<Entity, Or<(With<VoxelChunk>, With<ChunkGenerationTask>)>>,
+    loaded: Query<&VoxelChunk>,
+    regions: Query<Entity, With<TriggerRegion>>,
+    time: Res<Time>,
+) {
+    for command in world_commands.read() {
+        // Edited chunks go to the store before their entities do. Dropping a
+        // pending task cancels it. Trigger regions belong to the world and go
+        // with it.
+        store.unload(&mut saver, loaded.iter());
+        for entity in chunks.iter().chain(regions.iter()) {
+            commands.entity(entity).despawn_recursive();
+        }
+        *progress = GenerationProgress::default();
+
+        if let WorldCommand::Regenerate = command {
+            // The store only stands in for the scene it was made in
+            if store.scene.is_some_and(|scene| scene != generation_settings.scene) {
+                store.drop_world(&mut saver);
+            }
+            let unloaded = store.take_unloaded(&mut saver);
+            let reloaded: HashSet<IVec3> = unloaded.iter().map(|chunk| chunk.position).collect();
+            for chunk in unloaded {
+                let transform = Transform::from_translation(chunk.world_center(settings.voxel_size));
+                commands.spawn((chunk, SpatialBundle::from_transform(transform)));
+            }
+            for region in &store.regions {
+                commands.spawn(region.clone());
+            }
+
+            let generator = generation_settings.generator();
+            let task_pool = AsyncComputeTaskPool::get();
+            let mut positions = match generation_settings.world_size {
+                Some(size) => chunks_in_world_size(size),
+                None => generator.chunk_positions(&settings),
+            };
+            // Stored chunks edited outside the generated area come back too
+            let generated: HashSet<IVec3> = positions.iter().copied().collect();
+            positions.extend(store.stored_positions().into_iter().filter(|position| !generated.contains(position)));
+            positions.retain(|position| !reloaded.contains(position));
+
+            progress.requested = positions.len();
+            progress.started_at = time.elapsed_seconds();
+            info!("Requested {} chunks from {}", positions.len(), generator.name());
+
+            for position in positions {
+                let (generator, stored) = (generator.clone(), store.reader());
+                // Stored chunks are read back in place of generating them
+                let task = task_pool.spawn(async move {
+                    chunk_span!("generate_chunk", position);
+                    match stored.read(position) {
+                        Some(Ok(chunk)) => chunk,
+                        Some(Err(err)) => {
+                            warn!("Stored chunk {}: {}; regenerating it", position, err);
+                            generator.generate_chunk(position)
+                        }
+                        None => generator.generate_chunk(position),
+                    }
+                });
+                commands.spawn(ChunkGenerationTask(task));
+            }
+        }
+    }
+}
+
+// Moves finished chunks out of their tasks without blocking the frame
+fn poll_generation_tasks(
+    mut commands: Commands,
+    mut tasks: Query<(Entity, &mut ChunkGenerationTask)>,
+    mut progress: ResMut<GenerationProgress>,
+    settings: Res<VoxelRenderSettings>,
+    time: Res<Time>,
+) {
+    for (entity, mut task) in tasks.iter_mut() {
+        if let Some(chunk) = future::block_on(future::poll_once(&mut task.0)) {
+            progress.completed += 1;
+
+            // Shapes leave many chunks in their bounds empty
+            if chunk.voxels.is_empty() && chunk.hidden_voxels.is_empty() {
+                commands.entity(entity).despawn();
+                continue;
+            }
+
+            let transform = Transform::from_translation(chunk.world_center(settings.voxel_size));
+            commands
+                .entity(entity)
+                .remove::<ChunkGenerationTask>()
+                .insert((chunk, SpatialBundle::from_transform(transform)));
+        }
+    }
+
+    if !progress.is_complete() {
+        progress.elapsed = time.elapsed_seconds() - progress.started_at;
+    }
+}
+
+fn setup_progress_overlay(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_sections([
+                    TextSection::new("Generating world\n", header_text_style()),
+                    TextSection::from_style(body_text_style()),
+                ]),
+                ProgressText,
+            ));
+        });
+}
+
+fn update_progress_overlay(
+    progress: Res<GenerationProgress>,
+    mut query: Query<(&mut Text, &Parent), With<ProgressText>>,
+    mut visibility: Query<&mut Visibility>,
+) {
+    if !progress.is_changed() {
+        return;
+    }
+
+    for (mut text, parent) in &mut query {
+        if let Ok(mut visibility) = visibility.get_mut(parent.get()) {
+            *visibility = if progress.is_complete() {
+                Visibility::Hidden
+            } else {
+                Visibility::Inherited
+            };
+        }
+        if progress.is_complete() {
+            continue;
+        }
+
+        let eta = match progress.eta() {
+            Some(seconds) => format!("{:.1}s", seconds),
+            None => "--".to_string(),
+        };
+        text.sections[1].value = format!(
+            "{:.0}% ({}/{} chunks)\nETA: {}\n",
+            progress.fraction() * 100.0,
+            progress.completed,
+            progress.requested,
+            eta,
+        );
+    }
+}
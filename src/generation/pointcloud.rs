@@ -0,0 +1,509 @@
+// src/generation/pointcloud.rs
+use bevy::{
+    prelude::*,
+    tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
+    utils::HashMap,
+    window::FileDragAndDrop,
+};
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use crate::bindings::{Action, ActionInput};
+use crate::voxel::{EditOp, VoxelEditor, VoxelEdits};
+use crate::voxel_types::KIND_PLAIN;
+use super::GenerationProgress;
+
+mod qubicle;
+
+// Files dropped on the window with these extensions are imported
+const EXTENSIONS: [&str; 5] = ["ply", "xyz", "txt", "pts", "qb"];
+// Points read between checks for cancellation
+const CANCEL_CHECK_POINTS: u64 = 65_536;
+// For points without a color
+const DEFAULT_POINT_COLOR: [u8; 3] = [200, 200, 200];
+
+pub struct PointCloudImportPlugin;
+
+impl Plugin for PointCloudImportPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PointCloudImporter>()
+            .add_systems(Update, (import_dropped_files, start_import, cancel_import, poll_import).chain());
+    }
+}
+
+// Voxelizes PLY and XYZ point clouds on the compute pool, streaming them from
+// disk. Points falling in the same cell average their colors. The result is
+// applied as one edit once the world has finished generating, so it can be undone.
+// Qubicle .qb models go through the same path, one model voxel per world voxel.
+// Escape cancels a running import.
+#[derive(Resource)]
+pub struct PointCloudImporter {
+    // Cell size in the cloud's units
+    pub voxel_size: f32,
+    // World voxel the cloud's origin lands on
+    pub origin: IVec3,
+    // Started on the next update, as for --import-pointcloud or a dropped file
+    pub pending: Option<PathBuf>,
+    running: Option<RunningImport>,
+}
+
+impl Default for PointCloudImporter {
+    fn default() -> Self {
+        Self {
+            voxel_size: 0.1,
+            origin: IVec3::ZERO,
+            pending: None,
+            running: None,
+        }
+    }
+}
+
+impl PointCloudImporter {
+    // Fraction of the file read by the running import
+    pub fn progress(&self) -> Option<f32> {
+        self.running.as_ref().map(RunningImport::fraction)
+    }
+}
+
+struct RunningImport {
+    path: PathBuf,
+    task: Option<Task<Result<ImportedCloud, PointCloudError>>>,
+    // Waiting for generation to finish before it's applied
+    finished: Option<ImportedCloud>,
+    bytes_read: Arc<AtomicU64>,
+    size: u64,
+    cancel: Arc<AtomicBool>,
+    logged_tenths: u64,
+}
+
+impl RunningImport {
+    fn fraction(&self) -> f32 {
+        if self.finished.is_some() || self.size == 0 {
+            return 1.0;
+        }
+        (self.bytes_read.load(Ordering::Relaxed) as f64 / self.size as f64).min(1.0) as f32
+    }
+}
+
+struct ImportedCloud {
+    edits: VoxelEdits,
+    points: u64,
+    voxels: usize,
+}
+
+#[derive(Debug)]
+pub enum PointCloudError {
+    Io(std::io::Error),
+    Format(String),
+    Cancelled,
+}
+
+impl fmt::Display for PointCloudError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PointCloudError::Io(err) => write!(f, "could not read file: {}", err),
+            PointCloudError::Format(reason) => write!(f, "invalid file: {}", reason),
+            PointCloudError::Cancelled => write!(f, "import cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for PointCloudError {}
+
+fn format_error(reason: impl Into<String>) -> PointCloudError {
+    PointCloudError::Format(reason.into())
+}
+
+// Counts the bytes pulled from the file, for progress
+struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.inner.read(buf)?;
+        self.read.fetch_add(count as u64, Ordering::Relaxed);
+        Ok(count)
+    }
+}
+
+// Summed colors and point count per cell
+struct VoxelGrid {
+    voxel_size: f32,
+    cells: HashMap<IVec3, ([u64; 3], u64)>,
+    points: u64,
+    cancel: Arc<AtomicBool>,
+}
+
+impl VoxelGrid {
+    fn add(&mut self, point: Vec3, color: [u8; 3]) -> Result<(), PointCloudError> {
+        self.points += 1;
+        if self.points % CANCEL_CHECK_POINTS == 0 && self.cancel.load(Ordering::Relaxed) {
+            return Err(PointCloudError::Cancelled);
+        }
+        if !point.is_finite() {
+            return Ok(());
+        }
+        // Point clouds are Z-up and the world is Y-up
+        let world = Vec3::new(point.x, point.z, -point.y);
+        let cell = (world / self.voxel_size).floor().as_ivec3();
+        let (sums, count) = self.cells.entry(cell).or_default();
+        for (sum, channel) in sums.iter_mut().zip(color) {
+            *sum += channel as u64;
+        }
+        *count += 1;
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    LittleEndian,
+    BigEndian,
+}
+
+#[derive(Clone, Copy)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    fn parse(name: &str) -> Option<Self> {
+        Some(match name {
+            "char" | "int8" => Scalar::I8,
+            "uchar" | "uint8" => Scalar::U8,
+            "short" | "int16" => Scalar::I16,
+            "ushort" | "uint16" => Scalar::U16,
+            "int" | "int32" => Scalar::I32,
+            "uint" | "uint32" => Scalar::U32,
+            "float" | "float32" => Scalar::F32,
+            "double" | "float64" => Scalar::F64,
+            _ => return None,
+        })
+    }
+
+    fn size(self) -> usize {
+        match self {
+            Scalar::I8 | Scalar::U8 => 1,
+            Scalar::I16 | Scalar::U16 => 2,
+            Scalar::I32 | Scalar::U32 | Scalar::F32 => 4,
+            Scalar::F64 => 8,
+        }
+    }
+
+    // Value of full intensity when the scalar holds a color channel
+    fn color_scale(self) -> f64 {
+        match self {
+            Scalar::I16 | Scalar::U16 => 65_535.0,
+            Scalar::F32 | Scalar::F64 => 1.0,
+            _ => 255.0,
+        }
+    }
+
+    fn decode(self, bytes: &[u8], format: PlyFormat) -> f64 {
+        macro_rules! number {
+            ($ty:ty) => {{
+                let bytes = bytes.try_into().unwrap_or_default();
+                if format == PlyFormat::BigEndian { <$ty>::from_be_bytes(bytes) as f64 } else { <$ty>::from_le_bytes(bytes) as f64 }
+            }};
+        }
+        match self {
+            Scalar::I8 => number!(i8),
+            Scalar::U8 => number!(u8),
+            Scalar::I16 => number!(i16),
+            Scalar::U16 => number!(u16),
+            Scalar::I32 => number!(i32),
+            Scalar::U32 => number!(u32),
+            Scalar::F32 => number!(f32),
+            Scalar::F64 => number!(f64),
+        }
+    }
+}
+
+fn color_channel(value: f64, scale: f64) -> u8 {
+    (value / scale * 255.0).round().clamp(0.0, 255.0) as u8
+}
+
+// Reads the vertex element, which must come first; later elements such as faces
+// are ignored
+fn read_ply(reader: &mut impl BufRead, grid: &mut VoxelGrid) -> Result<(), PointCloudError> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(PointCloudError::Io)?;
+    if line.trim() != "ply" {
+        return Err(format_error("missing ply header"));
+    }
+    let mut format = None;
+    let mut vertices: Option<u64> = None;
+    let mut in_vertex = false;
+    let mut properties: Vec<(String, Scalar)> = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(PointCloudError::Io)? == 0 {
+            return Err(format_error("header ends early"));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            ["format", kind, _] => {
+                format = Some(match *kind {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::LittleEndian,
+                    "binary_big_endian" => PlyFormat::BigEndian,
+                    other => return Err(format_error(format!("unknown format {}", other))),
+                });
+            }
+            ["element", "vertex", count] if vertices.is_none() => {
+                vertices = Some(count.parse().map_err(|_| format_error("invalid vertex count"))?);
+                in_vertex = true;
+            }
+            ["element", ..] => {
+                if vertices.is_none() {
+                    return Err(format_error("vertex must be the first element"));
+                }
+                in_vertex = false;
+            }
+            ["property", "list", ..] if in_vertex => return Err(format_error("list properties on vertices aren't supported")),
+            ["property", kind, name] if in_vertex => {
+                let scalar = Scalar::parse(kind).ok_or_else(|| format_error(format!("unknown property type {}", kind)))?;
+                properties.push((name.to_string(), scalar));
+            }
+            ["end_header"] => break,
+            // Comments and other elements' properties
+            _ => {}
+        }
+    }
+    let format = format.ok_or_else(|| format_error("missing format"))?;
+    let vertices = vertices.ok_or_else(|| format_error("no vertex element"))?;
+    let find = |names: &[&str]| properties.iter().position(|(name, _)| names.contains(&name.as_str()));
+    let (Some(x), Some(y), Some(z)) = (find(&["x"]), find(&["y"]), find(&["z"])) else {
+        return Err(format_error("vertices need x, y and z"));
+    };
+    let colors = match (find(&["red", "r", "diffuse_red"]), find(&["green", "g", "diffuse_green"]), find(&["blue", "b", "diffuse_blue"])) {
+        (Some(r), Some(g), Some(b)) => Some([r, g, b]),
+        _ => None,
+    };
+
+    let mut values = vec![0.0; properties.len()];
+    let row_size: usize = properties.iter().map(|(_, scalar)| scalar.size()).sum();
+    let mut row = vec![0; row_size];
+    for _ in 0..vertices {
+        if format == PlyFormat::Ascii {
+            line.clear();
+            if reader.read_line(&mut line).map_err(PointCloudError::Io)? == 0 {
+                return Err(format_error("file ends before its last vertex"));
+            }
+            let mut words = line.split_whitespace();
+            for value in values.iter_mut() {
+                *value = words
+                    .next()
+                    .and_then(|word| word.parse().ok())
+                    .ok_or_else(|| format_error("vertex has too few values"))?;
+            }
+        } else {
+            reader.read_exact(&mut row).map_err(PointCloudError::Io)?;
+            let mut offset = 0;
+            for (value, (_, scalar)) in values.iter_mut().zip(&properties) {
+                *value = scalar.decode(&row[offset..offset + scalar.size()], format);
+                offset += scalar.size();
+            }
+        }
+        let point = Vec3::new(values[x] as f32, values[y] as f32, values[z] as f32);
+        let color = match colors {
+            Some(channels) => channels.map(|index| color_channel(values[index], properties[index].1.color_scale())),
+            None => DEFAULT_POINT_COLOR,
+        };
+        grid.add(point, color)?;
+    }
+    Ok(())
+}
+
+// Lines of "x y z", optionally followed by "r g b", or by intensity then "r g b"
+// as in .pts files. Whitespace or commas separate values. Integer colors are out
+// of 255 and fractional ones out of 1. Lines that don't start with three numbers,
+// such as headers and point counts, are skipped.
+fn read_xyz(reader: &mut impl BufRead, grid: &mut VoxelGrid) -> Result<(), PointCloudError> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).map_err(PointCloudError::Io)? == 0 {
+            return Ok(());
+        }
+        let words: Vec<&str> = line.split(|c: char| c.is_whitespace() || c == ',').filter(|word| !word.is_empty()).collect();
+        let Some(coordinates) = words.get(..3).and_then(|words| words.iter().map(|word| word.parse::<f32>().ok()).collect::<Option<Vec<_>>>()) else {
+            continue;
+        };
+        let color_words = match words.len() {
+            6 => words.get(3..6),
+            7.. => words.get(4..7),
+            _ => None,
+        };
+        let color = color_words
+            .and_then(|words| {
+                words
+                    .iter()
+                    .map(|word| {
+                        let value: f64 = word.parse().ok()?;
+                        Some(color_channel(value, if word.contains('.') { 1.0 } else { 255.0 }))
+                    })
+                    .collect::<Option<Vec<_>>>()
+            })
+            .map_or(DEFAULT_POINT_COLOR, |channels| [channels[0], channels[1], channels[2]]);
+        grid.add(Vec3::new(coordinates[0], coordinates[1], coordinates[2]), color)?;
+    }
+}
+
+// Runs on the compute pool
+fn import_point_cloud(
+    path: &Path,
+    voxel_size: f32,
+    origin: IVec3,
+    bytes_read: Arc<AtomicU64>,
+    cancel: Arc<AtomicBool>,
+) -> Result<ImportedCloud, PointCloudError> {
+    let file = File::open(path).map_err(PointCloudError::Io)?;
+    let mut reader = BufReader::new(CountingReader {
+        inner: file,
+        read: bytes_read,
+    });
+    let extension = path.extension().and_then(|extension| extension.to_str()).map(str::to_ascii_lowercase);
+    if extension.as_deref() == Some("qb") {
+        return qubicle::read_qb(&mut reader, origin, &cancel);
+    }
+    let mut grid = VoxelGrid {
+        voxel_size,
+        cells: HashMap::default(),
+        points: 0,
+        cancel,
+    };
+    if extension.as_deref() == Some("ply") {
+        read_ply(&mut reader, &mut grid)?;
+    } else {
+        read_xyz(&mut reader, &mut grid)?;
+    }
+
+    let mut edits = VoxelEdits::default();
+    for (cell, (sums, count)) in &grid.cells {
+        let [r, g, b] = sums.map(|sum| (sum / count) as u8);
+        edits.push(origin + *cell, EditOp::Place {
+            color: Color::rgb_u8(r, g, b),
+            kind: KIND_PLAIN,
+        });
+    }
+    Ok(ImportedCloud {
+        edits,
+        points: grid.points,
+        voxels: grid.cells.len(),
+    })
+}
+
+fn import_dropped_files(mut drops: EventReader<FileDragAndDrop>, mut importer: ResMut<PointCloudImporter>) {
+    for drop in drops.read() {
+        let FileDragAndDrop::DroppedFile { path_buf, .. } = drop else {
+            continue;
+        };
+        let supported = path_buf
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()));
+        if supported {
+            importer.pending = Some(path_buf.clone());
+        } else {
+            info!("Can't import {}; imports must be .ply, .xyz, .txt, .pts or .qb", path_buf.display());
+        }
+    }
+}
+
+fn start_import(mut importer: ResMut<PointCloudImporter>) {
+    if importer.pending.is_none() {
+        return;
+    }
+    if importer.running.is_some() {
+        info!("Already importing a file");
+        importer.pending = None;
+        return;
+    }
+    let Some(path) = importer.pending.take() else {
+        return;
+    };
+    let size = std::fs::metadata(&path).map_or(0, |metadata| metadata.len());
+    let bytes_read = Arc::new(AtomicU64::new(0));
+    let cancel = Arc::new(AtomicBool::new(false));
+    let (voxel_size, origin) = (importer.voxel_size, importer.origin);
+    let task = {
+        let (path, bytes_read, cancel) = (path.clone(), bytes_read.clone(), cancel.clone());
+        AsyncComputeTaskPool::get().spawn(async move { import_point_cloud(&path, voxel_size, origin, bytes_read, cancel) })
+    };
+    info!("Importing {} at {} units per voxel", path.display(), voxel_size);
+    importer.running = Some(RunningImport {
+        path,
+        task: Some(task),
+        finished: None,
+        bytes_read,
+        size,
+        cancel,
+        logged_tenths: 0,
+    });
+}
+
+fn cancel_import(input: ActionInput, importer: Res<PointCloudImporter>) {
+    if let Some(running) = importer.running.as_ref().filter(|_| input.just_pressed(Action::CancelImport)) {
+        running.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn poll_import(
+    mut importer: ResMut<PointCloudImporter>,
+    progress: Res<GenerationProgress>,
+    mut editor: VoxelEditor,
+) {
+    let Some(running) = importer.running.as_mut() else {
+        return;
+    };
+    let tenths = (running.fraction() * 10.0) as u64;
+    if tenths > running.logged_tenths && tenths < 10 {
+        running.logged_tenths = tenths;
+        info!("Importing {}: {}%", running.path.display(), tenths * 10);
+    }
+
+    if let Some(task) = running.task.as_mut() {
+        let Some(result) = future::block_on(future::poll_once(task)) else {
+            return;
+        };
+        running.task = None;
+        match result {
+            Ok(cloud) => running.finished = Some(cloud),
+            Err(err) => {
+                warn!("{}: {}", running.path.display(), err);
+                importer.running = None;
+                return;
+            }
+        }
+    }
+    // Chunks generated afterwards would replace the imported ones
+    if !progress.is_complete() {
+        return;
+    }
+    let Some(cloud) = running.finished.take() else {
+        return;
+    };
+    let chunks = editor.apply(cloud.edits);
+    info!(
+        "Imported {} points from {} as {} voxels in {} chunks",
+        cloud.points,
+        running.path.display(),
+        cloud.voxels,
+        chunks,
+    );
+    importer.running = None;
+}
@@ -0,0 +1,231 @@
+// src/generation/pointcloud/qubicle.rs
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::io::Read;
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::voxel::{EditOp, VoxelEdits};
+use crate::voxel_types::KIND_PLAIN;
+use super::{format_error, ImportedCloud, PointCloudError};
+
+// Qubicle Binary 1.1
+const QB_VERSION: [u8; 4] = [1, 1, 0, 0];
+// In compressed matrices, marks a run as count then color
+const CODE_FLAG: u32 = 2;
+// In compressed matrices, ends the current z slice
+const NEXT_SLICE_FLAG: u32 = 6;
+// Largest matrix along any axis; guards against corrupt sizes
+const MAX_MATRIX_SIZE: u32 = 4096;
+// Farthest a voxel may land from the world origin along any axis. Past this,
+// world positions as f32 no longer hold whole voxels.
+const MAX_WORLD_COORDINATE: i32 = 1 << 24;
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, PointCloudError> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes).map_err(PointCloudError::Io)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+// Header fields that change how matrices are read
+struct QbHeader {
+    bgra: bool,
+    right_handed: bool,
+    compressed: bool,
+}
+
+impl QbHeader {
+    // None for an empty cell. With the visibility mask encoded, alpha holds the
+    // visible sides instead, but zero still means empty.
+    fn color(&self, data: u32) -> Option<Color> {
+        let [first, g, third, alpha] = data.to_le_bytes();
+        if alpha == 0 {
+            return None;
+        }
+        let (r, b) = if self.bgra { (third, first) } else { (first, third) };
+        Some(Color::rgb_u8(r, g, b))
+    }
+
+    // Cell of a matrix voxel relative to the import origin. Qubicle is Y-up like
+    // the world; left-handed files flip z. None when it leaves the i32 range.
+    fn world_cell(&self, position: IVec3, offset: UVec3) -> Option<IVec3> {
+        let cell = checked_add(position, offset.as_ivec3())?;
+        if self.right_handed {
+            Some(cell)
+        } else {
+            // -z - 1, which can't overflow
+            Some(IVec3::new(cell.x, cell.y, !cell.z))
+        }
+    }
+}
+
+fn checked_add(a: IVec3, b: IVec3) -> Option<IVec3> {
+    Some(IVec3::new(a.x.checked_add(b.x)?, a.y.checked_add(b.y)?, a.z.checked_add(b.z)?))
+}
+
+fn in_world(cell: IVec3) -> bool {
+    cell.cmpge(IVec3::splat(-MAX_WORLD_COORDINATE)).all() && cell.cmple(IVec3::splat(MAX_WORLD_COORDINATE)).all()
+}
+
+// Reads every matrix, placed by its position relative to origin, one voxel per
+// world voxel. Matrices may be stored plain or run-length encoded per z slice.
+pub(super) fn read_qb(reader: &mut impl Read, origin: IVec3, cancel: &AtomicBool) -> Result<ImportedCloud, PointCloudError> {
+    let mut version = [0; 4];
+    reader.read_exact(&mut version).map_err(PointCloudError::Io)?;
+    if version != QB_VERSION {
+        return Err(format_error(format!("unsupported Qubicle version {:?}", version)));
+    }
+    let header = QbHeader {
+        bgra: read_u32(reader)? == 1,
+        right_handed: read_u32(reader)? == 1,
+        compressed: read_u32(reader)? != 0,
+    };
+    // Visibility mask flag; alpha is read the same way either way
+    read_u32(reader)?;
+    let matrices = read_u32(reader)?;
+
+    let mut cells: HashMap<IVec3, Color> = HashMap::default();
+    let mut read = 0;
+    for _ in 0..matrices {
+        let mut name_length = [0];
+        reader.read_exact(&mut name_length).map_err(PointCloudError::Io)?;
+        let mut name = vec![0; name_length[0] as usize];
+        reader.read_exact(&mut name).map_err(PointCloudError::Io)?;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        let size = [read_u32(reader)?, read_u32(reader)?, read_u32(reader)?];
+        if size.iter().any(|&axis| axis > MAX_MATRIX_SIZE) {
+            return Err(format_error(format!("matrix {} is too large", name)));
+        }
+        let position = IVec3::new(read_u32(reader)? as i32, read_u32(reader)? as i32, read_u32(reader)? as i32);
+        let [width, height, depth] = size;
+        // A corrupt position could put voxels anywhere, so each is bounds checked
+        let mut place = |x: u32, y: u32, z: u32, data: u32| -> Result<(), PointCloudError> {
+            read += 1;
+            let Some(color) = header.color(data) else {
+                return Ok(());
+            };
+            let cell = header
+                .world_cell(position, UVec3::new(x, y, z))
+                .and_then(|cell| checked_add(origin, cell))
+                .filter(|cell| in_world(*cell))
+                .ok_or_else(|| format_error(format!("matrix {} places voxels outside the world", name)))?;
+            cells.insert(cell, color);
+            Ok(())
+        };
+
+        for z in 0..depth {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(PointCloudError::Cancelled);
+            }
+            if !header.compressed {
+                for y in 0..height {
+                    for x in 0..width {
+                        place(x, y, z, read_u32(reader)?)?;
+                    }
+                }
+                continue;
+            }
+            // Runs fill the slice row by row until the slice flag
+            let mut index = 0;
+            loop {
+                let data = read_u32(reader)?;
+                if data == NEXT_SLICE_FLAG {
+                    break;
+                }
+                let (count, data) = if data == CODE_FLAG { (read_u32(reader)?, read_u32(reader)?) } else { (1, data) };
+                if index as u64 + count as u64 > width as u64 * height as u64 {
+                    return Err(format_error("compressed run overflows its slice"));
+                }
+                for _ in 0..count {
+                    place(index % width, index / width, z, data)?;
+                    index += 1;
+                }
+            }
+        }
+    }
+
+    let mut edits = VoxelEdits::default();
+    for (cell, color) in &cells {
+        edits.push(*cell, EditOp::Place { color: *color, kind: KIND_PLAIN });
+    }
+    Ok(ImportedCloud {
+        edits,
+        points: read,
+        voxels: cells.len(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A left-handed 3×2×2 matrix named body at (1, 0, -1), stored plain and
+    // compressed: red at (0, 0, 0) and (1, 0, 0), green at (1, 1, 0) and blue at
+    // (0, 0, 1)
+    const MODEL_PLAIN: &[u8] = include_bytes!("../../../fixtures/model_plain.qb");
+    const MODEL_COMPRESSED: &[u8] = include_bytes!("../../../fixtures/model_compressed.qb");
+
+    fn read(bytes: &[u8], origin: IVec3) -> Result<ImportedCloud, PointCloudError> {
+        read_qb(&mut &bytes[..], origin, &AtomicBool::new(false))
+    }
+
+    fn placed(cloud: &ImportedCloud, cell: IVec3) -> Option<[u8; 4]> {
+        match cloud.edits.get(cell) {
+            Some(EditOp::Place { color, .. }) => Some(color.as_rgba_u8()),
+            _ => None,
+        }
+    }
+
+    // One plain left-handed matrix holding a single red voxel at x = 1
+    fn single_voxel_model(position: IVec3) -> Vec<u8> {
+        let mut bytes = QB_VERSION.to_vec();
+        for value in [0, 0, 0, 0, 1] {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        bytes.push(1);
+        bytes.push(b'm');
+        for value in [2, 1, 1] {
+            bytes.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        for axis in position.to_array() {
+            bytes.extend_from_slice(&axis.to_le_bytes());
+        }
+        bytes.extend_from_slice(&[0, 0, 0, 0, 255, 0, 0, 255]);
+        bytes
+    }
+
+    #[test]
+    fn plain_and_compressed_models_place_the_same_voxels() {
+        let origin = IVec3::new(10, 20, 30);
+        for bytes in [MODEL_PLAIN, MODEL_COMPRESSED] {
+            let cloud = read(bytes, origin).unwrap();
+            assert_eq!(cloud.points, 12);
+            assert_eq!(cloud.voxels, 4);
+            // Left-handed, so the matrix's z - 1 flips to -z
+            assert_eq!(placed(&cloud, origin + IVec3::new(1, 0, 0)), Some([255, 0, 0, 255]));
+            assert_eq!(placed(&cloud, origin + IVec3::new(2, 0, 0)), Some([255, 0, 0, 255]));
+            assert_eq!(placed(&cloud, origin + IVec3::new(2, 1, 0)), Some([0, 255, 0, 255]));
+            assert_eq!(placed(&cloud, origin + IVec3::new(1, 0, -1)), Some([0, 0, 255, 255]));
+        }
+    }
+
+    #[test]
+    fn truncated_model_is_an_error() {
+        for bytes in [MODEL_PLAIN, MODEL_COMPRESSED] {
+            assert!(matches!(read(&bytes[..bytes.len() - 4], IVec3::ZERO), Err(PointCloudError::Io(_))));
+        }
+    }
+
+    #[test]
+    fn voxels_outside_the_world_are_refused() {
+        // Past i32::MAX, where the matrix position and voxel offset would overflow
+        let overflowing = single_voxel_model(IVec3::new(i32::MAX, 0, 0));
+        assert!(matches!(read(&overflowing, IVec3::ZERO), Err(PointCloudError::Format(_))));
+        // In range in the file, but the import origin pushes it out
+        let edge = single_voxel_model(IVec3::new(MAX_WORLD_COORDINATE - 1, 0, 0));
+        assert!(read(&edge, IVec3::ZERO).is_ok());
+        assert!(matches!(read(&edge, IVec3::X), Err(PointCloudError::Format(_))));
+        assert!(matches!(read(&edge, IVec3::splat(i32::MAX)), Err(PointCloudError::Format(_))));
+        // Flipping z at i32::MIN doesn't overflow, but lands far outside
+        let flipped = single_voxel_model(IVec3::new(0, 0, i32::MIN));
+        assert!(matches!(read(&flipped, IVec3::ZERO), Err(PointCloudError::Format(_))));
+    }
+}
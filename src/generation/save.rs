@@ -0,0 +1,1138 @@
+// src/generation/save.rs
+use bevy::{
+    ecs::system::SystemState,
+    prelude::*,
+    tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use crate::bindings::{Action, ActionInput};
+use crate::bytes::{ByteError, ByteReader};
+use crate::console::RegisterConsoleCommand;
+use crate::triggers::TriggerRegion;
+use crate::voxel::{ChunkLight, ChunkOccupancy, LocalPos, VoxelChanged, VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::Voxel;
+use super::{DemoScene, GenerationSettings, TerrainConfig, WorldCommand};
+
+mod fixture;
+mod migration;
+use fixture::capture_chunk_fixture;
+pub use fixture::decode_fixture;
+use migration::{migrate_chunk_data, migrate_file};
+
+const SAVE_DIRECTORY: &str = "saves";
+const WORLD_FILE: &str = "world.bin";
+const MAGIC: &[u8; 4] = b"WVXS";
+// Bumped whenever the file layout changes, along with a migration from the
+// previous version in save/migration.rs; older builds refuse newer saves
+const FORMAT_VERSION: u32 = 3;
+// Layout of a single chunk's data, stored at its start and migrated the same way
+const CHUNK_DATA_VERSION: u32 = 2;
+const CHUNK_CELLS: usize = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+// Cell values in the run-length data; palette entries start after these
+const EMPTY_CELL: u16 = 0;
+// Solid for occlusion but without a stored voxel, as generators leave buried cells
+const FILLED_CELL: u16 = 1;
+const FIRST_PALETTE_CELL: u16 = 2;
+// Color channels then kind
+const PALETTE_ENTRY_BYTES: usize = 4 * 4 + 2;
+// Length then value, for cells and light alike
+const RUN_BYTES: usize = 2 + 2;
+// Largest chunk data possible, bounding what a corrupt length can make us allocate
+const MAX_CHUNK_DATA_BYTES: usize =
+    4 + 2 + u16::MAX as usize * PALETTE_ENTRY_BYTES + 4 + CHUNK_CELLS * RUN_BYTES + 4 + CHUNK_CELLS * RUN_BYTES;
+const ZSTD_LEVEL: i32 = 3;
+
+pub struct WorldSavePlugin;
+
+impl Plugin for WorldSavePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WorldSaver>()
+            .init_resource::<ChunkStore>()
+            .init_resource::<SaveSettings>()
+            .add_systems(Startup, load_world_at_startup)
+            .add_systems(Update, (track_changed_chunks, quick_save_and_load, autosave, poll_save_task, write_unloaded_chunks).chain())
+            .add_systems(Update, capture_chunk_fixture)
+            .register_console_command("save", "save <name>: saves the world to saves/<name>", save_command)
+            .register_console_command("load", "load <name>: loads saves/<name> and regenerates", load_command);
+    }
+}
+
+// Save names become directory names, so they can't leave saves/
+fn console_save_name(args: &[&str]) -> Result<String, String> {
+    let name = args.first().ok_or("missing name")?;
+    if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        Ok(name.to_string())
+    } else {
+        Err(format!("invalid name '{}'; use letters, digits, - and _", name))
+    }
+}
+
+fn save_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name = console_save_name(args)?;
+    if world.resource::<WorldSaver>().task.is_some() {
+        return Err("a save is already running".to_string());
+    }
+    let mut state = SystemState::<(
+        Res<Time>,
+        Res<SaveSettings>,
+        ResMut<WorldSaver>,
+        ResMut<ChunkStore>,
+        Res<GenerationSettings>,
+        Query<&VoxelChunk>,
+        Query<&TriggerRegion>,
+    )>::new(world);
+    let (time, save_settings, mut saver, mut store, generation_settings, chunks, regions) = state.get_mut(world);
+    // Chunks the store holds are carried over from its file into the new one
+    saver.name = name;
+    let now = time.elapsed_seconds_f64();
+    start_save(&mut saver, &mut store, &generation_settings, &chunks, &regions, save_settings.compression, now);
+    Ok(format!("Saving to {}", saver.path().display()))
+}
+
+fn load_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name = console_save_name(args)?;
+    if world.resource::<WorldSaver>().task.is_some() {
+        return Err("a save is already running".to_string());
+    }
+    let mut state = SystemState::<(ResMut<WorldSaver>, ResMut<GenerationSettings>, ResMut<ChunkStore>, EventWriter<WorldCommand>)>::new(world);
+    let (mut saver, mut generation_settings, mut store, mut world_commands) = state.get_mut(world);
+    let previous = std::mem::replace(&mut saver.name, name);
+    match load_world(&mut saver, &mut generation_settings, &mut store) {
+        Ok(count) => {
+            world_commands.send(WorldCommand::Regenerate);
+            Ok(format!("Loaded {} chunks from {}", count, saver.path().display()))
+        }
+        Err(err) => {
+            saver.name = previous;
+            Err(err.to_string())
+        }
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct SaveSettings {
+    // Minutes between autosaves; 0 turns autosave off
+    pub autosave_minutes: f32,
+    // Applies to chunks written from now on; loading reads any of them
+    pub compression: Compression,
+}
+
+impl Default for SaveSettings {
+    fn default() -> Self {
+        Self {
+            autosave_minutes: 5.0,
+            compression: Compression::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Compression {
+    None,
+    // Fast, for frequent autosaves
+    #[default]
+    Lz4,
+    // Smaller files, slower to write
+    Zstd,
+}
+
+impl Compression {
+    // Stored before each chunk's data
+    fn flag(self) -> u8 {
+        match self {
+            Compression::None => 0,
+            Compression::Lz4 => 1,
+            Compression::Zstd => 2,
+        }
+    }
+
+    fn from_flag(flag: u8) -> Option<Self> {
+        match flag {
+            0 => Some(Compression::None),
+            1 => Some(Compression::Lz4),
+            2 => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+
+    // Returns the compression actually used with the compressed data; zstd failing
+    // falls back to storing the data as is
+    fn compress(self, data: &[u8]) -> (Self, Vec<u8>) {
+        match self {
+            Compression::None => (self, data.to_vec()),
+            Compression::Lz4 => (self, lz4_flex::compress_prepend_size(data)),
+            Compression::Zstd => match zstd::bulk::compress(data, ZSTD_LEVEL) {
+                Ok(compressed) => (self, compressed),
+                Err(_) => (Compression::None, data.to_vec()),
+            },
+        }
+    }
+}
+
+// What a finished save wrote; the sizes cover only the chunks serialized this time
+struct SaveSummary {
+    chunks: usize,
+    compression: Compression,
+    raw_bytes: usize,
+    stored_bytes: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SaveStatus {
+    Saving,
+    Saved,
+    Failed,
+}
+
+// Writes the edited chunks and the settings that generated them to
+// saves/<name>/world.bin, which serves as the ChunkStore. Shift+F5 saves and
+// Shift+F9 loads; a save that exists at startup is loaded in place of a fresh
+// world. Every save, autosaves included, only flushes the loaded chunks edited
+// since the last one and the edited chunks unloaded since. Saves run one at a
+// time, so a quick-save during an autosave waits for it.
+#[derive(Resource)]
+pub struct WorldSaver {
+    pub name: String,
+    pub load_at_startup: bool,
+    // Latest state and when it was reached, in elapsed seconds, for the overlay
+    pub status: Option<(SaveStatus, f64)>,
+    // Serializing and writing on the compute pool
+    task: Option<Task<Result<SaveSummary, SaveError>>>,
+    // Chunks edited since the last save began, and those the running save writes
+    dirty: HashSet<IVec3>,
+    saving: HashSet<IVec3>,
+    // Unloaded chunks the running save writes, dropped from the store once it has
+    saving_unloaded: HashSet<IVec3>,
+    quick_save_queued: bool,
+    next_autosave: Option<f64>,
+}
+
+impl Default for WorldSaver {
+    fn default() -> Self {
+        Self {
+            name: String::from("quicksave"),
+            load_at_startup: true,
+            status: None,
+            task: None,
+            dirty: HashSet::default(),
+            saving: HashSet::default(),
+            saving_unloaded: HashSet::default(),
+            quick_save_queued: false,
+            next_autosave: None,
+        }
+    }
+}
+
+impl WorldSaver {
+    pub fn named(name: String) -> Self {
+        Self {
+            name,
+            ..default()
+        }
+    }
+
+    pub fn path(&self) -> PathBuf {
+        Path::new(SAVE_DIRECTORY).join(&self.name).join(WORLD_FILE)
+    }
+
+    // Edits tracked for a world that's being replaced
+    fn forget_world(&mut self) {
+        self.dirty.clear();
+        self.saving.clear();
+        self.saving_unloaded.clear();
+    }
+}
+
+// Where a chunk's stored data sits in the save file
+#[derive(Clone, Copy, Debug)]
+struct StoredChunk {
+    offset: u64,
+    flag: u8,
+    length: u32,
+}
+
+// The save file the store reads from and where each chunk is in it. A save
+// replaces both under the write lock, so a read never seeks into the wrong file.
+#[derive(Default)]
+struct StoreIndex {
+    // None until a save is loaded or written
+    path: Option<PathBuf>,
+    chunks: HashMap<IVec3, StoredChunk>,
+}
+
+impl StoreIndex {
+    fn read(&self, position: IVec3) -> Option<Result<VoxelChunk, SaveError>> {
+        let (path, stored) = (self.path.as_ref()?, self.chunks.get(&position)?);
+        Some(read_stored_chunk(path, position, *stored))
+    }
+}
+
+// The save as a region store for the world being played. It holds only chunks
+// that differ from what the generator makes: edited chunks are kept when they
+// unload until a save writes them, and regenerating reads stored chunks back from
+// the file one at a time before falling back to the generator. A crash loses at
+// most the edits made since their chunk was last written. Switching scenes drops
+// the store.
+//
+// Worlds have a fixed size and nothing streams chunks out by distance, so chunks
+// only unload when a world command (regenerate, load, clear) despawns them all.
+#[derive(Resource, Default)]
+pub struct ChunkStore {
+    // Scene the stored chunks belong to
+    pub(super) scene: Option<DemoScene>,
+    pub(super) regions: Vec<TriggerRegion>,
+    // Shared with the tasks reading chunks back and the save rewriting the file
+    index: Arc<RwLock<StoreIndex>>,
+    // Edited chunks that were unloaded, until a save has written them
+    unloaded: HashMap<IVec3, ChunkSnapshot>,
+    // Some were unloaded since the last save started
+    unwritten: bool,
+}
+
+impl ChunkStore {
+    // Positions of the chunks in the file; unloaded ones come from take_unloaded
+    pub(super) fn stored_positions(&self) -> Vec<IVec3> {
+        self.index.read().unwrap().chunks.keys().copied().collect()
+    }
+
+    pub(super) fn reader(&self) -> StoreReader {
+        StoreReader(self.index.clone())
+    }
+
+    // Unloaded chunks, loaded again. They're still unwritten, so they count as
+    // edited until the next save.
+    pub(super) fn take_unloaded(&mut self, saver: &mut WorldSaver) -> Vec<VoxelChunk> {
+        self.unloaded
+            .drain()
+            .map(|(position, chunk)| {
+                saver.saving_unloaded.remove(&position);
+                saver.dirty.insert(position);
+                chunk.into_chunk()
+            })
+            .collect()
+    }
+
+    // Called before chunks are despawned, which today is handle_world_commands
+    // dropping the whole world. Edited ones, and those the running save might still
+    // fail to write, are kept until a save writes them.
+    pub(super) fn unload<'a>(&mut self, saver: &mut WorldSaver, chunks: impl IntoIterator<Item = &'a VoxelChunk>) {
+        for chunk in chunks {
+            let edited = saver.dirty.remove(&chunk.position);
+            if !edited && !saver.saving.contains(&chunk.position) {
+                continue;
+            }
+            self.unloaded.insert(chunk.position, ChunkSnapshot::of(chunk));
+            if edited {
+                self.unwritten = true;
+            } else {
+                saver.saving_unloaded.insert(chunk.position);
+            }
+        }
+    }
+
+    // Leaves the world behind, with whatever of it wasn't written yet
+    pub(super) fn drop_world(&mut self, saver: &mut WorldSaver) {
+        *self = ChunkStore::default();
+        saver.forget_world();
+    }
+}
+
+// Reads stored chunks back on the compute pool
+#[derive(Clone)]
+pub(super) struct StoreReader(Arc<RwLock<StoreIndex>>);
+
+impl StoreReader {
+    // None when the store doesn't hold the chunk, so the generator makes it
+    pub(super) fn read(&self, position: IVec3) -> Option<Result<VoxelChunk, SaveError>> {
+        self.0.read().unwrap().read(position)
+    }
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Io(std::io::Error),
+    Serialize(ron::Error),
+    Parse(ron::error::SpannedError),
+    NotASave,
+    NewerVersion { found: u32, supported: u32 },
+    Corrupt(&'static str),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaveError::Io(err) => write!(f, "could not access world save: {}", err),
+            SaveError::Serialize(err) => write!(f, "could not serialize world settings: {}", err),
+            SaveError::Parse(err) => write!(f, "could not parse saved world settings: {}", err),
+            SaveError::NotASave => write!(f, "not a world save"),
+            SaveError::NewerVersion { found, supported } => write!(
+                f,
+                "world save uses format version {}, but this build reads up to version {}; update to load it",
+                found, supported,
+            ),
+            SaveError::Corrupt(reason) => write!(f, "world save is corrupt: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<ByteError> for SaveError {
+    fn from(err: ByteError) -> Self {
+        SaveError::Corrupt(err.reason())
+    }
+}
+
+// Copy of a chunk's voxel data taken on the main thread, so serialization can run
+// off it
+#[derive(Clone)]
+struct ChunkSnapshot {
+    position: IVec3,
+    voxels: Vec<Voxel>,
+    occupancy: ChunkOccupancy,
+    light: ChunkLight,
+}
+
+impl ChunkSnapshot {
+    fn of(chunk: &VoxelChunk) -> Self {
+        Self {
+            position: chunk.position,
+            voxels: chunk.voxels.iter().chain(chunk.hidden_voxels.iter()).cloned().collect(),
+            occupancy: chunk.occupancy.clone(),
+            light: chunk.light.clone(),
+        }
+    }
+
+    fn into_chunk(self) -> VoxelChunk {
+        let mut chunk = VoxelChunk::with_occupancy(self.position, self.voxels, self.occupancy);
+        chunk.filter_occluded_voxels();
+        chunk.light = self.light;
+        chunk
+    }
+}
+
+struct SavedSettings {
+    // Name of the generator, used to find its scene again
+    generator: String,
+    seed: u32,
+    shape_size: i32,
+    surface_only: bool,
+    terrain: TerrainConfig,
+}
+
+fn cell_index(pos: LocalPos) -> usize {
+    ((pos.z * CHUNK_SIZE + pos.y) * CHUNK_SIZE + pos.x) as usize
+}
+
+fn cell_pos(index: usize) -> LocalPos {
+    let index = index as i32;
+    LocalPos::new(index % CHUNK_SIZE, index / CHUNK_SIZE % CHUNK_SIZE, index / (CHUNK_SIZE * CHUNK_SIZE))
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_i32(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_f32(out: &mut Vec<u8>, value: f32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_u32(out, value.len() as u32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+// Id, then the min and max corners
+fn write_region(out: &mut Vec<u8>, region: &TriggerRegion) {
+    write_string(out, &region.id);
+    for corner in [region.min(), region.max()] {
+        for axis in corner.to_array() {
+            write_f32(out, axis);
+        }
+    }
+}
+
+// Position, then the chunk data behind a compression flag and its stored length.
+// Returns the data's size before and after compression.
+fn write_chunk(out: &mut Vec<u8>, chunk: &ChunkSnapshot, compression: Compression) -> (usize, usize) {
+    let mut data = Vec::new();
+    write_chunk_data(&mut data, chunk);
+    let (compression, payload) = compression.compress(&data);
+    write_i32(out, chunk.position.x);
+    write_i32(out, chunk.position.y);
+    write_i32(out, chunk.position.z);
+    out.push(compression.flag());
+    write_u32(out, payload.len() as u32);
+    out.extend_from_slice(&payload);
+    (data.len(), payload.len())
+}
+
+// Palette of distinct color and kind pairs, then the cells as runs of one value,
+// then the sunlight levels as runs the same way
+fn write_chunk_data(out: &mut Vec<u8>, chunk: &ChunkSnapshot) {
+    let mut cells = vec![EMPTY_CELL; CHUNK_CELLS];
+    for (index, cell) in cells.iter_mut().enumerate() {
+        if chunk.occupancy.is_solid(cell_pos(index)) {
+            *cell = FILLED_CELL;
+        }
+    }
+    // Keyed by bit pattern, since floats don't hash
+    let mut palette: Vec<([f32; 4], u16)> = Vec::new();
+    let mut palette_index: HashMap<([u32; 4], u16), u16> = HashMap::default();
+    for voxel in &chunk.voxels {
+        let rgba = voxel.color.as_rgba_f32();
+        let value = *palette_index.entry((rgba.map(f32::to_bits), voxel.kind)).or_insert_with(|| {
+            palette.push((rgba, voxel.kind));
+            FIRST_PALETTE_CELL + (palette.len() - 1) as u16
+        });
+        cells[cell_index(LocalPos::from_vec3(voxel.position))] = value;
+    }
+
+    write_u32(out, CHUNK_DATA_VERSION);
+    write_u16(out, palette.len() as u16);
+    for (rgba, kind) in &palette {
+        for channel in rgba {
+            out.extend_from_slice(&channel.to_le_bytes());
+        }
+        write_u16(out, *kind);
+    }
+    write_runs(out, cells);
+    write_runs(out, chunk.light.levels().iter().map(|level| *level as u16));
+}
+
+// Count, then each run of equal values as its length and value
+fn write_runs(out: &mut Vec<u8>, values: impl IntoIterator<Item = u16>) {
+    let mut runs: Vec<(u16, u16)> = Vec::new();
+    for value in values {
+        match runs.last_mut() {
+            Some((length, last)) if *last == value => *length += 1,
+            _ => runs.push((1, value)),
+        }
+    }
+    write_u32(out, runs.len() as u32);
+    for (length, value) in runs {
+        write_u16(out, length);
+        write_u16(out, value);
+    }
+}
+
+// Runs on the compute pool. With merge, chunks in the store's file that aren't
+// being written are copied over without being decompressed. Trigger regions follow the chunks and are
+// always written in full. Written to a temporary file first so a failed save leaves the previous one intact,
+// then swapped in along with the store's index.
+fn write_world(
+    path: PathBuf,
+    settings: SavedSettings,
+    chunks: Vec<ChunkSnapshot>,
+    regions: Vec<TriggerRegion>,
+    store: Arc<RwLock<StoreIndex>>,
+    merge: bool,
+    compression: Compression,
+) -> Result<SaveSummary, SaveError> {
+    let terrain = ron::ser::to_string(&settings.terrain).map_err(SaveError::Serialize)?;
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    write_u32(&mut out, FORMAT_VERSION);
+    write_string(&mut out, &settings.generator);
+    write_u32(&mut out, settings.seed);
+    write_i32(&mut out, settings.shape_size);
+    out.push(settings.surface_only as u8);
+    write_string(&mut out, &terrain);
+
+    // Saves run one at a time, so only this task changes the file
+    let source = if merge { store.read().unwrap().path.clone() } else { None };
+    let existing = match &source {
+        Some(source) => migrate_file(std::fs::read(source).map_err(SaveError::Io)?)?,
+        None => Vec::new(),
+    };
+    let mut kept = Vec::new();
+    if source.is_some() {
+        let written: HashSet<IVec3> = chunks.iter().map(|chunk| chunk.position).collect();
+        kept = chunk_records(&existing)?;
+        kept.retain(|(position, _, _)| !written.contains(position));
+    }
+    write_u32(&mut out, (kept.len() + chunks.len()) as u32);
+    for (position, flag, stored) in kept {
+        write_i32(&mut out, position.x);
+        write_i32(&mut out, position.y);
+        write_i32(&mut out, position.z);
+        out.push(flag);
+        write_u32(&mut out, stored.len() as u32);
+        out.extend_from_slice(stored);
+    }
+    let (mut raw_bytes, mut stored_bytes) = (0, 0);
+    for chunk in &chunks {
+        let (raw, stored) = write_chunk(&mut out, chunk, compression);
+        raw_bytes += raw;
+        stored_bytes += stored;
+    }
+    write_u32(&mut out, regions.len() as u32);
+    for region in &regions {
+        write_region(&mut out, region);
+    }
+
+    let (_, stored, _) = index_save(&out)?;
+    let temporary = write_temporary(&path, &out)?;
+    let mut index = store.write().unwrap();
+    std::fs::rename(&temporary, &path).map_err(SaveError::Io)?;
+    *index = StoreIndex {
+        path: Some(path),
+        chunks: stored,
+    };
+    Ok(SaveSummary {
+        chunks: chunks.len(),
+        compression,
+        raw_bytes,
+        stored_bytes,
+    })
+}
+
+// Written beside the file it replaces, so a failed write leaves that intact
+fn write_temporary(path: &Path, bytes: &[u8]) -> Result<PathBuf, SaveError> {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).map_err(SaveError::Io)?;
+    }
+    let temporary = path.with_extension("tmp");
+    std::fs::write(&temporary, bytes).map_err(SaveError::Io)?;
+    Ok(temporary)
+}
+
+// A chunk's data after its position, decompressed
+fn read_chunk_data(position: IVec3, data: &[u8]) -> Result<VoxelChunk, SaveError> {
+    let data = migrate_chunk_data(data)?;
+    let reader = &mut ByteReader { bytes: &data };
+    // Current after migrating
+    reader.u32()?;
+    let palette_len = reader.u16()?;
+    let mut palette = Vec::with_capacity(palette_len as usize);
+    for _ in 0..palette_len {
+        let color = Color::rgba(reader.f32()?, reader.f32()?, reader.f32()?, reader.f32()?);
+        palette.push((color, reader.u16()?));
+    }
+
+    let mut voxels = Vec::new();
+    let mut filled = Vec::new();
+    let mut index = 0;
+    for _ in 0..reader.u32()? {
+        let (length, value) = (reader.u16()? as usize, reader.u16()?);
+        if index + length > CHUNK_CELLS {
+            return Err(SaveError::Corrupt("chunk runs overflow the chunk"));
+        }
+        for cell in index..index + length {
+            let pos = cell_pos(cell);
+            match value {
+                EMPTY_CELL => {}
+                FILLED_CELL => filled.push(pos),
+                _ => {
+                    let (color, kind) = *palette
+                        .get((value - FIRST_PALETTE_CELL) as usize)
+                        .ok_or(SaveError::Corrupt("cell refers past the chunk palette"))?;
+                    voxels.push(Voxel {
+                        position: Vec3::new(pos.x as f32, pos.y as f32, pos.z as f32),
+                        color,
+                        kind,
+                    });
+                }
+            }
+        }
+        index += length;
+    }
+    if index != CHUNK_CELLS {
+        return Err(SaveError::Corrupt("chunk runs don't cover the chunk"));
+    }
+
+    let mut occupancy = ChunkOccupancy::from_voxels(&voxels);
+    for pos in filled {
+        occupancy.set(pos, true);
+    }
+
+    // Chunks saved before light was stored keep the default until they're relit
+    let light_runs = reader.u32()?;
+    let light = if light_runs == 0 {
+        ChunkLight::default()
+    } else {
+        let mut levels = Vec::with_capacity(CHUNK_CELLS);
+        for _ in 0..light_runs {
+            let (length, level) = (reader.u16()? as usize, reader.u16()?);
+            if levels.len() + length > CHUNK_CELLS || level > u8::MAX as u16 {
+                return Err(SaveError::Corrupt("light runs overflow the chunk"));
+            }
+            levels.extend(std::iter::repeat(level as u8).take(length));
+        }
+        ChunkLight::from_levels(levels).ok_or(SaveError::Corrupt("light runs don't cover the chunk"))?
+    };
+
+    let snapshot = ChunkSnapshot {
+        position,
+        voxels,
+        occupancy,
+        light,
+    };
+    Ok(snapshot.into_chunk())
+}
+
+fn read_header(reader: &mut ByteReader) -> Result<SavedSettings, SaveError> {
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(SaveError::NotASave);
+    }
+    let version = reader.u32()?;
+    if version > FORMAT_VERSION {
+        return Err(SaveError::NewerVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    let generator = reader.string()?;
+    let seed = reader.u32()?;
+    let shape_size = reader.i32()?;
+    let surface_only = reader.u8()? != 0;
+    let terrain = ron::from_str(&reader.string()?).map_err(SaveError::Parse)?;
+    let settings = SavedSettings {
+        generator,
+        seed,
+        shape_size,
+        surface_only,
+        terrain,
+    };
+    Ok(settings)
+}
+
+// The next chunk's position, compression flag and stored data
+fn next_record<'a>(reader: &mut ByteReader<'a>) -> Result<(IVec3, u8, &'a [u8]), SaveError> {
+    let position = IVec3::new(reader.i32()?, reader.i32()?, reader.i32()?);
+    let flag = reader.u8()?;
+    let length = reader.u32()? as usize;
+    Ok((position, flag, reader.take(length)?))
+}
+
+// Decompresses and decodes one chunk's stored data
+fn decode_chunk(position: IVec3, flag: u8, stored: &[u8]) -> Result<VoxelChunk, SaveError> {
+    let data: Cow<[u8]> = match Compression::from_flag(flag) {
+        Some(Compression::None) => Cow::Borrowed(stored),
+        Some(Compression::Lz4) => {
+            // Checked first so a corrupt size can't demand a huge buffer
+            let size = ByteReader { bytes: stored }.u32()? as usize;
+            if size > MAX_CHUNK_DATA_BYTES {
+                return Err(SaveError::Corrupt("chunk data is larger than a chunk can be"));
+            }
+            let data = lz4_flex::decompress_size_prepended(stored)
+                .map_err(|_| SaveError::Corrupt("chunk data doesn't decompress"))?;
+            Cow::Owned(data)
+        }
+        Some(Compression::Zstd) => {
+            let data = zstd::bulk::decompress(stored, MAX_CHUNK_DATA_BYTES)
+                .map_err(|_| SaveError::Corrupt("chunk data doesn't decompress"))?;
+            Cow::Owned(data)
+        }
+        None => return Err(SaveError::Corrupt("unknown chunk compression")),
+    };
+    read_chunk_data(position, &data)
+}
+
+// Each chunk's position, compression flag and stored data, without decoding it,
+// from a save in the current format
+fn chunk_records(bytes: &[u8]) -> Result<Vec<(IVec3, u8, &[u8])>, SaveError> {
+    let mut reader = ByteReader { bytes };
+    read_header(&mut reader)?;
+    let count = reader.u32()?;
+    (0..count).map(|_| next_record(&mut reader)).collect()
+}
+
+fn read_region(reader: &mut ByteReader) -> Result<TriggerRegion, SaveError> {
+    let id = reader.string()?;
+    let min = Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?);
+    let max = Vec3::new(reader.f32()?, reader.f32()?, reader.f32()?);
+    Ok(TriggerRegion::new(id, min, max))
+}
+
+// The settings, where each chunk's stored data is and the trigger regions of a
+// save in the current format. Damage to the framing between chunks fails it; a
+// chunk's own data is only decoded when it's read back.
+fn index_save(bytes: &[u8]) -> Result<(SavedSettings, HashMap<IVec3, StoredChunk>, Vec<TriggerRegion>), SaveError> {
+    let mut reader = ByteReader { bytes };
+    let settings = read_header(&mut reader)?;
+    let count = reader.u32()?;
+    let mut chunks = HashMap::default();
+    for _ in 0..count {
+        let (position, flag, stored) = next_record(&mut reader)?;
+        let offset = (bytes.len() - reader.bytes.len() - stored.len()) as u64;
+        let length = stored.len() as u32;
+        chunks.insert(position, StoredChunk { offset, flag, length });
+    }
+    let count = reader.u32()?;
+    let regions = (0..count).map(|_| read_region(&mut reader)).collect::<Result<_, _>>()?;
+    Ok((settings, chunks, regions))
+}
+
+// Indexes a save for the store. One in an older format is upgraded on disk first,
+// since chunks are read back from where they lie in the file.
+fn open_store(path: &Path) -> Result<(SavedSettings, HashMap<IVec3, StoredChunk>, Vec<TriggerRegion>), SaveError> {
+    let bytes = std::fs::read(path).map_err(SaveError::Io)?;
+    let current = bytes.get(MAGIC.len()..MAGIC.len() + 4) == Some(FORMAT_VERSION.to_le_bytes().as_slice());
+    let bytes = migrate_file(bytes)?;
+    let index = index_save(&bytes)?;
+    if !current {
+        let temporary = write_temporary(path, &bytes)?;
+        std::fs::rename(&temporary, path).map_err(SaveError::Io)?;
+    }
+    Ok(index)
+}
+
+fn read_stored_chunk(path: &Path, position: IVec3, stored: StoredChunk) -> Result<VoxelChunk, SaveError> {
+    let mut file = std::fs::File::open(path).map_err(SaveError::Io)?;
+    file.seek(SeekFrom::Start(stored.offset)).map_err(SaveError::Io)?;
+    let mut data = vec![0; stored.length as usize];
+    file.read_exact(&mut data).map_err(SaveError::Io)?;
+    decode_chunk(position, stored.flag, &data)
+}
+
+// Applies a save's settings and makes it the store the next regeneration reads
+// chunks back from
+fn load_world(
+    saver: &mut WorldSaver,
+    generation_settings: &mut GenerationSettings,
+    store: &mut ChunkStore,
+) -> Result<usize, SaveError> {
+    let path = saver.path();
+    let (saved, chunks, regions) = open_store(&path)?;
+    generation_settings.seed = saved.seed;
+    generation_settings.shape_size = saved.shape_size;
+    generation_settings.surface_only = saved.surface_only;
+    generation_settings.terrain = saved.terrain;
+    // Missing chunks of an unknown generator come from the current scene
+    match DemoScene::ALL.into_iter().find(|scene| scene.build(generation_settings).name() == saved.generator) {
+        Some(scene) => generation_settings.scene = scene,
+        None => warn!("Save was generated by unknown generator {}; keeping {:?}", saved.generator, generation_settings.scene),
+    }
+    let count = chunks.len();
+    // Edits to the world being replaced go with it
+    saver.forget_world();
+    *store = ChunkStore {
+        scene: Some(generation_settings.scene),
+        regions,
+        index: Arc::new(RwLock::new(StoreIndex {
+            path: Some(path),
+            chunks,
+        })),
+        ..default()
+    };
+    Ok(count)
+}
+
+// Runs before the initial regeneration, which then reads the stored chunks back
+fn load_world_at_startup(
+    mut saver: ResMut<WorldSaver>,
+    mut generation_settings: ResMut<GenerationSettings>,
+    mut store: ResMut<ChunkStore>,
+) {
+    if !saver.load_at_startup || !saver.path().exists() {
+        return;
+    }
+    match load_world(&mut saver, &mut generation_settings, &mut store) {
+        Ok(count) => info!("Loaded {} chunks from {}", count, saver.path().display()),
+        Err(err) => warn!("{}; generating a new world", err),
+    }
+}
+
+// Starts writing the loaded chunks edited since the last save and the edited
+// chunks unloaded since, along with the world settings and trigger regions. The
+// rest of the store is copied over from its file without being decoded, so a
+// save only serializes what changed. Changing a region alone doesn't call for an autosave; it's
+// written with the next save.
+fn start_save(
+    saver: &mut WorldSaver,
+    store: &mut ChunkStore,
+    generation_settings: &GenerationSettings,
+    chunks: &Query<&VoxelChunk>,
+    regions: &Query<&TriggerRegion>,
+    compression: Compression,
+    now: f64,
+) {
+    // A store of another scene has nothing to carry over
+    let merge = store.scene == Some(generation_settings.scene);
+    let settings = SavedSettings {
+        generator: generation_settings.generator().name().to_string(),
+        seed: generation_settings.seed,
+        shape_size: generation_settings.shape_size,
+        surface_only: generation_settings.surface_only,
+        terrain: generation_settings.terrain.clone(),
+    };
+    let mut snapshots: Vec<ChunkSnapshot> = chunks
+        .iter()
+        .filter(|chunk| saver.dirty.contains(&chunk.position))
+        .map(ChunkSnapshot::of)
+        .collect();
+    // Edited chunks that are gone are saved empty, so loading doesn't regenerate them
+    let loaded: HashSet<IVec3> = snapshots.iter().map(|chunk| chunk.position).collect();
+    let missing: Vec<IVec3> = saver.dirty.difference(&loaded).copied().collect();
+    snapshots.extend(missing.into_iter().map(|position| ChunkSnapshot {
+        position,
+        voxels: Vec::new(),
+        occupancy: ChunkOccupancy::from_voxels(&[]),
+        light: ChunkLight::default(),
+    }));
+    // Kept in the store until the save is known to have written them
+    snapshots.extend(store.unloaded.values().cloned());
+    let regions: Vec<TriggerRegion> = regions.iter().cloned().collect();
+
+    saver.saving = std::mem::take(&mut saver.dirty);
+    saver.saving_unloaded = store.unloaded.keys().copied().collect();
+    store.unwritten = false;
+    store.scene = Some(generation_settings.scene);
+    store.regions = regions.clone();
+    saver.status = Some((SaveStatus::Saving, now));
+    let (path, index) = (saver.path(), store.index.clone());
+    let task = AsyncComputeTaskPool::get()
+        .spawn(async move { write_world(path, settings, snapshots, regions, index, merge, compression) });
+    saver.task = Some(task);
+}
+
+// Chunks edited since the last save. Unloading them hands them to the store.
+fn track_changed_chunks(mut saver: ResMut<WorldSaver>, mut changes: EventReader<VoxelChanged>) {
+    for change in changes.read() {
+        saver.dirty.insert(change.chunk);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn quick_save_and_load(
+    time: Res<Time>,
+    input: ActionInput,
+    save_settings: Res<SaveSettings>,
+    mut saver: ResMut<WorldSaver>,
+    mut store: ResMut<ChunkStore>,
+    mut generation_settings: ResMut<GenerationSettings>,
+    mut world_commands: EventWriter<WorldCommand>,
+    chunks: Query<&VoxelChunk>,
+    regions: Query<&TriggerRegion>,
+) {
+    if !input.pressed(Action::WorldSaveModifier) {
+        return;
+    }
+
+    if input.just_pressed(Action::QuickSave) {
+        if saver.task.is_some() {
+            info!("Quick-saving once the current save finishes");
+            saver.quick_save_queued = true;
+        } else {
+            let now = time.elapsed_seconds_f64();
+            start_save(&mut saver, &mut store, &generation_settings, &chunks, &regions, save_settings.compression, now);
+        }
+    }
+
+    if input.just_pressed(Action::QuickLoad) {
+        // The running save replaces the file being indexed
+        if saver.task.is_some() {
+            warn!("Can't load while a save is running");
+            return;
+        }
+        match load_world(&mut saver, &mut generation_settings, &mut store) {
+            Ok(count) => {
+                info!("Loaded {} chunks from {}", count, saver.path().display());
+                world_commands.send(WorldCommand::Regenerate);
+            }
+            Err(err) => warn!("{}", err),
+        }
+    }
+}
+
+// Every interval, saves the chunks edited since the last save. A save still
+// running pushes the autosave back to when it finishes.
+fn autosave(
+    time: Res<Time>,
+    settings: Res<SaveSettings>,
+    mut saver: ResMut<WorldSaver>,
+    mut store: ResMut<ChunkStore>,
+    generation_settings: Res<GenerationSettings>,
+    chunks: Query<&VoxelChunk>,
+    regions: Query<&TriggerRegion>,
+) {
+    if settings.autosave_minutes <= 0.0 {
+        saver.next_autosave = None;
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    let interval = settings.autosave_minutes as f64 * 60.0;
+    let Some(next) = saver.next_autosave else {
+        saver.next_autosave = Some(now + interval);
+        return;
+    };
+    if now < next || saver.task.is_some() {
+        return;
+    }
+    saver.next_autosave = Some(now + interval);
+    if saver.dirty.is_empty() && store.unloaded.is_empty() {
+        return;
+    }
+    info!("Autosaving {} edited chunks", saver.dirty.len() + store.unloaded.len());
+    start_save(&mut saver, &mut store, &generation_settings, &chunks, &regions, settings.compression, now);
+}
+
+fn poll_save_task(
+    time: Res<Time>,
+    settings: Res<SaveSettings>,
+    mut saver: ResMut<WorldSaver>,
+    mut store: ResMut<ChunkStore>,
+    generation_settings: Res<GenerationSettings>,
+    chunks: Query<&VoxelChunk>,
+    regions: Query<&TriggerRegion>,
+) {
+    let Some(task) = saver.task.as_mut() else {
+        return;
+    };
+    let Some(result) = future::block_on(future::poll_once(task)) else {
+        return;
+    };
+    saver.task = None;
+    let now = time.elapsed_seconds_f64();
+    let written = std::mem::take(&mut saver.saving_unloaded);
+    match result {
+        Ok(summary) => {
+            info!(
+                "Saved {} chunks to {} ({:?} compression, {:.2}x)",
+                summary.chunks,
+                saver.path().display(),
+                summary.compression,
+                summary.raw_bytes as f32 / summary.stored_bytes.max(1) as f32,
+            );
+            for position in written {
+                store.unloaded.remove(&position);
+            }
+            saver.saving.clear();
+            saver.status = Some((SaveStatus::Saved, now));
+        }
+        Err(err) => {
+            warn!("{}", err);
+            // Written again by the next save; unloaded ones are still in the store
+            let saving = std::mem::take(&mut saver.saving);
+            saver.dirty.extend(saving.into_iter().filter(|position| !store.unloaded.contains_key(position)));
+            // A file that couldn't be merged into is replaced
+            if !matches!(err, SaveError::Io(_)) {
+                *store.index.write().unwrap() = StoreIndex::default();
+            }
+            saver.status = Some((SaveStatus::Failed, now));
+        }
+    }
+    if std::mem::take(&mut saver.quick_save_queued) {
+        start_save(&mut saver, &mut store, &generation_settings, &chunks, &regions, settings.compression, now);
+    }
+}
+
+// Chunks are written as soon as they're unloaded, once any running save is done.
+// After a failed save they wait for the next autosave instead of retrying at once.
+fn write_unloaded_chunks(
+    time: Res<Time>,
+    settings: Res<SaveSettings>,
+    mut saver: ResMut<WorldSaver>,
+    mut store: ResMut<ChunkStore>,
+    generation_settings: Res<GenerationSettings>,
+    chunks: Query<&VoxelChunk>,
+    regions: Query<&TriggerRegion>,
+) {
+    if saver.task.is_some() || !store.unwritten {
+        return;
+    }
+    let now = time.elapsed_seconds_f64();
+    start_save(&mut saver, &mut store, &generation_settings, &chunks, &regions, settings.compression, now);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn saved_settings() -> SavedSettings {
+        SavedSettings {
+            generator: "Flat".to_string(),
+            seed: 7,
+            shape_size: 16,
+            surface_only: true,
+            terrain: TerrainConfig::default(),
+        }
+    }
+
+    fn chunk(position: IVec3, color: Color) -> VoxelChunk {
+        let voxel = |x, y| Voxel {
+            position: Vec3::new(x, y, 0.0),
+            color,
+            kind: 1,
+        };
+        VoxelChunk::new(position, vec![voxel(0.0, 0.0), voxel(1.0, 0.0), voxel(0.0, 1.0)])
+    }
+
+    fn stored_color(reader: &StoreReader, position: IVec3) -> [u8; 4] {
+        let chunk = reader.read(position).unwrap().unwrap();
+        assert_eq!(chunk.position, position);
+        assert_eq!(chunk.voxels.len() + chunk.hidden_voxels.len(), 3);
+        chunk.voxels[0].color.as_rgba_u8()
+    }
+
+    #[test]
+    fn store_reads_chunks_back_and_carries_unwritten_ones_over() {
+        let directory = std::env::temp_dir().join(format!("worldvox-store-{}", std::process::id()));
+        let path = directory.join(WORLD_FILE);
+        let index = Arc::new(RwLock::new(StoreIndex::default()));
+        let reader = StoreReader(index.clone());
+        let (first, second) = (IVec3::new(0, 0, 0), IVec3::new(-1, 2, 3));
+
+        let chunks = vec![ChunkSnapshot::of(&chunk(first, Color::RED)), ChunkSnapshot::of(&chunk(second, Color::GREEN))];
+        write_world(path.clone(), saved_settings(), chunks, Vec::new(), index.clone(), false, Compression::Lz4).unwrap();
+        assert_eq!(stored_color(&reader, first), [255, 0, 0, 255]);
+        assert_eq!(stored_color(&reader, second), [0, 255, 0, 255]);
+        assert!(reader.read(IVec3::ONE).is_none());
+
+        // Only the chunk edited since is written; the other is copied from the file
+        let chunks = vec![ChunkSnapshot::of(&chunk(first, Color::BLUE))];
+        let regions = vec![TriggerRegion::new("gate", Vec3::ZERO, Vec3::ONE)];
+        write_world(path.clone(), saved_settings(), chunks, regions, index.clone(), true, Compression::Zstd).unwrap();
+        assert_eq!(stored_color(&reader, first), [0, 0, 255, 255]);
+        assert_eq!(stored_color(&reader, second), [0, 255, 0, 255]);
+
+        let (saved, stored, regions) = open_store(&path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+        assert_eq!((saved.seed, stored.len()), (7, 2));
+        assert_eq!(regions[0].id, "gate");
+    }
+
+    #[test]
+    fn unloaded_edits_stay_in_the_store_until_written() {
+        let mut saver = WorldSaver::default();
+        let mut store = ChunkStore::default();
+        let (edited, untouched, writing) = (
+            chunk(IVec3::X, Color::RED),
+            chunk(IVec3::Y, Color::GREEN),
+            chunk(IVec3::Z, Color::BLUE),
+        );
+        saver.dirty.insert(edited.position);
+        // The running save might still fail to write this one
+        saver.saving.insert(writing.position);
+
+        store.unload(&mut saver, [&edited, &untouched, &writing]);
+        assert!(saver.dirty.is_empty());
+        assert!(store.unwritten);
+        let mut unloaded: Vec<IVec3> = store.unloaded.keys().copied().collect();
+        unloaded.sort_by_key(|position| position.to_array());
+        assert_eq!(unloaded, [IVec3::Z, IVec3::X]);
+        assert_eq!(saver.saving_unloaded.iter().collect::<Vec<_>>(), [&IVec3::Z]);
+
+        // Loaded again, but still not written
+        let reloaded = store.take_unloaded(&mut saver);
+        assert_eq!(reloaded.len(), 2);
+        assert!(reloaded.iter().all(|chunk| chunk.voxels.len() + chunk.hidden_voxels.len() == 3));
+        assert!(store.unloaded.is_empty() && saver.saving_unloaded.is_empty());
+        assert!(saver.dirty.contains(&IVec3::X) && saver.dirty.contains(&IVec3::Z));
+    }
+}
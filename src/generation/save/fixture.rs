@@ -0,0 +1,72 @@
+// src/generation/save/fixture.rs
+use bevy::prelude::*;
+use std::fmt::Write;
+use crate::bindings::{Action, ActionInput};
+use crate::bytes::ByteReader;
+use crate::picking::TargetedVoxel;
+use crate::screenshot::timestamp;
+use crate::voxel::{split_world, VoxelChunk, VoxelWorld};
+use super::{cell_pos, read_chunk_data, write_chunk_data, write_i32, ChunkSnapshot, SaveError, CHUNK_CELLS};
+
+const FIXTURE_DIRECTORY: &str = "fixtures";
+// Bytes per line of the generated array
+const BYTES_PER_LINE: usize = 16;
+
+// Behind VoxelChunk::from_fixture: the chunk's position, then chunk data in the
+// save format, uncompressed
+pub fn decode_fixture(bytes: &[u8]) -> Result<VoxelChunk, SaveError> {
+    let mut reader = ByteReader { bytes };
+    let position = IVec3::new(reader.i32()?, reader.i32()?, reader.i32()?);
+    read_chunk_data(position, reader.bytes)
+}
+
+fn fixture_bytes(chunk: &VoxelChunk) -> Vec<u8> {
+    let mut out = Vec::new();
+    for axis in chunk.position.to_array() {
+        write_i32(&mut out, axis);
+    }
+    write_chunk_data(&mut out, &ChunkSnapshot::of(chunk));
+    out
+}
+
+// A const byte array named after the chunk's position, ready to paste into a test
+fn fixture_source(position: IVec3, bytes: &[u8]) -> String {
+    let name = position.to_array().map(|axis| if axis < 0 { format!("M{}", -axis) } else { axis.to_string() }).join("_");
+    let mut source = format!("// Chunk {} captured with Ctrl+Q; decode with VoxelChunk::from_fixture\n", position);
+    let _ = writeln!(source, "pub const CHUNK_{}: &[u8] = &[", name);
+    for line in bytes.chunks(BYTES_PER_LINE) {
+        let values: Vec<String> = line.iter().map(|byte| format!("0x{:02x}", byte)).collect();
+        let _ = writeln!(source, "    {},", values.join(", "));
+    }
+    source.push_str("];\n");
+    source
+}
+
+// Writes the targeted chunk as a fixture, checking it decodes to the same cells
+pub(super) fn capture_chunk_fixture(input: ActionInput, target: Res<TargetedVoxel>, world: VoxelWorld) {
+    if !input.pressed(Action::EditModifier) || !input.just_pressed(Action::CaptureFixture) {
+        return;
+    }
+    let Some(chunk) = target.hit.and_then(|hit| world.chunk(split_world(hit.world).0)) else {
+        info!("No chunk targeted to capture");
+        return;
+    };
+    let bytes = fixture_bytes(chunk);
+    let same_cells = |decoded: &VoxelChunk| {
+        (0..CHUNK_CELLS).all(|index| decoded.occupancy.is_solid(cell_pos(index)) == chunk.occupancy.is_solid(cell_pos(index)))
+    };
+    match VoxelChunk::from_fixture(&bytes) {
+        Ok(decoded) if same_cells(&decoded) => {}
+        Ok(_) => warn!("Fixture for chunk {} doesn't decode to the same cells", chunk.position),
+        Err(err) => warn!("Fixture for chunk {} doesn't decode: {}", chunk.position, err),
+    }
+    if let Err(err) = std::fs::create_dir_all(FIXTURE_DIRECTORY) {
+        warn!("Could not create {}: {}", FIXTURE_DIRECTORY, err);
+        return;
+    }
+    let path = format!("{}/chunk-{}.rs", FIXTURE_DIRECTORY, timestamp());
+    match std::fs::write(&path, fixture_source(chunk.position, &bytes)) {
+        Ok(()) => info!("Captured chunk {} as {} ({} bytes)", chunk.position, path, bytes.len()),
+        Err(err) => warn!("Could not write {}: {}", path, err),
+    }
+}
@@ -0,0 +1,236 @@
+// src/generation/save/migration.rs
+use std::borrow::Cow;
+use crate::bytes::ByteReader;
+use super::{
+    read_header, write_u32, Compression, SaveError, CHUNK_DATA_VERSION, FORMAT_VERSION, MAGIC,
+    PALETTE_ENTRY_BYTES, RUN_BYTES,
+};
+
+// Rewrites a whole save from one format version to the next
+type FileMigration = fn(&[u8]) -> Result<Vec<u8>, SaveError>;
+// Rewrites one chunk's decompressed data from one layout version to the next
+type ChunkDataMigration = fn(&[u8]) -> Result<Vec<u8>, SaveError>;
+
+// Entry i reads version i + 1. Sized by the current versions, so bumping one
+// without adding its migration doesn't build.
+const FILE_MIGRATIONS: [FileMigration; FORMAT_VERSION as usize - 1] = [file_v1_to_v2, file_v2_to_v3];
+const CHUNK_DATA_MIGRATIONS: [ChunkDataMigration; CHUNK_DATA_VERSION as usize - 1] = [chunk_data_v1_to_v2];
+
+// Brings a save up to the current format, one version at a time. Saves from a
+// newer build are refused rather than misread.
+pub(super) fn migrate_file(bytes: Vec<u8>) -> Result<Vec<u8>, SaveError> {
+    let mut reader = ByteReader { bytes: &bytes };
+    if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+        return Err(SaveError::NotASave);
+    }
+    let mut version = reader.u32()?;
+    if version > FORMAT_VERSION {
+        return Err(SaveError::NewerVersion {
+            found: version,
+            supported: FORMAT_VERSION,
+        });
+    }
+    if version == 0 {
+        return Err(SaveError::Corrupt("format version 0 doesn't exist"));
+    }
+    let mut bytes = bytes;
+    while version < FORMAT_VERSION {
+        bytes = FILE_MIGRATIONS[version as usize - 1](&bytes)?;
+        version += 1;
+    }
+    Ok(bytes)
+}
+
+// Same for one chunk's data, which starts with its layout version
+pub(super) fn migrate_chunk_data(data: &[u8]) -> Result<Cow<[u8]>, SaveError> {
+    let mut version = ByteReader { bytes: data }.u32()?;
+    if version > CHUNK_DATA_VERSION {
+        return Err(SaveError::NewerVersion {
+            found: version,
+            supported: CHUNK_DATA_VERSION,
+        });
+    }
+    if version == 0 {
+        return Err(SaveError::Corrupt("chunk data version 0 doesn't exist"));
+    }
+    let mut data = Cow::Borrowed(data);
+    while version < CHUNK_DATA_VERSION {
+        data = Cow::Owned(CHUNK_DATA_MIGRATIONS[version as usize - 1](&data)?);
+        version += 1;
+    }
+    Ok(data)
+}
+
+// Version 1 chunk data doesn't store its length, so it's walked over
+fn skip_v1_chunk_data(reader: &mut ByteReader) -> Result<(), SaveError> {
+    reader.u32()?;
+    let palette_len = reader.u16()? as usize;
+    reader.take(palette_len * PALETTE_ENTRY_BYTES)?;
+    let runs = reader.u32()? as usize;
+    reader.take(runs * RUN_BYTES)?;
+    Ok(())
+}
+
+// Version 2 frames each chunk's data with a compression flag and its length
+fn file_v1_to_v2(bytes: &[u8]) -> Result<Vec<u8>, SaveError> {
+    let mut reader = ByteReader { bytes };
+    read_header(&mut reader)?;
+    let header = &bytes[..bytes.len() - reader.bytes.len()];
+    let mut out = Vec::with_capacity(bytes.len());
+    out.extend_from_slice(MAGIC);
+    write_u32(&mut out, 2);
+    out.extend_from_slice(&header[MAGIC.len() + 4..]);
+
+    let count = reader.u32()?;
+    write_u32(&mut out, count);
+    for _ in 0..count {
+        out.extend_from_slice(reader.take(3 * 4)?);
+        let start = reader.bytes;
+        skip_v1_chunk_data(&mut reader)?;
+        let data = &start[..start.len() - reader.bytes.len()];
+        out.push(Compression::None.flag());
+        write_u32(&mut out, data.len() as u32);
+        out.extend_from_slice(data);
+    }
+    Ok(out)
+}
+
+// Version 3 adds the trigger regions after the chunks; older saves have none
+fn file_v2_to_v3(bytes: &[u8]) -> Result<Vec<u8>, SaveError> {
+    let mut out = Vec::with_capacity(bytes.len() + 4);
+    out.extend_from_slice(MAGIC);
+    write_u32(&mut out, 3);
+    out.extend_from_slice(&bytes[MAGIC.len() + 4..]);
+    write_u32(&mut out, 0);
+    Ok(out)
+}
+
+// Chunk data version 2 adds sunlight runs after the cells; none means the chunk
+// is lit from scratch once it's loaded
+fn chunk_data_v1_to_v2(data: &[u8]) -> Result<Vec<u8>, SaveError> {
+    let mut out = Vec::with_capacity(data.len() + 4);
+    write_u32(&mut out, 2);
+    out.extend_from_slice(&data[4..]);
+    write_u32(&mut out, 0);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{prelude::*, utils::HashMap};
+    use crate::triggers::TriggerRegion;
+    use crate::voxel::{ChunkLight, LocalPos, VoxelChunk};
+    use super::super::{open_store, SavedSettings, StoreIndex};
+
+    // Format 1, from before chunk data was framed: one chunk at (1, 0, -2) in chunk
+    // data version 1, with red voxels at x 0 and 1, a green one above the first and
+    // a solid cell without a voxel at (5, 5, 5)
+    const WORLD_V1: &[u8] = include_bytes!("../../../fixtures/world_v1.bin");
+    // Format 2, from before trigger regions: the same chunk, still in chunk data
+    // version 1, then the same cells at (-1, 2, 0) in version 2, lit 15 for z below 8
+    // and 7 above
+    const WORLD_V2: &[u8] = include_bytes!("../../../fixtures/world_v2.bin");
+
+    // Opens a copy of the fixture as the store does, which upgrades it on disk, then
+    // reads each chunk back from the upgraded file
+    fn read_fixture(name: &str, bytes: &[u8]) -> (SavedSettings, HashMap<IVec3, VoxelChunk>, Vec<TriggerRegion>) {
+        let path = std::env::temp_dir().join(format!("worldvox-{}-{}.bin", name, std::process::id()));
+        std::fs::write(&path, bytes).unwrap();
+        let (settings, stored, regions) = open_store(&path).unwrap();
+        let upgraded = std::fs::read(&path).unwrap();
+        assert_eq!(&upgraded[MAGIC.len()..MAGIC.len() + 4], &FORMAT_VERSION.to_le_bytes());
+        let index = StoreIndex {
+            path: Some(path.clone()),
+            chunks: stored,
+        };
+        let chunks = index
+            .chunks
+            .keys()
+            .map(|&position| (position, index.read(position).unwrap().unwrap()))
+            .collect();
+        std::fs::remove_file(&path).unwrap();
+        (settings, chunks, regions)
+    }
+
+    fn assert_fixture_cells(chunk: &VoxelChunk) {
+        let voxel = |x, y, z| {
+            chunk
+                .voxels
+                .iter()
+                .chain(chunk.hidden_voxels.iter())
+                .find(|voxel| LocalPos::from_vec3(voxel.position) == LocalPos::new(x, y, z))
+                .map(|voxel| (voxel.color.as_rgba_u8(), voxel.kind))
+        };
+        assert_eq!(chunk.voxels.len() + chunk.hidden_voxels.len(), 3);
+        assert_eq!(voxel(0, 0, 0), Some(([255, 0, 0, 255], 1)));
+        assert_eq!(voxel(1, 0, 0), Some(([255, 0, 0, 255], 1)));
+        assert_eq!(voxel(0, 1, 0), Some(([0, 255, 0, 255], 2)));
+        assert!(chunk.occupancy.is_solid(LocalPos::new(5, 5, 5)));
+        assert!(!chunk.occupancy.is_solid(LocalPos::new(2, 0, 0)));
+    }
+
+    #[test]
+    fn v1_save_loads_through_both_file_migrations() {
+        let migrated = migrate_file(WORLD_V1.to_vec()).unwrap();
+        assert_eq!(&migrated[MAGIC.len()..MAGIC.len() + 4], &FORMAT_VERSION.to_le_bytes());
+        let chained = file_v2_to_v3(&file_v1_to_v2(WORLD_V1).unwrap()).unwrap();
+        assert_eq!(migrated, chained);
+
+        let (settings, chunks, regions) = read_fixture("v1", WORLD_V1);
+        assert_eq!(settings.generator, "Terrain");
+        assert_eq!((settings.seed, settings.shape_size, settings.surface_only), (42, 81, true));
+        assert_eq!(settings.terrain.soil_depth, 3);
+        assert_eq!(chunks.len(), 1);
+        let chunk = &chunks[&IVec3::new(1, 0, -2)];
+        assert_fixture_cells(chunk);
+        // Saved before light was stored, so it keeps the default until relit
+        assert_eq!(chunk.light.levels(), ChunkLight::default().levels());
+        assert!(regions.is_empty());
+    }
+
+    #[test]
+    fn v2_save_migrates_each_chunks_data_from_its_own_version() {
+        let (_, chunks, regions) = read_fixture("v2", WORLD_V2);
+        assert_eq!(chunks.len(), 2);
+        assert!(regions.is_empty());
+
+        let unlit = &chunks[&IVec3::new(1, 0, -2)];
+        assert_fixture_cells(unlit);
+        assert_eq!(unlit.light.levels(), ChunkLight::default().levels());
+
+        let lit = &chunks[&IVec3::new(-1, 2, 0)];
+        assert_fixture_cells(lit);
+        assert_eq!(lit.light.level(LocalPos::new(15, 15, 7)), 15);
+        assert_eq!(lit.light.level(LocalPos::new(0, 0, 8)), 7);
+    }
+
+    #[test]
+    fn chunk_data_v1_gains_empty_light_runs() {
+        let migrated = chunk_data_v1_to_v2(&[1, 0, 0, 0, 0xaa, 0xbb]).unwrap();
+        assert_eq!(migrated, [2, 0, 0, 0, 0xaa, 0xbb, 0, 0, 0, 0]);
+        let migrated = migrate_chunk_data(&[1, 0, 0, 0, 0xaa]).unwrap();
+        assert_eq!(&*migrated, &[2, 0, 0, 0, 0xaa, 0, 0, 0, 0]);
+        // Current data is passed through untouched
+        assert!(matches!(migrate_chunk_data(&[2, 0, 0, 0]), Ok(Cow::Borrowed(_))));
+    }
+
+    #[test]
+    fn newer_and_unknown_versions_are_refused() {
+        let mut future = WORLD_V2.to_vec();
+        future[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&(FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            migrate_file(future),
+            Err(SaveError::NewerVersion { found, supported }) if found == FORMAT_VERSION + 1 && supported == FORMAT_VERSION
+        ));
+        assert!(matches!(
+            migrate_chunk_data(&(CHUNK_DATA_VERSION + 1).to_le_bytes()),
+            Err(SaveError::NewerVersion { found, .. }) if found == CHUNK_DATA_VERSION + 1
+        ));
+
+        let mut zero = WORLD_V1.to_vec();
+        zero[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert!(matches!(migrate_file(zero), Err(SaveError::Corrupt(_))));
+        assert!(matches!(migrate_file(b"RIFF\x01\0\0\0".to_vec()), Err(SaveError::NotASave)));
+    }
+}
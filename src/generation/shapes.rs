@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+use crate::voxel::{VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::{Voxel, VoxelRenderSettings, KIND_PLAIN};
+use super::WorldGenerator;
+
+// Chunk positions covering the voxel range [min, max)
+pub fn chunks_in_bounds(min: IVec3, max: IVec3) -> Vec<IVec3> {
+    let min_chunk = min.div_euclid(IVec3::splat(CHUNK_SIZE));
+    let max_chunk = (max - IVec3::ONE).div_euclid(IVec3::splat(CHUNK_SIZE));
+
+    let mut positions = Vec::new();
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                positions.push(IVec3::new(x, y, z));
+            }
+        }
+    }
+    positions
+}
+
+// Builds a chunk by sampling a shape at every voxel inside it
+fn sample_chunk(position: IVec3, sample: impl Fn(IVec3) -> Option<Color>) -> VoxelChunk {
+    let origin = position * CHUNK_SIZE;
+    let mut voxels = Vec::new();
+
+    for x in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let local = IVec3::new(x, y, z);
+                if let Some(color) = sample(origin + local) {
+                    voxels.push(Voxel {
+                        position: local.as_vec3(),
+                        color,
+                        kind: KIND_PLAIN,
+                    });
+                }
+            }
+        }
+    }
+
+    let mut chunk = VoxelChunk::new(position, voxels);
+    chunk.filter_occluded_voxels();
+    chunk
+}
+
+// Hue follows the angle around the Y axis, lightness follows height
+fn shape_color(pos: Vec3, size: f32) -> Color {
+    Color::hsl(
+        pos.x.atan2(pos.z).to_degrees() + 180.0,
+        0.7,
+        (0.35 + (pos.y / size + 0.5) * 0.3).clamp(0.3, 0.7),
+    )
+}
+
+pub struct GradientCubeGenerator;
+
+impl WorldGenerator for GradientCubeGenerator {
+    fn name(&self) -> &'static str {
+        "Cube grid"
+    }
+
+    fn chunk_positions(&self, settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        super::chunks_within_render_distance(settings)
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        let mut voxels = Vec::new();
+        let extent = CHUNK_SIZE - 1;
+        let center = Vec3::splat(extent as f32 / 2.0);
+
+        // Fill the chunk with a gradient cube, leaving a one voxel gap to its neighbors
+        for x in 0..extent {
+            for y in 0..extent {
+                for z in 0..extent {
+                    let pos = Vec3::new(x as f32, y as f32, z as f32);
+
+                    let color = Color::hsl(
+                        (pos.x.atan2(pos.z).to_degrees() + 180.0) / 360.0 * 360.0,
+                        (pos.y / extent as f32 * 0.5 + 0.5).clamp(0.2, 1.0),
+                        (1.0 - (pos - center).length() / extent as f32 * 0.5).clamp(0.3, 0.7),
+                    );
+
+                    voxels.push(Voxel {
+                        position: pos,
+                        color,
+                        kind: KIND_PLAIN,
+                    });
+                }
+            }
+        }
+
+        let mut chunk = VoxelChunk::new(position, voxels);
+        chunk.filter_occluded_voxels();
+        chunk
+    }
+}
+
+pub struct SphereGenerator {
+    pub radius: f32,
+}
+
+impl WorldGenerator for SphereGenerator {
+    fn name(&self) -> &'static str {
+        "Sphere"
+    }
+
+    fn chunk_positions(&self, _settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let extent = IVec3::splat(self.radius.ceil() as i32);
+        chunks_in_bounds(-extent, extent)
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        sample_chunk(position, |p| {
+            let pos = p.as_vec3() + Vec3::splat(0.5);
+            (pos.length() <= self.radius).then(|| shape_color(pos, self.radius * 2.0))
+        })
+    }
+}
+
+// Torus lying in the XZ plane
+pub struct TorusGenerator {
+    pub major_radius: f32,
+    pub minor_radius: f32,
+}
+
+impl WorldGenerator for TorusGenerator {
+    fn name(&self) -> &'static str {
+        "Torus"
+    }
+
+    fn chunk_positions(&self, _settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let horizontal = (self.major_radius + self.minor_radius).ceil() as i32;
+        let vertical = self.minor_radius.ceil() as i32;
+        let extent = IVec3::new(horizontal, vertical, horizontal);
+        chunks_in_bounds(-extent, extent)
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        sample_chunk(position, |p| {
+            let pos = p.as_vec3() + Vec3::splat(0.5);
+            let ring = Vec2::new(Vec2::new(pos.x, pos.z).length() - self.major_radius, pos.y);
+            (ring.length() <= self.minor_radius)
+                .then(|| shape_color(pos, self.minor_radius * 2.0))
+        })
+    }
+}
+
+// Level n sponge is 3^n voxels across; a good occlusion stress test
+pub struct MengerSpongeGenerator {
+    pub level: u32,
+}
+
+impl MengerSpongeGenerator {
+    // Largest sponge that fits within the given size
+    pub fn fitting(size: i32) -> Self {
+        let mut level = 0;
+        while 3i32.pow(level + 1) <= size {
+            level += 1;
+        }
+        Self { level }
+    }
+
+    fn size(&self) -> i32 {
+        3i32.pow(self.level)
+    }
+
+    fn contains(&self, p: IVec3) -> bool {
+        let (mut x, mut y, mut z) = (p.x, p.y, p.z);
+        for _ in 0..self.level {
+            // A voxel is removed if two of its base-3 digits are the middle third
+            let middle = [x % 3 == 1, y % 3 == 1, z % 3 == 1];
+            if middle.iter().filter(|m| **m).count() >= 2 {
+                return false;
+            }
+            x /= 3;
+            y /= 3;
+            z /= 3;
+        }
+        true
+    }
+}
+
+impl WorldGenerator for MengerSpongeGenerator {
+    fn name(&self) -> &'static str {
+        "Menger sponge"
+    }
+
+    fn chunk_positions(&self, _settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let half = self.size() / 2;
+        chunks_in_bounds(IVec3::splat(-half), IVec3::splat(self.size() - half))
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        let size = self.size();
+        let half = size / 2;
+        sample_chunk(position, |p| {
+            let shifted = p + IVec3::splat(half);
+            let inside = shifted.cmpge(IVec3::ZERO).all() && shifted.cmplt(IVec3::splat(size)).all();
+            (inside && self.contains(shifted))
+                .then(|| shape_color(p.as_vec3(), size as f32))
+        })
+    }
+}
+
+// Steps climbing along +X against a back wall. Every step edge is an inside
+// corner, and the run crosses several chunk borders, which makes AO easy to judge.
+pub struct StaircaseGenerator {
+    pub steps: i32,
+    // Depth of each step in voxels
+    pub tread: i32,
+    pub width: i32,
+}
+
+impl StaircaseGenerator {
+    fn length(&self) -> i32 {
+        self.steps * self.tread
+    }
+
+    fn contains(&self, p: IVec3) -> bool {
+        let x = p.x + self.length() / 2;
+        let z = p.z + self.width / 2;
+        if x < 0 || x >= self.length() || p.y < 0 || z < 0 || z > self.width {
+            return false;
+        }
+        // The wall along the back edge stands above the tallest step
+        (z == self.width && p.y <= self.steps) || (z < self.width && p.y <= x / self.tread)
+    }
+}
+
+impl WorldGenerator for StaircaseGenerator {
+    fn name(&self) -> &'static str {
+        "Staircase"
+    }
+
+    fn chunk_positions(&self, _settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let half_length = self.length() / 2;
+        let half_width = self.width / 2;
+        chunks_in_bounds(
+            IVec3::new(-half_length, 0, -half_width),
+            IVec3::new(self.length() - half_length, self.steps + 1, self.width - half_width + 1),
+        )
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        // A flat light color keeps the baked shading the only variation
+        sample_chunk(position, |p| self.contains(p).then_some(Color::rgb(0.85, 0.85, 0.8)))
+    }
+}
+
+// A stone basin of water with a glass cube standing in it, rising above the
+// surface. Shows translucent sorting and faces seen through other translucent faces.
+pub struct GlassPoolGenerator {
+    pub size: i32,
+}
+
+impl GlassPoolGenerator {
+    fn sample(&self, p: IVec3) -> Option<Color> {
+        let half = self.size / 2;
+        let depth = self.size / 4;
+        let cube_half = (self.size / 8).max(1);
+        if p.x < -half || p.x >= half || p.z < -half || p.z >= half || p.y < 0 {
+            return None;
+        }
+
+        let wall = p.x == -half || p.x == half - 1 || p.z == -half || p.z == half - 1;
+        if p.y == 0 || (wall && p.y <= depth) {
+            return Some(Color::rgb(0.5, 0.5, 0.52));
+        }
+        if p.x.abs() < cube_half && p.z.abs() < cube_half && p.y <= depth + cube_half {
+            return Some(Color::rgba(0.85, 0.95, 1.0, 0.3));
+        }
+        (p.y < depth).then_some(Color::rgba(0.15, 0.35, 0.8, 0.55))
+    }
+}
+
+impl WorldGenerator for GlassPoolGenerator {
+    fn name(&self) -> &'static str {
+        "Glass pool"
+    }
+
+    fn chunk_positions(&self, _settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let half = self.size / 2;
+        let height = self.size / 4 + (self.size / 8).max(1) + 1;
+        chunks_in_bounds(IVec3::new(-half, 0, -half), IVec3::new(half, height, half))
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        sample_chunk(position, |p| self.sample(p))
+    }
+}
@@ -0,0 +1,299 @@
+use bevy::prelude::*;
+use crate::voxel::{ChunkOccupancy, LocalPos, VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::{Voxel, VoxelRenderSettings, KIND_DIRT, KIND_GRASS, KIND_STONE};
+use std::sync::Arc;
+use super::{chunks_within_render_distance, Heightmap, TerrainConfig, WorldGenerator};
+
+// Offsets the warp noise away from the height octaves
+const WARP_SEED_OFFSET: u32 = 1013;
+
+fn hash(x: i32, z: i32, seed: u32) -> f32 {
+    let mut h = (x as u32).wrapping_mul(0x27d4_eb2d)
+        ^ (z as u32).wrapping_mul(0x1656_67b1)
+        ^ seed.wrapping_mul(0x9e37_79b9);
+    h ^= h >> 15;
+    h = h.wrapping_mul(0x85eb_ca6b);
+    h ^= h >> 13;
+    h as f32 / u32::MAX as f32
+}
+
+// Smoothly interpolated lattice noise in [0, 1]
+fn value_noise(x: f32, z: f32, seed: u32) -> f32 {
+    let (x0, z0) = (x.floor() as i32, z.floor() as i32);
+    let (tx, tz) = (x - x0 as f32, z - z0 as f32);
+    let (sx, sz) = (tx * tx * (3.0 - 2.0 * tx), tz * tz * (3.0 - 2.0 * tz));
+
+    let a = hash(x0, z0, seed);
+    let b = hash(x0 + 1, z0, seed);
+    let c = hash(x0, z0 + 1, seed);
+    let d = hash(x0 + 1, z0 + 1, seed);
+
+    let top = a + (b - a) * sx;
+    let bottom = c + (d - c) * sx;
+    top + (bottom - top) * sz
+}
+
+pub struct TerrainGenerator {
+    pub seed: u32,
+    // Emit only the exposed shell instead of filling columns and culling afterwards
+    pub surface_only: bool,
+    pub config: TerrainConfig,
+    // Heights come from the image instead of noise, scaled by the config
+    pub heightmap: Option<Arc<Heightmap>>,
+}
+
+impl TerrainGenerator {
+    pub fn height_at(&self, x: i32, z: i32) -> i32 {
+        let config = &self.config;
+        // Below the ground outside the image, so nothing is filled there
+        if let Some(heightmap) = &self.heightmap {
+            return heightmap.sample(x, z).map_or(-1, |t| (config.base_height + t * config.height_scale) as i32);
+        }
+        let (mut x, mut z) = (x as f32, z as f32);
+
+        // Domain warp bends the sample position before evaluating the octaves
+        if config.warp_strength > 0.0 {
+            let warp_seed = self.seed.wrapping_add(WARP_SEED_OFFSET);
+            let (wx, wz) = (x * config.warp_frequency, z * config.warp_frequency);
+            x += (value_noise(wx, wz, warp_seed) * 2.0 - 1.0) * config.warp_strength;
+            z += (value_noise(wx, wz, warp_seed.wrapping_add(1)) * 2.0 - 1.0) * config.warp_strength;
+        }
+
+        let mut total = 0.0;
+        let mut max = 0.0;
+        for (i, octave) in config.octaves.iter().enumerate() {
+            let seed = self.seed.wrapping_add(i as u32);
+            total += value_noise(x * octave.frequency, z * octave.frequency, seed) * octave.amplitude;
+            max += octave.amplitude;
+        }
+        (config.base_height + total / max * config.height_scale) as i32
+    }
+
+    fn color_at(&self, y: i32, height: i32) -> Color {
+        let config = &self.config;
+        if y == height {
+            let t = (y as f32 - config.base_height) / config.height_scale.max(1.0);
+            config.ramp_color(t.clamp(0.0, 1.0))
+        } else if y >= height - config.soil_depth {
+            let [r, g, b] = config.soil_color;
+            Color::rgb(r, g, b)
+        } else {
+            let [r, g, b] = config.stone_color;
+            Color::rgb(r, g, b)
+        }
+    }
+
+    fn kind_at(&self, y: i32, height: i32) -> u16 {
+        if y == height {
+            KIND_GRASS
+        } else if y >= height - self.config.soil_depth {
+            KIND_DIRT
+        } else {
+            KIND_STONE
+        }
+    }
+
+    // Column heights for the chunk plus a one voxel border
+    fn heights(&self, origin: IVec3) -> Vec<i32> {
+        let width = CHUNK_SIZE + 2;
+        let mut heights = Vec::with_capacity((width * width) as usize);
+        for z in -1..=CHUNK_SIZE {
+            for x in -1..=CHUNK_SIZE {
+                heights.push(self.height_at(origin.x + x, origin.z + z));
+            }
+        }
+        heights
+    }
+
+    fn filled_chunk(&self, position: IVec3) -> VoxelChunk {
+        let origin = position * CHUNK_SIZE;
+        let mut voxels = Vec::new();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let height = self.height_at(origin.x + x, origin.z + z);
+                for y in 0..CHUNK_SIZE {
+                    let world_y = origin.y + y;
+                    if world_y <= height {
+                        voxels.push(Voxel {
+                            position: Vec3::new(x as f32, y as f32, z as f32),
+                            color: self.color_at(world_y, height),
+                            kind: self.kind_at(world_y, height),
+                        });
+                    }
+                }
+            }
+        }
+
+        let mut chunk = VoxelChunk::new(position, voxels);
+        chunk.filter_occluded_voxels();
+        chunk
+    }
+
+    // Produces the same voxel set as filled_chunk without ever storing interior voxels
+    fn surface_chunk(&self, position: IVec3) -> VoxelChunk {
+        let origin = position * CHUNK_SIZE;
+        let heights = self.heights(origin);
+        let width = CHUNK_SIZE + 2;
+        let height_of = |x: i32, z: i32| heights[((z + 1) * width + x + 1) as usize];
+        let last = CHUNK_SIZE - 1;
+        let mut voxels = Vec::new();
+        let mut occupancy = ChunkOccupancy::default();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                let height = height_of(x, z);
+                let lowest_neighbor = height_of(x + 1, z)
+                    .min(height_of(x - 1, z))
+                    .min(height_of(x, z + 1))
+                    .min(height_of(x, z - 1));
+                let on_side = x == 0 || x == last || z == 0 || z == last;
+
+                for y in 0..CHUNK_SIZE {
+                    let world_y = origin.y + y;
+                    if world_y > height {
+                        break;
+                    }
+                    occupancy.set(LocalPos::new(x, y, z), true);
+
+                    // Occlusion treats chunk borders as open, so keep those voxels too
+                    let exposed = world_y == height
+                        || world_y > lowest_neighbor
+                        || on_side
+                        || y == 0
+                        || y == last;
+                    if exposed {
+                        voxels.push(Voxel {
+                            position: Vec3::new(x as f32, y as f32, z as f32),
+                            color: self.color_at(world_y, height),
+                            kind: self.kind_at(world_y, height),
+                        });
+                    }
+                }
+            }
+        }
+
+        VoxelChunk::with_occupancy(position, voxels, occupancy)
+    }
+}
+
+impl WorldGenerator for TerrainGenerator {
+    fn name(&self) -> &'static str {
+        if self.heightmap.is_some() { "Heightmap" } else { "Terrain" }
+    }
+
+    fn chunk_positions(&self, settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let layers = self.config.max_height() / CHUNK_SIZE + 1;
+        // A heightmap covers its image rather than the render distance
+        let columns = match &self.heightmap {
+            Some(heightmap) => {
+                let (min, max) = heightmap.bounds();
+                let (min, max) = (min.div_euclid(IVec2::splat(CHUNK_SIZE)), (max - IVec2::ONE).div_euclid(IVec2::splat(CHUNK_SIZE)));
+                (min.x..=max.x).flat_map(|x| (min.y..=max.y).map(move |z| IVec3::new(x, 0, z))).collect()
+            }
+            None => chunks_within_render_distance(settings),
+        };
+        columns
+            .into_iter()
+            .flat_map(|column| (0..layers).map(move |y| IVec3::new(column.x, y, column.z)))
+            .collect()
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        if self.surface_only {
+            self.surface_chunk(position)
+        } else {
+            self.filled_chunk(position)
+        }
+    }
+}
+
+// Level ground at a fixed height, the best case for greedy meshing
+pub struct FlatGenerator {
+    pub height: i32,
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn name(&self) -> &'static str {
+        "Flat world"
+    }
+
+    fn chunk_positions(&self, settings: &VoxelRenderSettings) -> Vec<IVec3> {
+        let layers = self.height / CHUNK_SIZE + 1;
+        chunks_within_render_distance(settings)
+            .into_iter()
+            .flat_map(|column| (0..layers).map(move |y| IVec3::new(column.x, y, column.z)))
+            .collect()
+    }
+
+    fn generate_chunk(&self, position: IVec3) -> VoxelChunk {
+        let origin_y = position.y * CHUNK_SIZE;
+        let mut voxels = Vec::new();
+
+        for x in 0..CHUNK_SIZE {
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    let world_y = origin_y + y;
+                    if world_y > self.height {
+                        break;
+                    }
+                    let (color, kind) = if world_y == self.height {
+                        (Color::rgb(0.3, 0.6, 0.25), KIND_GRASS)
+                    } else {
+                        (Color::rgb(0.45, 0.32, 0.2), KIND_DIRT)
+                    };
+                    voxels.push(Voxel {
+                        position: Vec3::new(x as f32, y as f32, z as f32),
+                        color,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        let mut chunk = VoxelChunk::new(position, voxels);
+        chunk.filter_occluded_voxels();
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cell and kind of every voxel left visible, sorted so the two paths compare
+    fn visible(chunk: &VoxelChunk) -> Vec<(i32, i32, i32, u16)> {
+        let mut cells: Vec<_> = chunk
+            .voxels
+            .iter()
+            .map(|voxel| {
+                let pos = LocalPos::from_vec3(voxel.position);
+                (pos.x, pos.y, pos.z, voxel.kind)
+            })
+            .collect();
+        cells.sort_unstable();
+        cells
+    }
+
+    #[test]
+    fn surface_chunk_matches_fill_then_cull() {
+        let generator = TerrainGenerator {
+            seed: 7,
+            surface_only: true,
+            config: TerrainConfig::default(),
+            heightmap: None,
+        };
+        // Layers the default heights cross, in a column away from the origin
+        for position in [IVec3::new(3, 0, -2), IVec3::new(3, 1, -2)] {
+            let origin = position * CHUNK_SIZE;
+            let heights = generator.heights(origin);
+            assert!(heights.iter().any(|height| *height != heights[0]), "test chunk should vary by column");
+
+            let filled = generator.filled_chunk(position);
+            let mut surface = generator.surface_chunk(position);
+            surface.filter_occluded_voxels();
+            assert!(!filled.voxels.is_empty());
+            assert_eq!(visible(&surface), visible(&filled));
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::BoxedFuture,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use super::{DemoScene, GenerationSettings, WorldCommand};
+
+const CONFIG_PATH: &str = "terrain.ron";
+// Editors often write a file several times per save
+const RELOAD_DEBOUNCE_SECS: f32 = 0.5;
+
+pub struct TerrainConfigPlugin;
+
+impl Plugin for TerrainConfigPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<TerrainConfig>()
+            .init_asset_loader::<TerrainConfigLoader>()
+            .init_resource::<TerrainConfigState>()
+            .add_systems(Startup, load_terrain_config)
+            .add_systems(Update, (
+                watch_terrain_config,
+                apply_terrain_config,
+            ).chain());
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct Octave {
+    pub frequency: f32,
+    pub amplitude: f32,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ColorStop {
+    // Normalized terrain height in [0, 1]
+    pub height: f32,
+    pub color: [f32; 3],
+}
+
+#[derive(Asset, TypePath, Serialize, Deserialize, Debug, Clone)]
+pub struct TerrainConfig {
+    pub base_height: f32,
+    pub height_scale: f32,
+    pub octaves: Vec<Octave>,
+    pub warp_strength: f32,
+    pub warp_frequency: f32,
+    // Surface colors by height, sorted from lowest to highest stop
+    pub color_ramp: Vec<ColorStop>,
+    pub soil_color: [f32; 3],
+    pub soil_depth: i32,
+    pub stone_color: [f32; 3],
+}
+
+impl Default for TerrainConfig {
+    fn default() -> Self {
+        Self {
+            base_height: 12.0,
+            height_scale: 20.0,
+            octaves: vec![
+                Octave { frequency: 0.02, amplitude: 1.0 },
+                Octave { frequency: 0.04, amplitude: 0.5 },
+                Octave { frequency: 0.08, amplitude: 0.25 },
+                Octave { frequency: 0.16, amplitude: 0.125 },
+            ],
+            warp_strength: 0.0,
+            warp_frequency: 0.01,
+            color_ramp: vec![
+                ColorStop { height: 0.0, color: [0.3, 0.6, 0.25] },
+                ColorStop { height: 1.0, color: [0.3, 0.8, 0.25] },
+            ],
+            soil_color: [0.45, 0.32, 0.2],
+            soil_depth: 3,
+            stone_color: [0.5, 0.5, 0.52],
+        }
+    }
+}
+
+impl TerrainConfig {
+    pub fn max_height(&self) -> i32 {
+        (self.base_height + self.height_scale).ceil() as i32
+    }
+
+    // Surface color for a normalized height, interpolated between ramp stops
+    pub fn ramp_color(&self, t: f32) -> Color {
+        let stops = &self.color_ramp;
+        let upper = stops.iter().position(|stop| stop.height >= t).unwrap_or(stops.len() - 1);
+        let lower = upper.saturating_sub(1);
+        let (a, b) = (stops[lower], stops[upper]);
+
+        let span = b.height - a.height;
+        let mix = if span > 0.0 { ((t - a.height) / span).clamp(0.0, 1.0) } else { 0.0 };
+        let rgb: [f32; 3] = std::array::from_fn(|i| a.color[i] + (b.color[i] - a.color[i]) * mix);
+        Color::rgb(rgb[0], rgb[1], rgb[2])
+    }
+
+    fn validate(&self) -> Result<(), TerrainConfigError> {
+        if self.octaves.is_empty() {
+            return Err(TerrainConfigError::Invalid("at least one octave is required".into()));
+        }
+        if let Some(octave) = self.octaves.iter().find(|o| o.frequency <= 0.0 || o.amplitude <= 0.0) {
+            return Err(TerrainConfigError::Invalid(format!(
+                "octave frequency and amplitude must be positive, got {:?}",
+                octave,
+            )));
+        }
+        if self.color_ramp.is_empty() {
+            return Err(TerrainConfigError::Invalid("color_ramp needs at least one stop".into()));
+        }
+        if self.color_ramp.windows(2).any(|pair| pair[0].height > pair[1].height) {
+            return Err(TerrainConfigError::Invalid("color_ramp stops must be sorted by height".into()));
+        }
+        if self.height_scale < 0.0 || self.base_height < 0.0 {
+            return Err(TerrainConfigError::Invalid("base_height and height_scale must not be negative".into()));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum TerrainConfigError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Invalid(String),
+}
+
+impl fmt::Display for TerrainConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerrainConfigError::Io(err) => write!(f, "could not read terrain config: {}", err),
+            TerrainConfigError::Parse(err) => write!(f, "could not parse terrain config: {}", err),
+            TerrainConfigError::Invalid(reason) => write!(f, "invalid terrain config: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for TerrainConfigError {}
+
+#[derive(Default)]
+struct TerrainConfigLoader;
+
+impl AssetLoader for TerrainConfigLoader {
+    type Asset = TerrainConfig;
+    type Settings = ();
+    type Error = TerrainConfigError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<TerrainConfig, TerrainConfigError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await.map_err(TerrainConfigError::Io)?;
+            let config: TerrainConfig = ron::de::from_bytes(&bytes).map_err(TerrainConfigError::Parse)?;
+            config.validate()?;
+            Ok(config)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ron"]
+    }
+}
+
+#[derive(Resource, Default)]
+struct TerrainConfigState {
+    handle: Handle<TerrainConfig>,
+    // Time at which a pending change should be applied
+    apply_at: Option<f32>,
+}
+
+fn load_terrain_config(asset_server: Res<AssetServer>, mut state: ResMut<TerrainConfigState>) {
+    state.handle = asset_server.load(CONFIG_PATH);
+}
+
+// Failed reloads never produce an event here, so the previous parameters stay in use
+fn watch_terrain_config(
+    mut events: EventReader<AssetEvent<TerrainConfig>>,
+    mut state: ResMut<TerrainConfigState>,
+    time: Res<Time>,
+) {
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } if *id == state.handle.id() => {
+                state.apply_at = Some(time.elapsed_seconds() + RELOAD_DEBOUNCE_SECS);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn apply_terrain_config(
+    mut state: ResMut<TerrainConfigState>,
+    configs: Res<Assets<TerrainConfig>>,
+    mut generation_settings: ResMut<GenerationSettings>,
+    mut world_commands: EventWriter<WorldCommand>,
+    time: Res<Time>,
+) {
+    let Some(apply_at) = state.apply_at else {
+        return;
+    };
+    if time.elapsed_seconds() < apply_at {
+        return;
+    }
+    state.apply_at = None;
+
+    if let Some(config) = configs.get(&state.handle) {
+        info!("Applying terrain config with {} octaves", config.octaves.len());
+        generation_settings.terrain = config.clone();
+        if matches!(generation_settings.scene, DemoScene::Terrain | DemoScene::Heightmap) {
+            world_commands.send(WorldCommand::Regenerate);
+        }
+    }
+}
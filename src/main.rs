@@ -1,29 +1,126 @@
 // src/main.rs
-use bevy::prelude::*;
+use bevy::{
+    app::ScheduleRunnerPlugin,
+    prelude::*,
+    window::ExitCondition,
+    winit::WinitPlugin,
+};
+use std::time::Duration;
+
+#[macro_use]
+mod trace;
+mod cli;
+mod bytes;
 
 mod voxel;
 mod voxel_types;
+mod bindings;
 mod render;
 mod camera;
 mod diagnostics;
+mod editing;
+mod generation;
+mod picking;
+mod screenshot;
+mod settings;
+mod console;
+mod triggers;
+#[cfg(feature = "physics")]
+mod physics;
 
 use voxel::VoxelPlugin;
+use bindings::KeyBindingsPlugin;
 use camera::CameraPlugin;
-use diagnostics::DiagnosticsPlugin;
+use diagnostics::{BenchmarkRun, DiagnosticsPlugin};
+use editing::EditingPlugin;
+use generation::GenerationPlugin;
+use picking::PickingPlugin;
+use screenshot::ScreenshotPlugin;
+use console::ConsolePlugin;
+use triggers::TriggerPlugin;
+use cli::{CliArgs, USAGE};
+use settings::{SettingsPlugin, DEFAULT_SETTINGS_FILE};
+
+// Frame pacing without a window to drive it
+const HEADLESS_FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
 
 fn main() {
-    App::new()
-        .add_plugins((
-            DefaultPlugins.set(WindowPlugin {
-                primary_window: Some(Window {
-                    title: "Voxel Engine".into(),
-                    ..default()
-                }),
-                ..default()
-            }),
-            VoxelPlugin,
-            CameraPlugin,
-            DiagnosticsPlugin,
-        ))
-        .run();
-}
\ No newline at end of file
+    let cli = match CliArgs::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => exit_with_usage(&err),
+    };
+    if cli.help {
+        println!("{}", USAGE);
+        return;
+    }
+
+    // Read before the window is created, since it holds the window options
+    let mut settings = SettingsPlugin::load(cli.settings.clone().unwrap_or_else(|| DEFAULT_SETTINGS_FILE.into()));
+    if let Some(distance) = cli.render_distance {
+        settings.file.render.render_distance = distance;
+        // Keep a one-off override out of the settings file
+        settings.writable = false;
+    }
+    if cli.benchmark.is_some() {
+        settings.writable = false;
+    }
+
+    let mut app = App::new();
+    if let Err(err) = cli.insert_resources(&mut app) {
+        exit_with_usage(&err);
+    }
+
+    // Benchmarks always run headless and unpaced, so frame times are the work done
+    if cli.headless || cli.benchmark.is_some() {
+        let frame_time = if cli.benchmark.is_some() { Duration::ZERO } else { HEADLESS_FRAME_TIME };
+        app.add_plugins((
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    close_when_requested: false,
+                })
+                .disable::<WinitPlugin>(),
+            ScheduleRunnerPlugin::run_loop(frame_time),
+        ));
+    } else {
+        app.add_plugins(DefaultPlugins.set(WindowPlugin {
+            primary_window: Some(settings.window()),
+            ..default()
+        }));
+    }
+
+    app.add_plugins((
+        KeyBindingsPlugin,
+        settings,
+        VoxelPlugin,
+        CameraPlugin,
+        DiagnosticsPlugin,
+        GenerationPlugin,
+        PickingPlugin,
+        ScreenshotPlugin,
+        EditingPlugin,
+        ConsolePlugin,
+        TriggerPlugin,
+    ));
+    #[cfg(feature = "physics")]
+    app.add_plugins(physics::PhysicsPlugin);
+    // Taken before running, since the runner takes the app with it
+    let benchmark = app.world.get_resource::<BenchmarkRun>().map(|run| (run.name.clone(), run.outcome.clone()));
+    app.run();
+
+    if let Some((name, outcome)) = benchmark {
+        if outcome.passed().is_none() {
+            eprintln!("Benchmark {} stopped before it finished", name);
+        }
+        let code = outcome.exit_code();
+        if code != 0 {
+            std::process::exit(code);
+        }
+    }
+}
+
+fn exit_with_usage(err: &str) -> ! {
+    eprintln!("error: {}\n\n{}", err, USAGE);
+    std::process::exit(2);
+}
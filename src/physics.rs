@@ -0,0 +1,260 @@
+// src/physics.rs
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use std::collections::VecDeque;
+use crate::camera::CameraController;
+use crate::console::RegisterConsoleCommand;
+use crate::voxel::{LocalPos, VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::VoxelRenderSettings;
+
+// Chunks whose collider is built per frame, so a burst of edits or a long flight
+// spreads the work
+const MAX_COLLIDER_BUILDS_PER_FRAME: usize = 8;
+// Colliders are dropped this far past the physics distance, so chunks on the
+// edge don't rebuild as the camera moves back and forth
+const COLLIDER_KEEP_MARGIN: f32 = 16.0;
+const BALL_RADIUS: f32 = 0.4;
+const BALL_SPAWN_INTERVAL_SECS: f32 = 0.2;
+const BALL_SPAWN_HEIGHT: f32 = 12.0;
+const BALL_RESTITUTION: f32 = 0.7;
+// Oldest balls are removed past this
+const MAX_BALLS: usize = 200;
+
+// Rapier colliders for chunks near the camera, and the bouncing ball demo
+pub struct PhysicsPlugin;
+
+impl Plugin for PhysicsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+            .init_resource::<PhysicsSettings>()
+            .init_resource::<BallRain>()
+            .add_systems(Update, (update_chunk_colliders, rain_balls))
+            .register_console_command(
+                "physics",
+                "physics <distance <d>|balls>: sets the collider distance or toggles the ball demo",
+                physics_command,
+            );
+    }
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct PhysicsSettings {
+    // Chunks farther than this from the camera have no collider
+    pub distance: f32,
+}
+
+impl Default for PhysicsSettings {
+    fn default() -> Self {
+        Self { distance: 64.0 }
+    }
+}
+
+// Chunk data version the collider was built from
+#[derive(Component)]
+struct ChunkCollider {
+    version: u32,
+}
+
+#[derive(Resource, Default)]
+struct BallRain {
+    enabled: bool,
+    timer: f32,
+    balls: VecDeque<Entity>,
+    assets: Option<(Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+// Merges a chunk's solid cells into boxes: each box grows along x, then y, then z
+// while every cell it would take in is solid and unclaimed. Returns the lowest cell
+// and size of each box.
+fn merge_solid_boxes(chunk: &VoxelChunk) -> Vec<(IVec3, IVec3)> {
+    let size = CHUNK_SIZE as usize;
+    let index = |x: i32, y: i32, z: i32| (z as usize * size + y as usize) * size + x as usize;
+    let mut claimed = vec![false; size * size * size];
+    let free = |claimed: &[bool], x: i32, y: i32, z: i32| {
+        !claimed[index(x, y, z)] && chunk.occupancy.is_solid(LocalPos::new(x, y, z))
+    };
+
+    let mut boxes = Vec::new();
+    for z in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                if !free(&claimed, x, y, z) {
+                    continue;
+                }
+                let mut width = 1;
+                while x + width < CHUNK_SIZE && free(&claimed, x + width, y, z) {
+                    width += 1;
+                }
+                let mut height = 1;
+                while y + height < CHUNK_SIZE && (x..x + width).all(|x| free(&claimed, x, y + height, z)) {
+                    height += 1;
+                }
+                let mut depth = 1;
+                while z + depth < CHUNK_SIZE
+                    && (y..y + height).all(|y| (x..x + width).all(|x| free(&claimed, x, y, z + depth)))
+                {
+                    depth += 1;
+                }
+                for cz in z..z + depth {
+                    for cy in y..y + height {
+                        for cx in x..x + width {
+                            claimed[index(cx, cy, cz)] = true;
+                        }
+                    }
+                }
+                boxes.push((IVec3::new(x, y, z), IVec3::new(width, height, depth)));
+            }
+        }
+    }
+    boxes
+}
+
+// A compound of the chunk's merged boxes, relative to the chunk entity, which sits
+// at the chunk center. None when the chunk has no solid voxels.
+fn chunk_collider(chunk: &VoxelChunk, voxel_size: f32) -> Option<Collider> {
+    let half_chunk = Vec3::splat(CHUNK_SIZE as f32 * voxel_size / 2.0);
+    let shapes: Vec<(Vec3, Quat, Collider)> = merge_solid_boxes(chunk)
+        .into_iter()
+        .map(|(min, size)| {
+            let half_extents = size.as_vec3() * voxel_size / 2.0;
+            let center = min.as_vec3() * voxel_size + half_extents - half_chunk;
+            (center, Quat::IDENTITY, Collider::cuboid(half_extents.x, half_extents.y, half_extents.z))
+        })
+        .collect();
+    (!shapes.is_empty()).then(|| Collider::compound(shapes))
+}
+
+// Builds colliders for chunks within the physics distance whose data changed since
+// their last build, and removes them from chunks that have moved out of range
+fn update_chunk_colliders(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    physics: Res<PhysicsSettings>,
+    camera: Query<&GlobalTransform, With<CameraController>>,
+    chunks: Query<(Entity, &VoxelChunk, Option<&ChunkCollider>)>,
+) {
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+    let camera_position = camera_transform.translation();
+    let keep_distance = physics.distance + COLLIDER_KEEP_MARGIN;
+
+    let mut stale: Vec<(f32, Entity, &VoxelChunk)> = Vec::new();
+    for (entity, chunk, collider) in chunks.iter() {
+        let distance = chunk.world_center(settings.voxel_size).distance(camera_position);
+        match collider {
+            Some(_) if distance > keep_distance => {
+                commands.entity(entity).remove::<(Collider, ChunkCollider)>();
+            }
+            Some(collider) if collider.version == chunk.version => {}
+            // Chunks already in range stay until they pass the margin
+            Some(_) => stale.push((distance, entity, chunk)),
+            None if distance <= physics.distance => stale.push((distance, entity, chunk)),
+            None => {}
+        }
+    }
+
+    // Nearest first, so the ground under the camera is solid soonest
+    stale.sort_by(|a, b| a.0.total_cmp(&b.0));
+    for (_, entity, chunk) in stale.into_iter().take(MAX_COLLIDER_BUILDS_PER_FRAME) {
+        chunk_span!("chunk_collider", chunk.position);
+        let mut entity_commands = commands.entity(entity);
+        match chunk_collider(chunk, settings.voxel_size) {
+            Some(collider) => entity_commands.insert(collider),
+            None => entity_commands.remove::<Collider>(),
+        };
+        entity_commands.insert(ChunkCollider { version: chunk.version });
+    }
+}
+
+// Drops balls above the camera while the demo is on, removing the oldest past the
+// cap and any that leave the physics distance, where nothing would stop them
+#[allow(clippy::too_many_arguments)]
+fn rain_balls(
+    mut commands: Commands,
+    time: Res<Time>,
+    physics: Res<PhysicsSettings>,
+    mut rain: ResMut<BallRain>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    camera: Query<&GlobalTransform, With<CameraController>>,
+    balls: Query<&GlobalTransform, With<RigidBody>>,
+) {
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+    let camera_position = camera_transform.translation();
+
+    rain.balls.retain(|&ball| {
+        let in_range = balls
+            .get(ball)
+            .is_ok_and(|transform| transform.translation().distance(camera_position) <= physics.distance);
+        if !in_range {
+            commands.entity(ball).despawn_recursive();
+        }
+        in_range
+    });
+    if !rain.enabled {
+        return;
+    }
+
+    rain.timer += time.delta_seconds();
+    if rain.timer < BALL_SPAWN_INTERVAL_SECS {
+        return;
+    }
+    rain.timer = 0.0;
+    let (mesh, material) = rain
+        .assets
+        .get_or_insert_with(|| {
+            let mesh = meshes.add(Mesh::from(shape::UVSphere { radius: BALL_RADIUS, sectors: 16, stacks: 8 }));
+            let material = materials.add(StandardMaterial {
+                base_color: Color::rgb(0.9, 0.3, 0.2),
+                ..default()
+            });
+            (mesh, material)
+        })
+        .clone();
+
+    // Scattered a little, so the balls don't stack into a column
+    let seconds = time.elapsed_seconds();
+    let scatter = Vec3::new((seconds * 7.3).sin(), 0.0, (seconds * 5.1).cos()) * 2.0;
+    let ball = commands
+        .spawn((
+            PbrBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(camera_position + Vec3::Y * BALL_SPAWN_HEIGHT + scatter),
+                ..default()
+            },
+            RigidBody::Dynamic,
+            Collider::ball(BALL_RADIUS),
+            Restitution::coefficient(BALL_RESTITUTION),
+        ))
+        .id();
+    rain.balls.push_back(ball);
+    if rain.balls.len() > MAX_BALLS {
+        if let Some(oldest) = rain.balls.pop_front() {
+            commands.entity(oldest).despawn_recursive();
+        }
+    }
+}
+
+fn physics_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    match args.first().copied() {
+        Some("distance") => {
+            let distance: f32 = args
+                .get(1)
+                .and_then(|word| word.parse().ok())
+                .filter(|distance: &f32| distance.is_finite() && *distance >= 0.0)
+                .ok_or("distance must be a positive number")?;
+            world.resource_mut::<PhysicsSettings>().distance = distance;
+            Ok(format!("physics distance = {}", distance))
+        }
+        Some("balls") => {
+            let mut rain = world.resource_mut::<BallRain>();
+            rain.enabled = !rain.enabled;
+            Ok(format!("Ball demo {}", if rain.enabled { "on" } else { "off" }))
+        }
+        _ => Ok(format!("physics distance is {}; use physics distance <d> or physics balls", world.resource::<PhysicsSettings>().distance)),
+    }
+}
@@ -0,0 +1,62 @@
+// src/picking.rs
+use bevy::prelude::*;
+
+use crate::camera::CameraController;
+use crate::render::HighlightedVoxel;
+use crate::voxel::{LocalPos, RayFilter, VoxelHit, VoxelWorld};
+
+// Furthest voxel the crosshair can target, in world units
+const PICK_REACH: f32 = 24.0;
+
+pub struct PickingPlugin;
+
+impl Plugin for PickingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<TargetedVoxel>()
+            .add_systems(Update, pick_targeted_voxel);
+    }
+}
+
+// Voxel under the crosshair and its color, refreshed every frame
+#[derive(Resource, Default)]
+pub struct TargetedVoxel {
+    pub hit: Option<VoxelHit>,
+    pub color: Color,
+}
+
+// Casts from the camera along its view direction and mirrors the hit into the
+// highlight
+fn pick_targeted_voxel(
+    mut commands: Commands,
+    camera: Query<&GlobalTransform, With<CameraController>>,
+    world: VoxelWorld,
+    mut target: ResMut<TargetedVoxel>,
+) {
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+
+    let hit = world.raycast(camera_transform.translation(), camera_transform.forward(), PICK_REACH, RayFilter::default());
+    // Only exposed voxels are stored; a hit from outside always lands on one
+    let color = hit
+        .and_then(|hit| {
+            world
+                .chunk(hit.chunk)?
+                .voxels
+                .iter()
+                .find(|voxel| LocalPos::from_vec3(voxel.position) == hit.local)
+        })
+        .map_or(Color::NONE, |voxel| voxel.color);
+
+    match hit {
+        Some(hit) => commands.insert_resource(HighlightedVoxel {
+            chunk: hit.chunk,
+            local: hit.local,
+            face: hit.face(),
+        }),
+        None if target.hit.is_some() => commands.remove_resource::<HighlightedVoxel>(),
+        None => {}
+    }
+    target.hit = hit;
+    target.color = color;
+}
@@ -1,63 +1,174 @@
 // src/render/billboard.rs
 use bevy::{
+    pbr::{MaterialPipeline, MaterialPipelineKey},
     prelude::*,
-    render::{render_resource::*, mesh::*},
+    reflect::TypePath,
+    render::{
+        mesh::*,
+        render_resource::*,
+        texture::{ImageFilterMode, ImageSampler, ImageSamplerDescriptor},
+    },
 };
 
-use crate::voxel::{VoxelChunk};
-use crate::voxel_types::VoxelRenderSettings;
+use crate::voxel::{process_dirty_chunks, CullingStats, DirtyChunks, LodSettings, SystemTimings, VoxelChunk};
+use crate::voxel_types::{RenderMode, VoxelRenderSettings};
+use super::fog::VoxelFog;
+use super::material_cache::MaterialCache;
+
+const SHADER_PATH: &str = "shaders/billboard_entity.wgsl";
+
+pub type BillboardMaterialCache = MaterialCache<FacingBillboardMaterial>;
 
 pub struct BillboardPlugin;
 
 impl Plugin for BillboardPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<BillboardAssets>()
+        app.add_plugins(MaterialPlugin::<FacingBillboardMaterial>::default())
+            .init_resource::<BillboardAssets>()
+            .init_resource::<BillboardMaterialCache>()
+            .init_resource::<BillboardStats>()
             .add_systems(Startup, setup_billboard_assets)
-            .add_systems(Update, update_billboards);
+            .add_systems(Update, (
+                despawn_billboards,
+                spawn_billboards,
+            ).chain().after(process_dirty_chunks));
+    }
+}
+
+// Expands the unit quad along the camera's right and up vectors in the vertex
+// shader, so billboard transforms never need rotating
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct FacingBillboardMaterial {
+    #[uniform(0)]
+    pub color: Color,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+    #[uniform(3)]
+    pub fog: VoxelFog,
+}
+
+impl Material for FacingBillboardMaterial {
+    fn vertex_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    // Blended billboards go through the transparent phase, sorted back to front
+    fn alpha_mode(&self) -> AlphaMode {
+        if self.color.a() < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        }
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        descriptor.primitive.cull_mode = None;
+        Ok(())
     }
 }
 
 #[derive(Component)]
-struct BillboardMarker;
+pub struct BillboardMarker;
+
+// Parent of a chunk's billboard entities and the chunk version they were built from
+#[derive(Component)]
+struct ChunkBillboards {
+    root: Entity,
+    count: usize,
+    version: u32,
+}
+
+impl ChunkBillboards {
+    // The billboards plus their root
+    fn entity_count(&self) -> usize {
+        self.count + 1
+    }
+}
 
+// Billboard entity churn for the current frame
 #[derive(Resource, Default)]
-struct BillboardAssets {
-    circle_texture: Option<Handle<Image>>,
+pub struct BillboardStats {
+    pub spawned: usize,
+    pub despawned: usize,
 }
 
-fn create_circle_texture(images: &mut ResMut<Assets<Image>>) -> Handle<Image> {
-    let size = 64u32;
-    let mut texture_data = Vec::with_capacity((size * size * 4) as usize);
-    
+#[derive(Resource, Default)]
+pub(super) struct BillboardAssets {
+    pub(super) circle_texture: Option<Handle<Image>>,
+    quad_mesh: Option<Handle<Mesh>>,
+}
+
+// White disc with its edge feathered over `softness` pixels of the base level.
+// Smaller levels are box filtered down from the one above, so distant billboards
+// sample an averaged edge instead of sparkling.
+fn create_circle_texture(images: &mut Assets<Image>, size: u32, softness: f32) -> Handle<Image> {
+    let size = size.max(1).next_power_of_two();
+    let radius = size as f32 / 2.0 - softness;
+
+    let mut level: Vec<u8> = Vec::with_capacity((size * size) as usize);
     for y in 0..size {
         for x in 0..size {
-            let distance = Vec2::new(
-                (x as f32 / size as f32 - 0.5) * 2.0,
-                (y as f32 / size as f32 - 0.5) * 2.0
-            ).length();
-            
-            let alpha = if distance <= 0.95 {
-                1.0
-            } else if distance <= 1.0 {
-                1.0 - (distance - 0.95) / 0.05
-            } else {
-                0.0
-            };
-            
-            texture_data.extend_from_slice(&[255, 255, 255, (alpha * 255.0) as u8]);
+            // Signed distance from the edge, in pixels, from the pixel center
+            let offset = Vec2::new(x as f32 + 0.5, y as f32 + 0.5) - Vec2::splat(size as f32 / 2.0);
+            let distance = offset.length() - radius;
+            let alpha = (0.5 - distance / softness.max(f32::EPSILON)).clamp(0.0, 1.0);
+            level.push((alpha * 255.0).round() as u8);
         }
     }
 
-    let texture = Image::new(
+    let mut alpha_levels = vec![level];
+    let mut level_size = size;
+    while level_size > 1 {
+        let previous = alpha_levels.last().unwrap();
+        let half = level_size / 2;
+        let mut level = Vec::with_capacity((half * half) as usize);
+        for y in 0..half {
+            for x in 0..half {
+                let texel = |dx: u32, dy: u32| previous[((y * 2 + dy) * level_size + x * 2 + dx) as usize] as u32;
+                level.push(((texel(0, 0) + texel(1, 0) + texel(0, 1) + texel(1, 1) + 2) / 4) as u8);
+            }
+        }
+        alpha_levels.push(level);
+        level_size = half;
+    }
+
+    let rgba = |levels: &[Vec<u8>]| -> Vec<u8> {
+        levels.iter().flatten().flat_map(|alpha| [255, 255, 255, *alpha]).collect()
+    };
+    // Image::new only accepts the base level, so the mip chain is swapped in after
+    let mut texture = Image::new(
         Extent3d {
             width: size,
             height: size,
             depth_or_array_layers: 1,
         },
         TextureDimension::D2,
-        texture_data,
+        rgba(&alpha_levels[..1]),
         TextureFormat::Rgba8UnormSrgb,
     );
+    texture.data = rgba(&alpha_levels);
+    texture.texture_descriptor.mip_level_count = alpha_levels.len() as u32;
+    texture.sampler = ImageSampler::Descriptor(ImageSamplerDescriptor {
+        mag_filter: ImageFilterMode::Linear,
+        min_filter: ImageFilterMode::Linear,
+        mipmap_filter: ImageFilterMode::Linear,
+        ..default()
+    });
 
     images.add(texture)
 }
@@ -97,78 +208,210 @@ fn create_billboard_mesh() -> Mesh {
 }
 
 fn setup_billboard_assets(
+    settings: Res<VoxelRenderSettings>,
     mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
     mut billboard_assets: ResMut<BillboardAssets>,
 ) {
-    let texture_handle = create_circle_texture(&mut images);
+    let texture_handle = create_circle_texture(
+        &mut images,
+        settings.circle_texture_size,
+        settings.circle_edge_softness,
+    );
     billboard_assets.circle_texture = Some(texture_handle);
+    billboard_assets.quad_mesh = Some(meshes.add(create_billboard_mesh()));
+}
+
+struct BillboardSpawner<'a> {
+    mesh: Handle<Mesh>,
+    texture: Handle<Image>,
+    voxel_size: f32,
+    light_ambient: f32,
+    lod: &'a LodSettings,
+    fog: VoxelFog,
+}
+
+impl BillboardSpawner<'_> {
+    // Spawns one billboard per voxel under a new root parented to the chunk.
+    // Distant LODs draw one larger billboard per block of voxels.
+    fn spawn(
+        &self,
+        commands: &mut Commands,
+        chunk_entity: Entity,
+        chunk: &VoxelChunk,
+        material_cache: &mut BillboardMaterialCache,
+        materials: &mut Assets<FacingBillboardMaterial>,
+    ) -> ChunkBillboards {
+        let chunk_center = chunk.world_center(self.voxel_size);
+        let factor = self.lod.factor(chunk.lod_level);
+        let voxels = chunk.lod_voxels(factor);
+        let scale = self.voxel_size * 2.0 * factor as f32;
+        let root = commands.spawn(SpatialBundle::INHERITED_IDENTITY).id();
+        commands.entity(chunk_entity).add_child(root);
+
+        commands.entity(root).with_children(|parent| {
+            for voxel in voxels.iter() {
+                let world_pos = chunk.get_voxel_world_position(voxel, self.voxel_size);
+                let material = material_cache.get_or_create(
+                    chunk.lit_color(voxel, self.light_ambient),
+                    |color| FacingBillboardMaterial {
+                        color,
+                        texture: self.texture.clone(),
+                        fog: self.fog,
+                    },
+                    materials,
+                );
+
+                parent.spawn((
+                    MaterialMeshBundle {
+                        mesh: self.mesh.clone(),
+                        material,
+                        transform: Transform::from_translation(world_pos - chunk_center)
+                            .with_scale(Vec3::splat(scale)),
+                        ..default()
+                    },
+                    BillboardMarker,
+                ));
+            }
+        });
+
+        ChunkBillboards {
+            root,
+            count: voxels.len(),
+            version: chunk.version,
+        }
+    }
 }
 
-fn update_billboards(
+fn despawn_billboards(
     mut commands: Commands,
     settings: Res<VoxelRenderSettings>,
-    chunks: Query<&VoxelChunk>,
-    camera: Query<&Transform, With<Camera>>,
-    old_billboards: Query<Entity, With<BillboardMarker>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    billboarded_chunks: Query<(Entity, &ChunkBillboards)>,
+    mut stats: ResMut<BillboardStats>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    stats.spawned = 0;
+    stats.despawned = 0;
+
+    // Don't render if in debug mode or another render mode is active
+    if !settings.debug_mode && settings.render_mode == RenderMode::Billboards {
+        return;
+    }
+
+    for (chunk_entity, billboards) in billboarded_chunks.iter() {
+        commands.entity(billboards.root).despawn_recursive();
+        commands.entity(chunk_entity).remove::<ChunkBillboards>();
+        stats.despawned += billboards.count;
+        culling_stats.entities_despawned += billboards.entity_count();
+    }
+}
+
+// Billboards are only (re)spawned for new chunks and chunks whose version changed
+#[allow(clippy::too_many_arguments)]
+fn spawn_billboards(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    dirty: Res<DirtyChunks>,
+    new_chunks: Query<(Entity, &VoxelChunk), Without<ChunkBillboards>>,
+    mut billboarded_chunks: Query<(&VoxelChunk, &mut ChunkBillboards)>,
+    mut materials: ResMut<Assets<FacingBillboardMaterial>>,
     billboard_assets: Res<BillboardAssets>,
+    lod: Res<LodSettings>,
+    mut material_cache: ResMut<BillboardMaterialCache>,
+    mut stats: ResMut<BillboardStats>,
+    mut culling_stats: ResMut<CullingStats>,
+    mut timings: ResMut<SystemTimings>,
 ) {
-    // Remove old billboards
-    for entity in old_billboards.iter() {
-        commands.entity(entity).despawn();
+    if settings.debug_mode || settings.render_mode != RenderMode::Billboards {
+        return;
+    }
+    let _timing = timings.span("billboard spawn");
+    if new_chunks.is_empty() && dirty.is_empty() {
+        return;
     }
 
-    // Don't render if in debug mode
-    if settings.debug_mode {
+    let (Some(circle_texture), Some(quad_mesh)) = (&billboard_assets.circle_texture, &billboard_assets.quad_mesh) else {
         return;
+    };
+
+    let spawner = BillboardSpawner {
+        mesh: quad_mesh.clone(),
+        texture: circle_texture.clone(),
+        voxel_size: settings.voxel_size,
+        light_ambient: settings.light_ambient,
+        lod: &lod,
+        fog: VoxelFog::from_settings(&settings),
+    };
+
+    for (chunk_entity, chunk) in new_chunks.iter() {
+        chunk_span!("spawn_billboards", chunk.position);
+        let billboards = spawner.spawn(&mut commands, chunk_entity, chunk, &mut material_cache, &mut materials);
+        stats.spawned += billboards.count;
+        culling_stats.entities_spawned += billboards.entity_count();
+        commands.entity(chunk_entity).insert(billboards);
     }
 
-    let camera_transform = camera.single();
-    let mesh_handle = meshes.add(create_billboard_mesh());
+    for chunk_entity in dirty.iter() {
+        let Ok((chunk, mut billboards)) = billboarded_chunks.get_mut(chunk_entity) else {
+            continue;
+        };
+        if billboards.version == chunk.version {
+            continue;
+        }
+        chunk_span!("respawn_billboards", chunk.position);
 
-    if let Some(circle_texture) = &billboard_assets.circle_texture {
-        for chunk in chunks.iter() {
-            if !chunk.visible {
-                continue;
-            }
+        commands.entity(billboards.root).despawn_recursive();
+        stats.despawned += billboards.count;
+        culling_stats.entities_despawned += billboards.entity_count();
+        *billboards = spawner.spawn(&mut commands, chunk_entity, chunk, &mut material_cache, &mut materials);
+        stats.spawned += billboards.count;
+        culling_stats.entities_spawned += billboards.entity_count();
+    }
+}
 
-            for voxel in &chunk.voxels {
-                let world_pos = Vec3::new(
-                    (chunk.position.x as f32 * settings.voxel_size) + (voxel.position.x * settings.voxel_size),
-                    (chunk.position.y as f32 * settings.voxel_size) + (voxel.position.y * settings.voxel_size),
-                    (chunk.position.z as f32 * settings.voxel_size) + (voxel.position.z * settings.voxel_size),
-                );
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::asset::AssetPlugin;
+    use crate::voxel_types::{Voxel, KIND_PLAIN};
 
-                let to_camera = (camera_transform.translation - world_pos).normalize();
-                let camera_up = camera_transform.local_y();
-                let right = camera_up.cross(-to_camera).normalize();
-                let up = (-to_camera).cross(right).normalize();
-                let rotation = Quat::from_mat3(&Mat3::from_cols(right, up, -to_camera));
-
-                let material = materials.add(StandardMaterial {
-                    base_color: voxel.color,
-                    base_color_texture: Some(circle_texture.clone()),
-                    alpha_mode: AlphaMode::Mask(0.1),
-                    unlit: true,
-                    double_sided: true,
-                    ..default()
-                });
-
-                commands.spawn((
-                    PbrBundle {
-                        mesh: mesh_handle.clone(),
-                        material,
-                        transform: Transform {
-                            translation: world_pos,
-                            rotation,
-                            scale: Vec3::splat(settings.voxel_size * 2.0),
-                        },
-                        ..default()
-                    },
-                    BillboardMarker,
-                ));
-            }
+    #[test]
+    fn respawning_billboards_adds_no_meshes() {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+            .init_asset::<Mesh>()
+            .init_asset::<Image>()
+            .init_asset::<FacingBillboardMaterial>()
+            .init_resource::<VoxelRenderSettings>()
+            .init_resource::<LodSettings>()
+            .init_resource::<DirtyChunks>()
+            .init_resource::<CullingStats>()
+            .init_resource::<SystemTimings>()
+            .init_resource::<BillboardAssets>()
+            .init_resource::<BillboardMaterialCache>()
+            .init_resource::<BillboardStats>()
+            .add_systems(Startup, setup_billboard_assets)
+            .add_systems(Update, (despawn_billboards, spawn_billboards).chain());
+
+        let voxels = (0..4)
+            .map(|x| Voxel {
+                position: Vec3::new(x as f32, 0.0, 0.0),
+                color: Color::rgb(0.2, 0.5, 0.8),
+                kind: KIND_PLAIN,
+            })
+            .collect();
+        let chunk = app.world.spawn((VoxelChunk::new(IVec3::ZERO, voxels), SpatialBundle::default())).id();
+        app.update();
+        let meshes = app.world.resource::<Assets<Mesh>>().len();
+        assert_eq!(meshes, 1);
+
+        // Every frame the chunk changes, so its billboards are respawned
+        for _ in 0..5 {
+            app.world.get_mut::<VoxelChunk>(chunk).unwrap().version += 1;
+            app.world.resource_mut::<DirtyChunks>().mark(chunk);
+            app.update();
+            assert_eq!(app.world.resource::<BillboardStats>().spawned, 4);
+            assert_eq!(app.world.resource::<Assets<Mesh>>().len(), meshes);
         }
     }
-}
\ No newline at end of file
+}
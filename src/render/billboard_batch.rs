@@ -0,0 +1,411 @@
+use bevy::{
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        mesh::{Indices, MeshVertexBufferLayout, VertexAttributeValues},
+        render_resource::{
+            AsBindGroup, PrimitiveTopology, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError,
+        },
+    },
+    utils::HashMap,
+};
+
+use crate::voxel::{
+    dominant_open_direction, process_dirty_chunks, CullingStats, DirtyChunks, FaceMask, LodSettings, SystemTimings, VoxelChunk,
+};
+use crate::voxel_types::{RenderMode, Voxel, VoxelRenderSettings};
+use super::billboard::BillboardAssets;
+use super::fog::VoxelFog;
+use super::mesh_tasks::{MeshBuild, MeshBuildQueue, MeshingStats, MAX_BUILDS_IN_FLIGHT, MAX_UPLOADS_PER_FRAME};
+
+const SHADER_PATH: &str = "shaders/billboard.wgsl";
+
+pub struct BatchedBillboardPlugin;
+
+impl Plugin for BatchedBillboardPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<BillboardMaterial>::default())
+            .init_resource::<BatchedBillboardAssets>()
+            .add_systems(Update, (
+                despawn_batched_billboards,
+                queue_batched_billboards,
+                start_batched_billboard_builds,
+                upload_batched_billboards,
+            ).chain().after(process_dirty_chunks));
+    }
+}
+
+// Splat diameter relative to the voxel size. Above the face diagonal, so
+// neighboring discs overlap into a closed surface.
+const SPLAT_OVERLAP: f32 = 1.5;
+
+// Every vertex carries its voxel center; the vertex shader pushes it out to the
+// quad corner along the camera axes, so camera movement never touches the mesh.
+// Splats push it out in the plane of the voxel's normal instead.
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+#[bind_group_data(BillboardMaterialKey)]
+pub struct BillboardMaterial {
+    #[uniform(0)]
+    pub size: f32,
+    #[texture(1)]
+    #[sampler(2)]
+    pub texture: Handle<Image>,
+    #[uniform(3)]
+    pub fog: VoxelFog,
+    pub splats: bool,
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct BillboardMaterialKey {
+    splats: bool,
+}
+
+impl From<&BillboardMaterial> for BillboardMaterialKey {
+    fn from(material: &BillboardMaterial) -> Self {
+        Self { splats: material.splats }
+    }
+}
+
+impl Material for BillboardMaterial {
+    fn vertex_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if key.bind_group_data.splats {
+            descriptor.vertex.shader_defs.push("SPLATS".into());
+        }
+        let vertex_layout = layout.get_layout(&[
+            Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+            Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+            Mesh::ATTRIBUTE_COLOR.at_shader_location(2),
+            Mesh::ATTRIBUTE_NORMAL.at_shader_location(3),
+        ])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+// Batched mesh for a chunk, the child entity drawing it, and the chunk version it reflects
+#[derive(Component)]
+struct BatchedBillboards {
+    handle: Handle<Mesh>,
+    entity: Entity,
+    version: u32,
+}
+
+// Built mesh and the LOD factor it was built for
+type BatchedBillboardBuild = MeshBuild<(Mesh, u32)>;
+
+#[derive(Resource, Default)]
+struct BatchedBillboardAssets {
+    // One material per LOD factor and orientation, since the size lives in the material
+    materials: HashMap<(u32, bool), Handle<BillboardMaterial>>,
+    queue: MeshBuildQueue,
+    // Whether the current children are splats or billboards
+    splats: bool,
+}
+
+// Both modes draw the same meshes; Some(true) when drawing them as splats
+fn batched_mode(settings: &VoxelRenderSettings) -> Option<bool> {
+    match settings.render_mode {
+        _ if settings.debug_mode => None,
+        RenderMode::BatchedBillboards => Some(false),
+        RenderMode::Splats => Some(true),
+        _ => None,
+    }
+}
+
+const CORNER_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+// Voxel positions relative to the chunk entity, which sits at the chunk center
+fn billboard_centers<'a>(
+    chunk: &'a VoxelChunk,
+    voxels: &'a [Voxel],
+    voxel_size: f32,
+) -> impl Iterator<Item = [f32; 3]> + 'a {
+    let chunk_center = chunk.world_center(voxel_size);
+    voxels
+        .iter()
+        .map(move |voxel| (chunk.get_voxel_world_position(voxel, voxel_size) - chunk_center).to_array())
+}
+
+// Billboards have no surface to light, so each one is shaded as if it faced the
+// direction its voxel is most open to
+fn billboard_normals(masks: &[FaceMask]) -> impl Iterator<Item = [f32; 3]> + '_ {
+    masks.iter().map(|mask| dominant_open_direction(*mask).to_array())
+}
+
+// `voxels` and `masks` are the chunk's voxels and face masks at its current LOD
+fn build_billboard_mesh(
+    chunk: &VoxelChunk,
+    voxels: &[Voxel],
+    masks: &[FaceMask],
+    voxel_size: f32,
+    light_ambient: f32,
+) -> Mesh {
+    let voxel_count = voxels.len();
+    let mut positions = Vec::with_capacity(voxel_count * 4);
+    let mut uvs = Vec::with_capacity(voxel_count * 4);
+    let mut colors = Vec::with_capacity(voxel_count * 4);
+    let mut normals = Vec::with_capacity(voxel_count * 4);
+    let mut indices = Vec::with_capacity(voxel_count * 6);
+
+    let quads = voxels.iter().zip(billboard_centers(chunk, voxels, voxel_size)).zip(billboard_normals(masks));
+    for ((voxel, center), normal) in quads {
+        let base = positions.len() as u32;
+        let color = chunk.lit_color(voxel, light_ambient).as_linear_rgba_f32();
+        for uv in CORNER_UVS {
+            positions.push(center);
+            uvs.push(uv);
+            colors.push(color);
+            normals.push(normal);
+        }
+        indices.extend_from_slice(&[base, base + 2, base + 1, base, base + 3, base + 2]);
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+// Rewrites only the vertex colors and normals when the chunk still has the same voxels
+// in the same places. Returns false if the layout changed and the mesh needs a rebuild.
+fn patch_billboard_colors(
+    mesh: &mut Mesh,
+    chunk: &VoxelChunk,
+    voxels: &[Voxel],
+    masks: &[FaceMask],
+    voxel_size: f32,
+    light_ambient: f32,
+) -> bool {
+    let layout_matches = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => {
+            positions.len() == voxels.len() * 4
+                && positions
+                    .chunks_exact(4)
+                    .zip(billboard_centers(chunk, voxels, voxel_size))
+                    .all(|(quad, center)| quad[0] == center)
+        }
+        _ => false,
+    };
+    if !layout_matches {
+        return false;
+    }
+
+    let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute_mut(Mesh::ATTRIBUTE_COLOR) else {
+        return false;
+    };
+    for (quad, voxel) in colors.chunks_exact_mut(4).zip(voxels) {
+        quad.fill(chunk.lit_color(voxel, light_ambient).as_linear_rgba_f32());
+    }
+
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL) else {
+        return false;
+    };
+    for (quad, normal) in normals.chunks_exact_mut(4).zip(billboard_normals(masks)) {
+        quad.fill(normal);
+    }
+    true
+}
+
+fn despawn_batched_billboards(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    mut batched_assets: ResMut<BatchedBillboardAssets>,
+    batched_chunks: Query<(Entity, &BatchedBillboards)>,
+    builds: Query<Entity, With<BatchedBillboardBuild>>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    let mode = batched_mode(&settings);
+    if mode == Some(batched_assets.splats) {
+        return;
+    }
+
+    // Switching between billboards and splats swaps every material, so start over
+    batched_assets.splats = mode.unwrap_or_default();
+    batched_assets.queue.clear();
+    for entity in builds.iter() {
+        commands.entity(entity).remove::<BatchedBillboardBuild>();
+    }
+    for (chunk_entity, batched) in batched_chunks.iter() {
+        commands.entity(batched.entity).despawn_recursive();
+        commands.entity(chunk_entity).remove::<BatchedBillboards>();
+        culling_stats.entities_despawned += 1;
+    }
+}
+
+fn queue_batched_billboards(
+    settings: Res<VoxelRenderSettings>,
+    dirty: Res<DirtyChunks>,
+    new_chunks: Query<Entity, (With<VoxelChunk>, Without<BatchedBillboards>, Without<BatchedBillboardBuild>)>,
+    batched_chunks: Query<(&VoxelChunk, &BatchedBillboards)>,
+    mut batched_assets: ResMut<BatchedBillboardAssets>,
+) {
+    if batched_mode(&settings) != Some(batched_assets.splats) {
+        return;
+    }
+
+    let changed: Vec<Entity> = dirty
+        .iter()
+        .filter(|entity| {
+            batched_chunks
+                .get(*entity)
+                .is_ok_and(|(chunk, batched)| batched.version != chunk.version)
+        })
+        .chain(new_chunks.iter())
+        .collect();
+    for entity in changed {
+        batched_assets.queue.push(entity);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn start_batched_billboard_builds(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    lod: Res<LodSettings>,
+    chunks: Query<(&VoxelChunk, Option<&BatchedBillboards>)>,
+    builds: Query<(), With<BatchedBillboardBuild>>,
+    meshes: Res<Assets<Mesh>>,
+    mut batched_assets: ResMut<BatchedBillboardAssets>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    if batched_mode(&settings) != Some(batched_assets.splats) {
+        return;
+    }
+    let _timing = timings.span("billboard rebuild");
+
+    let slots = MAX_BUILDS_IN_FLIGHT.saturating_sub(builds.iter().count());
+    for entity in batched_assets.queue.take(slots) {
+        let Ok((chunk, batched)) = chunks.get(entity) else {
+            continue;
+        };
+        let factor = lod.factor(chunk.lod_level);
+        let (voxel_size, light_ambient) = (settings.voxel_size, settings.light_ambient);
+        let snapshot = chunk.clone();
+        // Patching a copy of the current mesh keeps the colors-only fast path
+        let current = batched.and_then(|batched| meshes.get(&batched.handle)).cloned();
+        commands.entity(entity).insert(BatchedBillboardBuild::spawn(chunk.version, move || {
+            chunk_span!("build_billboard_mesh", snapshot.position);
+            let voxels = snapshot.lod_voxels(factor);
+            let masks = snapshot.lod_face_masks(factor);
+            let mesh = match current {
+                Some(mut mesh)
+                    if patch_billboard_colors(&mut mesh, &snapshot, &voxels, &masks, voxel_size, light_ambient) =>
+                {
+                    mesh
+                }
+                _ => build_billboard_mesh(&snapshot, &voxels, &masks, voxel_size, light_ambient),
+            };
+            (mesh, factor)
+        }));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn upload_batched_billboards(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    mut builds: Query<(Entity, &VoxelChunk, &mut BatchedBillboardBuild, Option<&mut BatchedBillboards>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<BillboardMaterial>>,
+    billboard_assets: Res<BillboardAssets>,
+    mut batched_assets: ResMut<BatchedBillboardAssets>,
+    mut stats: ResMut<MeshingStats>,
+    mut culling_stats: ResMut<CullingStats>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    let _timing = timings.span("billboard upload");
+    stats.queued += batched_assets.queue.len();
+
+    let splats = batched_assets.splats;
+    let Some(circle_texture) = &billboard_assets.circle_texture else {
+        return;
+    };
+    let mut material_for = |factor: u32| {
+        batched_assets
+            .materials
+            .entry((factor, splats))
+            .or_insert_with(|| {
+                let scale = if splats { SPLAT_OVERLAP } else { 2.0 };
+                materials.add(BillboardMaterial {
+                    size: settings.voxel_size * scale * factor as f32,
+                    texture: circle_texture.clone(),
+                    fog: VoxelFog::from_settings(&settings),
+                    splats,
+                })
+            })
+            .clone()
+    };
+    let mut uploads = 0;
+
+    for (chunk_entity, chunk, mut build, batched) in builds.iter_mut() {
+        if !build.is_ready() {
+            stats.building += 1;
+            continue;
+        }
+        if uploads == MAX_UPLOADS_PER_FRAME {
+            stats.ready += 1;
+            continue;
+        }
+        let Some((mesh, factor)) = build.take() else {
+            continue;
+        };
+        commands.entity(chunk_entity).remove::<BatchedBillboardBuild>();
+
+        // The chunk changed while this was building and is queued again
+        if build.version != chunk.version {
+            stats.discarded += 1;
+            continue;
+        }
+        uploads += 1;
+
+        match batched {
+            Some(mut batched) => {
+                if let Some(existing) = meshes.get_mut(&batched.handle) {
+                    *existing = mesh;
+                }
+                // The LOD may have changed, which changes the billboard size
+                commands.entity(batched.entity).insert(material_for(factor));
+                batched.version = build.version;
+            }
+            None => {
+                let handle = meshes.add(mesh);
+                let child = commands
+                    .spawn(MaterialMeshBundle {
+                        mesh: handle.clone(),
+                        material: material_for(factor),
+                        ..default()
+                    })
+                    .id();
+                commands
+                    .entity(chunk_entity)
+                    .add_child(child)
+                    .insert(BatchedBillboards {
+                        handle,
+                        entity: child,
+                        version: build.version,
+                    });
+                culling_stats.entities_spawned += 1;
+            }
+        }
+        stats.meshes_rebuilt += 1;
+    }
+}
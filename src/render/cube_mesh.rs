@@ -0,0 +1,555 @@
+use bevy::{
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::texture::{ImageLoaderSettings, ImageSampler},
+    utils::HashMap,
+};
+
+use crate::bindings::{Action, ActionInput};
+use crate::voxel::{
+    process_dirty_chunks, CullingStats, DirtyChunks, LodSettings, NeighborhoodOccupancy, SystemTimings, VoxelChunk, CHUNK_SIZE,
+};
+use crate::voxel_types::{RenderMode, VoxelRenderSettings, VoxelTypeRegistry};
+use super::fog::{LodFade, VoxelFog, VoxelFogExtension, VoxelMeshMaterial};
+use super::mesh_tasks::{MeshBuild, MeshBuildQueue, MeshingStats, MAX_BUILDS_IN_FLIGHT, MAX_UPLOADS_PER_FRAME};
+use super::mesher::{build_cube_mesh, build_greedy_mesh, AmbientOcclusion, AtlasLayout, ChunkMeshes};
+
+const PLACEHOLDER_ATLAS_PATH: &str = "textures/atlas.png";
+
+pub struct CubeMeshPlugin;
+
+impl Plugin for CubeMeshPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<CubeMeshAssets>()
+            .add_systems(Startup, setup_cube_mesh_assets)
+            .add_systems(Update, (
+                toggle_texture_atlas,
+                despawn_cube_meshes,
+                queue_cube_meshes,
+                start_cube_mesh_builds,
+                upload_cube_meshes,
+                advance_lod_fades,
+            ).chain().after(process_dirty_chunks));
+    }
+}
+
+#[derive(Component)]
+struct CubeMeshMarker;
+
+// Mesh built for a chunk, the child entity drawing it, and the chunk version it reflects
+#[derive(Component)]
+pub struct ChunkMesh {
+    pub handle: Handle<Mesh>,
+    pub entity: Entity,
+    // Only chunks with translucent voxels have one
+    pub translucent: Option<TranslucentMesh>,
+    pub version: u32,
+    // Level of detail the mesh was built at
+    pub lod_level: usize,
+}
+
+// Translucent faces drawn by a second child at the chunk center, which is where
+// the transparent phase measures its sort distance from
+pub struct TranslucentMesh {
+    pub handle: Handle<Mesh>,
+    pub entity: Entity,
+}
+
+// The previous level's child, kept drawing while the new one dithers in. Each fade
+// has its own pair of materials, dropped when it ends. A chunk has at most one fade;
+// starting another ends the current one first.
+#[derive(Component)]
+struct LodCrossFade {
+    outgoing: Entity,
+    outgoing_material: Handle<VoxelMeshMaterial>,
+    incoming_material: Handle<VoxelMeshMaterial>,
+    elapsed: f32,
+}
+
+type CubeMeshBuild = MeshBuild<ChunkMeshes>;
+
+#[derive(Resource, Default)]
+struct CubeMeshAssets {
+    material: Handle<VoxelMeshMaterial>,
+    translucent_material: Handle<VoxelMeshMaterial>,
+    // Atlas the current meshes and material were built with
+    atlas: Option<Handle<Image>>,
+    queue: MeshBuildQueue,
+}
+
+fn setup_cube_mesh_assets(
+    settings: Res<VoxelRenderSettings>,
+    mut materials: ResMut<Assets<VoxelMeshMaterial>>,
+    mut cube_mesh_assets: ResMut<CubeMeshAssets>,
+) {
+    let fog = VoxelFogExtension {
+        fog: VoxelFog::from_settings(&settings),
+        lod_fade: LodFade::NONE,
+    };
+    // Vertex colors are multiplied with the white base color
+    cube_mesh_assets.material = materials.add(VoxelMeshMaterial {
+        base: StandardMaterial {
+            base_color: Color::WHITE,
+            perceptual_roughness: 0.9,
+            ..default()
+        },
+        extension: fog.clone(),
+    });
+    // Vertex alpha is blended, so only the base color stays opaque white
+    cube_mesh_assets.translucent_material = materials.add(VoxelMeshMaterial {
+        base: StandardMaterial {
+            base_color: Color::WHITE,
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.1,
+            ..default()
+        },
+        extension: fog,
+    });
+}
+
+fn toggle_texture_atlas(
+    input: ActionInput,
+    asset_server: Res<AssetServer>,
+    mut settings: ResMut<VoxelRenderSettings>,
+) {
+    if input.just_pressed(Action::ToggleAtlas) {
+        settings.atlas = match settings.atlas {
+            Some(_) => None,
+            // Nearest filtering keeps the low resolution tiles crisp
+            None => Some(asset_server.load_with_settings(
+                PLACEHOLDER_ATLAS_PATH,
+                |loader: &mut ImageLoaderSettings| loader.sampler = ImageSampler::nearest(),
+            )),
+        };
+        info!("Texture atlas: {}", if settings.atlas.is_some() { "on" } else { "off" });
+    }
+}
+
+fn despawn_cube_meshes(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    mut cube_mesh_assets: ResMut<CubeMeshAssets>,
+    meshed_chunks: Query<(Entity, &ChunkMesh, Option<&LodCrossFade>)>,
+    builds: Query<Entity, With<CubeMeshBuild>>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    // Meshes built for another atlas have the wrong UVs, so rebuild them all
+    let atlas_changed = settings.atlas != cube_mesh_assets.atlas;
+    if settings.render_mode == RenderMode::CubeMesh && !settings.debug_mode && !atlas_changed {
+        return;
+    }
+
+    cube_mesh_assets.queue.clear();
+    for entity in builds.iter() {
+        commands.entity(entity).remove::<CubeMeshBuild>();
+    }
+
+    for (chunk_entity, chunk_mesh, fade) in meshed_chunks.iter() {
+        commands.entity(chunk_mesh.entity).despawn_recursive();
+        culling_stats.entities_despawned += 1;
+        if let Some(fade) = fade {
+            commands.entity(fade.outgoing).despawn_recursive();
+            commands.entity(chunk_entity).remove::<LodCrossFade>();
+            culling_stats.entities_despawned += 1;
+        }
+        if let Some(translucent) = &chunk_mesh.translucent {
+            commands.entity(translucent.entity).despawn_recursive();
+            culling_stats.entities_despawned += 1;
+        }
+        commands.entity(chunk_entity).remove::<ChunkMesh>();
+    }
+}
+
+// Greedy quads span several tiles, which one atlas rectangle can't express,
+// so textured chunks always use one quad per face
+fn build_mesh(
+    chunk: &VoxelChunk,
+    voxel_size: f32,
+    greedy: bool,
+    atlas: Option<&AtlasLayout>,
+    ao: Option<&AmbientOcclusion>,
+    light_ambient: f32,
+) -> ChunkMeshes {
+    if greedy && atlas.is_none() {
+        build_greedy_mesh(chunk, voxel_size, ao, Some(light_ambient))
+    } else {
+        build_cube_mesh(chunk, voxel_size, atlas, ao, Some(light_ambient))
+    }
+}
+
+// AO reads occupancy across chunk borders, so it needs the loaded neighbors
+fn ambient_occlusion(
+    chunk: &VoxelChunk,
+    settings: &VoxelRenderSettings,
+    chunks_by_position: &HashMap<IVec3, (Entity, &VoxelChunk)>,
+) -> Option<AmbientOcclusion> {
+    (settings.ao_strength > 0.0).then(|| AmbientOcclusion {
+        neighborhood: NeighborhoodOccupancy::gather(&chunk.occupancy, |offset| {
+            chunks_by_position
+                .get(&(chunk.position + offset))
+                .map(|(_, neighbor)| &neighbor.occupancy)
+        }),
+        strength: settings.ao_strength,
+    })
+}
+
+// Spawns, updates or removes the translucent child to match the rebuilt meshes
+fn apply_translucent_mesh(
+    commands: &mut Commands,
+    chunk_entity: Entity,
+    current: &mut Option<TranslucentMesh>,
+    mesh: Option<Mesh>,
+    meshes: &mut Assets<Mesh>,
+    material: &Handle<VoxelMeshMaterial>,
+    culling_stats: &mut CullingStats,
+) {
+    match (current.as_ref(), mesh) {
+        (Some(existing), Some(mesh)) => {
+            if let Some(existing) = meshes.get_mut(&existing.handle) {
+                *existing = mesh;
+            }
+        }
+        (None, Some(mesh)) => {
+            let handle = meshes.add(mesh);
+            let entity = commands
+                .spawn((
+                    MaterialMeshBundle {
+                        mesh: handle.clone(),
+                        material: material.clone(),
+                        ..default()
+                    },
+                    CubeMeshMarker,
+                    // Water and glass would cast solid shadows
+                    NotShadowCaster,
+                ))
+                .id();
+            commands.entity(chunk_entity).add_child(entity);
+            *current = Some(TranslucentMesh { handle, entity });
+            culling_stats.entities_spawned += 1;
+        }
+        (Some(existing), None) => {
+            commands.entity(existing.entity).despawn_recursive();
+            *current = None;
+            culling_stats.entities_despawned += 1;
+        }
+        (None, None) => {}
+    }
+}
+
+// Copies of the shared opaque material for the two sides of a cross-fade
+fn lod_fade_materials(
+    materials: &mut Assets<VoxelMeshMaterial>,
+    shared: &Handle<VoxelMeshMaterial>,
+) -> Option<(Handle<VoxelMeshMaterial>, Handle<VoxelMeshMaterial>)> {
+    let base = materials.get(shared)?.clone();
+    let mut with_fade = |outgoing| {
+        let mut material = base.clone();
+        material.extension.lod_fade = LodFade { progress: 0.0, outgoing };
+        materials.add(material)
+    };
+    Some((with_fade(1), with_fade(0)))
+}
+
+fn atlas_layout(
+    settings: &VoxelRenderSettings,
+    images: &Assets<Image>,
+    registry: &VoxelTypeRegistry,
+) -> Option<AtlasLayout> {
+    let image = images.get(settings.atlas.as_ref()?)?;
+    let size = image.size();
+    let tile_size = settings.atlas_tile_size.max(1);
+    Some(AtlasLayout {
+        registry: registry.clone(),
+        columns: (size.x as u32 / tile_size).max(1),
+        rows: (size.y as u32 / tile_size).max(1),
+        inset: Vec2::new(0.5 / size.x, 0.5 / size.y),
+    })
+}
+
+// Queues new chunks, changed chunks, and the neighbors whose border AO they affect
+fn queue_cube_meshes(
+    settings: Res<VoxelRenderSettings>,
+    dirty: Res<DirtyChunks>,
+    all_chunks: Query<(Entity, &VoxelChunk)>,
+    new_chunks: Query<Entity, (With<VoxelChunk>, Without<ChunkMesh>, Without<CubeMeshBuild>)>,
+    meshed_chunks: Query<(&VoxelChunk, &ChunkMesh)>,
+    mut cube_mesh_assets: ResMut<CubeMeshAssets>,
+) {
+    if settings.render_mode != RenderMode::CubeMesh || settings.debug_mode {
+        return;
+    }
+
+    // Only chunks in the dirty queue can have a newer version than their mesh
+    let mut changed: Vec<Entity> = dirty
+        .iter()
+        .filter(|entity| {
+            meshed_chunks
+                .get(*entity)
+                .is_ok_and(|(chunk, chunk_mesh)| chunk_mesh.version != chunk.version)
+        })
+        .collect();
+    changed.extend(new_chunks.iter().filter(|entity| !cube_mesh_assets.queue.contains(*entity)));
+    if changed.is_empty() {
+        return;
+    }
+
+    // Border AO of existing meshes changes when a neighbor arrives or is edited
+    if settings.ao_strength > 0.0 {
+        let chunks_by_position: HashMap<IVec3, Entity> =
+            all_chunks.iter().map(|(entity, chunk)| (chunk.position, entity)).collect();
+        let positions: Vec<IVec3> = changed
+            .iter()
+            .filter_map(|entity| all_chunks.get(*entity).ok())
+            .map(|(_, chunk)| chunk.position)
+            .collect();
+        for position in positions {
+            for x in -1..=1 {
+                for y in -1..=1 {
+                    for z in -1..=1 {
+                        if let Some(neighbor) = chunks_by_position.get(&(position + IVec3::new(x, y, z))) {
+                            if meshed_chunks.contains(*neighbor) {
+                                changed.push(*neighbor);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    for entity in changed {
+        cube_mesh_assets.queue.push(entity);
+    }
+}
+
+// Starts builds for queued chunks on snapshots of their data. A chunk that is
+// queued again while building gets a fresh build, which cancels the old one.
+#[allow(clippy::too_many_arguments)]
+fn start_cube_mesh_builds(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    registry: Res<VoxelTypeRegistry>,
+    chunks: Query<(Entity, &VoxelChunk)>,
+    builds: Query<(), With<CubeMeshBuild>>,
+    images: Res<Assets<Image>>,
+    mut materials: ResMut<Assets<VoxelMeshMaterial>>,
+    mut cube_mesh_assets: ResMut<CubeMeshAssets>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    if settings.render_mode != RenderMode::CubeMesh || settings.debug_mode {
+        return;
+    }
+    let _timing = timings.span("mesh build");
+
+    let atlas = atlas_layout(&settings, &images, &registry);
+    // Wait for the atlas to load rather than building meshes without UVs for it
+    if settings.atlas.is_some() && atlas.is_none() {
+        return;
+    }
+    if settings.atlas != cube_mesh_assets.atlas {
+        for handle in [&cube_mesh_assets.material, &cube_mesh_assets.translucent_material] {
+            if let Some(material) = materials.get_mut(handle) {
+                material.base.base_color_texture = settings.atlas.clone();
+            }
+        }
+        cube_mesh_assets.atlas = settings.atlas.clone();
+    }
+
+    let slots = MAX_BUILDS_IN_FLIGHT.saturating_sub(builds.iter().count());
+    if slots == 0 || cube_mesh_assets.queue.is_empty() {
+        return;
+    }
+
+    let chunks_by_position: HashMap<IVec3, (Entity, &VoxelChunk)> = if settings.ao_strength > 0.0 {
+        chunks.iter().map(|(entity, chunk)| (chunk.position, (entity, chunk))).collect()
+    } else {
+        HashMap::default()
+    };
+
+    for entity in cube_mesh_assets.queue.take(slots) {
+        let Ok((_, chunk)) = chunks.get(entity) else {
+            continue;
+        };
+        let snapshot = chunk.clone();
+        let ao = ambient_occlusion(chunk, &settings, &chunks_by_position);
+        let atlas = atlas.clone();
+        let (voxel_size, greedy) = (settings.voxel_size, settings.greedy_meshing);
+        let light_ambient = settings.light_ambient;
+        commands.entity(entity).insert(CubeMeshBuild::spawn(chunk.version, move || {
+            chunk_span!("build_cube_mesh", snapshot.position);
+            build_mesh(&snapshot, voxel_size, greedy, atlas.as_ref(), ao.as_ref(), light_ambient)
+        }));
+    }
+}
+
+// Swaps finished builds into the chunk meshes, spawning the children on first upload.
+// A visible chunk changing level gets a new child that cross-fades with the old one.
+#[allow(clippy::too_many_arguments)]
+fn upload_cube_meshes(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    lod: Res<LodSettings>,
+    mut builds: Query<(Entity, &VoxelChunk, &mut CubeMeshBuild, Option<&mut ChunkMesh>, Option<&LodCrossFade>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<VoxelMeshMaterial>>,
+    cube_mesh_assets: Res<CubeMeshAssets>,
+    mut stats: ResMut<MeshingStats>,
+    mut culling_stats: ResMut<CullingStats>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    let _timing = timings.span("mesh upload");
+    stats.queued += cube_mesh_assets.queue.len();
+
+    // Chunk entities sit at their center, mesh vertices start at the chunk corner
+    let corner_offset = Vec3::splat(-(CHUNK_SIZE as f32) * settings.voxel_size / 2.0);
+    let mut uploads = 0;
+
+    for (chunk_entity, chunk, mut build, chunk_mesh, fade) in builds.iter_mut() {
+        if !build.is_ready() {
+            stats.building += 1;
+            continue;
+        }
+        if uploads == MAX_UPLOADS_PER_FRAME {
+            stats.ready += 1;
+            continue;
+        }
+        let Some(built) = build.take() else {
+            continue;
+        };
+        commands.entity(chunk_entity).remove::<CubeMeshBuild>();
+
+        // The chunk changed while this was building and is queued again
+        if build.version != chunk.version {
+            stats.discarded += 1;
+            continue;
+        }
+        uploads += 1;
+
+        match chunk_mesh {
+            Some(mut chunk_mesh) => {
+                let fade_materials = if chunk_mesh.lod_level != chunk.lod_level
+                    && lod.fade_duration > 0.0
+                    && chunk.cull_reason.is_none()
+                {
+                    lod_fade_materials(&mut materials, &cube_mesh_assets.material)
+                } else {
+                    None
+                };
+                match fade_materials {
+                    Some((outgoing_material, incoming_material)) => {
+                        // A fade still running ends here, so only one old child is ever kept
+                        if let Some(fade) = fade {
+                            commands.entity(fade.outgoing).despawn_recursive();
+                            culling_stats.entities_despawned += 1;
+                        }
+                        commands.entity(chunk_mesh.entity).insert(outgoing_material.clone());
+                        let handle = meshes.add(built.opaque);
+                        let child = commands
+                            .spawn((
+                                MaterialMeshBundle {
+                                    mesh: handle.clone(),
+                                    material: incoming_material.clone(),
+                                    transform: Transform::from_translation(corner_offset),
+                                    ..default()
+                                },
+                                CubeMeshMarker,
+                            ))
+                            .id();
+                        commands.entity(chunk_entity).add_child(child).insert(LodCrossFade {
+                            outgoing: chunk_mesh.entity,
+                            outgoing_material,
+                            incoming_material,
+                            elapsed: 0.0,
+                        });
+                        chunk_mesh.handle = handle;
+                        chunk_mesh.entity = child;
+                        culling_stats.entities_spawned += 1;
+                    }
+                    None => {
+                        // Overwrite the existing asset so the child keeps its handle
+                        if let Some(existing) = meshes.get_mut(&chunk_mesh.handle) {
+                            *existing = built.opaque;
+                        }
+                    }
+                }
+                apply_translucent_mesh(
+                    &mut commands,
+                    chunk_entity,
+                    &mut chunk_mesh.translucent,
+                    built.translucent,
+                    &mut meshes,
+                    &cube_mesh_assets.translucent_material,
+                    &mut culling_stats,
+                );
+                chunk_mesh.version = build.version;
+                chunk_mesh.lod_level = chunk.lod_level;
+            }
+            None => {
+                let handle = meshes.add(built.opaque);
+                let mut translucent = None;
+                apply_translucent_mesh(
+                    &mut commands,
+                    chunk_entity,
+                    &mut translucent,
+                    built.translucent,
+                    &mut meshes,
+                    &cube_mesh_assets.translucent_material,
+                    &mut culling_stats,
+                );
+                let child = commands
+                    .spawn((
+                        MaterialMeshBundle {
+                            mesh: handle.clone(),
+                            material: cube_mesh_assets.material.clone(),
+                            transform: Transform::from_translation(corner_offset),
+                            ..default()
+                        },
+                        CubeMeshMarker,
+                    ))
+                    .id();
+                commands
+                    .entity(chunk_entity)
+                    .add_child(child)
+                    .insert(ChunkMesh {
+                        handle,
+                        entity: child,
+                        translucent,
+                        version: build.version,
+                        lod_level: chunk.lod_level,
+                    });
+                culling_stats.entities_spawned += 1;
+            }
+        }
+        stats.meshes_rebuilt += 1;
+    }
+}
+
+// Moves cross-fades along, then drops the old child and hands the new one back the
+// shared material once they finish
+fn advance_lod_fades(
+    mut commands: Commands,
+    time: Res<Time>,
+    lod: Res<LodSettings>,
+    mut fades: Query<(Entity, &ChunkMesh, &mut LodCrossFade)>,
+    mut materials: ResMut<Assets<VoxelMeshMaterial>>,
+    cube_mesh_assets: Res<CubeMeshAssets>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    for (chunk_entity, chunk_mesh, mut fade) in fades.iter_mut() {
+        fade.elapsed += time.delta_seconds();
+        let progress = if lod.fade_duration > 0.0 { fade.elapsed / lod.fade_duration } else { 1.0 };
+
+        if progress >= 1.0 {
+            commands.entity(fade.outgoing).despawn_recursive();
+            commands.entity(chunk_mesh.entity).insert(cube_mesh_assets.material.clone());
+            commands.entity(chunk_entity).remove::<LodCrossFade>();
+            culling_stats.entities_despawned += 1;
+            continue;
+        }
+        for handle in [&fade.outgoing_material, &fade.incoming_material] {
+            if let Some(material) = materials.get_mut(handle) {
+                material.extension.lod_fade.progress = progress;
+            }
+        }
+    }
+}
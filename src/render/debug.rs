@@ -0,0 +1,183 @@
+use bevy::{
+    pbr::wireframe::{Wireframe, WireframePlugin},
+    prelude::*,
+    render::{renderer::RenderDevice, settings::WgpuFeatures},
+};
+
+use crate::bindings::{Action, ActionInput};
+use crate::triggers::TriggerRegion;
+use crate::voxel::{process_dirty_chunks, CameraMotion, CullReason, FrozenView, VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::VoxelRenderSettings;
+use super::cube_mesh::ChunkMesh;
+
+pub struct DebugRenderPlugin;
+
+impl Plugin for DebugRenderPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(WireframePlugin)
+            // Culling toggles take effect in the same frame
+            .add_systems(Update, toggle_debug_rendering.before(process_dirty_chunks))
+            .add_systems(Update, (
+                draw_chunk_bounds,
+                draw_trigger_regions,
+                draw_frozen_view,
+                sync_wireframes,
+            ).chain());
+    }
+}
+
+// Wireframes draw with line polygon mode. The default wgpu settings request it
+// wherever the adapter has it; GL, WebGL2 and some mobile adapters don't.
+fn wireframe_supported(device: Option<&RenderDevice>) -> bool {
+    device.is_some_and(|device| device.features().contains(WgpuFeatures::POLYGON_MODE_LINE))
+}
+
+fn toggle_debug_rendering(
+    input: ActionInput,
+    mut settings: ResMut<VoxelRenderSettings>,
+    motion: Res<CameraMotion>,
+    mut frozen: ResMut<FrozenView>,
+    device: Option<Res<RenderDevice>>,
+) {
+    if input.just_pressed(Action::ToggleChunkBounds) {
+        settings.show_chunk_bounds = !settings.show_chunk_bounds;
+        info!("Chunk bounds: {}", if settings.show_chunk_bounds { "on" } else { "off" });
+    }
+    if input.just_pressed(Action::ToggleWireframe) {
+        if wireframe_supported(device.as_deref()) {
+            settings.wireframe = !settings.wireframe;
+            info!("Wireframe: {}", if settings.wireframe { "on" } else { "off" });
+        } else {
+            warn!("Wireframe is unavailable: the graphics adapter has no line polygon mode");
+        }
+    }
+
+    if !input.pressed(Action::DebugModifier) {
+        return;
+    }
+    if input.just_pressed(Action::ToggleFrozenView) {
+        // The last view culling used is the live one while nothing is frozen
+        frozen.view = if frozen.view.is_some() { None } else { motion.view() };
+        info!("Frozen view: {}", if frozen.view.is_some() { "on" } else { "off" });
+        return;
+    }
+    let (name, enabled) = if input.just_pressed(Action::ToggleFrustumCulling) {
+        ("Frustum culling", &mut settings.frustum_culling)
+    } else if input.just_pressed(Action::ToggleDistanceCulling) {
+        ("Distance culling", &mut settings.distance_culling)
+    } else if input.just_pressed(Action::ToggleVoxelOcclusion) {
+        ("Voxel occlusion", &mut settings.voxel_occlusion)
+    } else if input.just_pressed(Action::ToggleChunkOcclusion) {
+        ("Chunk occlusion", &mut settings.chunk_occlusion)
+    } else {
+        return;
+    };
+    *enabled = !*enabled;
+    info!("{}: {}", name, if *enabled { "on" } else { "off" });
+}
+
+// Green is visible, red frustum culled, yellow beyond render distance, gray occluded,
+// blue a reduced LOD, purple an impostor
+fn chunk_bounds_color(chunk: &VoxelChunk) -> Color {
+    match chunk.cull_reason {
+        Some(CullReason::Distance) => Color::YELLOW,
+        Some(CullReason::Frustum) => Color::RED,
+        Some(CullReason::Occluded) => Color::GRAY,
+        Some(CullReason::Impostor) => Color::PURPLE,
+        None if chunk.lod_level > 0 => Color::BLUE,
+        None => Color::GREEN,
+    }
+}
+
+fn draw_chunk_bounds(
+    mut gizmos: Gizmos,
+    settings: Res<VoxelRenderSettings>,
+    chunks: Query<&VoxelChunk>,
+) {
+    if !settings.show_chunk_bounds {
+        return;
+    }
+
+    let extent = Vec3::splat(CHUNK_SIZE as f32 * settings.voxel_size);
+    for chunk in chunks.iter() {
+        let transform = Transform::from_translation(chunk.world_center(settings.voxel_size)).with_scale(extent);
+        gizmos.cuboid(transform, chunk_bounds_color(chunk));
+    }
+}
+
+// Shown along with chunk bounds
+fn draw_trigger_regions(
+    mut gizmos: Gizmos,
+    settings: Res<VoxelRenderSettings>,
+    regions: Query<&TriggerRegion>,
+) {
+    if !settings.show_chunk_bounds {
+        return;
+    }
+
+    for region in regions.iter() {
+        let size = Vec3::from(region.aabb.half_extents) * 2.0;
+        let transform = Transform::from_translation(region.aabb.center.into()).with_scale(size);
+        gizmos.cuboid(transform, Color::ORANGE);
+    }
+}
+
+// Outlines the frozen frustum. Bevy's perspective has no far plane, so its far
+// corners come from pushing the near ones out along their rays to the render
+// distance; an orthographic view has a real far plane to unproject.
+fn draw_frozen_view(
+    mut gizmos: Gizmos,
+    settings: Res<VoxelRenderSettings>,
+    frozen: Res<FrozenView>,
+) {
+    let Some(view) = frozen.view else {
+        return;
+    };
+    let inverse = view.view_projection.inverse();
+    // Reversed depth puts the near plane at NDC z = 1 and the far plane at 0
+    let unproject = |x: f32, y: f32, z: f32| inverse.project_point3(Vec3::new(x, y, z));
+    let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+    let near = corners.map(|(x, y)| unproject(x, y, 1.0));
+    let far = if view.orthographic {
+        corners.map(|(x, y)| unproject(x, y, 0.0))
+    } else {
+        let near_distance = (unproject(0.0, 0.0, 1.0) - view.translation).length();
+        if near_distance <= f32::EPSILON {
+            return;
+        }
+        let scale = settings.render_distance / near_distance;
+        near.map(|corner| view.translation + (corner - view.translation) * scale)
+    };
+
+    for i in 0..4 {
+        let next = (i + 1) % 4;
+        gizmos.line(near[i], near[next], Color::CYAN);
+        gizmos.line(far[i], far[next], Color::CYAN);
+        gizmos.line(near[i], far[i], Color::CYAN);
+    }
+}
+
+// Adds or removes the Wireframe marker on every cube mesh child to match the setting.
+// Never added where the adapter can't draw them, whatever the setting says.
+fn sync_wireframes(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    device: Option<Res<RenderDevice>>,
+    meshed_chunks: Query<&ChunkMesh>,
+    wireframes: Query<Has<Wireframe>>,
+) {
+    let wireframe = settings.wireframe && wireframe_supported(device.as_deref());
+    for chunk_mesh in meshed_chunks.iter() {
+        let translucent = chunk_mesh.translucent.as_ref().map(|translucent| translucent.entity);
+        for entity in std::iter::once(chunk_mesh.entity).chain(translucent) {
+            let Ok(has_wireframe) = wireframes.get(entity) else {
+                continue;
+            };
+            if wireframe && !has_wireframe {
+                commands.entity(entity).insert(Wireframe);
+            } else if !wireframe && has_wireframe {
+                commands.entity(entity).remove::<Wireframe>();
+            }
+        }
+    }
+}
@@ -0,0 +1,118 @@
+use bevy::{
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    reflect::TypePath,
+    render::render_resource::{AsBindGroup, ShaderRef, ShaderType},
+};
+
+use crate::voxel_types::VoxelRenderSettings;
+use super::billboard::FacingBillboardMaterial;
+use super::billboard_batch::BillboardMaterial;
+
+// Defines the `voxel_fog` import used by every voxel shader
+const FOG_SHADER_PATH: &str = "shaders/voxel_fog.wgsl";
+const MESH_SHADER_PATH: &str = "shaders/voxel_mesh.wgsl";
+
+pub type VoxelMeshMaterial = ExtendedMaterial<StandardMaterial, VoxelFogExtension>;
+
+pub struct VoxelFogPlugin;
+
+impl Plugin for VoxelFogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<VoxelMeshMaterial>::default())
+            .init_resource::<VoxelFogAssets>()
+            .add_systems(Startup, load_fog_shader)
+            .add_systems(PostUpdate, sync_fog_uniforms);
+    }
+}
+
+// Fog and fade distances in world units, mirrored from VoxelRenderSettings
+#[derive(ShaderType, Clone, Copy, Debug, Default, PartialEq)]
+pub struct VoxelFog {
+    pub color: Vec4,
+    pub fog_start: f32,
+    pub fog_end: f32,
+    pub fade_start: f32,
+    pub fade_end: f32,
+}
+
+impl VoxelFog {
+    pub fn from_settings(settings: &VoxelRenderSettings) -> Self {
+        let distance = settings.render_distance;
+        Self {
+            color: Vec4::from_array(settings.fog_color.as_linear_rgba_f32()),
+            fog_start: distance * settings.fog_start,
+            fog_end: distance,
+            fade_start: distance * (1.0 - settings.fade_band),
+            fade_end: distance,
+        }
+    }
+}
+
+// Dithered cross-fade between the meshes of a chunk's old and new level of detail
+#[derive(ShaderType, Clone, Copy, Debug, PartialEq)]
+pub struct LodFade {
+    // Fraction of the way from the outgoing mesh to the incoming one
+    pub progress: f32,
+    // Nonzero on the outgoing mesh, which draws the pixels the incoming one skips
+    pub outgoing: u32,
+}
+
+impl LodFade {
+    pub const NONE: Self = Self {
+        progress: 1.0,
+        outgoing: 0,
+    };
+}
+
+// Adds fog, the distance fade and the LOD cross-fade to StandardMaterial for the
+// mesh render paths
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct VoxelFogExtension {
+    #[uniform(100)]
+    pub fog: VoxelFog,
+    #[uniform(101)]
+    pub lod_fade: LodFade,
+}
+
+impl MaterialExtension for VoxelFogExtension {
+    fn fragment_shader() -> ShaderRef {
+        MESH_SHADER_PATH.into()
+    }
+}
+
+#[derive(Resource, Default)]
+struct VoxelFogAssets {
+    // Held so the import stays loaded
+    shader: Handle<Shader>,
+}
+
+fn load_fog_shader(asset_server: Res<AssetServer>, mut fog_assets: ResMut<VoxelFogAssets>) {
+    fog_assets.shader = asset_server.load(FOG_SHADER_PATH);
+}
+
+// Every voxel material carries its own copy of the fog uniform, so push changed
+// settings into all of them
+fn sync_fog_uniforms(
+    settings: Res<VoxelRenderSettings>,
+    mut last_fog: Local<Option<VoxelFog>>,
+    mut mesh_materials: ResMut<Assets<VoxelMeshMaterial>>,
+    mut billboard_materials: ResMut<Assets<FacingBillboardMaterial>>,
+    mut batched_materials: ResMut<Assets<BillboardMaterial>>,
+) {
+    let fog = VoxelFog::from_settings(&settings);
+    if *last_fog == Some(fog) {
+        return;
+    }
+    *last_fog = Some(fog);
+
+    for (_, material) in mesh_materials.iter_mut() {
+        material.extension.fog = fog;
+    }
+    for (_, material) in billboard_materials.iter_mut() {
+        material.fog = fog;
+    }
+    for (_, material) in batched_materials.iter_mut() {
+        material.fog = fog;
+    }
+}
@@ -0,0 +1,247 @@
+// src/render/gltf_export.rs
+use std::fmt;
+use std::path::Path;
+use crate::voxel::{VoxelWorld, WorldRegion};
+use super::region_mesh::{mesh_region, Primitive};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_JSON: u32 = 0x4E4F_534A;
+const CHUNK_BIN: u32 = 0x004E_4942;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const OPAQUE_MATERIAL: usize = 0;
+const TRANSLUCENT_MATERIAL: usize = 1;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Io(std::io::Error),
+    // No loaded voxels inside the region
+    Empty,
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GltfError::Io(err) => write!(f, "could not write .glb file: {}", err),
+            GltfError::Empty => write!(f, "no voxels to export"),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+// Accessors and buffer views over one binary buffer, written out as JSON
+#[derive(Default)]
+struct GlbBuilder {
+    bin: Vec<u8>,
+    views: Vec<String>,
+    accessors: Vec<String>,
+}
+
+impl GlbBuilder {
+    fn push_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        self.views.push(format!(
+            r#"{{"buffer":0,"byteOffset":{},"byteLength":{},"target":{}}}"#,
+            self.bin.len(),
+            bytes.len(),
+            target,
+        ));
+        self.bin.extend_from_slice(bytes);
+        self.views.len() - 1
+    }
+
+    fn push_floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str, bounds: bool) -> usize {
+        let bytes: Vec<u8> = values.iter().flatten().flat_map(|value| value.to_le_bytes()).collect();
+        let view = self.push_view(&bytes, TARGET_ARRAY_BUFFER);
+        // POSITION accessors must carry their bounds
+        let bounds = if bounds {
+            let min: [f32; N] = std::array::from_fn(|i| values.iter().map(|value| value[i]).fold(f32::INFINITY, f32::min));
+            let max: [f32; N] = std::array::from_fn(|i| values.iter().map(|value| value[i]).fold(f32::NEG_INFINITY, f32::max));
+            format!(r#","min":{},"max":{}"#, json_floats(&min), json_floats(&max))
+        } else {
+            String::new()
+        };
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"{}"{}}}"#,
+            view,
+            COMPONENT_FLOAT,
+            values.len(),
+            kind,
+            bounds,
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let bytes: Vec<u8> = indices.iter().flat_map(|index| index.to_le_bytes()).collect();
+        let view = self.push_view(&bytes, TARGET_ELEMENT_ARRAY_BUFFER);
+        self.accessors.push(format!(
+            r#"{{"bufferView":{},"componentType":{},"count":{},"type":"SCALAR"}}"#,
+            view,
+            COMPONENT_UNSIGNED_INT,
+            indices.len(),
+        ));
+        self.accessors.len() - 1
+    }
+
+    fn push_primitive(&mut self, primitive: &Primitive) -> String {
+        let position = self.push_floats(&primitive.positions, "VEC3", true);
+        let normal = self.push_floats(&primitive.normals, "VEC3", false);
+        let color = self.push_floats(&primitive.colors, "VEC4", false);
+        let indices = self.push_indices(&primitive.indices);
+        let material = if primitive.translucent { TRANSLUCENT_MATERIAL } else { OPAQUE_MATERIAL };
+        format!(
+            r#"{{"attributes":{{"POSITION":{},"NORMAL":{},"COLOR_0":{}}},"indices":{},"material":{}}}"#,
+            position, normal, color, indices, material,
+        )
+    }
+}
+
+fn json_floats(values: &[f32]) -> String {
+    let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+    format!("[{}]", values.join(","))
+}
+
+// Greedy meshes the voxels inside a region, whatever the render mode, and writes
+// them as a binary glTF. Each chunk is a node placed relative to the region's min
+// corner, with vertex colors in COLOR_0. Translucent voxels get their own
+// primitive with a blended material.
+pub fn export_gltf(world: &VoxelWorld, region: WorldRegion, path: &Path) -> Result<(), GltfError> {
+    let mut builder = GlbBuilder::default();
+    let mut nodes = Vec::new();
+    let mut meshes = Vec::new();
+    let found = mesh_region(world, region, |chunk| {
+        let primitives: Vec<String> = chunk.primitives.iter().map(|primitive| builder.push_primitive(primitive)).collect();
+        meshes.push(format!(r#"{{"primitives":[{}]}}"#, primitives.join(",")));
+        nodes.push(format!(
+            r#"{{"name":"chunk {} {} {}","mesh":{},"translation":{}}}"#,
+            chunk.position.x,
+            chunk.position.y,
+            chunk.position.z,
+            meshes.len() - 1,
+            json_floats(&chunk.translation.to_array()),
+        ));
+    });
+    if !found {
+        return Err(GltfError::Empty);
+    }
+
+    let node_indices: Vec<String> = (0..nodes.len()).map(|index| index.to_string()).collect();
+    let json = format!(
+        concat!(
+            r#"{{"asset":{{"version":"2.0","generator":"bevy_voxel"}},"scene":0,"scenes":[{{"nodes":[{}]}}],"#,
+            r#""nodes":[{}],"meshes":[{}],"materials":[{},{}],"accessors":[{}],"bufferViews":[{}],"buffers":[{{"byteLength":{}}}]}}"#,
+        ),
+        node_indices.join(","),
+        nodes.join(","),
+        meshes.join(","),
+        r#"{"name":"Opaque","pbrMetallicRoughness":{"baseColorFactor":[1,1,1,1],"metallicFactor":0,"roughnessFactor":1}}"#,
+        r#"{"name":"Translucent","alphaMode":"BLEND","pbrMetallicRoughness":{"baseColorFactor":[1,1,1,1],"metallicFactor":0,"roughnessFactor":1}}"#,
+        builder.accessors.join(","),
+        builder.views.join(","),
+        builder.bin.len(),
+    );
+
+    // Both chunks are padded to 4 bytes, JSON with spaces and binary with zeros
+    let mut json = json.into_bytes();
+    json.resize(json.len().next_multiple_of(4), b' ');
+    let mut bin = builder.bin;
+    bin.resize(bin.len().next_multiple_of(4), 0);
+    let total = 12 + 8 + json.len() + 8 + bin.len();
+    let mut file = Vec::with_capacity(total);
+    for word in [GLB_MAGIC, GLB_VERSION, total as u32, json.len() as u32, CHUNK_JSON] {
+        file.extend_from_slice(&word.to_le_bytes());
+    }
+    file.extend_from_slice(&json);
+    for word in [bin.len() as u32, CHUNK_BIN] {
+        file.extend_from_slice(&word.to_le_bytes());
+    }
+    file.extend_from_slice(&bin);
+    std::fs::write(path, file).map_err(GltfError::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{
+        asset::LoadState,
+        ecs::system::SystemState,
+        gltf::{Gltf, GltfMesh, GltfPlugin},
+        prelude::*,
+    };
+    use crate::voxel::{test_world, VoxelChunk};
+    use crate::voxel_types::Voxel;
+
+    fn voxel(x: f32, color: Color) -> Voxel {
+        Voxel {
+            position: Vec3::new(x, 0.0, 0.0),
+            color,
+            kind: 1,
+        }
+    }
+
+    // Exports two stone voxels and a water voxel, then loads the file the way a
+    // game would, through the asset server and Bevy's glTF loader
+    #[test]
+    fn exported_glb_loads_with_colors_and_blended_water() {
+        let stone = Color::rgb(0.5, 0.5, 0.5);
+        let water = Color::rgba(0.2, 0.4, 0.8, 0.6);
+        let mut world = test_world(vec![VoxelChunk::new(
+            IVec3::ZERO,
+            vec![voxel(0.0, stone), voxel(1.0, stone), voxel(3.0, water)],
+        )]);
+        let directory = std::env::temp_dir().join(format!("worldvox-gltf-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        export_gltf(&state.get(&world), WorldRegion::chunk(IVec3::ZERO), &directory.join("export.glb")).unwrap();
+
+        let mut app = App::new();
+        app.add_plugins((
+            MinimalPlugins,
+            AssetPlugin {
+                file_path: directory.to_string_lossy().into_owned(),
+                ..default()
+            },
+        ))
+        .init_asset::<Mesh>()
+        .init_asset::<StandardMaterial>()
+        .init_asset::<Scene>()
+        .add_plugins(GltfPlugin::default());
+        app.finish();
+        app.cleanup();
+
+        let handle: Handle<Gltf> = app.world.resource::<AssetServer>().load("export.glb");
+        for _ in 0..500 {
+            app.update();
+            match app.world.resource::<AssetServer>().load_state(handle.id()) {
+                LoadState::Loaded => break,
+                LoadState::Failed => panic!("the exported .glb failed to load"),
+                _ => std::thread::sleep(std::time::Duration::from_millis(5)),
+            }
+        }
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let gltf = app.world.resource::<Assets<Gltf>>().get(&handle).expect("the exported .glb never loaded");
+        assert_eq!(gltf.meshes.len(), 1);
+        let gltf_mesh = app.world.resource::<Assets<GltfMesh>>().get(&gltf.meshes[0]).unwrap();
+        assert_eq!(gltf_mesh.primitives.len(), 2);
+
+        let meshes = app.world.resource::<Assets<Mesh>>();
+        let materials = app.world.resource::<Assets<StandardMaterial>>();
+        let mut blended = 0;
+        for primitive in &gltf_mesh.primitives {
+            let mesh = meshes.get(&primitive.mesh).unwrap();
+            assert!(mesh.attribute(Mesh::ATTRIBUTE_COLOR).is_some(), "COLOR_0 was not loaded");
+            // Both the stone bar and the lone water voxel are a greedy box of six quads
+            assert_eq!(mesh.indices().map(|indices| indices.len()), Some(36));
+            let material = materials.get(primitive.material.as_ref().unwrap()).unwrap();
+            if material.alpha_mode == AlphaMode::Blend {
+                blended += 1;
+            }
+        }
+        assert_eq!(blended, 1, "only the water primitive is blended");
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::prelude::*;
+
+use crate::voxel::{LocalPos, CHUNK_SIZE, FACE_NEIGHBORS};
+use crate::voxel_types::VoxelRenderSettings;
+
+// Grows outlines past the voxel so they don't z-fight with its faces
+const OUTLINE_INFLATE: f32 = 1.02;
+const OUTLINE_COLOR: Color = Color::WHITE;
+const FACE_COLOR: Color = Color::YELLOW;
+const REGION_COLOR: Color = Color::rgb(0.3, 0.6, 1.0);
+const REGION_FILL_COLOR: Color = Color::rgba(0.3, 0.6, 1.0, 0.2);
+
+pub struct HighlightPlugin;
+
+impl Plugin for HighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup_region_fill)
+            .add_systems(Update, (
+                draw_voxel_highlight,
+                draw_region_highlight,
+            ));
+    }
+}
+
+// Voxel under the cursor, set by picking. Remove the resource to clear the highlight.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct HighlightedVoxel {
+    pub chunk: IVec3,
+    pub local: LocalPos,
+    // Index into FACE_NEIGHBORS of the face the cursor hit
+    pub face: Option<usize>,
+}
+
+impl HighlightedVoxel {
+    fn world_center(&self, voxel_size: f32) -> Vec3 {
+        let local = IVec3::new(self.local.x, self.local.y, self.local.z);
+        ((self.chunk * CHUNK_SIZE + local).as_vec3() + Vec3::splat(0.5)) * voxel_size
+    }
+}
+
+// Box of voxels for box selection, in world voxel coordinates with both corners
+// inclusive. Remove the resource to clear the highlight.
+#[derive(Resource, Clone, Copy, Debug)]
+pub struct HighlightedRegion {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+#[derive(Component)]
+struct RegionFill;
+
+fn setup_region_fill(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
+            material: materials.add(StandardMaterial {
+                base_color: REGION_FILL_COLOR,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        RegionFill,
+    ));
+}
+
+fn draw_voxel_highlight(
+    mut gizmos: Gizmos,
+    settings: Res<VoxelRenderSettings>,
+    highlighted: Option<Res<HighlightedVoxel>>,
+) {
+    let Some(highlighted) = highlighted else {
+        return;
+    };
+
+    let size = settings.voxel_size;
+    let center = highlighted.world_center(size);
+    let transform = Transform::from_translation(center).with_scale(Vec3::splat(size * OUTLINE_INFLATE));
+    gizmos.cuboid(transform, OUTLINE_COLOR);
+
+    if let Some(offset) = highlighted.face.and_then(|face| FACE_NEIGHBORS.get(face)) {
+        let normal = Vec3::new(offset.x as f32, offset.y as f32, offset.z as f32);
+        // Rects lie in the XY plane, facing +Z
+        gizmos.rect(
+            center + normal * size * OUTLINE_INFLATE / 2.0,
+            Quat::from_rotation_arc(Vec3::Z, normal),
+            Vec2::splat(size * 0.9),
+            FACE_COLOR,
+        );
+    }
+}
+
+fn draw_region_highlight(
+    mut gizmos: Gizmos,
+    settings: Res<VoxelRenderSettings>,
+    region: Option<Res<HighlightedRegion>>,
+    mut fill: Query<(&mut Transform, &mut Visibility), With<RegionFill>>,
+) {
+    let Ok((mut fill_transform, mut visibility)) = fill.get_single_mut() else {
+        return;
+    };
+    let Some(region) = region else {
+        *visibility = Visibility::Hidden;
+        return;
+    };
+
+    let min = region.min.min(region.max).as_vec3() * settings.voxel_size;
+    let max = (region.min.max(region.max) + IVec3::ONE).as_vec3() * settings.voxel_size;
+    let transform = Transform::from_translation((min + max) / 2.0).with_scale((max - min) * OUTLINE_INFLATE);
+    gizmos.cuboid(transform, REGION_COLOR);
+
+    *fill_transform = transform;
+    *visibility = Visibility::Visible;
+}
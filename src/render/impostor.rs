@@ -0,0 +1,319 @@
+use bevy::{
+    core_pipeline::{clear_color::ClearColorConfig, tonemapping::Tonemapping},
+    pbr::NotShadowCaster,
+    prelude::*,
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        render_resource::{Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages},
+        view::RenderLayers,
+    },
+};
+
+use crate::camera::CameraController;
+use crate::voxel::{process_dirty_chunks, CameraMotion, CullReason, CullingStats, LodSettings, VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::VoxelRenderSettings;
+use super::fog::{LodFade, VoxelFog, VoxelFogExtension, VoxelMeshMaterial};
+use super::mesher::build_cube_mesh;
+
+const IMPOSTOR_RESOLUTION: u32 = 128;
+// Only capture cameras and the geometry they snapshot live on this layer
+const CAPTURE_LAYER: u8 = 1;
+// A capture camera stays alive this long so its target is drawn before it goes
+const CAPTURE_FRAMES: u32 = 2;
+// Snapshots are retaken once the view direction drifts this far from the capture
+const REFRESH_ANGLE_DEGREES: f32 = 30.0;
+
+pub struct ImpostorPlugin;
+
+impl Plugin for ImpostorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ImpostorCache>()
+            .add_systems(Startup, setup_impostor_assets)
+            .add_systems(Update, (
+                update_impostors,
+                run_impostor_captures,
+            ).chain().after(process_dirty_chunks));
+    }
+}
+
+// Camera-facing quad drawn in place of a distant chunk, textured with a snapshot of it
+#[derive(Component)]
+struct ChunkImpostor {
+    quad: Entity,
+    image: Handle<Image>,
+    // Chunk version and view direction of the last snapshot, None until the first
+    captured: Option<(u32, Vec3)>,
+}
+
+// Captures run one at a time, since every capture shares CAPTURE_LAYER
+struct ActiveCapture {
+    chunk: Entity,
+    camera: Entity,
+    geometry: Entity,
+    version: u32,
+    direction: Vec3,
+    frames_left: u32,
+}
+
+#[derive(Resource, Default)]
+struct ImpostorCache {
+    quad_mesh: Handle<Mesh>,
+    capture_material: Handle<StandardMaterial>,
+    // Render targets of released impostors, reused before allocating new ones
+    free_images: Vec<Handle<Image>>,
+    // Chunks waiting for a snapshot, oldest first
+    queue: Vec<Entity>,
+    active: Option<ActiveCapture>,
+}
+
+fn setup_impostor_assets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut cache: ResMut<ImpostorCache>,
+) {
+    cache.quad_mesh = meshes.add(Mesh::from(shape::Quad::new(Vec2::ONE)));
+    // Vertex colors are multiplied with the white base color
+    cache.capture_material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+}
+
+fn impostor_image(images: &mut Assets<Image>) -> Handle<Image> {
+    let size = Extent3d {
+        width: IMPOSTOR_RESOLUTION,
+        height: IMPOSTOR_RESOLUTION,
+        depth_or_array_layers: 1,
+    };
+    let mut image = Image {
+        texture_descriptor: TextureDescriptor {
+            label: Some("chunk_impostor"),
+            size,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Bgra8UnormSrgb,
+            mip_level_count: 1,
+            sample_count: 1,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST | TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        },
+        ..default()
+    };
+    image.resize(size);
+    images.add(image)
+}
+
+// Both the capture camera and the quad use this, so the snapshot lands upright
+fn view_up(direction: Vec3) -> Vec3 {
+    if direction.y.abs() > 0.99 {
+        Vec3::Z
+    } else {
+        Vec3::Y
+    }
+}
+
+// Radius of the sphere around a chunk, which the snapshot and quad both cover
+fn chunk_radius(voxel_size: f32) -> f32 {
+    CHUNK_SIZE as f32 * voxel_size * 3f32.sqrt() / 2.0
+}
+
+// Creates and releases impostors as chunks cross the threshold, keeps the quads
+// facing the camera, and queues snapshots that are missing or out of date. Whether a
+// chunk has an impostor depends on its distance alone, so one that's frustum or
+// occlusion culled keeps its quad and snapshot, hidden, until it's back in view.
+#[allow(clippy::too_many_arguments)]
+fn update_impostors(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    lod: Res<LodSettings>,
+    motion: Res<CameraMotion>,
+    camera: Query<&GlobalTransform, With<CameraController>>,
+    chunks: Query<(Entity, &VoxelChunk, &GlobalTransform, Option<&ChunkImpostor>)>,
+    mut quads: Query<(&mut Transform, &mut Visibility), Without<VoxelChunk>>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<VoxelMeshMaterial>>,
+    mut cache: ResMut<ImpostorCache>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    let (Ok(camera_transform), Some(view)) = (camera.get_single(), motion.view()) else {
+        return;
+    };
+    let camera_position = camera_transform.translation();
+    let refresh_cos = REFRESH_ANGLE_DEGREES.to_radians().cos();
+
+    for (entity, chunk, transform, impostor) in chunks.iter() {
+        // Same distance and hysteresis culling uses to pick the Impostor reason
+        let distance = view.distance_to(chunk.world_center(settings.voxel_size));
+        let wanted = chunk.cull_reason != Some(CullReason::Distance) && lod.is_impostor(distance, impostor.is_some());
+        // Drawn only while nothing else culls the chunk
+        let shown = chunk.cull_reason == Some(CullReason::Impostor);
+        match (wanted, impostor) {
+            (true, None) => {
+                let image = cache.free_images.pop().unwrap_or_else(|| impostor_image(&mut images));
+                let material = materials.add(VoxelMeshMaterial {
+                    base: StandardMaterial {
+                        base_color_texture: Some(image.clone()),
+                        unlit: true,
+                        alpha_mode: AlphaMode::Mask(0.5),
+                        cull_mode: None,
+                        ..default()
+                    },
+                    extension: VoxelFogExtension {
+                        fog: VoxelFog::from_settings(&settings),
+                        lod_fade: LodFade::NONE,
+                    },
+                });
+                let quad = commands
+                    .spawn((
+                        MaterialMeshBundle {
+                            mesh: cache.quad_mesh.clone(),
+                            material,
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        },
+                        NotShadowCaster,
+                    ))
+                    .id();
+                commands.entity(entity).add_child(quad).insert(ChunkImpostor {
+                    quad,
+                    image,
+                    captured: None,
+                });
+                culling_stats.entities_spawned += 1;
+            }
+            (true, Some(impostor)) => {
+                let direction = (transform.translation() - camera_position).normalize_or_zero();
+                // Snapshots are only taken of chunks in view, the first one included
+                let stale = match impostor.captured {
+                    Some((version, captured_direction)) => {
+                        version != chunk.version || direction.dot(captured_direction) < refresh_cos
+                    }
+                    None => true,
+                };
+                let capturing = cache.active.as_ref().is_some_and(|active| active.chunk == entity);
+                if shown && stale && !capturing && !cache.queue.contains(&entity) {
+                    cache.queue.push(entity);
+                }
+
+                if let Ok((mut quad_transform, mut visibility)) = quads.get_mut(impostor.quad) {
+                    if shown {
+                        *quad_transform = Transform::from_scale(Vec3::splat(chunk_radius(settings.voxel_size) * 2.0))
+                            .looking_to(direction, view_up(direction));
+                    }
+                    // Visible overrides the chunk, which is hidden while it's an impostor
+                    let target = if shown && impostor.captured.is_some() { Visibility::Visible } else { Visibility::Hidden };
+                    if *visibility != target {
+                        *visibility = target;
+                    }
+                }
+            }
+            (false, Some(impostor)) => {
+                commands.entity(impostor.quad).despawn_recursive();
+                commands.entity(entity).remove::<ChunkImpostor>();
+                cache.free_images.push(impostor.image.clone());
+                cache.queue.retain(|queued| *queued != entity);
+                culling_stats.entities_despawned += 1;
+            }
+            (false, None) => {}
+        }
+    }
+}
+
+// Snapshots one chunk at a time: a capture camera renders an orthographic view of
+// the chunk's mesh into the impostor's texture, then both are removed
+fn run_impostor_captures(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    camera: Query<&GlobalTransform, With<CameraController>>,
+    chunks: Query<&VoxelChunk>,
+    mut impostors: Query<&mut ChunkImpostor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut cache: ResMut<ImpostorCache>,
+) {
+    if let Some(active) = &mut cache.active {
+        active.frames_left = active.frames_left.saturating_sub(1);
+        if active.frames_left > 0 {
+            return;
+        }
+        commands.entity(active.camera).despawn_recursive();
+        commands.entity(active.geometry).despawn_recursive();
+        if let Ok(mut impostor) = impostors.get_mut(active.chunk) {
+            impostor.captured = Some((active.version, active.direction));
+        }
+        cache.active = None;
+    }
+
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+    // Chunks that went away are dropped; ones whose impostor isn't spawned yet wait
+    cache.queue.retain(|entity| chunks.contains(*entity));
+    let Some(index) = cache.queue.iter().position(|entity| impostors.contains(*entity)) else {
+        return;
+    };
+    let entity = cache.queue.remove(index);
+    let (Ok(chunk), Ok(impostor)) = (chunks.get(entity), impostors.get(entity)) else {
+        return;
+    };
+
+    let voxel_size = settings.voxel_size;
+    let center = chunk.world_center(voxel_size);
+    let radius = chunk_radius(voxel_size);
+    let direction = (center - camera_transform.translation()).normalize_or_zero();
+
+    // Translucent faces are left out of snapshots
+    let built = build_cube_mesh(chunk, voxel_size, None, None, Some(settings.light_ambient));
+    let half_extent = CHUNK_SIZE as f32 * voxel_size / 2.0;
+    let geometry = commands
+        .spawn((
+            PbrBundle {
+                mesh: meshes.add(built.opaque),
+                material: cache.capture_material.clone(),
+                transform: Transform::from_translation(center - Vec3::splat(half_extent)),
+                ..default()
+            },
+            RenderLayers::layer(CAPTURE_LAYER),
+            NotShadowCaster,
+        ))
+        .id();
+    let camera = commands
+        .spawn((
+            Camera3dBundle {
+                camera: Camera {
+                    order: -1,
+                    target: RenderTarget::Image(impostor.image.clone()),
+                    ..default()
+                },
+                camera_3d: Camera3d {
+                    clear_color: ClearColorConfig::Custom(Color::NONE),
+                    ..default()
+                },
+                projection: OrthographicProjection {
+                    scaling_mode: ScalingMode::Fixed {
+                        width: radius * 2.0,
+                        height: radius * 2.0,
+                    },
+                    near: 0.0,
+                    far: radius * 4.0,
+                    ..default()
+                }
+                .into(),
+                // The quad is tonemapped with the rest of the scene
+                tonemapping: Tonemapping::None,
+                transform: Transform::from_translation(center - direction * radius * 2.0)
+                    .looking_to(direction, view_up(direction)),
+                ..default()
+            },
+            RenderLayers::layer(CAPTURE_LAYER),
+        ))
+        .id();
+
+    cache.active = Some(ActiveCapture {
+        chunk: entity,
+        camera,
+        geometry,
+        version: chunk.version,
+        direction,
+        frames_left: CAPTURE_FRAMES,
+    });
+}
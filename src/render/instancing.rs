@@ -0,0 +1,399 @@
+use bevy::{
+    core_pipeline::core_3d::Transparent3d,
+    ecs::{
+        query::QueryItem,
+        system::{lifetimeless::*, SystemParamItem},
+    },
+    pbr::{MeshPipeline, MeshPipelineKey, RenderMeshInstances, SetMeshBindGroup, SetMeshViewBindGroup},
+    prelude::*,
+    render::{
+        batching::NoAutomaticBatching,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        mesh::{GpuBufferInfo, MeshVertexBufferLayout},
+        render_asset::RenderAssets,
+        render_phase::{
+            AddRenderCommand, DrawFunctions, PhaseItem, RenderCommand, RenderCommandResult,
+            RenderPhase, SetItemPipeline, TrackedRenderPass,
+        },
+        render_resource::*,
+        renderer::RenderDevice,
+        view::{ExtractedView, NoFrustumCulling},
+        Render, RenderApp, RenderSet,
+    },
+    utils::{HashMap, HashSet},
+};
+use bytemuck::{Pod, Zeroable};
+use std::sync::Arc;
+
+use crate::voxel::{process_dirty_chunks, CullingStats, DirtyChunks, LodSettings, SystemTimings, VoxelChunk};
+use crate::voxel_types::{RenderMode, VoxelRenderSettings};
+
+const SHADER_PATH: &str = "shaders/voxel_instancing.wgsl";
+
+pub struct InstancingPlugin;
+
+impl Plugin for InstancingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<InstancingAssets>()
+            .add_plugins(ExtractComponentPlugin::<VoxelInstances>::extract_visible())
+            .add_systems(Startup, setup_instancing_assets)
+            .add_systems(Update, (
+                despawn_instanced_chunks,
+                update_instanced_chunks,
+            ).chain().after(process_dirty_chunks));
+
+        app.sub_app_mut(RenderApp)
+            .add_render_command::<Transparent3d, DrawVoxelInstances>()
+            .init_resource::<SpecializedMeshPipelines<VoxelInstancePipeline>>()
+            .init_resource::<InstanceBufferCache>()
+            .add_systems(Render, (
+                queue_voxel_instances.in_set(RenderSet::QueueMeshes),
+                prepare_instance_buffers.in_set(RenderSet::PrepareResources),
+            ));
+    }
+
+    fn finish(&self, app: &mut App) {
+        app.sub_app_mut(RenderApp).init_resource::<VoxelInstancePipeline>();
+    }
+}
+
+// Per-voxel data read by the vertex shader. Positions are in world space so the
+// shader does not depend on the per-entity mesh uniform.
+#[derive(Clone, Copy, Pod, Zeroable)]
+#[repr(C)]
+pub struct VoxelInstance {
+    pub position: [f32; 3],
+    pub scale: f32,
+    // RGBA8, unpacked with unpack4x8unorm
+    pub color: u32,
+}
+
+// Instance data for one chunk, shared with the render world without copying
+#[derive(Component, Clone)]
+pub struct VoxelInstances {
+    pub version: u32,
+    pub data: Arc<Vec<VoxelInstance>>,
+}
+
+impl ExtractComponent for VoxelInstances {
+    type Query = &'static VoxelInstances;
+    type Filter = ();
+    type Out = Self;
+
+    fn extract_component(item: QueryItem<'_, Self::Query>) -> Option<Self> {
+        Some(item.clone())
+    }
+}
+
+// Links a chunk to the child entity drawing its instances
+#[derive(Component)]
+struct InstancedChunk {
+    entity: Entity,
+}
+
+#[derive(Resource, Default)]
+struct InstancingAssets {
+    cube: Handle<Mesh>,
+}
+
+fn setup_instancing_assets(
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut instancing_assets: ResMut<InstancingAssets>,
+) {
+    instancing_assets.cube = meshes.add(Mesh::from(shape::Cube { size: 1.0 }));
+}
+
+// The shader writes to an sRGB target, so colors are packed in linear space
+fn pack_color(color: Color) -> u32 {
+    color.as_linear_rgba_u32()
+}
+
+// Distant LODs draw one cube per block of voxels, scaled to cover the block
+fn build_instances(chunk: &VoxelChunk, voxel_size: f32, lod: &LodSettings, light_ambient: f32) -> VoxelInstances {
+    let factor = lod.factor(chunk.lod_level);
+    let data = chunk
+        .lod_voxels(factor)
+        .iter()
+        .map(|voxel| {
+            let center = chunk.get_voxel_world_position(voxel, voxel_size) + Vec3::splat(voxel_size / 2.0);
+            VoxelInstance {
+                position: center.to_array(),
+                scale: voxel_size * factor as f32,
+                color: pack_color(chunk.lit_color(voxel, light_ambient)),
+            }
+        })
+        .collect();
+
+    VoxelInstances {
+        version: chunk.version,
+        data: Arc::new(data),
+    }
+}
+
+fn despawn_instanced_chunks(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    instanced_chunks: Query<(Entity, &InstancedChunk)>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    if settings.render_mode == RenderMode::Instanced && !settings.debug_mode {
+        return;
+    }
+
+    for (chunk_entity, instanced) in instanced_chunks.iter() {
+        commands.entity(instanced.entity).despawn_recursive();
+        commands.entity(chunk_entity).remove::<InstancedChunk>();
+        culling_stats.entities_despawned += 1;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_instanced_chunks(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    dirty: Res<DirtyChunks>,
+    new_chunks: Query<(Entity, &VoxelChunk), Without<InstancedChunk>>,
+    instanced_chunks: Query<(&VoxelChunk, &InstancedChunk)>,
+    mut instances: Query<&mut VoxelInstances>,
+    instancing_assets: Res<InstancingAssets>,
+    lod: Res<LodSettings>,
+    mut culling_stats: ResMut<CullingStats>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    if settings.render_mode != RenderMode::Instanced || settings.debug_mode {
+        return;
+    }
+    let _timing = timings.span("instancing");
+
+    for (chunk_entity, chunk) in new_chunks.iter() {
+        let child = commands
+            .spawn((
+                instancing_assets.cube.clone(),
+                SpatialBundle::INHERITED_IDENTITY,
+                build_instances(chunk, settings.voxel_size, &lod, settings.light_ambient),
+                // The cube mesh bounds say nothing about where the instances are
+                NoFrustumCulling,
+                // Every chunk shares the cube mesh, so keep draws from being merged
+                NoAutomaticBatching,
+            ))
+            .id();
+        commands
+            .entity(chunk_entity)
+            .add_child(child)
+            .insert(InstancedChunk { entity: child });
+        culling_stats.entities_spawned += 1;
+    }
+
+    for entity in dirty.iter() {
+        let Ok((chunk, instanced)) = instanced_chunks.get(entity) else {
+            continue;
+        };
+        if let Ok(mut chunk_instances) = instances.get_mut(instanced.entity) {
+            if chunk_instances.version != chunk.version {
+                *chunk_instances = build_instances(chunk, settings.voxel_size, &lod, settings.light_ambient);
+            }
+        }
+    }
+}
+
+#[derive(Component)]
+struct InstanceBuffer {
+    buffer: Buffer,
+    length: usize,
+}
+
+struct CachedInstanceBuffer {
+    version: u32,
+    buffer: Buffer,
+    length: usize,
+}
+
+// Render world entities are cleared every frame, so GPU buffers live here and are
+// only recreated when the chunk version changes
+#[derive(Resource, Default)]
+struct InstanceBufferCache {
+    buffers: HashMap<Entity, CachedInstanceBuffer>,
+}
+
+fn prepare_instance_buffers(
+    mut commands: Commands,
+    query: Query<(Entity, &VoxelInstances)>,
+    render_device: Res<RenderDevice>,
+    mut cache: ResMut<InstanceBufferCache>,
+) {
+    let mut seen = HashSet::new();
+
+    for (entity, instances) in &query {
+        seen.insert(entity);
+        let stale = cache
+            .buffers
+            .get(&entity)
+            .map_or(true, |cached| cached.version != instances.version);
+
+        if stale {
+            let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+                label: Some("voxel instance buffer"),
+                contents: bytemuck::cast_slice(instances.data.as_slice()),
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            });
+            cache.buffers.insert(entity, CachedInstanceBuffer {
+                version: instances.version,
+                buffer,
+                length: instances.data.len(),
+            });
+        }
+
+        let cached = &cache.buffers[&entity];
+        commands.entity(entity).insert(InstanceBuffer {
+            buffer: cached.buffer.clone(),
+            length: cached.length,
+        });
+    }
+
+    // Hidden or despawned chunks give their buffers back
+    cache.buffers.retain(|entity, _| seen.contains(entity));
+}
+
+#[allow(clippy::too_many_arguments)]
+fn queue_voxel_instances(
+    transparent_3d_draw_functions: Res<DrawFunctions<Transparent3d>>,
+    pipeline: Res<VoxelInstancePipeline>,
+    msaa: Res<Msaa>,
+    mut pipelines: ResMut<SpecializedMeshPipelines<VoxelInstancePipeline>>,
+    pipeline_cache: Res<PipelineCache>,
+    meshes: Res<RenderAssets<Mesh>>,
+    render_mesh_instances: Res<RenderMeshInstances>,
+    instanced: Query<Entity, With<VoxelInstances>>,
+    mut views: Query<(&ExtractedView, &mut RenderPhase<Transparent3d>)>,
+) {
+    let draw_function = transparent_3d_draw_functions.read().id::<DrawVoxelInstances>();
+    let msaa_key = MeshPipelineKey::from_msaa_samples(msaa.samples());
+
+    for (view, mut transparent_phase) in &mut views {
+        let view_key = msaa_key | MeshPipelineKey::from_hdr(view.hdr);
+        let rangefinder = view.rangefinder3d();
+
+        for entity in &instanced {
+            let Some(mesh_instance) = render_mesh_instances.get(&entity) else {
+                continue;
+            };
+            let Some(mesh) = meshes.get(mesh_instance.mesh_asset_id) else {
+                continue;
+            };
+            let key = view_key | MeshPipelineKey::from_primitive_topology(mesh.primitive_topology);
+            let pipeline_id = pipelines
+                .specialize(&pipeline_cache, &pipeline, key, &mesh.layout)
+                .unwrap();
+
+            transparent_phase.add(Transparent3d {
+                entity,
+                pipeline: pipeline_id,
+                draw_function,
+                distance: rangefinder
+                    .distance_translation(&mesh_instance.transforms.transform.translation),
+                batch_range: 0..1,
+                dynamic_offset: None,
+            });
+        }
+    }
+}
+
+#[derive(Resource)]
+struct VoxelInstancePipeline {
+    shader: Handle<Shader>,
+    mesh_pipeline: MeshPipeline,
+}
+
+impl FromWorld for VoxelInstancePipeline {
+    fn from_world(world: &mut World) -> Self {
+        let shader = world.resource::<AssetServer>().load(SHADER_PATH);
+        let mesh_pipeline = world.resource::<MeshPipeline>().clone();
+
+        Self {
+            shader,
+            mesh_pipeline,
+        }
+    }
+}
+
+impl SpecializedMeshPipeline for VoxelInstancePipeline {
+    type Key = MeshPipelineKey;
+
+    fn specialize(
+        &self,
+        key: Self::Key,
+        layout: &MeshVertexBufferLayout,
+    ) -> Result<RenderPipelineDescriptor, SpecializedMeshPipelineError> {
+        let mut descriptor = self.mesh_pipeline.specialize(key, layout)?;
+
+        descriptor.vertex.shader = self.shader.clone();
+        // Locations 0-7 are reserved for standard mesh attributes
+        descriptor.vertex.buffers.push(VertexBufferLayout {
+            array_stride: std::mem::size_of::<VoxelInstance>() as u64,
+            step_mode: VertexStepMode::Instance,
+            attributes: vec![
+                VertexAttribute {
+                    format: VertexFormat::Float32x4,
+                    offset: 0,
+                    shader_location: 8,
+                },
+                VertexAttribute {
+                    format: VertexFormat::Uint32,
+                    offset: VertexFormat::Float32x4.size(),
+                    shader_location: 9,
+                },
+            ],
+        });
+        descriptor.fragment.as_mut().unwrap().shader = self.shader.clone();
+        Ok(descriptor)
+    }
+}
+
+type DrawVoxelInstances = (
+    SetItemPipeline,
+    SetMeshViewBindGroup<0>,
+    SetMeshBindGroup<1>,
+    DrawMeshInstanced,
+);
+
+struct DrawMeshInstanced;
+
+impl<P: PhaseItem> RenderCommand<P> for DrawMeshInstanced {
+    type Param = (SRes<RenderAssets<Mesh>>, SRes<RenderMeshInstances>);
+    type ViewWorldQuery = ();
+    type ItemWorldQuery = Read<InstanceBuffer>;
+
+    #[inline]
+    fn render<'w>(
+        item: &P,
+        _view: (),
+        instance_buffer: &'w InstanceBuffer,
+        (meshes, render_mesh_instances): SystemParamItem<'w, '_, Self::Param>,
+        pass: &mut TrackedRenderPass<'w>,
+    ) -> RenderCommandResult {
+        let Some(mesh_instance) = render_mesh_instances.get(&item.entity()) else {
+            return RenderCommandResult::Failure;
+        };
+        let Some(gpu_mesh) = meshes.into_inner().get(mesh_instance.mesh_asset_id) else {
+            return RenderCommandResult::Failure;
+        };
+
+        pass.set_vertex_buffer(0, gpu_mesh.vertex_buffer.slice(..));
+        pass.set_vertex_buffer(1, instance_buffer.buffer.slice(..));
+
+        match &gpu_mesh.buffer_info {
+            GpuBufferInfo::Indexed {
+                buffer,
+                index_format,
+                count,
+            } => {
+                pass.set_index_buffer(buffer.slice(..), 0, *index_format);
+                pass.draw_indexed(0..*count, 0, 0..instance_buffer.length as u32);
+            }
+            GpuBufferInfo::NonIndexed => {
+                pass.draw(0..gpu_mesh.vertex_count, 0..instance_buffer.length as u32);
+            }
+        }
+        RenderCommandResult::Success
+    }
+}
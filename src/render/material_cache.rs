@@ -0,0 +1,81 @@
+use bevy::{prelude::*, utils::HashMap};
+
+const CHANNEL_BITS: u32 = 5;
+const CHANNEL_LEVELS: f32 = ((1 << CHANNEL_BITS) - 1) as f32;
+
+// Shared materials keyed by color quantized to 5 bits per channel, alpha included so
+// translucent voxels get their own blended materials. Materials are created on
+// first use and never removed, so a static scene stops allocating.
+#[derive(Resource)]
+pub struct MaterialCache<M: Asset> {
+    materials: HashMap<u32, Handle<M>>,
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl<M: Asset> Default for MaterialCache<M> {
+    fn default() -> Self {
+        Self {
+            materials: HashMap::default(),
+            hits: 0,
+            misses: 0,
+        }
+    }
+}
+
+impl<M: Asset> MaterialCache<M> {
+    fn quantize(color: Color) -> u32 {
+        let [r, g, b, a] = color.as_rgba_f32();
+        let channel = |value: f32| (value.clamp(0.0, 1.0) * CHANNEL_LEVELS).round() as u32;
+        (channel(a) << (CHANNEL_BITS * 3))
+            | (channel(r) << (CHANNEL_BITS * 2))
+            | (channel(g) << CHANNEL_BITS)
+            | channel(b)
+    }
+
+    fn dequantize(key: u32) -> Color {
+        let mask = (1 << CHANNEL_BITS) - 1;
+        let channel = |shift: u32| ((key >> shift) & mask) as f32 / CHANNEL_LEVELS;
+        Color::rgba(
+            channel(CHANNEL_BITS * 2),
+            channel(CHANNEL_BITS),
+            channel(0),
+            channel(CHANNEL_BITS * 3),
+        )
+    }
+
+    // Returns the shared material for this color, built by `create` on a miss
+    pub fn get_or_create(
+        &mut self,
+        color: Color,
+        create: impl FnOnce(Color) -> M,
+        materials: &mut Assets<M>,
+    ) -> Handle<M> {
+        let key = Self::quantize(color);
+        if let Some(handle) = self.materials.get(&key) {
+            self.hits += 1;
+            return handle.clone();
+        }
+
+        self.misses += 1;
+        let handle = materials.add(create(Self::dequantize(key)));
+        self.materials.insert(key, handle.clone());
+        handle
+    }
+
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    pub fn hit_rate(&self) -> f64 {
+        let lookups = self.hits + self.misses;
+        if lookups == 0 {
+            return 0.0;
+        }
+        self.hits as f64 / lookups as f64
+    }
+}
@@ -0,0 +1,96 @@
+use bevy::{
+    prelude::*,
+    tasks::{futures_lite::future, AsyncComputeTaskPool, Task},
+};
+
+// Builds running at once per render path; the rest wait in the queue
+pub const MAX_BUILDS_IN_FLIGHT: usize = 32;
+// Finished builds uploaded per frame, so a burst of rebuilds spreads over frames
+pub const MAX_UPLOADS_PER_FRAME: usize = 16;
+
+pub struct MeshTaskPlugin;
+
+impl Plugin for MeshTaskPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeshingStats>()
+            .add_systems(First, reset_meshing_stats);
+    }
+}
+
+// Chunk meshes go through three states: queued until a build slot frees up,
+// building on the async compute pool, and ready until the main thread uploads them
+#[derive(Resource, Default)]
+pub struct MeshingStats {
+    pub meshes_rebuilt: usize,
+    pub queued: usize,
+    pub building: usize,
+    pub ready: usize,
+    // Builds thrown away because the chunk changed while they ran
+    pub discarded: usize,
+}
+
+fn reset_meshing_stats(mut stats: ResMut<MeshingStats>) {
+    *stats = MeshingStats::default();
+}
+
+// Chunks waiting for a build, oldest first
+#[derive(Default)]
+pub struct MeshBuildQueue {
+    entities: Vec<Entity>,
+}
+
+impl MeshBuildQueue {
+    pub fn push(&mut self, entity: Entity) {
+        if !self.entities.contains(&entity) {
+            self.entities.push(entity);
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    // Removes and returns up to count of the oldest entries
+    pub fn take(&mut self, count: usize) -> Vec<Entity> {
+        let count = count.min(self.entities.len());
+        self.entities.drain(..count).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entities.clear();
+    }
+}
+
+// A build running on a snapshot of the chunk at `version`. Removing the component
+// drops the task, which cancels it.
+#[derive(Component)]
+pub struct MeshBuild<T: Send + 'static> {
+    task: Task<T>,
+    pub version: u32,
+}
+
+impl<T: Send + 'static> MeshBuild<T> {
+    pub fn spawn(version: u32, build: impl FnOnce() -> T + Send + 'static) -> Self {
+        Self {
+            task: AsyncComputeTaskPool::get().spawn(async move { build() }),
+            version,
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    // The result, once the task has finished; never blocks
+    pub fn take(&mut self) -> Option<T> {
+        future::block_on(future::poll_once(&mut self.task))
+    }
+}
@@ -0,0 +1,562 @@
+use bevy::{
+    prelude::*,
+    render::{mesh::Indices, render_resource::PrimitiveTopology},
+    utils::HashMap,
+};
+use crate::voxel::{light_brightness, ChunkOccupancy, FaceMask, LocalPos, NeighborhoodOccupancy, VoxelChunk, CHUNK_SIZE, FACE_NEIGHBORS};
+use crate::voxel_types::{Voxel, VoxelTypeRegistry, KIND_PLAIN};
+
+// Corners of each unit cube face, wound counter-clockwise when seen from outside.
+// Ordered to match FACE_NEIGHBORS.
+const FACE_CORNERS: [[[f32; 3]; 4]; 6] = [
+    [[1.0, 0.0, 0.0], [1.0, 1.0, 0.0], [1.0, 1.0, 1.0], [1.0, 0.0, 1.0]], // Right
+    [[0.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 1.0], [0.0, 1.0, 0.0]], // Left
+    [[0.0, 1.0, 0.0], [0.0, 1.0, 1.0], [1.0, 1.0, 1.0], [1.0, 1.0, 0.0]], // Up
+    [[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [1.0, 0.0, 1.0], [0.0, 0.0, 1.0]], // Down
+    [[0.0, 0.0, 1.0], [1.0, 0.0, 1.0], [1.0, 1.0, 1.0], [0.0, 1.0, 1.0]], // Front
+    [[0.0, 0.0, 0.0], [0.0, 1.0, 0.0], [1.0, 1.0, 0.0], [1.0, 0.0, 0.0]], // Back
+];
+
+const FACE_UVS: [[f32; 2]; 4] = [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]];
+
+fn face_normal(face: usize) -> [f32; 3] {
+    let offset = FACE_NEIGHBORS[face];
+    [offset.x as f32, offset.y as f32, offset.z as f32]
+}
+
+// Maps voxel kinds and faces to tile rectangles in a texture atlas. Owns its copy
+// of the registry so background mesh builds can carry it.
+#[derive(Clone)]
+pub struct AtlasLayout {
+    pub registry: VoxelTypeRegistry,
+    pub columns: u32,
+    pub rows: u32,
+    // Half a texel in UV space, keeps filtering from bleeding into neighbor tiles
+    pub inset: Vec2,
+}
+
+impl AtlasLayout {
+    fn face_uvs(&self, kind: u16, face: usize) -> [[f32; 2]; 4] {
+        let tile = self.registry.get(kind).tiles.for_face(face);
+        let tile_size = Vec2::new(1.0 / self.columns as f32, 1.0 / self.rows as f32);
+        let min = Vec2::new((tile % self.columns) as f32, (tile / self.columns) as f32) * tile_size + self.inset;
+        let max = min + tile_size - self.inset * 2.0;
+
+        // Project the corners onto the face plane so side textures stay upright
+        let axis = face / 2;
+        FACE_CORNERS[face].map(|corner| {
+            let (u, v) = match axis {
+                0 => (corner[2], 1.0 - corner[1]),
+                1 => (corner[0], corner[2]),
+                _ => (corner[0], 1.0 - corner[1]),
+            };
+            [min.x + (max.x - min.x) * u, min.y + (max.y - min.y) * v]
+        })
+    }
+}
+
+// AO of a face in quad order along its (u, v) axes: (-,-), (+,-), (+,+), (-,+).
+// Every corner open is the common case, so faces without occluders compare equal.
+const OPEN_FACE_AO: [u8; 4] = [3; 4];
+
+// Corner darkening baked into vertex colors from the occupancy around each face
+pub struct AmbientOcclusion {
+    pub neighborhood: NeighborhoodOccupancy,
+    // 0 leaves colors untouched, 1 turns fully enclosed corners black
+    pub strength: f32,
+}
+
+impl AmbientOcclusion {
+    // Classic three neighbor term: 3 is fully open, 0 is an inside corner
+    fn corner(&self, pos: LocalPos, face: usize, u_sign: i32, v_sign: i32) -> u8 {
+        let axis = face / 2;
+        let normal = FACE_NEIGHBORS[face];
+        let base = [pos.x + normal.x, pos.y + normal.y, pos.z + normal.z];
+        let solid = |du: i32, dv: i32| {
+            let mut cell = base;
+            cell[(axis + 1) % 3] += du;
+            cell[(axis + 2) % 3] += dv;
+            self.neighborhood.is_solid(LocalPos::new(cell[0], cell[1], cell[2]))
+        };
+
+        let (side_u, side_v, diagonal) = (solid(u_sign, 0), solid(0, v_sign), solid(u_sign, v_sign));
+        if side_u && side_v {
+            0
+        } else {
+            3 - side_u as u8 - side_v as u8 - diagonal as u8
+        }
+    }
+
+    fn face(&self, pos: LocalPos, face: usize) -> [u8; 4] {
+        [(-1, -1), (1, -1), (1, 1), (-1, 1)].map(|(u, v)| self.corner(pos, face, u, v))
+    }
+
+    fn brightness(&self, ao: u8) -> f32 {
+        1.0 - self.strength * (3 - ao) as f32 / 3.0
+    }
+}
+
+// Baked sunlight on a face, from the open cell in front of it. Faces on the chunk
+// border use the voxel's own level, the brightest cell around it, since the cell in
+// front belongs to the neighbor.
+fn face_light(chunk: &VoxelChunk, pos: LocalPos, face: usize) -> u8 {
+    let offset = FACE_NEIGHBORS[face];
+    let front = LocalPos::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z);
+    chunk.light.level(if ChunkOccupancy::in_bounds(front) { front } else { pos })
+}
+
+// Index into the quad-order AO array for a unit face corner
+fn corner_ao_index(face: usize, corner: [f32; 3]) -> usize {
+    let axis = face / 2;
+    let u = corner[(axis + 1) % 3] > 0.5;
+    let v = corner[(axis + 2) % 3] > 0.5;
+    match (u, v) {
+        (false, false) => 0,
+        (true, false) => 1,
+        (true, true) => 2,
+        (false, true) => 3,
+    }
+}
+
+#[derive(Default)]
+struct MeshBuffers {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl MeshBuffers {
+    // `shade` scales the color at each corner
+    fn push_quad(&mut self, corners: [Vec3; 4], normal: [f32; 3], uvs: [[f32; 2]; 4], color: [f32; 4], shade: [f32; 4]) {
+        let base = self.positions.len() as u32;
+        for ((corner, uv), brightness) in corners.iter().zip(uvs).zip(shade) {
+            self.positions.push(corner.to_array());
+            self.normals.push(normal);
+            self.uvs.push(uv);
+            self.colors.push([color[0] * brightness, color[1] * brightness, color[2] * brightness, color[3]]);
+        }
+
+        // Split along the brighter diagonal so shading interpolates symmetrically
+        if shade[0] + shade[2] >= shade[1] + shade[3] {
+            self.indices.extend_from_slice(&[base, base + 1, base + 2, base, base + 2, base + 3]);
+        } else {
+            self.indices.extend_from_slice(&[base + 1, base + 2, base + 3, base + 1, base + 3, base]);
+        }
+    }
+
+    fn push_face(&mut self, origin: Vec3, face: usize, color: [f32; 4], uvs: [[f32; 2]; 4], shade: [f32; 4], voxel_size: f32) {
+        let corners = FACE_CORNERS[face].map(|corner| (origin + Vec3::from_array(corner)) * voxel_size);
+        self.push_quad(corners, face_normal(face), uvs, color, shade);
+    }
+
+    fn into_mesh(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+// Opaque faces in chunk corner coordinates, plus translucent faces relative to the
+// chunk center so Bevy's transparent phase sorts them back to front by chunk distance
+pub struct ChunkMeshes {
+    pub opaque: Mesh,
+    pub translucent: Option<Mesh>,
+}
+
+#[derive(Default)]
+struct ChunkBuffers {
+    opaque: MeshBuffers,
+    translucent: MeshBuffers,
+}
+
+impl ChunkBuffers {
+    fn for_color(&mut self, color: [f32; 4]) -> &mut MeshBuffers {
+        if color[3] < 1.0 {
+            &mut self.translucent
+        } else {
+            &mut self.opaque
+        }
+    }
+
+    fn finish(mut self, voxel_size: f32) -> ChunkMeshes {
+        let translucent = if self.translucent.positions.is_empty() {
+            None
+        } else {
+            let half_extent = CHUNK_SIZE as f32 * voxel_size / 2.0;
+            for position in &mut self.translucent.positions {
+                *position = position.map(|coord| coord - half_extent);
+            }
+            Some(self.translucent.into_mesh())
+        };
+
+        ChunkMeshes {
+            opaque: self.opaque.into_mesh(),
+            translucent,
+        }
+    }
+}
+
+// Emits one quad per exposed voxel face, in chunk-local coordinates.
+// Exposure is read from the chunk occupancy, the same data the occlusion pass uses.
+// With an atlas, UVs address each face's tile and the vertex color tints it.
+// With a light ambient, faces are darkened by the baked sunlight on them.
+pub fn build_cube_mesh(
+    chunk: &VoxelChunk,
+    voxel_size: f32,
+    atlas: Option<&AtlasLayout>,
+    ao: Option<&AmbientOcclusion>,
+    light_ambient: Option<f32>,
+) -> ChunkMeshes {
+    let mut buffers = ChunkBuffers::default();
+
+    for (index, voxel) in chunk.voxels.iter().enumerate() {
+        let pos = LocalPos::from_vec3(voxel.position);
+        let color = voxel.color.as_linear_rgba_f32();
+        let mask = chunk.face_mask(index);
+
+        for face in 0..FACE_NEIGHBORS.len() {
+            if mask & (1 << face) != 0 {
+                let uvs = atlas.map_or(FACE_UVS, |atlas| atlas.face_uvs(voxel.kind, face));
+                let light = light_ambient.map_or(1.0, |ambient| light_brightness(face_light(chunk, pos, face), ambient));
+                let shade = match ao {
+                    Some(ao) => {
+                        let face_ao = ao.face(pos, face);
+                        FACE_CORNERS[face].map(|corner| ao.brightness(face_ao[corner_ao_index(face, corner)]) * light)
+                    }
+                    None => [light; 4],
+                };
+                buffers
+                    .for_color(color)
+                    .push_face(voxel.position, face, color, uvs, shade, voxel_size);
+            }
+        }
+    }
+
+    buffers.finish(voxel_size)
+}
+
+// Colors of a chunk interned so faces can be compared by exact index
+struct ChunkPalette {
+    colors: Vec<[f32; 4]>,
+    // Palette index and face mask per cell, None where the chunk has no visible voxel
+    cells: Vec<Option<(u16, FaceMask)>>,
+}
+
+impl ChunkPalette {
+    fn new(chunk: &VoxelChunk) -> Self {
+        let mut colors = Vec::new();
+        let mut lookup: HashMap<[u32; 4], u16> = HashMap::default();
+        let mut cells = vec![None; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize];
+
+        for (voxel_index, voxel) in chunk.voxels.iter().enumerate() {
+            let color = voxel.color.as_linear_rgba_f32();
+            let index = *lookup.entry(color.map(f32::to_bits)).or_insert_with(|| {
+                colors.push(color);
+                (colors.len() - 1) as u16
+            });
+            cells[Self::cell(LocalPos::from_vec3(voxel.position))] = Some((index, chunk.face_mask(voxel_index)));
+        }
+
+        Self { colors, cells }
+    }
+
+    fn cell(pos: LocalPos) -> usize {
+        ((pos.z * CHUNK_SIZE + pos.y) * CHUNK_SIZE + pos.x) as usize
+    }
+
+    fn get(&self, pos: LocalPos) -> Option<(u16, FaceMask)> {
+        self.cells[Self::cell(pos)]
+    }
+}
+
+fn local_from_axes(axis: usize, slice: i32, u: i32, v: i32) -> LocalPos {
+    let mut coords = [0; 3];
+    coords[axis] = slice;
+    coords[(axis + 1) % 3] = u;
+    coords[(axis + 2) % 3] = v;
+    LocalPos::new(coords[0], coords[1], coords[2])
+}
+
+// Merges coplanar faces of the same palette color into the largest rectangles
+// it can, one axis slice at a time. Quads never overlap, so seams cannot z-fight.
+// With AO, faces only merge when their corner AO matches too, and with a light
+// ambient, when their baked sunlight does.
+pub fn build_greedy_mesh(
+    chunk: &VoxelChunk,
+    voxel_size: f32,
+    ao: Option<&AmbientOcclusion>,
+    light_ambient: Option<f32>,
+) -> ChunkMeshes {
+    let palette = ChunkPalette::new(chunk);
+    let mut buffers = ChunkBuffers::default();
+    let size = CHUNK_SIZE as usize;
+    let mut mask: Vec<Option<(u16, [u8; 4], u8)>> = vec![None; size * size];
+
+    for face in 0..FACE_NEIGHBORS.len() {
+        let axis = face / 2;
+        let positive = face % 2 == 0;
+        let (u_axis, v_axis) = ((axis + 1) % 3, (axis + 2) % 3);
+
+        for slice in 0..CHUNK_SIZE {
+            // Palette index, AO and light of every exposed face in this slice
+            for v in 0..CHUNK_SIZE {
+                for u in 0..CHUNK_SIZE {
+                    let pos = local_from_axes(axis, slice, u, v);
+                    mask[(v * CHUNK_SIZE + u) as usize] = palette
+                        .get(pos)
+                        .filter(|(_, mask)| mask & (1 << face) != 0)
+                        .map(|(color, _)| {
+                            let face_ao = ao.map_or(OPEN_FACE_AO, |ao| ao.face(pos, face));
+                            let light = light_ambient.map_or(0, |_| face_light(chunk, pos, face));
+                            (color, face_ao, light)
+                        });
+                }
+            }
+
+            for v in 0..size {
+                let mut u = 0;
+                while u < size {
+                    let Some((color, face_ao, light)) = mask[v * size + u] else {
+                        u += 1;
+                        continue;
+                    };
+
+                    let mut width = 1;
+                    while u + width < size && mask[v * size + u + width] == Some((color, face_ao, light)) {
+                        width += 1;
+                    }
+
+                    let mut height = 1;
+                    'grow: while v + height < size {
+                        for du in 0..width {
+                            if mask[(v + height) * size + u + du] != Some((color, face_ao, light)) {
+                                break 'grow;
+                            }
+                        }
+                        height += 1;
+                    }
+
+                    for dv in 0..height {
+                        for du in 0..width {
+                            mask[(v + dv) * size + u + du] = None;
+                        }
+                    }
+
+                    let plane = if positive { slice + 1 } else { slice };
+                    let origin = local_from_axes(axis, plane, u as i32, v as i32);
+                    let origin = Vec3::new(origin.x as f32, origin.y as f32, origin.z as f32);
+                    let mut du = Vec3::ZERO;
+                    let mut dv = Vec3::ZERO;
+                    du[u_axis] = width as f32;
+                    dv[v_axis] = height as f32;
+
+                    let (w, h) = (width as f32, height as f32);
+                    let corners = if positive {
+                        [origin, origin + du, origin + du + dv, origin + dv]
+                    } else {
+                        [origin, origin + dv, origin + du + dv, origin + du]
+                    };
+                    let uvs = if positive {
+                        [[0.0, 0.0], [w, 0.0], [w, h], [0.0, h]]
+                    } else {
+                        [[0.0, 0.0], [0.0, h], [w, h], [w, 0.0]]
+                    };
+                    let corner_ao = if positive {
+                        face_ao
+                    } else {
+                        [face_ao[0], face_ao[3], face_ao[2], face_ao[1]]
+                    };
+                    let light = light_ambient.map_or(1.0, |ambient| light_brightness(light, ambient));
+                    let shade = match ao {
+                        Some(ao) => corner_ao.map(|value| ao.brightness(value) * light),
+                        None => [light; 4],
+                    };
+
+                    let color = palette.colors[color as usize];
+                    buffers.for_color(color).push_quad(
+                        corners.map(|corner| corner * voxel_size),
+                        face_normal(face),
+                        uvs,
+                        color,
+                        shade,
+                    );
+                    u += width;
+                }
+            }
+        }
+    }
+
+    buffers.finish(voxel_size)
+}
+
+// Coarse stand-in for a chunk that only casts shadows. Every factor³ block with an
+// opaque cell becomes one solid voxel, culled voxels included, then greedy merged.
+pub fn build_shadow_proxy_mesh(chunk: &VoxelChunk, voxel_size: f32, factor: i32) -> Mesh {
+    let blocks = (CHUNK_SIZE + factor - 1) / factor;
+    let mut voxels = Vec::new();
+    for z in 0..blocks {
+        for y in 0..blocks {
+            for x in 0..blocks {
+                let block = IVec3::new(x, y, z);
+                let solid = (0..factor.pow(3)).any(|i| {
+                    let cell = block * factor + IVec3::new(i % factor, i / factor % factor, i / (factor * factor));
+                    chunk.occupancy.is_solid(LocalPos::new(cell.x, cell.y, cell.z))
+                });
+                if solid {
+                    voxels.push(Voxel {
+                        position: block.as_vec3(),
+                        color: Color::WHITE,
+                        kind: KIND_PLAIN,
+                    });
+                }
+            }
+        }
+    }
+
+    let coarse = VoxelChunk::new(chunk.position, voxels);
+    build_greedy_mesh(&coarse, voxel_size * factor as f32, None, None).opaque
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::mesh::VertexAttributeValues;
+
+    fn triangles(mesh: &Mesh) -> usize {
+        mesh.indices().map_or(0, |indices| indices.len() / 3)
+    }
+
+    fn solid_chunk() -> VoxelChunk {
+        let mut voxels = Vec::new();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    voxels.push(Voxel {
+                        position: Vec3::new(x as f32, y as f32, z as f32),
+                        color: Color::rgb(0.4, 0.6, 0.3),
+                        kind: KIND_PLAIN,
+                    });
+                }
+            }
+        }
+        let mut chunk = VoxelChunk::new(IVec3::ZERO, voxels);
+        chunk.filter_occluded_voxels();
+        chunk
+    }
+
+    // Steps rising along x in two colors split along z, so merging has to stop at
+    // each step edge and at the color change
+    fn staircase_chunk() -> VoxelChunk {
+        let mut voxels = Vec::new();
+        for z in 0..6 {
+            for x in 0..8 {
+                for y in 0..=x / 2 {
+                    let color = if z < 3 { Color::rgb(0.5, 0.5, 0.52) } else { Color::rgb(0.6, 0.4, 0.2) };
+                    voxels.push(Voxel {
+                        position: Vec3::new(x as f32, y as f32, z as f32),
+                        color,
+                        kind: KIND_PLAIN,
+                    });
+                }
+            }
+        }
+        VoxelChunk::new(IVec3::ZERO, voxels)
+    }
+
+    // Every unit face a mesh covers, as its normal and cell, with the quad's color.
+    // A quad w faces wide and h high gives w × h of them.
+    fn unit_faces(mesh: &Mesh) -> Vec<((IVec3, IVec3), [u32; 4])> {
+        let (
+            Some(VertexAttributeValues::Float32x3(positions)),
+            Some(VertexAttributeValues::Float32x3(normals)),
+            Some(VertexAttributeValues::Float32x4(colors)),
+        ) = (
+            mesh.attribute(Mesh::ATTRIBUTE_POSITION),
+            mesh.attribute(Mesh::ATTRIBUTE_NORMAL),
+            mesh.attribute(Mesh::ATTRIBUTE_COLOR),
+        )
+        else {
+            panic!("mesh is missing positions, normals or colors");
+        };
+        let mut faces = Vec::new();
+        for quad in 0..positions.len() / 4 {
+            let corners = positions[quad * 4..quad * 4 + 4].iter().map(|corner| Vec3::from_array(*corner));
+            let min = corners.clone().fold(Vec3::MAX, Vec3::min).round().as_ivec3();
+            let mut max = corners.fold(Vec3::MIN, Vec3::max).round().as_ivec3();
+            let normal = Vec3::from_array(normals[quad * 4]).as_ivec3();
+            let axis = (0..3).find(|&axis| normal[axis] != 0).unwrap();
+            max[axis] = min[axis] + 1;
+            for x in min.x..max.x {
+                for y in min.y..max.y {
+                    for z in min.z..max.z {
+                        faces.push(((normal, IVec3::new(x, y, z)), colors[quad * 4].map(f32::to_bits)));
+                    }
+                }
+            }
+        }
+        faces
+    }
+
+    #[test]
+    fn greedy_mesh_of_solid_chunk_is_one_quad_per_side() {
+        let chunk = solid_chunk();
+        let greedy = build_greedy_mesh(&chunk, 1.0, None, None);
+        let naive = build_cube_mesh(&chunk, 1.0, None, None, None);
+
+        assert_eq!(triangles(&greedy.opaque), 12);
+        // Every border cell face, two triangles each
+        let border_faces = 6 * (CHUNK_SIZE * CHUNK_SIZE) as usize;
+        assert_eq!(triangles(&naive.opaque), border_faces * 2);
+        assert!(greedy.translucent.is_none() && naive.translucent.is_none());
+    }
+
+    #[test]
+    fn greedy_quads_tile_the_naive_faces_exactly() {
+        let chunk = staircase_chunk();
+        let greedy = build_greedy_mesh(&chunk, 1.0, None, None);
+        let naive = build_cube_mesh(&chunk, 1.0, None, None, None);
+        let greedy_faces = unit_faces(&greedy.opaque);
+        let covered: HashMap<_, _> = greedy_faces.iter().copied().collect();
+
+        // No face is covered twice, so merged quads can't z-fight at their seams,
+        // and together they cover exactly the naive surface in its colors, so it
+        // stays watertight
+        assert_eq!(covered.len(), greedy_faces.len());
+        assert_eq!(covered, unit_faces(&naive.opaque).into_iter().collect::<HashMap<_, _>>());
+        assert!(triangles(&greedy.opaque) * 2 < triangles(&naive.opaque));
+    }
+
+    #[test]
+    fn ao_reads_neighbor_chunk_at_border() {
+        let cell = |x: i32, y: i32, z: i32| Voxel {
+            position: Vec3::new(x as f32, y as f32, z as f32),
+            color: Color::WHITE,
+            kind: KIND_PLAIN,
+        };
+        // A voxel on the +x border, and one up and across it in the next chunk
+        let center = ChunkOccupancy::from_voxels(&[cell(CHUNK_SIZE - 1, 0, 4)]);
+        let right = ChunkOccupancy::from_voxels(&[cell(0, 1, 4)]);
+        let pos = LocalPos::new(CHUNK_SIZE - 1, 0, 4);
+        let up = 2;
+
+        let isolated = AmbientOcclusion {
+            neighborhood: NeighborhoodOccupancy::gather(&center, |_| None),
+            strength: 1.0,
+        };
+        assert_eq!(isolated.face(pos, up), OPEN_FACE_AO);
+
+        // The top face's u axis is z and v is x, so the two +x corners are darkened
+        let seamed = AmbientOcclusion {
+            neighborhood: NeighborhoodOccupancy::gather(&center, |offset| (offset == IVec3::X).then_some(&right)),
+            strength: 1.0,
+        };
+        assert_eq!(seamed.face(pos, up), [3, 3, 2, 2]);
+        assert!(seamed.brightness(2) < isolated.brightness(3));
+    }
+}
@@ -1,3 +1,31 @@
-// src/render/mod.rs
 mod billboard;
-pub use billboard::BillboardPlugin;
\ No newline at end of file
+mod billboard_batch;
+mod cube_mesh;
+mod debug;
+mod fog;
+mod gltf_export;
+mod highlight;
+mod impostor;
+mod instancing;
+mod material_cache;
+mod mesh_tasks;
+mod mesher;
+mod obj_export;
+mod points;
+mod region_mesh;
+mod shadows;
+mod sky;
+pub use billboard::{BillboardMarker, BillboardMaterialCache, BillboardPlugin, BillboardStats, FacingBillboardMaterial};
+pub use billboard_batch::BatchedBillboardPlugin;
+pub use cube_mesh::{ChunkMesh, CubeMeshPlugin};
+pub use debug::DebugRenderPlugin;
+pub use fog::{VoxelFogPlugin, VoxelMeshMaterial};
+pub use gltf_export::{export_gltf, GltfError};
+pub use highlight::{HighlightPlugin, HighlightedRegion, HighlightedVoxel};
+pub use impostor::ImpostorPlugin;
+pub use instancing::InstancingPlugin;
+pub use mesh_tasks::{MeshTaskPlugin, MeshingStats};
+pub use obj_export::{export_obj, ObjError};
+pub use points::PointCloudPlugin;
+pub use shadows::ShadowPlugin;
+pub use sky::{SkyPlugin, SkySettings};
\ No newline at end of file
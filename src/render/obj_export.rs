@@ -0,0 +1,67 @@
+// src/render/obj_export.rs
+use bevy::prelude::*;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::voxel::{VoxelWorld, WorldRegion};
+use super::region_mesh::mesh_region;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(std::io::Error),
+    // No loaded voxels inside the region
+    Empty,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ObjError::Io(err) => write!(f, "could not write .obj file: {}", err),
+            ObjError::Empty => write!(f, "no voxels to export"),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+// Greedy meshes the voxels inside a region and writes them as a Wavefront OBJ,
+// one object per chunk, placed relative to the region's min corner. Vertex colors
+// use the "v x y z r g b" extension in sRGB; alpha is dropped. Each chunk is
+// written as soon as it's meshed.
+pub fn export_obj(world: &VoxelWorld, region: WorldRegion, path: &Path) -> Result<(), ObjError> {
+    let mut out = BufWriter::new(File::create(path).map_err(ObjError::Io)?);
+    let mut result = writeln!(out, "# Exported by bevy_voxel");
+    // OBJ indices are 1-based and count every vertex written so far
+    let mut vertex_base = 1;
+    let found = mesh_region(world, region, |chunk| {
+        if result.is_err() {
+            return;
+        }
+        result = (|| -> std::io::Result<()> {
+            writeln!(out, "o chunk_{}_{}_{}", chunk.position.x, chunk.position.y, chunk.position.z)?;
+            for primitive in &chunk.primitives {
+                for (position, color) in primitive.positions.iter().zip(&primitive.colors) {
+                    let position = Vec3::from_array(*position) + chunk.translation;
+                    let [r, g, b, _] = Color::rgba_linear(color[0], color[1], color[2], color[3]).as_rgba_f32();
+                    writeln!(out, "v {} {} {} {:.4} {:.4} {:.4}", position.x, position.y, position.z, r, g, b)?;
+                }
+                for normal in &primitive.normals {
+                    writeln!(out, "vn {} {} {}", normal[0], normal[1], normal[2])?;
+                }
+                for triangle in primitive.indices.chunks_exact(3) {
+                    let [a, b, c] = [triangle[0], triangle[1], triangle[2]].map(|index| index as usize + vertex_base);
+                    writeln!(out, "f {a}//{a} {b}//{b} {c}//{c}")?;
+                }
+                vertex_base += primitive.positions.len();
+            }
+            Ok(())
+        })();
+    });
+    if !found {
+        drop(out);
+        let _ = std::fs::remove_file(path);
+        return Err(ObjError::Empty);
+    }
+    result.and_then(|()| out.flush()).map_err(ObjError::Io)
+}
@@ -0,0 +1,205 @@
+use bevy::{
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        mesh::{Indices, MeshVertexBufferLayout},
+        render_resource::{
+            AsBindGroup, PrimitiveTopology, RenderPipelineDescriptor, ShaderRef,
+            SpecializedMeshPipelineError,
+        },
+    },
+};
+
+use crate::voxel::{process_dirty_chunks, CullingStats, DirtyChunks, SystemTimings, VoxelChunk};
+use crate::voxel_types::{RenderMode, VoxelRenderSettings};
+
+const SHADER_PATH: &str = "shaders/points.wgsl";
+
+pub struct PointCloudPlugin;
+
+impl Plugin for PointCloudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<PointCloudMaterial>::default())
+            .init_resource::<PointCloudAssets>()
+            .add_systems(Update, (
+                despawn_point_clouds,
+                update_point_clouds,
+            ).chain().after(process_dirty_chunks));
+    }
+}
+
+// Draws one dot per voxel. PointList meshes rasterize as single pixels since wgpu
+// has no point size; meshes with UVs are expanded into small quads instead.
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct PointCloudMaterial {
+    // World space quad size; quads never shrink below a pixel
+    #[uniform(0)]
+    pub point_size: f32,
+}
+
+impl Material for PointCloudMaterial {
+    fn vertex_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        let vertex_layout = if layout.contains(Mesh::ATTRIBUTE_UV_0) {
+            descriptor.vertex.shader_defs.push("POINT_QUADS".into());
+            layout.get_layout(&[
+                Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+                Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+                Mesh::ATTRIBUTE_COLOR.at_shader_location(2),
+            ])?
+        } else {
+            layout.get_layout(&[
+                Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+                Mesh::ATTRIBUTE_COLOR.at_shader_location(2),
+            ])?
+        };
+        descriptor.vertex.buffers = vec![vertex_layout];
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+// Point mesh for a chunk, the child entity drawing it, and the chunk version it reflects
+#[derive(Component)]
+struct ChunkPoints {
+    handle: Handle<Mesh>,
+    entity: Entity,
+    version: u32,
+}
+
+#[derive(Resource, Default)]
+struct PointCloudAssets {
+    material: Option<Handle<PointCloudMaterial>>,
+}
+
+const CORNER_UVS: [[f32; 2]; 4] = [[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]];
+
+fn build_point_mesh(chunk: &VoxelChunk, settings: &VoxelRenderSettings) -> Mesh {
+    let voxel_size = settings.voxel_size;
+    let chunk_center = chunk.world_center(voxel_size);
+    let vertices_per_voxel = if settings.point_quads { 4 } else { 1 };
+    let mut positions = Vec::with_capacity(chunk.voxels.len() * vertices_per_voxel);
+    let mut colors = Vec::with_capacity(chunk.voxels.len() * vertices_per_voxel);
+
+    for voxel in &chunk.voxels {
+        let center = chunk.get_voxel_world_position(voxel, voxel_size) + Vec3::splat(voxel_size / 2.0) - chunk_center;
+        let color = chunk.lit_color(voxel, settings.light_ambient).as_linear_rgba_f32();
+        for _ in 0..vertices_per_voxel {
+            positions.push(center.to_array());
+            colors.push(color);
+        }
+    }
+
+    if !settings.point_quads {
+        let mut mesh = Mesh::new(PrimitiveTopology::PointList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        return mesh;
+    }
+
+    let uvs: Vec<[f32; 2]> = (0..chunk.voxels.len()).flat_map(|_| CORNER_UVS).collect();
+    let indices = (0..chunk.voxels.len() as u32)
+        .flat_map(|i| {
+            let base = i * 4;
+            [base, base + 2, base + 1, base, base + 3, base + 2]
+        })
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+    mesh
+}
+
+fn despawn_point_clouds(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    point_chunks: Query<(Entity, &ChunkPoints)>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    if settings.render_mode == RenderMode::Points && !settings.debug_mode {
+        return;
+    }
+
+    for (chunk_entity, points) in point_chunks.iter() {
+        commands.entity(points.entity).despawn_recursive();
+        commands.entity(chunk_entity).remove::<ChunkPoints>();
+        culling_stats.entities_despawned += 1;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_point_clouds(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    dirty: Res<DirtyChunks>,
+    new_chunks: Query<(Entity, &VoxelChunk), Without<ChunkPoints>>,
+    mut point_chunks: Query<(&VoxelChunk, &mut ChunkPoints)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<PointCloudMaterial>>,
+    mut point_assets: ResMut<PointCloudAssets>,
+    mut culling_stats: ResMut<CullingStats>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    if settings.render_mode != RenderMode::Points || settings.debug_mode {
+        return;
+    }
+    let _timing = timings.span("point clouds");
+
+    let material = point_assets
+        .material
+        .get_or_insert_with(|| {
+            materials.add(PointCloudMaterial {
+                point_size: settings.voxel_size,
+            })
+        })
+        .clone();
+
+    for (chunk_entity, chunk) in new_chunks.iter() {
+        let handle = meshes.add(build_point_mesh(chunk, &settings));
+        let child = commands
+            .spawn(MaterialMeshBundle {
+                mesh: handle.clone(),
+                material: material.clone(),
+                ..default()
+            })
+            .id();
+        commands
+            .entity(chunk_entity)
+            .add_child(child)
+            .insert(ChunkPoints {
+                handle,
+                entity: child,
+                version: chunk.version,
+            });
+        culling_stats.entities_spawned += 1;
+    }
+
+    for entity in dirty.iter() {
+        let Ok((chunk, mut points)) = point_chunks.get_mut(entity) else {
+            continue;
+        };
+        if points.version == chunk.version {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(&points.handle) {
+            *mesh = build_point_mesh(chunk, &settings);
+        }
+        points.version = chunk.version;
+    }
+}
@@ -0,0 +1,117 @@
+// src/render/region_mesh.rs
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+    utils::HashMap,
+};
+use crate::voxel::{split_world, ChunkOccupancy, NeighborhoodOccupancy, VoxelChunk, VoxelWorld, WorldRegion, CHUNK_SIZE};
+use super::mesher::build_greedy_mesh;
+
+// Vertex data of one primitive, relative to its chunk's min corner
+pub(super) struct Primitive {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    // Linear RGBA
+    pub colors: Vec<[f32; 4]>,
+    pub indices: Vec<u32>,
+    pub translucent: bool,
+}
+
+impl Primitive {
+    fn from_mesh(mesh: &Mesh, offset: Vec3, translucent: bool) -> Option<Self> {
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+            return None;
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) else {
+            return None;
+        };
+        let Some(VertexAttributeValues::Float32x4(colors)) = mesh.attribute(Mesh::ATTRIBUTE_COLOR) else {
+            return None;
+        };
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+            return None;
+        };
+        if indices.is_empty() {
+            return None;
+        }
+        Some(Self {
+            positions: positions.iter().map(|position| (Vec3::from_array(*position) + offset).to_array()).collect(),
+            normals: normals.clone(),
+            colors: colors.clone(),
+            indices: indices.clone(),
+            translucent,
+        })
+    }
+}
+
+// Greedy meshed voxels of one chunk inside the region
+pub(super) struct RegionChunk {
+    pub position: IVec3,
+    // Chunk min corner relative to the region's min corner, in world units
+    pub translation: Vec3,
+    pub primitives: Vec<Primitive>,
+}
+
+// Copy of a chunk holding only the voxels inside the region
+fn clipped_chunk(chunk: &VoxelChunk, region: WorldRegion) -> Option<VoxelChunk> {
+    let origin = chunk.position * CHUNK_SIZE;
+    let voxels: Vec<_> = chunk
+        .voxels
+        .iter()
+        .chain(chunk.hidden_voxels.iter())
+        .filter(|voxel| region.contains(origin + voxel.position.as_ivec3()))
+        .cloned()
+        .collect();
+    (!voxels.is_empty()).then(|| VoxelChunk::new(chunk.position, voxels))
+}
+
+// Greedy meshes the voxels inside a region, whatever the render mode, one chunk at
+// a time so only one chunk's mesh is held at once. Faces between chunks in the
+// region are culled; faces on the region's edge stay. Returns false when the
+// region holds no loaded voxels.
+pub(super) fn mesh_region(world: &VoxelWorld, region: WorldRegion, mut emit: impl FnMut(RegionChunk)) -> bool {
+    let voxel_size = world.voxel_size();
+    let (min_chunk, _) = split_world(region.min);
+    let (max_chunk, _) = split_world(region.max);
+    let mut occupancy: HashMap<IVec3, ChunkOccupancy> = HashMap::default();
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                let position = IVec3::new(x, y, z);
+                if let Some(chunk) = world.chunk(position).and_then(|chunk| clipped_chunk(chunk, region)) {
+                    occupancy.insert(position, chunk.occupancy);
+                }
+            }
+        }
+    }
+    if occupancy.is_empty() {
+        return false;
+    }
+
+    let mut positions: Vec<IVec3> = occupancy.keys().copied().collect();
+    positions.sort_by_key(|position| (position.x, position.y, position.z));
+    // Translucent faces come out relative to the chunk center
+    let half_extent = Vec3::splat(CHUNK_SIZE as f32 * voxel_size / 2.0);
+    for position in positions {
+        // Clipped again rather than kept, so large regions don't hold every chunk
+        let Some(mut chunk) = world.chunk(position).and_then(|chunk| clipped_chunk(chunk, region)) else {
+            continue;
+        };
+        let neighborhood = NeighborhoodOccupancy::gather(&occupancy[&position], |offset| occupancy.get(&(position + offset)));
+        chunk.update_face_masks(Some(&neighborhood), true);
+        let built = build_greedy_mesh(&chunk, voxel_size, None, None);
+        let primitives: Vec<Primitive> = Primitive::from_mesh(&built.opaque, Vec3::ZERO, false)
+            .into_iter()
+            .chain(built.translucent.as_ref().and_then(|mesh| Primitive::from_mesh(mesh, half_extent, true)))
+            .collect();
+        if primitives.is_empty() {
+            continue;
+        }
+        emit(RegionChunk {
+            position,
+            translation: (position * CHUNK_SIZE - region.min).as_vec3() * voxel_size,
+            primitives,
+        });
+    }
+    true
+}
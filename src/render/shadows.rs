@@ -0,0 +1,183 @@
+use bevy::{
+    pbr::{CascadeShadowConfig, CascadeShadowConfigBuilder, DirectionalLightShadowMap},
+    prelude::*,
+    reflect::TypePath,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+
+use crate::voxel::{process_dirty_chunks, CullReason, CullingStats, DirtyChunks, VoxelChunk, CHUNK_SIZE};
+use crate::voxel_types::{RenderMode, VoxelRenderSettings};
+use super::mesher::build_shadow_proxy_mesh;
+
+const PROXY_SHADER_PATH: &str = "shaders/shadow_proxy.wgsl";
+// Voxels per proxy block edge; shadows don't need full resolution
+const PROXY_FACTOR: i32 = 2;
+
+pub struct ShadowPlugin;
+
+impl Plugin for ShadowPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<ShadowProxyMaterial>::default())
+            .init_resource::<ShadowProxyAssets>()
+            .add_systems(Startup, setup_shadow_proxy_assets)
+            .add_systems(Update, sync_shadow_settings)
+            .add_systems(Update, (
+                despawn_shadow_proxies,
+                update_shadow_proxies,
+                sync_shadow_proxy_visibility,
+            ).chain().after(process_dirty_chunks));
+    }
+}
+
+// Discards every fragment in the main pass. Shadow maps are drawn with the
+// prepass shaders, so the proxy still lands in them.
+#[derive(Asset, TypePath, AsBindGroup, Clone, Default)]
+pub struct ShadowProxyMaterial {}
+
+impl Material for ShadowProxyMaterial {
+    fn fragment_shader() -> ShaderRef {
+        PROXY_SHADER_PATH.into()
+    }
+}
+
+// Proxy mesh for a chunk, the child entity drawing it, and the chunk version it reflects
+#[derive(Component)]
+struct ShadowProxy {
+    handle: Handle<Mesh>,
+    entity: Entity,
+    version: u32,
+}
+
+#[derive(Resource, Default)]
+struct ShadowProxyAssets {
+    material: Handle<ShadowProxyMaterial>,
+}
+
+fn setup_shadow_proxy_assets(
+    mut materials: ResMut<Assets<ShadowProxyMaterial>>,
+    mut proxy_assets: ResMut<ShadowProxyAssets>,
+) {
+    proxy_assets.material = materials.add(ShadowProxyMaterial::default());
+}
+
+fn sync_shadow_settings(
+    settings: Res<VoxelRenderSettings>,
+    mut shadow_map: ResMut<DirectionalLightShadowMap>,
+    mut lights: Query<(Ref<DirectionalLight>, &mut CascadeShadowConfig)>,
+) {
+    if shadow_map.size != settings.shadow_map_size {
+        shadow_map.size = settings.shadow_map_size;
+    }
+
+    for (light, mut cascades) in lights.iter_mut() {
+        if settings.is_changed() || light.is_added() {
+            *cascades = CascadeShadowConfigBuilder {
+                num_cascades: settings.shadow_cascades.max(1),
+                first_cascade_far_bound: settings.shadow_distance * 0.1,
+                maximum_distance: settings.shadow_distance,
+                ..default()
+            }
+            .into();
+        }
+    }
+}
+
+// Billboards have no surface to cast from, so they get proxies; meshes cast their own
+fn proxies_active(settings: &VoxelRenderSettings) -> bool {
+    let billboard_mode = matches!(
+        settings.render_mode,
+        RenderMode::Billboards | RenderMode::BatchedBillboards | RenderMode::Splats
+    );
+    settings.billboard_shadow_proxies && billboard_mode && !settings.debug_mode
+}
+
+fn despawn_shadow_proxies(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    proxied_chunks: Query<(Entity, &ShadowProxy)>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    if proxies_active(&settings) {
+        return;
+    }
+
+    for (chunk_entity, proxy) in proxied_chunks.iter() {
+        commands.entity(proxy.entity).despawn_recursive();
+        commands.entity(chunk_entity).remove::<ShadowProxy>();
+        culling_stats.entities_despawned += 1;
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn update_shadow_proxies(
+    mut commands: Commands,
+    settings: Res<VoxelRenderSettings>,
+    dirty: Res<DirtyChunks>,
+    new_chunks: Query<(Entity, &VoxelChunk), Without<ShadowProxy>>,
+    mut proxied_chunks: Query<(&VoxelChunk, &mut ShadowProxy)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    proxy_assets: Res<ShadowProxyAssets>,
+    mut culling_stats: ResMut<CullingStats>,
+) {
+    if !proxies_active(&settings) {
+        return;
+    }
+
+    // Chunk entities sit at their center, mesh vertices start at the chunk corner
+    let corner_offset = Vec3::splat(-(CHUNK_SIZE as f32) * settings.voxel_size / 2.0);
+
+    for (chunk_entity, chunk) in new_chunks.iter() {
+        let handle = meshes.add(build_shadow_proxy_mesh(chunk, settings.voxel_size, PROXY_FACTOR));
+        let child = commands
+            .spawn(MaterialMeshBundle {
+                mesh: handle.clone(),
+                material: proxy_assets.material.clone(),
+                transform: Transform::from_translation(corner_offset),
+                ..default()
+            })
+            .id();
+        commands
+            .entity(chunk_entity)
+            .add_child(child)
+            .insert(ShadowProxy {
+                handle,
+                entity: child,
+                version: chunk.version,
+            });
+        culling_stats.entities_spawned += 1;
+    }
+
+    for entity in dirty.iter() {
+        let Ok((chunk, mut proxy)) = proxied_chunks.get_mut(entity) else {
+            continue;
+        };
+        if proxy.version == chunk.version {
+            continue;
+        }
+        if let Some(mesh) = meshes.get_mut(&proxy.handle) {
+            *mesh = build_shadow_proxy_mesh(chunk, settings.voxel_size, PROXY_FACTOR);
+        }
+        proxy.version = chunk.version;
+    }
+}
+
+// Chunks outside the view frustum still cast shadows into it, so proxies only
+// hide with distance. Visible overrides the chunk's own Visibility, which hides it
+// for any cull reason.
+fn sync_shadow_proxy_visibility(
+    chunks: Query<(&VoxelChunk, &ShadowProxy)>,
+    mut visibility: Query<&mut Visibility, Without<VoxelChunk>>,
+) {
+    for (chunk, proxy) in chunks.iter() {
+        if let Ok(mut visibility) = visibility.get_mut(proxy.entity) {
+            let target = if chunk.cull_reason == Some(CullReason::Distance) {
+                Visibility::Hidden
+            } else {
+                Visibility::Visible
+            };
+            if *visibility != target {
+                *visibility = target;
+            }
+        }
+    }
+}
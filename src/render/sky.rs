@@ -0,0 +1,202 @@
+use bevy::{
+    core_pipeline::Skybox,
+    pbr::{MaterialPipeline, MaterialPipelineKey, NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        mesh::MeshVertexBufferLayout,
+        render_resource::{
+            AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+            TextureViewDescriptor, TextureViewDimension,
+        },
+    },
+};
+
+use crate::camera::CameraController;
+use crate::voxel_types::VoxelRenderSettings;
+
+const SHADER_PATH: &str = "shaders/sky.wgsl";
+// Inside the camera's default far plane of 1000
+const SKY_RADIUS: f32 = 900.0;
+
+pub struct SkyPlugin;
+
+impl Plugin for SkyPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MaterialPlugin::<SkyMaterial>::default())
+            .init_resource::<SkySettings>()
+            .add_systems(Startup, setup_sky)
+            .add_systems(Update, (
+                sync_sky_colors,
+                apply_sky_cubemap,
+                follow_camera,
+            ));
+    }
+}
+
+#[derive(Resource)]
+pub struct SkySettings {
+    pub zenith_color: Color,
+    pub horizon_color: Color,
+    // Below the horizon, for views from above the terrain
+    pub ground_color: Color,
+    // Keep the voxel fog color at the horizon color, so distant chunks fade into the sky
+    pub fog_matches_horizon: bool,
+    // Six stacked square faces; replaces the gradient once loaded
+    pub cubemap: Option<Handle<Image>>,
+}
+
+impl Default for SkySettings {
+    fn default() -> Self {
+        Self {
+            zenith_color: Color::rgb(0.25, 0.45, 0.8),
+            horizon_color: Color::rgb(0.7, 0.8, 0.95),
+            ground_color: Color::rgb(0.35, 0.35, 0.38),
+            fog_matches_horizon: true,
+            cubemap: None,
+        }
+    }
+}
+
+// Vertical gradient from the view direction, drawn on a sphere around the camera
+#[derive(Asset, TypePath, AsBindGroup, Clone)]
+pub struct SkyMaterial {
+    #[uniform(0)]
+    pub zenith: Color,
+    #[uniform(1)]
+    pub horizon: Color,
+    #[uniform(2)]
+    pub ground: Color,
+}
+
+impl Material for SkyMaterial {
+    fn fragment_shader() -> ShaderRef {
+        SHADER_PATH.into()
+    }
+
+    // Seen from inside
+    fn specialize(
+        _pipeline: &MaterialPipeline<Self>,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayout,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+impl SkyMaterial {
+    fn from_settings(sky: &SkySettings) -> Self {
+        Self {
+            zenith: sky.zenith_color,
+            horizon: sky.horizon_color,
+            ground: sky.ground_color,
+        }
+    }
+}
+
+#[derive(Component)]
+struct SkyDome;
+
+fn setup_sky(
+    mut commands: Commands,
+    sky: Res<SkySettings>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+) {
+    commands.spawn((
+        MaterialMeshBundle {
+            mesh: meshes.add(Mesh::from(shape::UVSphere {
+                radius: 1.0,
+                sectors: 32,
+                stacks: 16,
+            })),
+            material: materials.add(SkyMaterial::from_settings(&sky)),
+            transform: Transform::from_scale(Vec3::splat(SKY_RADIUS)),
+            ..default()
+        },
+        SkyDome,
+        NotShadowCaster,
+        NotShadowReceiver,
+    ));
+}
+
+// Pushes changed sky colors into the dome, the clear color and the fog
+fn sync_sky_colors(
+    sky: Res<SkySettings>,
+    mut clear_color: ResMut<ClearColor>,
+    mut settings: ResMut<VoxelRenderSettings>,
+    domes: Query<&Handle<SkyMaterial>, With<SkyDome>>,
+    mut materials: ResMut<Assets<SkyMaterial>>,
+) {
+    if !sky.is_changed() {
+        return;
+    }
+
+    for handle in domes.iter() {
+        if let Some(material) = materials.get_mut(handle) {
+            *material = SkyMaterial::from_settings(&sky);
+        }
+    }
+    clear_color.0 = sky.horizon_color;
+    if sky.fog_matches_horizon && settings.fog_color != sky.horizon_color {
+        settings.fog_color = sky.horizon_color;
+    }
+}
+
+// Cubemaps load as one tall 2D image and are reinterpreted once, as in Bevy's skybox
+// example. The dome hides while a cubemap is in use.
+fn apply_sky_cubemap(
+    mut commands: Commands,
+    sky: Res<SkySettings>,
+    mut images: ResMut<Assets<Image>>,
+    cameras: Query<(Entity, Option<&Skybox>), With<CameraController>>,
+    mut domes: Query<&mut Visibility, With<SkyDome>>,
+) {
+    let cubemap = sky.cubemap.as_ref().filter(|handle| images.contains(*handle));
+    if let Some(handle) = cubemap {
+        let needs_reinterpret = images.get(handle).is_some_and(|image| image.texture_descriptor.array_layer_count() == 1);
+        if needs_reinterpret {
+            if let Some(image) = images.get_mut(handle) {
+                image.reinterpret_stacked_2d_as_array(image.height() / image.width());
+                image.texture_view_descriptor = Some(TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::Cube),
+                    ..default()
+                });
+            }
+        }
+    }
+
+    for (camera, skybox) in cameras.iter() {
+        match (cubemap, skybox) {
+            (Some(handle), Some(Skybox(current))) if current == handle => {}
+            (Some(handle), _) => {
+                commands.entity(camera).insert(Skybox(handle.clone()));
+            }
+            (None, Some(_)) => {
+                commands.entity(camera).remove::<Skybox>();
+            }
+            (None, None) => {}
+        }
+    }
+
+    let target = if cubemap.is_some() { Visibility::Hidden } else { Visibility::Inherited };
+    for mut visibility in domes.iter_mut() {
+        if *visibility != target {
+            *visibility = target;
+        }
+    }
+}
+
+fn follow_camera(
+    camera: Query<&Transform, (With<CameraController>, Without<SkyDome>)>,
+    mut domes: Query<&mut Transform, With<SkyDome>>,
+) {
+    let Ok(camera_transform) = camera.get_single() else {
+        return;
+    };
+    for mut transform in domes.iter_mut() {
+        transform.translation = camera_transform.translation;
+    }
+}
@@ -0,0 +1,236 @@
+// src/screenshot.rs
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::{renderer::RenderDevice, view::screenshot::ScreenshotManager},
+    window::PrimaryWindow,
+};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::bindings::{Action, ActionInput};
+use crate::camera::CameraController;
+use crate::console::RegisterConsoleCommand;
+
+mod supersample;
+
+use supersample::{release_supersample_captures, SupersampleCaptures, SupersamplePlugin};
+
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScreenshotSettings>()
+            .init_resource::<ScreenshotState>()
+            .init_resource::<FinishedCaptures>()
+            .add_plugins(SupersamplePlugin)
+            .add_systems(
+                Update,
+                (
+                    release_supersample_captures,
+                    restore_ui_after_capture,
+                    take_screenshot,
+                    report_finished_captures,
+                )
+                    .chain(),
+            )
+            .register_console_command(
+                "screenshot",
+                "screenshot <supersample|hide_ui>: toggles supersampled captures or leaving the UI out",
+                screenshot_command,
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct ScreenshotSettings {
+    // Where captures are written, relative to the working directory
+    pub directory: String,
+    // Leave the diagnostics, crosshair and other UI out of captures
+    pub hide_ui: bool,
+    // Render captures offscreen at twice the window resolution. These never
+    // include the UI.
+    pub supersample: bool,
+}
+
+impl Default for ScreenshotSettings {
+    fn default() -> Self {
+        Self {
+            directory: String::from("screenshots"),
+            hide_ui: true,
+            supersample: false,
+        }
+    }
+}
+
+// Path of the last capture and when it was written, in elapsed seconds, so the
+// overlay can confirm it for a moment
+#[derive(Resource, Default)]
+pub struct ScreenshotState {
+    pub last_saved: Option<(String, f64)>,
+    // The UI was hidden for a capture last frame and comes back this frame
+    ui_hidden: bool,
+}
+
+// Captures written, or failed, off the main thread, waiting to be reported
+#[derive(Resource, Clone, Default)]
+struct FinishedCaptures(Arc<Mutex<Vec<(String, Result<(), String>)>>>);
+
+impl FinishedCaptures {
+    fn push(&self, path: String, result: Result<(), String>) {
+        self.0.lock().unwrap().push((path, result));
+    }
+}
+
+// Captures the window to a timestamped PNG, or renders the view offscreen at a
+// higher resolution when supersampling. A window capture is of the frame rendered
+// after this system, so hiding the UI here leaves it out.
+#[allow(clippy::too_many_arguments)]
+fn take_screenshot(
+    mut commands: Commands,
+    input: ActionInput,
+    settings: Res<ScreenshotSettings>,
+    mut state: ResMut<ScreenshotState>,
+    mut screenshots: ResMut<ScreenshotManager>,
+    mut supersample: ResMut<SupersampleCaptures>,
+    mut images: ResMut<Assets<Image>>,
+    device: Res<RenderDevice>,
+    finished: Res<FinishedCaptures>,
+    window: Query<(Entity, &Window), With<PrimaryWindow>>,
+    camera: Query<(Entity, &Camera, &Transform, &Projection, &Tonemapping), With<CameraController>>,
+) {
+    if !input.just_pressed(Action::Screenshot) {
+        return;
+    }
+    let Ok((window, window_info)) = window.get_single() else {
+        return;
+    };
+
+    if let Err(err) = std::fs::create_dir_all(&settings.directory) {
+        warn!("Could not create {}: {}", settings.directory, err);
+        return;
+    }
+    let path = format!("{}/screenshot-{}.png", settings.directory, timestamp());
+
+    if settings.supersample {
+        let Ok((_, source, transform, projection, tonemapping)) = camera.get_single() else {
+            return;
+        };
+        let window_size = UVec2::new(window_info.physical_width(), window_info.physical_height());
+        info!("Rendering supersampled screenshot to {}", path);
+        supersample.start(
+            &mut commands,
+            &mut images,
+            &device,
+            (source, transform, projection, tonemapping),
+            window_size,
+            path,
+        );
+        return;
+    }
+
+    let finished = finished.clone();
+    let target = path.clone();
+    let requested = screenshots.take_screenshot(window, move |image| {
+        let result = save_png(image, &target);
+        finished.push(target, result);
+    });
+    if requested.is_err() {
+        // One is already pending for this frame
+        return;
+    }
+    info!("Saving screenshot to {}", path);
+
+    if settings.hide_ui {
+        for (entity, ..) in camera.iter() {
+            commands.entity(entity).insert(UiCameraConfig { show_ui: false });
+        }
+        state.ui_hidden = true;
+    }
+}
+
+// Confirms captures once they're on disk rather than when they're requested, so
+// a failed write isn't reported as saved
+fn report_finished_captures(
+    time: Res<Time>,
+    finished: Res<FinishedCaptures>,
+    mut state: ResMut<ScreenshotState>,
+) {
+    let done = std::mem::take(&mut *finished.0.lock().unwrap());
+    for (path, result) in done {
+        match result {
+            Ok(()) => {
+                info!("Saved screenshot to {}", path);
+                state.last_saved = Some((path, time.elapsed_seconds_f64()));
+            }
+            Err(err) => warn!("Could not save screenshot to {}: {}", path, err),
+        }
+    }
+}
+
+// Writes a capture as an RGB PNG, dropping the alpha the swapchain leaves behind
+fn save_png(image: Image, path: &str) -> Result<(), String> {
+    let image = image.try_into_dynamic().map_err(|err| err.to_string())?;
+    image.to_rgb8().save(path).map_err(|err| err.to_string())
+}
+
+fn restore_ui_after_capture(
+    mut commands: Commands,
+    mut state: ResMut<ScreenshotState>,
+    camera: Query<Entity, With<CameraController>>,
+) {
+    if !state.ui_hidden {
+        return;
+    }
+    for entity in camera.iter() {
+        commands.entity(entity).insert(UiCameraConfig { show_ui: true });
+    }
+    state.ui_hidden = false;
+}
+
+fn screenshot_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut settings = world.resource_mut::<ScreenshotSettings>();
+    match args.first().copied() {
+        Some("supersample") => {
+            settings.supersample = !settings.supersample;
+            Ok(format!("Supersampled screenshots {}", if settings.supersample { "on" } else { "off" }))
+        }
+        Some("hide_ui") => {
+            settings.hide_ui = !settings.hide_ui;
+            Ok(format!("UI {} screenshots", if settings.hide_ui { "left out of" } else { "kept in" }))
+        }
+        _ => Err("use screenshot supersample or screenshot hide_ui".to_string()),
+    }
+}
+
+// UTC time as YYYYMMDD-HHMMSS-mmm, so captures sort by when they were taken
+pub(crate) fn timestamp() -> String {
+    let since_epoch = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let seconds = since_epoch.as_secs();
+    let (year, month, day) = civil_from_days((seconds / 86_400) as i64);
+    let time_of_day = seconds % 86_400;
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}-{:03}",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60,
+        since_epoch.subsec_millis(),
+    )
+}
+
+// Date from days since 1970-01-01 in the proleptic Gregorian calendar (Howard
+// Hinnant's civil_from_days)
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+    let month = (if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 }) as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
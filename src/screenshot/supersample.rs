@@ -0,0 +1,253 @@
+// src/screenshot/supersample.rs
+use bevy::{
+    core_pipeline::tonemapping::Tonemapping,
+    prelude::*,
+    render::{
+        camera::RenderTarget,
+        render_asset::RenderAssets,
+        render_graph::{Node, NodeRunError, RenderGraph, RenderGraphContext},
+        render_resource::*,
+        renderer::{RenderContext, RenderDevice},
+        Extract, ExtractSchedule, Render, RenderApp, RenderSet,
+    },
+    tasks::IoTaskPool,
+};
+use super::{save_png, FinishedCaptures};
+
+// Captures render at this multiple of the window resolution
+pub const SUPERSAMPLE_FACTOR: u32 = 2;
+const COPY_NODE: &str = "supersample_copy";
+// Ahead of the impostor captures at -1, though each has its own target
+const CAPTURE_ORDER: isize = -2;
+
+// Renders a still through a temporary camera into an offscreen target, copies it
+// into a buffer after the cameras have drawn, and writes it out from there. Bevy's
+// screenshot API only reads back windows.
+pub(super) struct SupersamplePlugin;
+
+impl Plugin for SupersamplePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SupersampleCaptures>();
+        let finished = app.world.resource::<FinishedCaptures>().clone();
+        app.sub_app_mut(RenderApp)
+            .insert_resource(finished)
+            .init_resource::<ExtractedCaptures>()
+            .add_systems(ExtractSchedule, extract_supersample_captures)
+            .add_systems(Render, read_back_supersample_captures.in_set(RenderSet::Cleanup));
+    }
+
+    fn finish(&self, app: &mut App) {
+        let mut graph = app.sub_app_mut(RenderApp).world.resource_mut::<RenderGraph>();
+        graph.add_node(COPY_NODE, SupersampleCopyNode);
+        graph.add_node_edge(bevy::render::main_graph::node::CAMERA_DRIVER, COPY_NODE);
+    }
+}
+
+// A capture camera and where its frame is read back to and written. Each is
+// extracted, drawn and copied the frame it starts, then released the next.
+struct SupersampleCapture {
+    camera: Entity,
+    image: Handle<Image>,
+    buffer: Buffer,
+    size: UVec2,
+    path: String,
+}
+
+#[derive(Resource, Default)]
+pub(super) struct SupersampleCaptures {
+    pending: Vec<SupersampleCapture>,
+}
+
+impl SupersampleCaptures {
+    // Spawns a camera matching the source one, rendering at the factor times the
+    // window size, capped at the largest texture the device takes. The UI is laid
+    // out for the window, so it's always left out.
+    pub(super) fn start(
+        &mut self,
+        commands: &mut Commands,
+        images: &mut Assets<Image>,
+        device: &RenderDevice,
+        source: (&Camera, &Transform, &Projection, &Tonemapping),
+        window_size: UVec2,
+        path: String,
+    ) {
+        let max = device.limits().max_texture_dimension_2d;
+        let size = (window_size * SUPERSAMPLE_FACTOR).min(UVec2::splat(max)).max(UVec2::ONE);
+        let extent = Extent3d {
+            width: size.x,
+            height: size.y,
+            depth_or_array_layers: 1,
+        };
+        let mut image = Image {
+            texture_descriptor: TextureDescriptor {
+                label: Some("supersample_capture"),
+                size: extent,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8UnormSrgb,
+                mip_level_count: 1,
+                sample_count: 1,
+                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            },
+            ..default()
+        };
+        image.resize(extent);
+        let image = images.add(image);
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("supersample_readback"),
+            size: padded_row_bytes(size.x) as u64 * size.y as u64,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let (camera, transform, projection, tonemapping) = source;
+        let camera = commands
+            .spawn((
+                Camera3dBundle {
+                    camera: Camera {
+                        order: CAPTURE_ORDER,
+                        target: RenderTarget::Image(image.clone()),
+                        ..camera.clone()
+                    },
+                    projection: projection.clone(),
+                    tonemapping: *tonemapping,
+                    transform: *transform,
+                    ..default()
+                },
+                UiCameraConfig { show_ui: false },
+            ))
+            .id();
+        self.pending.push(SupersampleCapture {
+            camera,
+            image,
+            buffer,
+            size,
+            path,
+        });
+    }
+}
+
+// Rows of a texture copy are padded to the alignment wgpu requires
+fn padded_row_bytes(width: u32) -> u32 {
+    RenderDevice::align_copy_bytes_per_row(width as usize * 4) as u32
+}
+
+// Captures started last frame have been drawn and copied by now. Dropping the
+// image handle frees the target.
+pub(super) fn release_supersample_captures(mut commands: Commands, mut captures: ResMut<SupersampleCaptures>) {
+    for capture in captures.pending.drain(..) {
+        commands.entity(capture.camera).despawn_recursive();
+    }
+}
+
+#[derive(Clone)]
+struct ExtractedCapture {
+    image: Handle<Image>,
+    buffer: Buffer,
+    size: UVec2,
+    path: String,
+}
+
+#[derive(Resource, Default)]
+struct ExtractedCaptures(Vec<ExtractedCapture>);
+
+fn extract_supersample_captures(mut commands: Commands, captures: Extract<Res<SupersampleCaptures>>) {
+    let extracted = captures
+        .pending
+        .iter()
+        .map(|capture| ExtractedCapture {
+            image: capture.image.clone(),
+            buffer: capture.buffer.clone(),
+            size: capture.size,
+            path: capture.path.clone(),
+        })
+        .collect();
+    commands.insert_resource(ExtractedCaptures(extracted));
+}
+
+// Runs after every camera has drawn, so the targets hold this frame
+struct SupersampleCopyNode;
+
+impl Node for SupersampleCopyNode {
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let captures = world.resource::<ExtractedCaptures>();
+        let images = world.resource::<RenderAssets<Image>>();
+        for capture in &captures.0 {
+            let Some(gpu_image) = images.get(&capture.image) else {
+                continue;
+            };
+            render_context.command_encoder().copy_texture_to_buffer(
+                gpu_image.texture.as_image_copy(),
+                ImageCopyBuffer {
+                    buffer: &capture.buffer,
+                    layout: ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: Some(padded_row_bytes(capture.size.x)),
+                        rows_per_image: None,
+                    },
+                },
+                Extent3d {
+                    width: capture.size.x,
+                    height: capture.size.y,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        Ok(())
+    }
+}
+
+// Waits for the copies submitted this frame, strips the row padding and hands the
+// pixels to the IO pool to be written
+fn read_back_supersample_captures(
+    captures: Res<ExtractedCaptures>,
+    device: Res<RenderDevice>,
+    finished: Res<FinishedCaptures>,
+) {
+    for capture in &captures.0 {
+        let slice = capture.buffer.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.wgpu_device().poll(Maintain::Wait);
+        if !matches!(receiver.recv(), Ok(Ok(()))) {
+            finished.push(capture.path.clone(), Err("the capture could not be read back".to_string()));
+            continue;
+        }
+
+        let padded = padded_row_bytes(capture.size.x) as usize;
+        let row = capture.size.x as usize * 4;
+        let data: Vec<u8> = slice
+            .get_mapped_range()
+            .chunks(padded)
+            .take(capture.size.y as usize)
+            .flat_map(|padded_row| &padded_row[..row])
+            .copied()
+            .collect();
+        capture.buffer.unmap();
+
+        let image = Image::new(
+            Extent3d {
+                width: capture.size.x,
+                height: capture.size.y,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        let (path, finished) = (capture.path.clone(), finished.clone());
+        IoTaskPool::get()
+            .spawn(async move {
+                let result = save_png(image, &path);
+                finished.push(path, result);
+            })
+            .detach();
+    }
+}
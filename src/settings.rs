@@ -0,0 +1,525 @@
+// src/settings.rs
+use bevy::{
+    app::AppExit,
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::bindings::{Action, Binding, KeyBindings};
+use crate::camera::CameraController;
+use crate::diagnostics::OverlaySettings;
+use crate::generation::{Compression, SaveSettings};
+use crate::voxel::LodSettings;
+use crate::voxel_types::{RenderMode, VoxelRenderSettings};
+
+pub const DEFAULT_SETTINGS_FILE: &str = "settings.ron";
+// Runtime changes are written once they've settled for this long
+const SAVE_DEBOUNCE_SECS: f32 = 1.0;
+
+// Loads settings.ron, or the file given with --settings, into the render, LOD,
+// camera, save, overlay and binding defaults, and writes changes made at runtime back to it.
+// Fields the file leaves out take their defaults and unknown ones are ignored.
+pub struct SettingsPlugin {
+    pub path: String,
+    pub file: SettingsFile,
+    // False when the file couldn't be parsed, so it isn't overwritten with defaults
+    pub writable: bool,
+    // Problems found while loading, logged once logging is up
+    warnings: Vec<String>,
+}
+
+impl SettingsPlugin {
+    // Reads the settings file, creating it with the defaults when it doesn't exist
+    pub fn load(path: String) -> Self {
+        let mut warnings = Vec::new();
+
+        let (file, writable) = match SettingsFile::load(&path) {
+            Ok((file, unknown)) => {
+                warnings.extend(unknown.into_iter().map(|name| format!("Ignoring unknown setting {} in {}", name, path)));
+                (file, true)
+            }
+            Err(SettingsError::Io(err)) if err.kind() == std::io::ErrorKind::NotFound => {
+                let file = SettingsFile::default();
+                if let Err(err) = file.save(&path) {
+                    warnings.push(err.to_string());
+                }
+                (file, true)
+            }
+            Err(err) => {
+                warnings.push(format!("{}; using the default settings and leaving the file alone", err));
+                (SettingsFile::default(), false)
+            }
+        };
+        Self {
+            path,
+            file,
+            writable,
+            warnings,
+        }
+    }
+
+    pub fn window(&self) -> Window {
+        let window = &self.file.window;
+        Window {
+            title: "Voxel Engine".into(),
+            resolution: (window.width, window.height).into(),
+            mode: if window.fullscreen { WindowMode::BorderlessFullscreen } else { WindowMode::Windowed },
+            present_mode: if window.vsync { PresentMode::AutoVsync } else { PresentMode::AutoNoVsync },
+            ..default()
+        }
+    }
+}
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        info!("Using settings from {}", self.path);
+        for warning in &self.warnings {
+            warn!("{}", warning);
+        }
+        let mut render = VoxelRenderSettings::default();
+        self.file.render.apply(&mut render);
+        let mut lod = LodSettings::default();
+        self.file.lod.apply(&mut lod);
+        let mut save = SaveSettings::default();
+        self.file.save.apply(&mut save);
+        app.insert_resource(render)
+            .insert_resource(lod)
+            .insert_resource(save)
+            .insert_resource(self.file.overlay.clone())
+            .insert_resource(self.file.bindings())
+            .insert_resource(SettingsState {
+                path: self.path.clone(),
+                window: self.file.window.clone(),
+                camera: self.file.camera.clone(),
+                written: self.file.to_text().ok(),
+                writable: self.writable,
+                save_at: None,
+            })
+            .add_systems(PostStartup, apply_camera_settings)
+            .add_systems(Last, save_changed_settings);
+    }
+}
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(std::io::Error),
+    Parse(ron::error::SpannedError),
+    Serialize(ron::Error),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(err) => write!(f, "could not access settings: {}", err),
+            SettingsError::Parse(err) => write!(f, "could not parse settings: {}", err),
+            SettingsError::Serialize(err) => write!(f, "could not serialize settings: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct WindowSettings {
+    pub width: f32,
+    pub height: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        let resolution = Window::default().resolution;
+        Self {
+            width: resolution.width(),
+            height: resolution.height(),
+            fullscreen: false,
+            vsync: true,
+        }
+    }
+}
+
+// The persistent part of VoxelRenderSettings; debug views stay per session
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct RenderSettingsFile {
+    pub render_mode: RenderMode,
+    pub greedy_meshing: bool,
+    pub ao_strength: f32,
+    pub light_ambient: f32,
+    pub point_quads: bool,
+    pub voxel_size: f32,
+    pub render_distance: f32,
+    pub fog_color: Color,
+    pub fog_start: f32,
+    pub fade_band: f32,
+    pub shadow_map_size: usize,
+    pub shadow_cascades: usize,
+    pub shadow_distance: f32,
+    pub billboard_shadow_proxies: bool,
+    pub show_diagnostics: bool,
+    pub extended_diagnostics: bool,
+    pub frustum_culling: bool,
+    pub distance_culling: bool,
+    pub voxel_occlusion: bool,
+    pub chunk_occlusion: bool,
+}
+
+impl Default for RenderSettingsFile {
+    fn default() -> Self {
+        Self::from(&VoxelRenderSettings::default())
+    }
+}
+
+impl From<&VoxelRenderSettings> for RenderSettingsFile {
+    fn from(settings: &VoxelRenderSettings) -> Self {
+        Self {
+            render_mode: settings.render_mode,
+            greedy_meshing: settings.greedy_meshing,
+            ao_strength: settings.ao_strength,
+            light_ambient: settings.light_ambient,
+            point_quads: settings.point_quads,
+            voxel_size: settings.voxel_size,
+            render_distance: settings.render_distance,
+            fog_color: settings.fog_color,
+            fog_start: settings.fog_start,
+            fade_band: settings.fade_band,
+            shadow_map_size: settings.shadow_map_size,
+            shadow_cascades: settings.shadow_cascades,
+            shadow_distance: settings.shadow_distance,
+            billboard_shadow_proxies: settings.billboard_shadow_proxies,
+            show_diagnostics: settings.show_diagnostics,
+            extended_diagnostics: settings.extended_diagnostics,
+            frustum_culling: settings.frustum_culling,
+            distance_culling: settings.distance_culling,
+            voxel_occlusion: settings.voxel_occlusion,
+            chunk_occlusion: settings.chunk_occlusion,
+        }
+    }
+}
+
+impl RenderSettingsFile {
+    fn apply(&self, settings: &mut VoxelRenderSettings) {
+        settings.render_mode = self.render_mode;
+        settings.greedy_meshing = self.greedy_meshing;
+        settings.ao_strength = self.ao_strength;
+        settings.light_ambient = self.light_ambient;
+        settings.point_quads = self.point_quads;
+        settings.voxel_size = self.voxel_size;
+        settings.render_distance = self.render_distance;
+        settings.fog_color = self.fog_color;
+        settings.fog_start = self.fog_start;
+        settings.fade_band = self.fade_band;
+        settings.shadow_map_size = self.shadow_map_size;
+        settings.shadow_cascades = self.shadow_cascades;
+        settings.shadow_distance = self.shadow_distance;
+        settings.billboard_shadow_proxies = self.billboard_shadow_proxies;
+        settings.show_diagnostics = self.show_diagnostics;
+        settings.extended_diagnostics = self.extended_diagnostics;
+        settings.frustum_culling = self.frustum_culling;
+        settings.distance_culling = self.distance_culling;
+        settings.voxel_occlusion = self.voxel_occlusion;
+        settings.chunk_occlusion = self.chunk_occlusion;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct LodSettingsFile {
+    pub distances: Vec<(f32, f32)>,
+    pub hysteresis: f32,
+    pub impostor_distance: f32,
+    pub fade_duration: f32,
+}
+
+impl Default for LodSettingsFile {
+    fn default() -> Self {
+        Self::from(&LodSettings::default())
+    }
+}
+
+impl From<&LodSettings> for LodSettingsFile {
+    fn from(settings: &LodSettings) -> Self {
+        Self {
+            distances: settings.distances.clone(),
+            hysteresis: settings.hysteresis,
+            impostor_distance: settings.impostor_distance,
+            fade_duration: settings.fade_duration,
+        }
+    }
+}
+
+impl LodSettingsFile {
+    fn apply(&self, settings: &mut LodSettings) {
+        // LOD lookups expect at least the full detail level
+        if !self.distances.is_empty() {
+            settings.distances = self.distances.clone();
+        }
+        settings.hysteresis = self.hysteresis;
+        settings.impostor_distance = self.impostor_distance;
+        settings.fade_duration = self.fade_duration;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SaveSettingsFile {
+    // 0 turns autosave off
+    pub autosave_minutes: f32,
+    // None, Lz4 or Zstd, for chunks written from now on
+    pub compression: Compression,
+}
+
+impl Default for SaveSettingsFile {
+    fn default() -> Self {
+        Self::from(&SaveSettings::default())
+    }
+}
+
+impl From<&SaveSettings> for SaveSettingsFile {
+    fn from(settings: &SaveSettings) -> Self {
+        Self {
+            autosave_minutes: settings.autosave_minutes,
+            compression: settings.compression,
+        }
+    }
+}
+
+impl SaveSettingsFile {
+    fn apply(&self, settings: &mut SaveSettings) {
+        settings.autosave_minutes = self.autosave_minutes.max(0.0);
+        settings.compression = self.compression;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(default)]
+pub struct CameraSettingsFile {
+    pub speed: f32,
+    pub min_speed: f32,
+    pub max_speed: f32,
+    pub sprint_multiplier: f32,
+    pub precision_divisor: f32,
+    pub sensitivity: f32,
+    pub look_smoothing: f32,
+    pub fov_degrees: f32,
+    pub walk_speed: f32,
+}
+
+impl Default for CameraSettingsFile {
+    fn default() -> Self {
+        Self::from(&CameraController::default())
+    }
+}
+
+impl From<&CameraController> for CameraSettingsFile {
+    fn from(controller: &CameraController) -> Self {
+        Self {
+            speed: controller.speed,
+            min_speed: controller.min_speed,
+            max_speed: controller.max_speed,
+            sprint_multiplier: controller.sprint_multiplier,
+            precision_divisor: controller.precision_divisor,
+            sensitivity: controller.sensitivity,
+            look_smoothing: controller.look_smoothing,
+            fov_degrees: controller.fov_degrees,
+            walk_speed: controller.walk_speed,
+        }
+    }
+}
+
+impl CameraSettingsFile {
+    fn apply(&self, controller: &mut CameraController) {
+        controller.speed = self.speed;
+        controller.min_speed = self.min_speed;
+        controller.max_speed = self.max_speed;
+        controller.sprint_multiplier = self.sprint_multiplier;
+        controller.precision_divisor = self.precision_divisor;
+        controller.sensitivity = self.sensitivity;
+        controller.look_smoothing = self.look_smoothing;
+        controller.fov_degrees = self.fov_degrees;
+        controller.walk_speed = self.walk_speed;
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct SettingsFile {
+    pub window: WindowSettings,
+    pub render: RenderSettingsFile,
+    pub lod: LodSettingsFile,
+    pub camera: CameraSettingsFile,
+    pub save: SaveSettingsFile,
+    pub overlay: OverlaySettings,
+    // Inputs by action name; actions left out keep their default inputs
+    pub bindings: BTreeMap<String, Vec<Binding>>,
+}
+
+impl Default for SettingsFile {
+    fn default() -> Self {
+        Self {
+            window: WindowSettings::default(),
+            render: RenderSettingsFile::default(),
+            lod: LodSettingsFile::default(),
+            camera: CameraSettingsFile::default(),
+            save: SaveSettingsFile::default(),
+            overlay: OverlaySettings::default(),
+            bindings: bindings_by_name(&KeyBindings::default()),
+        }
+    }
+}
+
+impl SettingsFile {
+    // Also returns the names of fields in the file that settings don't have
+    pub fn load(path: &str) -> Result<(Self, Vec<String>), SettingsError> {
+        let text = std::fs::read_to_string(path).map_err(SettingsError::Io)?;
+        let file: SettingsFile = ron::from_str(&text).map_err(SettingsError::Parse)?;
+        // Serde skips unknown fields silently, so compare against the defaults' layout
+        let mut unknown = Vec::new();
+        if let (Ok(found), Ok(known)) = (
+            ron::from_str::<ron::Value>(&text),
+            Self::default().to_text().and_then(|text| ron::from_str(&text).map_err(SettingsError::Parse)),
+        ) {
+            unknown_fields("", &found, &known, &mut unknown);
+        }
+        Ok((file, unknown))
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), SettingsError> {
+        std::fs::write(path, self.to_text()?).map_err(SettingsError::Io)
+    }
+
+    fn to_text(&self) -> Result<String, SettingsError> {
+        ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()).map_err(SettingsError::Serialize)
+    }
+
+    fn bindings(&self) -> KeyBindings {
+        let mut bindings = KeyBindings::default();
+        for (name, inputs) in &self.bindings {
+            // Unknown names were already warned about
+            let Ok(action) = ron::from_str::<Action>(name) else {
+                continue;
+            };
+            bindings.unbind(action);
+            for binding in inputs {
+                bindings.add(action, *binding);
+            }
+        }
+        bindings
+    }
+}
+
+fn bindings_by_name(bindings: &KeyBindings) -> BTreeMap<String, Vec<Binding>> {
+    bindings
+        .iter()
+        .filter_map(|(action, inputs)| Some((ron::to_string(&action).ok()?, inputs.to_vec())))
+        .collect()
+}
+
+fn unknown_fields(prefix: &str, found: &ron::Value, known: &ron::Value, unknown: &mut Vec<String>) {
+    let (ron::Value::Map(found), ron::Value::Map(known)) = (found, known) else {
+        return;
+    };
+    for (key, value) in found.iter() {
+        let name = match key {
+            ron::Value::String(name) => format!("{}{}", prefix, name),
+            other => format!("{}{:?}", prefix, other),
+        };
+        match known.get(key) {
+            Some(known) => unknown_fields(&format!("{}.", name), value, known, unknown),
+            None => unknown.push(name),
+        }
+    }
+}
+
+#[derive(Resource)]
+struct SettingsState {
+    path: String,
+    // Kept as loaded when there's no window to read them from, as when headless
+    window: WindowSettings,
+    // Applied to the camera once it's spawned
+    camera: CameraSettingsFile,
+    // Text last written, so unchanged settings aren't rewritten
+    written: Option<String>,
+    writable: bool,
+    save_at: Option<f32>,
+}
+
+fn apply_camera_settings(state: Res<SettingsState>, mut query: Query<&mut CameraController>) {
+    for mut controller in &mut query {
+        state.camera.apply(&mut controller);
+    }
+}
+
+// Writes the settings a second after they stop changing, and when the app exits
+#[allow(clippy::too_many_arguments)]
+fn save_changed_settings(
+    time: Res<Time>,
+    mut exit: EventReader<AppExit>,
+    mut state: ResMut<SettingsState>,
+    render: Res<VoxelRenderSettings>,
+    lod: Res<LodSettings>,
+    save: Res<SaveSettings>,
+    overlay: Res<OverlaySettings>,
+    bindings: Res<KeyBindings>,
+    camera: Query<Ref<CameraController>>,
+    window: Query<Ref<Window>, With<PrimaryWindow>>,
+) {
+    if !state.writable {
+        return;
+    }
+    let changed = render.is_changed()
+        || lod.is_changed()
+        || save.is_changed()
+        || overlay.is_changed()
+        || bindings.is_changed()
+        || camera.iter().any(|controller| controller.is_changed())
+        || window.iter().any(|window| window.is_changed());
+    let now = time.elapsed_seconds();
+    if changed {
+        state.save_at = Some(now + SAVE_DEBOUNCE_SECS);
+    }
+    let exiting = exit.read().count() > 0;
+    if !exiting && state.save_at.map_or(true, |save_at| now < save_at) {
+        return;
+    }
+    state.save_at = None;
+
+    let mut file = SettingsFile {
+        window: state.window.clone(),
+        render: RenderSettingsFile::from(render.as_ref()),
+        lod: LodSettingsFile::from(lod.as_ref()),
+        camera: state.camera.clone(),
+        save: SaveSettingsFile::from(save.as_ref()),
+        overlay: overlay.clone(),
+        bindings: bindings_by_name(&bindings),
+    };
+    if let Ok(controller) = camera.get_single() {
+        file.camera = CameraSettingsFile::from(controller.as_ref());
+    }
+    if let Ok(window) = window.get_single() {
+        file.window = WindowSettings {
+            width: window.resolution.width(),
+            height: window.resolution.height(),
+            fullscreen: window.mode != WindowMode::Windowed,
+            vsync: matches!(window.present_mode, PresentMode::AutoVsync | PresentMode::Fifo),
+        };
+    }
+    let text = match file.to_text() {
+        Ok(text) => text,
+        Err(err) => {
+            warn!("{}", err);
+            return;
+        }
+    };
+    if state.written.as_ref() == Some(&text) {
+        return;
+    }
+    match std::fs::write(&state.path, &text) {
+        Ok(()) => state.written = Some(text),
+        Err(err) => warn!("Could not write {}: {}", state.path, err),
+    }
+}
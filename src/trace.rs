@@ -0,0 +1,12 @@
+// src/trace.rs
+
+// Enters a span for work on one chunk, with its position as a field, so Tracy and
+// chrome tracing captures show which chunk took how long. The span lasts until the
+// end of the enclosing block. Without the trace feature it expands to nothing and
+// the position isn't evaluated.
+macro_rules! chunk_span {
+    ($name:literal, $position:expr) => {
+        #[cfg(feature = "trace")]
+        let _span = bevy::log::info_span!($name, chunk = %$position).entered();
+    };
+}
@@ -0,0 +1,159 @@
+// src/triggers.rs
+use bevy::{prelude::*, render::primitives::Aabb, utils::HashMap};
+use crate::camera::CameraController;
+use crate::console::RegisterConsoleCommand;
+
+// Box-shaped areas in world units that report tracked entities coming and going,
+// for game logic like noticing the player entering a cave, or streaming hints
+// like loading the chunks behind a portal as the camera steps into it
+pub struct TriggerPlugin;
+
+impl Plugin for TriggerPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<RegionEntered>()
+            .add_event::<RegionExited>()
+            .init_resource::<TriggerOccupancy>()
+            .add_systems(PostUpdate, update_trigger_regions.after(TransformSystem::TransformPropagate))
+            .register_console_command(
+                "region",
+                "region <add <id> <x0> <y0> <z0> <x1> <y1> <z1>|remove <id>|list>: edits the trigger regions",
+                region_command,
+            );
+    }
+}
+
+// Saved with the world, and outlined while chunk bounds are shown
+#[derive(Component, Clone, Debug)]
+pub struct TriggerRegion {
+    pub id: String,
+    pub aabb: Aabb,
+}
+
+impl TriggerRegion {
+    pub fn new(id: impl Into<String>, min: Vec3, max: Vec3) -> Self {
+        Self {
+            id: id.into(),
+            aabb: Aabb::from_min_max(min.min(max), min.max(max)),
+        }
+    }
+
+    pub fn min(&self) -> Vec3 {
+        self.aabb.min().into()
+    }
+
+    pub fn max(&self) -> Vec3 {
+        self.aabb.max().into()
+    }
+
+    pub fn contains(&self, point: Vec3) -> bool {
+        point.cmpge(self.min()).all() && point.cmple(self.max()).all()
+    }
+}
+
+// Entities other than the camera whose position trigger regions watch
+#[derive(Component, Default)]
+pub struct TriggerTracked;
+
+// Sent when a tracked entity's position moves into a region
+#[derive(Event, Clone, Debug)]
+pub struct RegionEntered {
+    pub region: Entity,
+    pub id: String,
+    pub entity: Entity,
+}
+
+// Sent when a tracked entity leaves a region, and when either is despawned while
+// the entity is inside
+#[derive(Event, Clone, Debug)]
+pub struct RegionExited {
+    pub region: Entity,
+    pub id: String,
+    pub entity: Entity,
+}
+
+// Region and tracked entity pairs with the entity inside, with the region's id so
+// an exit can still name a region that's gone
+#[derive(Resource, Default)]
+struct TriggerOccupancy {
+    inside: HashMap<(Entity, Entity), String>,
+}
+
+// Runs after transforms propagate, so positions are this frame's
+fn update_trigger_regions(
+    regions: Query<(Entity, &TriggerRegion)>,
+    tracked: Query<(Entity, &GlobalTransform), Or<(With<CameraController>, With<TriggerTracked>)>>,
+    mut occupancy: ResMut<TriggerOccupancy>,
+    mut entered: EventWriter<RegionEntered>,
+    mut exited: EventWriter<RegionExited>,
+) {
+    let mut inside = HashMap::default();
+    for (region_entity, region) in regions.iter() {
+        for (entity, transform) in tracked.iter() {
+            if region.contains(transform.translation()) {
+                inside.insert((region_entity, entity), region.id.clone());
+            }
+        }
+    }
+    if inside == occupancy.inside {
+        return;
+    }
+
+    for (&(region, entity), id) in occupancy.inside.iter() {
+        if !inside.contains_key(&(region, entity)) {
+            exited.send(RegionExited { region, id: id.clone(), entity });
+        }
+    }
+    for (&(region, entity), id) in inside.iter() {
+        if !occupancy.inside.contains_key(&(region, entity)) {
+            entered.send(RegionEntered { region, id: id.clone(), entity });
+        }
+    }
+    occupancy.inside = inside;
+}
+
+fn region_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut regions = world.query::<(Entity, &TriggerRegion)>();
+    match args.first().copied() {
+        Some("add") => {
+            let id = *args.get(1).ok_or("missing id")?;
+            let corners: Vec<f32> = args[2..]
+                .iter()
+                .map(|word| word.parse())
+                .collect::<Result<_, _>>()
+                .map_err(|_| "corners must be numbers")?;
+            let [x0, y0, z0, x1, y1, z1] = corners[..] else {
+                return Err("expected two corners of three numbers each".to_string());
+            };
+            if regions.iter(world).any(|(_, region)| region.id == id) {
+                return Err(format!("region '{}' already exists", id));
+            }
+            let region = TriggerRegion::new(id, Vec3::new(x0, y0, z0), Vec3::new(x1, y1, z1));
+            world.spawn(region);
+            Ok(format!("Added region {}", id))
+        }
+        Some("remove") => {
+            let id = *args.get(1).ok_or("missing id")?;
+            let entity = regions
+                .iter(world)
+                .find_map(|(entity, region)| (region.id == id).then_some(entity))
+                .ok_or_else(|| format!("no region '{}'", id))?;
+            world.despawn(entity);
+            Ok(format!("Removed region {}", id))
+        }
+        Some("list") | None => {
+            let mut lines: Vec<String> = regions
+                .iter(world)
+                .map(|(_, region)| {
+                    let (min, max) = (region.min(), region.max());
+                    format!("{}: {:.1} {:.1} {:.1} to {:.1} {:.1} {:.1}", region.id, min.x, min.y, min.z, max.x, max.y, max.z)
+                })
+                .collect();
+            if lines.is_empty() {
+                return Ok("No trigger regions".to_string());
+            }
+            lines.sort();
+            Ok(lines.join("\n"))
+        }
+        Some(other) => Err(format!("unknown region command '{}'; use add, remove or list", other)),
+    }
+}
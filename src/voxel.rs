@@ -1,23 +1,104 @@
 // src/voxel.rs
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 use bevy::render::primitives::Aabb;
-use std::collections::HashMap;
-use crate::render::BillboardPlugin;
-use crate::voxel_types::{Voxel, VoxelRenderSettings};
+use bevy::utils::{HashMap, HashSet, Instant};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use std::sync::Mutex;
+use crate::render::{
+    BatchedBillboardPlugin, BillboardPlugin, CubeMeshPlugin, DebugRenderPlugin, HighlightPlugin, ImpostorPlugin,
+    InstancingPlugin, MeshTaskPlugin, PointCloudPlugin, ShadowPlugin, SkyPlugin, VoxelFogPlugin,
+};
+use crate::bindings::{Action, ActionInput};
+use crate::camera::CameraController;
+use crate::generation::{decode_fixture, SaveError};
+use crate::voxel_types::{Voxel, VoxelRenderSettings, VoxelTypeRegistry};
+
+mod collision;
+mod edit;
+mod flood_fill;
+mod light;
+mod model;
+mod occlusion;
+mod ply;
+mod raycast;
+mod region;
+mod surface;
+pub mod shapes;
+mod vox;
+pub use edit::{EditHistory, EditOp, VoxelChanged, VoxelEditor, VoxelEdits};
+pub use flood_fill::FloodFill;
+pub use light::{light_brightness, ChunkLight};
+use light::{propagate_sunlight, LightQueue};
+pub use model::{export_wvox, ModelVoxel, SpawnVoxelModelExt, StartupModels, VoxelModel, VoxelModelInstance, WvoxError};
+use model::{place_voxel_models, spawn_startup_models, VoxelModelLoader};
+pub use occlusion::{dominant_open_direction, ChunkOccupancy, FaceMask, NeighborhoodOccupancy, ALL_FACES};
+use occlusion::face_mask;
+pub use ply::{export_ply, PlyError};
+pub use raycast::{raycast_grid, split_world, RayFilter, VoxelHit};
+pub use vox::{export_vox, VoxError, WorldRegion};
 
 pub struct VoxelPlugin;
 
 impl Plugin for VoxelPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<VoxelRenderSettings>()
+            .init_resource::<VoxelTypeRegistry>()
             .init_resource::<LodSettings>()
-            .add_plugins(BillboardPlugin)
-            .add_systems(Startup, setup_voxel_scene)
+            .init_resource::<DirtyChunks>()
+            .init_resource::<LightQueue>()
+            .init_resource::<EditHistory>()
+            .init_resource::<ChunkOcclusion>()
+            .init_resource::<ChunkSpatialIndex>()
+            .init_resource::<CameraMotion>()
+            .init_resource::<FrozenView>()
+            .init_resource::<CullingStats>()
+            .init_resource::<SystemTimings>()
+            .init_resource::<StartupModels>()
+            .init_asset::<VoxelModel>()
+            .init_asset_loader::<VoxelModelLoader>()
+            .add_event::<VoxelChanged>()
+            .add_plugins((
+                BillboardPlugin,
+                BatchedBillboardPlugin,
+                CubeMeshPlugin,
+                MeshTaskPlugin,
+                InstancingPlugin,
+                PointCloudPlugin,
+                DebugRenderPlugin,
+                HighlightPlugin,
+                ImpostorPlugin,
+                ShadowPlugin,
+                SkyPlugin,
+                VoxelFogPlugin,
+            ))
+            .add_systems(Startup, (setup_voxel_scene, spawn_startup_models))
+            .add_systems(First, reset_culling_stats)
             .add_systems(Update, (
+                // Every render path runs after process_dirty_chunks and sees the new mode in
+                // the same frame, so the old one's teardown and the new one's first spawns
+                // apply together and the two never draw at once
+                cycle_render_mode.before(process_dirty_chunks),
+                track_camera_motion
+                    .before(update_chunk_occlusion)
+                    .before(update_chunk_visibility)
+                    .before(update_voxel_lod),
+                update_chunk_spatial_index.before(update_chunk_visibility),
+                update_chunk_occlusion.before(update_chunk_visibility),
                 update_chunk_visibility,
-                update_voxel_lod,
-                apply_occlusion_culling,
-            ));
+                update_voxel_lod.before(process_dirty_chunks),
+                place_voxel_models.before(process_dirty_chunks),
+                propagate_sunlight
+                    .after(update_chunk_spatial_index)
+                    .after(update_voxel_lod)
+                    .after(place_voxel_models)
+                    .before(process_dirty_chunks),
+                process_dirty_chunks,
+            ))
+            .add_systems(Last, clear_dirty_chunks);
     }
 }
 
@@ -45,17 +126,68 @@ impl LocalPos {
     }
 }
 
-#[derive(Component, Debug)]
+// Offsets to the six face neighbors of a voxel
+pub const FACE_NEIGHBORS: [LocalPos; 6] = [
+    LocalPos { x: 1, y: 0, z: 0 },  // Right
+    LocalPos { x: -1, y: 0, z: 0 }, // Left
+    LocalPos { x: 0, y: 1, z: 0 },  // Up
+    LocalPos { x: 0, y: -1, z: 0 }, // Down
+    LocalPos { x: 0, y: 0, z: 1 },  // Front
+    LocalPos { x: 0, y: 0, z: -1 }, // Back
+];
+
+#[derive(Component, Clone, Debug)]
 pub struct VoxelChunk {
     pub position: IVec3,
+    // Voxels to draw
     pub voxels: Vec<Voxel>,
+    // Exposed faces of each voxel, parallel to voxels
+    pub face_masks: Vec<FaceMask>,
+    // Voxels with no exposed face, set aside so they can come back when a neighbor
+    // is removed or voxel occlusion is switched off
+    pub hidden_voxels: Vec<Voxel>,
+    pub occupancy: ChunkOccupancy,
+    // Baked sunlight, kept up to date by propagate_sunlight
+    pub light: ChunkLight,
+    // Bumped whenever voxel data changes so derived data knows to rebuild
+    pub version: u32,
     pub bounds: Aabb,
-    pub visible: bool,
+    // Why the chunk entity's Visibility is Hidden, None while it's visible
+    pub cull_reason: Option<CullReason>,
     pub lod_level: usize,
+    // Voxels drawn at the current LOD, kept for diagnostics
+    pub lod_voxel_count: usize,
+    // How long the last face mask and LOD rebuild took, for the chunk inspector
+    pub rebuild_ms: f32,
+    // Downsampled voxels per LOD factor
+    lod_cache: HashMap<u32, DownsampledVoxels>,
+}
+
+// Output of VoxelChunk::downsample, with face masks parallel to the voxels
+#[derive(Clone, Debug, Default)]
+pub struct DownsampledVoxels {
+    pub voxels: Vec<Voxel>,
+    pub face_masks: Vec<FaceMask>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CullReason {
+    Distance,
+    Frustum,
+    // Every path from the camera to it passes through full chunks
+    Occluded,
+    // Drawn by an impostor instead of its voxels
+    Impostor,
 }
 
 impl VoxelChunk {
     pub fn new(position: IVec3, voxels: Vec<Voxel>) -> Self {
+        let occupancy = ChunkOccupancy::from_voxels(&voxels);
+        Self::with_occupancy(position, voxels, occupancy)
+    }
+
+    // For generators that only emit visible voxels but know the full solid set
+    pub fn with_occupancy(position: IVec3, voxels: Vec<Voxel>, occupancy: ChunkOccupancy) -> Self {
         // Calculate chunk bounds
         let min = Vec3::new(
             position.x as f32 * CHUNK_SIZE as f32,
@@ -64,16 +196,49 @@ impl VoxelChunk {
         );
         let max = min + Vec3::splat(CHUNK_SIZE as f32);
         let bounds = Aabb::from_min_max(min, max);
+        let face_masks = voxels
+            .iter()
+            .map(|voxel| face_mask(&occupancy, LocalPos::from_vec3(voxel.position), None))
+            .collect();
 
         Self {
             position,
             voxels,
+            face_masks,
+            hidden_voxels: Vec::new(),
+            occupancy,
+            light: ChunkLight::default(),
+            version: 0,
             bounds,
-            visible: true,
+            cull_reason: None,
             lod_level: 0,
+            lod_voxel_count: 0,
+            rebuild_ms: 0.0,
+            lod_cache: HashMap::default(),
         }
     }
 
+    // Decodes a chunk captured with Ctrl+Q, as pasted into a regression test
+    pub fn from_fixture(bytes: &[u8]) -> Result<Self, SaveError> {
+        decode_fixture(bytes)
+    }
+
+    // Heap bytes held by the voxel lists, masks, occupancy, light and LOD cache
+    pub fn memory_bytes(&self) -> usize {
+        let voxels = |list: &Vec<Voxel>| list.capacity() * std::mem::size_of::<Voxel>();
+        let masks = |list: &Vec<FaceMask>| list.capacity() * std::mem::size_of::<FaceMask>();
+        voxels(&self.voxels)
+            + masks(&self.face_masks)
+            + voxels(&self.hidden_voxels)
+            + self.occupancy.memory_bytes()
+            + self.light.memory_bytes()
+            + self
+                .lod_cache
+                .values()
+                .map(|downsampled| voxels(&downsampled.voxels) + masks(&downsampled.face_masks))
+                .sum::<usize>()
+    }
+
     pub fn get_voxel_world_position(&self, voxel: &Voxel, voxel_size: f32) -> Vec3 {
         Vec3::new(
             (self.position.x * CHUNK_SIZE) as f32 + voxel.position.x,
@@ -82,51 +247,107 @@ impl VoxelChunk {
         ) * voxel_size
     }
 
-    // Add occlusion culling method
-    pub fn filter_occluded_voxels(&mut self) {
-        use std::collections::HashSet;
-        
-        // Create a hashset of positions for quick lookups
-        let position_set: HashSet<LocalPos> = self.voxels
-            .iter()
-            .map(|v| LocalPos::from_vec3(v.position))
-            .collect();
+    pub fn world_center(&self, voxel_size: f32) -> Vec3 {
+        (self.position.as_vec3() + Vec3::splat(0.5)) * CHUNK_SIZE as f32 * voxel_size
+    }
 
-        // Keep only voxels that have at least one exposed face
-        self.voxels.retain(|voxel| {
+    // One voxel per factor³ block of stored voxels, for drawing at factor times the
+    // voxel size. Each sits at the block center, which keeps the silhouette in place,
+    // with the average color and most common kind of its block and the exposed faces
+    // of all of them. Blocks without stored voxels produce none.
+    pub fn downsample(&self, factor: u32) -> DownsampledVoxels {
+        let factor = factor.max(1) as i32;
+        let blocks = (CHUNK_SIZE + factor - 1) / factor;
+        let mut slots: Vec<Option<usize>> = vec![None; (blocks * blocks * blocks) as usize];
+        // Color sum, voxel count and kind counts per output voxel
+        let mut sums: Vec<(Vec4, u32, HashMap<u16, u32>)> = Vec::new();
+        let mut downsampled = DownsampledVoxels::default();
+        let offset = Vec3::splat((factor - 1) as f32 / 2.0);
+
+        for (index, voxel) in self.voxels.iter().enumerate() {
             let pos = LocalPos::from_vec3(voxel.position);
-            
-            // Check all six adjacent positions
-            let adjacent_positions = [
-                LocalPos::new(pos.x + 1, pos.y, pos.z), // Right
-                LocalPos::new(pos.x - 1, pos.y, pos.z), // Left
-                LocalPos::new(pos.x, pos.y + 1, pos.z), // Up
-                LocalPos::new(pos.x, pos.y - 1, pos.z), // Down
-                LocalPos::new(pos.x, pos.y, pos.z + 1), // Front
-                LocalPos::new(pos.x, pos.y, pos.z - 1), // Back
-            ];
-
-            // A voxel is visible if any adjacent position is empty (no voxel present)
-            // or is outside the chunk bounds
-            adjacent_positions.iter().any(|adj_pos| {
-                // Check if position is outside chunk bounds
-                if adj_pos.x < 0 || adj_pos.x >= CHUNK_SIZE ||
-                   adj_pos.y < 0 || adj_pos.y >= CHUNK_SIZE ||
-                   adj_pos.z < 0 || adj_pos.z >= CHUNK_SIZE 
-                {
-                    return true; // Voxel is exposed at chunk boundary
-                }
+            let block = IVec3::new(pos.x / factor, pos.y / factor, pos.z / factor);
+            let slot = &mut slots[((block.z * blocks + block.y) * blocks + block.x) as usize];
+            let slot = *slot.get_or_insert_with(|| {
+                downsampled.voxels.push(Voxel {
+                    position: (block * factor).as_vec3() + offset,
+                    ..voxel.clone()
+                });
+                downsampled.face_masks.push(0);
+                sums.push((Vec4::ZERO, 0, HashMap::default()));
+                sums.len() - 1
+            });
 
-                // Check if there's no voxel at this position
-                !position_set.contains(adj_pos)
-            })
-        });
+            downsampled.face_masks[slot] |= self.face_mask(index);
+            let (color, count, kinds) = &mut sums[slot];
+            *color += Vec4::from(voxel.color.as_linear_rgba_f32());
+            *count += 1;
+            *kinds.entry(voxel.kind).or_default() += 1;
+        }
+
+        for (voxel, (color, count, kinds)) in downsampled.voxels.iter_mut().zip(sums) {
+            let average = color / count as f32;
+            voxel.color = Color::rgba_linear(average.x, average.y, average.z, average.w);
+            if let Some((kind, _)) = kinds.into_iter().max_by_key(|(kind, count)| (*count, u16::MAX - kind)) {
+                voxel.kind = kind;
+            }
+        }
+
+        downsampled
+    }
+
+    // Downsamples at the factor and keeps the result until the voxel data changes
+    pub fn cache_lod(&mut self, factor: u32) {
+        if factor > 1 && !self.lod_cache.contains_key(&factor) {
+            let downsampled = self.downsample(factor);
+            self.lod_cache.insert(factor, downsampled);
+        }
+    }
+
+    pub fn clear_lod_cache(&mut self) {
+        self.lod_cache.clear();
+    }
+
+    // Voxels to draw at the factor, from the cache when it has them
+    pub fn lod_voxels(&self, factor: u32) -> Cow<'_, [Voxel]> {
+        if factor <= 1 {
+            return Cow::Borrowed(&self.voxels);
+        }
+        match self.lod_cache.get(&factor) {
+            Some(cached) => Cow::Borrowed(&cached.voxels),
+            None => Cow::Owned(self.downsample(factor).voxels),
+        }
+    }
+
+    // Exposed faces of the voxel at index, all of them if masks are out of date
+    pub fn face_mask(&self, index: usize) -> FaceMask {
+        self.face_masks.get(index).copied().unwrap_or(ALL_FACES)
+    }
+
+    // Face masks for lod_voxels(factor), in the same order
+    pub fn lod_face_masks(&self, factor: u32) -> Cow<'_, [FaceMask]> {
+        if factor <= 1 {
+            return Cow::Borrowed(&self.face_masks);
+        }
+        match self.lod_cache.get(&factor) {
+            Some(cached) => Cow::Borrowed(&cached.face_masks),
+            None => Cow::Owned(self.downsample(factor).face_masks),
+        }
     }
 }
 
+// (threshold distance, voxel scale factor) per LOD level
 #[derive(Resource)]
 pub struct LodSettings {
     pub distances: Vec<(f32, f32)>,
+    // How far past a threshold a chunk must move before its level changes, as a
+    // fraction of the width of the band below the threshold
+    pub hysteresis: f32,
+    // Chunks further than this are drawn as impostors. Keep it past the last
+    // threshold, or the last band is never drawn.
+    pub impostor_distance: f32,
+    // Seconds a chunk mesh cross-fades into its new level; 0 switches instantly
+    pub fade_duration: f32,
 }
 
 impl Default for LodSettings {
@@ -137,8 +358,80 @@ impl Default for LodSettings {
                 (50.0, 2.0),
                 (100.0, 4.0),
             ],
+            hysteresis: 0.1,
+            impostor_distance: 150.0,
+            fade_duration: 0.25,
+        }
+    }
+}
+
+impl LodSettings {
+    // Level whose band [threshold, next threshold) contains the distance
+    fn band(&self, distance: f32) -> usize {
+        self.distances
+            .iter()
+            .rposition(|(threshold, _)| distance >= *threshold)
+            .unwrap_or(0)
+    }
+
+    // Hysteresis margin around a distance, from the band it falls in. The first
+    // threshold has no band below it and gets none.
+    fn margin(&self, distance: f32) -> f32 {
+        let band = self.band(distance);
+        let start = self.distances.get(band).map_or(0.0, |(threshold, _)| *threshold);
+        let start = if start < distance {
+            start
+        } else {
+            // On a threshold, the band below it counts
+            band.checked_sub(1)
+                .and_then(|below| self.distances.get(below))
+                .map_or(start, |(threshold, _)| *threshold)
+        };
+        (distance - start) * self.hysteresis
+    }
+
+    // Keeps the current level until the distance is past the threshold between it
+    // and the new band by the margin
+    pub fn level_for(&self, distance: f32, current: usize) -> usize {
+        let band = self.band(distance);
+        // The threshold on the current level's side that the chunk crossed
+        let crossed = match band.cmp(&current) {
+            Ordering::Equal => return current,
+            Ordering::Greater => current + 1,
+            Ordering::Less => current,
+        };
+        let Some((threshold, _)) = self.distances.get(crossed) else {
+            return band;
+        };
+        let margin = self.margin(*threshold);
+        let held = if band > current {
+            distance < threshold + margin
+        } else {
+            distance >= threshold - margin
+        };
+        if held {
+            current
+        } else {
+            band
         }
     }
+
+    // Same hysteresis as the LOD bands, so chunks don't flicker at the threshold
+    pub fn is_impostor(&self, distance: f32, currently: bool) -> bool {
+        let margin = self.margin(self.impostor_distance);
+        let threshold = if currently {
+            self.impostor_distance - margin
+        } else {
+            self.impostor_distance + margin
+        };
+        distance > threshold
+    }
+
+    pub fn factor(&self, level: usize) -> u32 {
+        self.distances
+            .get(level)
+            .map_or(1, |(_, factor)| factor.max(1.0) as u32)
+    }
 }
 
 fn setup_voxel_scene(mut commands: Commands) {
@@ -157,97 +450,1094 @@ fn setup_voxel_scene(mut commands: Commands) {
         transform: Transform::from_xyz(4.0, 8.0, 4.0).looking_at(Vec3::ZERO, Vec3::Y),
         ..default()
     });
+}
 
-    // Create a single chunk for testing
-    let mut voxels = Vec::new();
-    
-    // Create a 15x15x15 cube of voxels
-    for x in 0..15 {
-        for y in 0..15 {
-            for z in 0..15 {
-                let pos = Vec3::new(x as f32, y as f32, z as f32);
-                
-                // Create gradient color based on position
-                let color = Color::hsl(
-                    (pos.x.atan2(pos.z).to_degrees() + 180.0) / 360.0 * 360.0,
-                    (pos.y / 15.0 * 0.5 + 0.5).clamp(0.2, 1.0),
-                    (1.0 - (pos - Vec3::splat(7.5)).length() / 15.0 * 0.5).clamp(0.3, 0.7),
-                );
-
-                voxels.push(Voxel {
-                    position: pos,
-                    color,
-                });
+fn cycle_render_mode(
+    input: ActionInput,
+    mut settings: ResMut<VoxelRenderSettings>,
+) {
+    if input.just_pressed(Action::CycleRenderMode) {
+        settings.render_mode = settings.render_mode.next();
+        info!("Render mode: {:?}", settings.render_mode);
+    }
+}
+
+// Chunks whose voxel data was modified this frame
+#[derive(Resource, Default)]
+pub struct DirtyChunks {
+    entities: Vec<Entity>,
+    // Chunks marked only because their level of detail changed
+    lod_only: Vec<Entity>,
+}
+
+impl DirtyChunks {
+    pub fn mark(&mut self, entity: Entity) {
+        if !self.entities.contains(&entity) {
+            self.entities.push(entity);
+        }
+        self.lod_only.retain(|marked| *marked != entity);
+    }
+
+    // Renderers rebuild the chunk, but its cached downsampled voxels stay valid
+    pub fn mark_lod_change(&mut self, entity: Entity) {
+        if !self.entities.contains(&entity) {
+            self.entities.push(entity);
+            self.lod_only.push(entity);
+        }
+    }
+
+    pub fn voxels_changed(&self, entity: Entity) -> bool {
+        self.contains(entity) && !self.lod_only.contains(&entity)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter().copied()
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entities.contains(&entity)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+}
+
+// Re-runs occlusion culling on modified chunks and bumps their version, spread
+// across the compute pool. Systems deriving data from chunks should run after this one.
+// Face masks on a chunk border depend on the chunk next to it, so loading a chunk
+// also refreshes its face neighbors, which turn dirty if their masks changed.
+pub fn process_dirty_chunks(
+    mut dirty: ResMut<DirtyChunks>,
+    mut chunks: Query<(Entity, &mut VoxelChunk)>,
+    lod: Res<LodSettings>,
+    settings: Res<VoxelRenderSettings>,
+    mut stats: ResMut<CullingStats>,
+    mut last_voxel_occlusion: Local<Option<bool>>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    let _timing = timings.span("face masks");
+    let by_position: HashMap<IVec3, Entity> = chunks
+        .iter()
+        .map(|(entity, chunk)| (chunk.position, entity))
+        .collect();
+
+    let mut targets: HashSet<Entity> = dirty.iter().collect();
+    // Toggling voxel occlusion reshuffles every chunk's hidden voxels
+    let cull = settings.voxel_occlusion;
+    if last_voxel_occlusion.replace(cull).is_some_and(|last| last != cull) {
+        targets.extend(by_position.values().copied());
+    }
+    let added: Vec<(Entity, IVec3)> = chunks
+        .iter_mut()
+        .filter(|(_, chunk)| chunk.is_added())
+        .map(|(entity, chunk)| (entity, chunk.position))
+        .collect();
+    for (entity, position) in added {
+        targets.insert(entity);
+        for offset in FACE_NEIGHBORS {
+            if let Some(neighbor) = by_position.get(&(position + IVec3::new(offset.x, offset.y, offset.z))) {
+                targets.insert(*neighbor);
+            }
+        }
+    }
+
+    // Gathering only reads occupancy and update_face_masks leaves occupancy alone, so
+    // each chunk's result is independent of the others and of the order they run in
+    let neighborhoods = Mutex::new(HashMap::default());
+    chunks.par_iter().for_each(|(entity, chunk)| {
+        if !targets.contains(&entity) {
+            return;
+        }
+        let neighborhood = NeighborhoodOccupancy::gather(&chunk.occupancy, |offset| {
+            let neighbor = by_position.get(&(chunk.position + offset))?;
+            chunks.get(*neighbor).ok().map(|(_, chunk)| &chunk.occupancy)
+        });
+        neighborhoods.lock().unwrap().insert(entity, neighborhood);
+    });
+    let neighborhoods: HashMap<Entity, NeighborhoodOccupancy> = neighborhoods.into_inner().unwrap();
+
+    let marks = Mutex::new(Vec::new());
+    let voxels_before = AtomicUsize::new(0);
+    let voxels_after = AtomicUsize::new(0);
+    let dirty_before: &DirtyChunks = &dirty;
+    chunks.par_iter_mut().for_each(|(entity, mut chunk)| {
+        let Some(neighborhood) = neighborhoods.get(&entity) else {
+            return;
+        };
+
+        chunk_span!("update_face_masks", chunk.position);
+        let started = Instant::now();
+        let was_dirty = dirty_before.contains(entity);
+        if dirty_before.voxels_changed(entity) {
+            chunk.clear_lod_cache();
+        }
+        voxels_before.fetch_add(chunk.voxels.len() + chunk.hidden_voxels.len(), AtomicOrdering::Relaxed);
+        let changed = chunk.update_face_masks(Some(neighborhood), cull);
+        voxels_after.fetch_add(chunk.voxels.len(), AtomicOrdering::Relaxed);
+        // New chunks are built from scratch, so they don't need a version bump
+        if was_dirty || (changed && !chunk.is_added()) {
+            chunk.version = chunk.version.wrapping_add(1);
+            if changed || !was_dirty {
+                marks.lock().unwrap().push(entity);
             }
         }
+
+        // Renderers read the current level's voxels after this, so they're cached now
+        let factor = lod.factor(chunk.lod_level);
+        chunk.cache_lod(factor);
+        chunk.lod_voxel_count = chunk.lod_voxels(factor).len();
+        chunk.rebuild_ms = started.elapsed().as_secs_f32() * 1000.0;
+    });
+
+    for entity in marks.into_inner().unwrap() {
+        dirty.mark(entity);
+    }
+    stats.voxels_before_culling += voxels_before.into_inner();
+    stats.voxels_after_culling += voxels_after.into_inner();
+}
+
+fn clear_dirty_chunks(mut dirty: ResMut<DirtyChunks>) {
+    dirty.entities.clear();
+    dirty.lod_only.clear();
+}
+
+// Six planes as (normal, distance) with normals pointing inward, extracted from a
+// view-projection matrix (Gribb & Hartmann). Bevy uses reversed depth with the near
+// plane at z = w; its infinite far plane extracts to a plane every point passes.
+pub struct FrustumPlanes {
+    planes: [Vec4; 6],
+}
+
+impl FrustumPlanes {
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let row = |i: usize| view_projection.row(i);
+        Self {
+            planes: [
+                row(3) + row(0), // Left
+                row(3) - row(0), // Right
+                row(3) + row(1), // Bottom
+                row(3) - row(1), // Top
+                row(3) - row(2), // Near
+                row(2),          // Far
+            ],
+        }
+    }
+
+    // False only when the box lies entirely behind one plane. Boxes near a frustum
+    // corner can pass while outside, which only costs drawing them.
+    pub fn intersects_aabb(&self, min: Vec3, max: Vec3) -> bool {
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate();
+            // The corner furthest along the plane normal
+            let positive = Vec3::select(normal.cmpge(Vec3::ZERO), max, min);
+            normal.dot(positive) + plane.w >= 0.0
+        })
+    }
+}
+
+// Chunk positions the camera can see into, from a flood fill over the chunk grid
+// that starts at the camera's chunk and doesn't pass through full chunks ("cave
+// culling"). Positions without a chunk count as air.
+#[derive(Resource, Default)]
+pub struct ChunkOcclusion {
+    camera_chunk: Option<IVec3>,
+    // None until the first fill, when nothing is occluded
+    reachable: Option<HashSet<IVec3>>,
+}
+
+impl ChunkOcclusion {
+    pub fn is_reachable(&self, position: IVec3) -> bool {
+        match &self.reachable {
+            Some(reachable) => reachable.contains(&position),
+            None => true,
+        }
     }
 
-    info!("Created cube with {} voxels", voxels.len());
-    
-    // Create chunk and apply occlusion culling before spawning
-    let mut chunk = VoxelChunk::new(IVec3::ZERO, voxels);
-    chunk.filter_occluded_voxels();
-    info!("After occlusion culling: {} voxels", chunk.voxels.len());
-    
-    commands.spawn(chunk);
+    // The fill stays inside the box around the loaded chunks and the camera, so it
+    // can't go around the world through the empty space outside it
+    fn fill(camera_chunk: IVec3, min: IVec3, max: IVec3, full: &HashSet<IVec3>) -> HashSet<IVec3> {
+        let mut reachable = HashSet::default();
+        let mut queue = VecDeque::from([camera_chunk]);
+        reachable.insert(camera_chunk);
+        while let Some(position) = queue.pop_front() {
+            // Full chunks are seen but not seen through, unless the camera is inside one
+            if position != camera_chunk && full.contains(&position) {
+                continue;
+            }
+            for offset in FACE_NEIGHBORS {
+                let next = position + IVec3::new(offset.x, offset.y, offset.z);
+                let inside = next.cmpge(min).all() && next.cmple(max).all();
+                if inside && reachable.insert(next) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        reachable
+    }
 }
 
-// System to apply occlusion culling when chunks are modified
-pub fn apply_occlusion_culling(
-    mut chunks: Query<&mut VoxelChunk, Changed<VoxelChunk>>,
+// Refills when the camera enters another chunk or the set of chunks changes
+fn update_chunk_occlusion(
+    settings: Res<VoxelRenderSettings>,
+    motion: Res<CameraMotion>,
+    chunks: Query<Ref<VoxelChunk>>,
+    mut removed: RemovedComponents<VoxelChunk>,
+    dirty: Res<DirtyChunks>,
+    mut occlusion: ResMut<ChunkOcclusion>,
+    mut timings: ResMut<SystemTimings>,
 ) {
-    for mut chunk in chunks.iter_mut() {
-        chunk.filter_occluded_voxels();
+    let _timing = timings.span("chunk occlusion");
+    let Some(view) = motion.view() else {
+        return;
+    };
+    let chunk_extent = CHUNK_SIZE as f32 * settings.voxel_size;
+    let camera_chunk = (view.translation / chunk_extent).floor().as_ivec3();
+
+    // Level of detail changes leave occupancy alone
+    let chunks_changed = removed.read().count() > 0
+        || dirty.iter().any(|entity| dirty.voxels_changed(entity))
+        || chunks.iter().any(|chunk| chunk.is_added());
+    if occlusion.camera_chunk == Some(camera_chunk) && !chunks_changed {
+        return;
+    }
+
+    let mut min = camera_chunk;
+    let mut max = camera_chunk;
+    let mut full = HashSet::default();
+    for chunk in chunks.iter() {
+        min = min.min(chunk.position);
+        max = max.max(chunk.position);
+        if chunk.occupancy.is_full() {
+            full.insert(chunk.position);
+        }
+    }
+
+    occlusion.camera_chunk = Some(camera_chunk);
+    occlusion.reachable = Some(ChunkOcclusion::fill(camera_chunk, min, max, &full));
+}
+
+// Chunks per bucket edge in ChunkSpatialIndex
+const INDEX_BUCKET_CHUNKS: i32 = 8;
+
+// Coarse grid over chunk positions, so culling can visit the chunks near the camera
+// instead of every chunk entity. Buckets are INDEX_BUCKET_CHUNKS chunks on a side.
+#[derive(Resource, Default)]
+pub struct ChunkSpatialIndex {
+    buckets: HashMap<IVec3, Vec<(IVec3, Entity)>>,
+    positions: HashMap<Entity, IVec3>,
+    by_position: HashMap<IVec3, Entity>,
+    // World size of a chunk, from the voxel size
+    chunk_extent: f32,
+}
+
+impl ChunkSpatialIndex {
+    pub fn len(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn insert(&mut self, entity: Entity, position: IVec3) {
+        self.remove(entity);
+        let bucket = position.div_euclid(IVec3::splat(INDEX_BUCKET_CHUNKS));
+        self.buckets.entry(bucket).or_default().push((position, entity));
+        self.positions.insert(entity, position);
+        self.by_position.insert(position, entity);
+    }
+
+    fn remove(&mut self, entity: Entity) {
+        let Some(position) = self.positions.remove(&entity) else {
+            return;
+        };
+        if self.by_position.get(&position) == Some(&entity) {
+            self.by_position.remove(&position);
+        }
+        let bucket = position.div_euclid(IVec3::splat(INDEX_BUCKET_CHUNKS));
+        if let Some(entries) = self.buckets.get_mut(&bucket) {
+            entries.retain(|(_, indexed)| *indexed != entity);
+            if entries.is_empty() {
+                self.buckets.remove(&bucket);
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.positions.keys().copied()
+    }
+
+    pub fn chunk_at(&self, position: IVec3) -> Option<Entity> {
+        self.by_position.get(&position).copied()
+    }
+
+    // Lowest and highest chunk y any loaded chunk could have, to bucket precision
+    pub fn chunk_y_range(&self) -> Option<(i32, i32)> {
+        let min = self.buckets.keys().map(|bucket| bucket.y).min()?;
+        let max = self.buckets.keys().map(|bucket| bucket.y).max()?;
+        Some((min * INDEX_BUCKET_CHUNKS, (max + 1) * INDEX_BUCKET_CHUNKS - 1))
+    }
+
+    // Chunks whose center is within radius of center, in world units
+    pub fn chunks_within_sphere(&self, center: Vec3, radius: f32) -> Vec<Entity> {
+        let bucket_extent = self.chunk_extent * INDEX_BUCKET_CHUNKS as f32;
+        if bucket_extent <= 0.0 {
+            return Vec::new();
+        }
+        let min = ((center - radius) / bucket_extent).floor().as_ivec3();
+        let max = ((center + radius) / bucket_extent).floor().as_ivec3();
+        let radius_squared = radius * radius;
+
+        let mut found = Vec::new();
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let Some(entries) = self.buckets.get(&IVec3::new(x, y, z)) else {
+                        continue;
+                    };
+                    found.extend(entries.iter().filter_map(|(position, entity)| {
+                        let chunk_center = (position.as_vec3() + Vec3::splat(0.5)) * self.chunk_extent;
+                        (chunk_center.distance_squared(center) <= radius_squared).then_some(*entity)
+                    }));
+                }
+            }
+        }
+        found
+    }
+
+    // Chunks whose full cube touches the frustum. Buckets are tested first, so whole
+    // buckets outside it are skipped.
+    pub fn chunks_intersecting_frustum(&self, frustum: &FrustumPlanes) -> Vec<Entity> {
+        let bucket_extent = self.chunk_extent * INDEX_BUCKET_CHUNKS as f32;
+        let mut found = Vec::new();
+        for (bucket, entries) in self.buckets.iter() {
+            let bucket_min = bucket.as_vec3() * bucket_extent;
+            if !frustum.intersects_aabb(bucket_min, bucket_min + Vec3::splat(bucket_extent)) {
+                continue;
+            }
+            found.extend(entries.iter().filter_map(|(position, entity)| {
+                let min = position.as_vec3() * self.chunk_extent;
+                frustum
+                    .intersects_aabb(min, min + Vec3::splat(self.chunk_extent))
+                    .then_some(*entity)
+            }));
+        }
+        found
     }
 }
 
+// Voxel lookups across chunk borders, in world voxel coordinates
+#[derive(SystemParam)]
+pub struct VoxelWorld<'w, 's> {
+    index: Res<'w, ChunkSpatialIndex>,
+    chunks: Query<'w, 's, &'static VoxelChunk>,
+    settings: Res<'w, VoxelRenderSettings>,
+}
+
+impl VoxelWorld<'_, '_> {
+    pub fn voxel_size(&self) -> f32 {
+        self.settings.voxel_size
+    }
+
+    pub fn chunk(&self, position: IVec3) -> Option<&VoxelChunk> {
+        self.index.chunk_at(position).and_then(|entity| self.chunks.get(entity).ok())
+    }
+
+    // Stored voxel at a cell, exposed or hidden. Solid cells a generator left
+    // without a voxel read as None.
+    pub fn voxel(&self, world: IVec3) -> Option<&Voxel> {
+        let (chunk, local) = split_world(world);
+        let chunk = self.chunk(chunk)?;
+        chunk
+            .voxels
+            .iter()
+            .chain(chunk.hidden_voxels.iter())
+            .find(|voxel| LocalPos::from_vec3(voxel.position) == local)
+    }
+
+    // Opaque voxels only; cells in chunks that aren't loaded read as empty
+    pub fn is_solid(&self, world: IVec3) -> bool {
+        let (chunk, local) = split_world(world);
+        self.chunk(chunk).is_some_and(|chunk| chunk.occupancy.is_solid(local))
+    }
+
+    // Height of the highest solid voxel in the column at or below a voxel, searching
+    // down at most depth voxels
+    pub fn ground_below(&self, world: IVec3, depth: i32) -> Option<i32> {
+        (world.y - depth..=world.y).rev().find(|y| self.is_solid(IVec3::new(world.x, *y, world.z)))
+    }
+}
+
+// World holding the chunks, already indexed, for tests that read it through a
+// SystemState<VoxelWorld>. Voxels are one world unit.
+#[cfg(test)]
+pub(crate) fn test_world(chunks: Vec<VoxelChunk>) -> World {
+    let mut world = World::new();
+    let mut index = ChunkSpatialIndex {
+        chunk_extent: CHUNK_SIZE as f32,
+        ..default()
+    };
+    for chunk in chunks {
+        let position = chunk.position;
+        let entity = world.spawn(chunk).id();
+        index.insert(entity, position);
+    }
+    world.insert_resource(index);
+    world.insert_resource(VoxelRenderSettings::default());
+    world
+}
+
+// Chunks don't move once spawned, so only additions and removals need tracking
+fn update_chunk_spatial_index(
+    settings: Res<VoxelRenderSettings>,
+    added: Query<(Entity, &VoxelChunk), Added<VoxelChunk>>,
+    mut removed: RemovedComponents<VoxelChunk>,
+    mut index: ResMut<ChunkSpatialIndex>,
+) {
+    let chunk_extent = CHUNK_SIZE as f32 * settings.voxel_size;
+    if index.chunk_extent != chunk_extent {
+        index.chunk_extent = chunk_extent;
+    }
+    for entity in removed.read() {
+        index.remove(entity);
+    }
+    for (entity, chunk) in added.iter() {
+        index.insert(entity, chunk.position);
+    }
+}
+
+// Camera movement below this, in world units, counts as standing still
+const CAMERA_MOTION_EPSILON: f32 = 1e-4;
+
+// Camera view the culling and LOD systems work from
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct CullingView {
+    pub view_projection: Mat4,
+    pub translation: Vec3,
+    pub forward: Vec3,
+    pub orthographic: bool,
+}
+
+impl CullingView {
+    // Distance used for render distance and LOD. An orthographic view sees the same
+    // at any depth, so only the offset across the view counts.
+    pub fn distance_to(&self, point: Vec3) -> f32 {
+        let offset = point - self.translation;
+        if self.orthographic {
+            offset.reject_from_normalized(self.forward).length()
+        } else {
+            offset.length()
+        }
+    }
+}
+
+// A snapshot of the view that culling and LOD use in place of the live camera, so
+// the culled region can be looked at from outside. Ctrl+5 toggles it.
+#[derive(Resource, Default)]
+pub struct FrozenView {
+    pub view: Option<CullingView>,
+}
+
+// Whether the camera's view changed since last frame. Camera-driven systems skip
+// their work while it hasn't and nothing else they depend on changed.
+#[derive(Resource, Default)]
+pub struct CameraMotion {
+    // View of the last frame, frozen or live; None until the camera exists
+    view: Option<CullingView>,
+    pub moved: bool,
+}
+
+impl CameraMotion {
+    pub fn view(&self) -> Option<CullingView> {
+        self.view
+    }
+}
+
+// Reads the GlobalTransform the culling systems use, so a resize or FOV change
+// counts as movement too. A frozen view stands still however the camera moves.
+fn track_camera_motion(
+    camera: Query<(&Camera, &GlobalTransform, &Projection), With<CameraController>>,
+    frozen: Res<FrozenView>,
+    mut motion: ResMut<CameraMotion>,
+) {
+    let view = match frozen.view {
+        Some(view) => view,
+        None => {
+            let Ok((camera, camera_transform, projection)) = camera.get_single() else {
+                return;
+            };
+            CullingView {
+                view_projection: camera.projection_matrix() * camera_transform.compute_matrix().inverse(),
+                translation: camera_transform.translation(),
+                forward: camera_transform.forward(),
+                orthographic: matches!(projection, Projection::Orthographic(_)),
+            }
+        }
+    };
+    let moved = match motion.view {
+        Some(last) => {
+            view.translation.distance(last.translation) > CAMERA_MOTION_EPSILON
+                || !view.view_projection.abs_diff_eq(last.view_projection, CAMERA_MOTION_EPSILON)
+        }
+        None => true,
+    };
+    if moved || motion.moved {
+        *motion = CameraMotion {
+            view: Some(view),
+            moved,
+        };
+    }
+}
+
+// Hides culled chunks through their Visibility, which their render children inherit.
+// The chunk is only written when its reason changes, so it isn't flagged as changed
+// every frame. Only chunks the index finds in range and in view are tested, along
+// with last frame's and new ones; everything else is already hidden. Nothing is
+// tested while the camera, settings, chunk set and occlusion all stay the same.
+#[allow(clippy::too_many_arguments)]
 fn update_chunk_visibility(
-    mut chunks: Query<(&mut VoxelChunk, &GlobalTransform)>,
-    camera: Query<(&Camera, &GlobalTransform)>,
+    mut chunks: Query<(&mut VoxelChunk, &mut Visibility)>,
+    added: Query<Entity, Added<VoxelChunk>>,
     settings: Res<VoxelRenderSettings>,
+    lod: Res<LodSettings>,
+    occlusion: Res<ChunkOcclusion>,
+    index: Res<ChunkSpatialIndex>,
+    motion: Res<CameraMotion>,
+    dirty: Res<DirtyChunks>,
+    mut stats: ResMut<CullingStats>,
+    mut last_candidates: Local<HashSet<Entity>>,
+    mut last_counts: Local<CullingStats>,
+    mut timings: ResMut<SystemTimings>,
 ) {
-    if let Ok((camera, camera_transform)) = camera.get_single() {
-        let view_projection = camera.projection_matrix() * camera_transform.compute_matrix();
-        
-        for (mut chunk, transform) in chunks.iter_mut() {
-            let chunk_center = transform.translation();
-            let radius = (CHUNK_SIZE as f32) * 0.866; // Approximate radius of chunk
-            
-            // Distance-based culling
-            let distance = (chunk_center - camera_transform.translation()).length();
-            if distance > settings.render_distance {
-                chunk.visible = false;
+    let _timing = timings.span("culling");
+    let unchanged = !motion.moved
+        && !settings.is_changed()
+        && !lod.is_changed()
+        && !occlusion.is_changed()
+        && !index.is_changed()
+        && !dirty.iter().any(|entity| dirty.voxels_changed(entity));
+    if unchanged {
+        stats.add_cull_counts(&last_counts);
+        return;
+    }
+
+    if let Some(view) = motion.view() {
+        let frustum = FrustumPlanes::from_view_projection(view.view_projection);
+        let mut counts = CullingStats::default();
+        let voxel_size = settings.voxel_size;
+
+        // Both queries test whole chunk cubes, so they keep every chunk the precise
+        // tests below could pass
+        // The sphere only bounds the range of a perspective view
+        let in_range = if settings.distance_culling && !view.orthographic {
+            index.chunks_within_sphere(view.translation, settings.render_distance)
+        } else {
+            index.iter().collect()
+        };
+        let candidates: HashSet<Entity> = if settings.frustum_culling {
+            let in_view: HashSet<Entity> = index.chunks_intersecting_frustum(&frustum).into_iter().collect();
+            in_range.iter().copied().filter(|entity| in_view.contains(entity)).collect()
+        } else {
+            in_range.iter().copied().collect()
+        };
+        // Chunks left out by the index are counted without being visited
+        counts.chunks_tested += index.len() - candidates.len();
+        counts.culled_distance += index.len() - in_range.len();
+        counts.culled_frustum += in_range.len() - candidates.len();
+
+        let to_test: HashSet<Entity> = candidates
+            .iter()
+            .chain(last_candidates.iter())
+            .copied()
+            .chain(added.iter())
+            .collect();
+        for entity in to_test {
+            let Ok((mut chunk, mut visibility)) = chunks.get_mut(entity) else {
                 continue;
+            };
+            let distance = view.distance_to(chunk.world_center(voxel_size));
+            // Bounds are in voxel units
+            let min = Vec3::from(chunk.bounds.min()) * voxel_size;
+            let max = Vec3::from(chunk.bounds.max()) * voxel_size;
+            let was_impostor = chunk.cull_reason == Some(CullReason::Impostor);
+
+            let reason = if settings.distance_culling && distance > settings.render_distance {
+                Some(CullReason::Distance)
+            } else if settings.frustum_culling && !frustum.intersects_aabb(min, max) {
+                Some(CullReason::Frustum)
+            } else if settings.chunk_occlusion && !occlusion.is_reachable(chunk.position) {
+                Some(CullReason::Occluded)
+            } else if lod.is_impostor(distance, was_impostor) {
+                // Past the last LOD band a single impostor quad stands in for the voxels
+                Some(CullReason::Impostor)
+            } else {
+                None
+            };
+            if candidates.contains(&entity) {
+                counts.record_cull(reason);
+            }
+
+            if chunk.cull_reason != reason {
+                chunk.cull_reason = reason;
+            }
+            let target = if reason.is_some() { Visibility::Hidden } else { Visibility::Inherited };
+            if *visibility != target {
+                *visibility = target;
             }
-            
-            // Frustum culling
-            let view_space_pos = view_projection * chunk_center.extend(1.0);
-            chunk.visible = view_space_pos.w > 0.0 && 
-                           view_space_pos.x.abs() <= view_space_pos.w + radius &&
-                           view_space_pos.y.abs() <= view_space_pos.w + radius &&
-                           view_space_pos.z >= -radius;
+        }
+        *last_candidates = candidates;
+        stats.add_cull_counts(&counts);
+        *last_counts = counts;
+    }
+}
+
+// Per-frame counters from the culling and render systems, reset in First. Systems
+// spreading work across threads total their counts before adding them here.
+#[derive(Resource, Default, Clone)]
+pub struct CullingStats {
+    pub chunks_tested: usize,
+    pub culled_distance: usize,
+    pub culled_frustum: usize,
+    pub culled_occluded: usize,
+    pub impostors: usize,
+    // Voxels in chunks whose face masks were recomputed, before and after setting
+    // aside the ones with no exposed face
+    pub voxels_before_culling: usize,
+    pub voxels_after_culling: usize,
+    // Entities drawing chunks (render children, impostor quads, shadow proxies)
+    pub entities_spawned: usize,
+    pub entities_despawned: usize,
+}
+
+impl CullingStats {
+    fn record_cull(&mut self, reason: Option<CullReason>) {
+        self.chunks_tested += 1;
+        match reason {
+            Some(CullReason::Distance) => self.culled_distance += 1,
+            Some(CullReason::Frustum) => self.culled_frustum += 1,
+            Some(CullReason::Occluded) => self.culled_occluded += 1,
+            Some(CullReason::Impostor) => self.impostors += 1,
+            None => {}
+        }
+    }
+
+    fn add_cull_counts(&mut self, other: &CullingStats) {
+        self.chunks_tested += other.chunks_tested;
+        self.culled_distance += other.culled_distance;
+        self.culled_frustum += other.culled_frustum;
+        self.culled_occluded += other.culled_occluded;
+        self.impostors += other.impostors;
+    }
+}
+
+// Weight of the newest sample in each moving average
+const TIMING_SMOOTHING: f64 = 0.05;
+// Sections not sampled for this long, such as inactive render paths, aren't listed
+const TIMING_STALE_SECS: f32 = 1.0;
+
+// Moving average milliseconds spent in the voxel and render systems' hot sections,
+// by name, with when each was last sampled. Render paths start timing after
+// their mode check.
+#[derive(Resource, Default)]
+pub struct SystemTimings {
+    averages: HashMap<&'static str, (f64, Instant)>,
+}
+
+impl SystemTimings {
+    // Times until the returned span is dropped, usually the end of the system
+    pub fn span(&mut self, name: &'static str) -> TimingSpan<'_> {
+        TimingSpan {
+            timings: self,
+            name,
+            started: Instant::now(),
         }
     }
+
+    fn record(&mut self, name: &'static str, ms: f64) {
+        let now = Instant::now();
+        self.averages
+            .entry(name)
+            .and_modify(|(average, sampled)| {
+                *average += (ms - *average) * TIMING_SMOOTHING;
+                *sampled = now;
+            })
+            .or_insert((ms, now));
+    }
+
+    // Slowest first, leaving out stale sections
+    pub fn slowest(&self, count: usize) -> Vec<(&'static str, f64)> {
+        let mut entries: Vec<(&'static str, f64)> = self
+            .averages
+            .iter()
+            .filter(|(_, (_, sampled))| sampled.elapsed().as_secs_f32() < TIMING_STALE_SECS)
+            .map(|(name, (ms, _))| (*name, *ms))
+            .collect();
+        entries.sort_by(|a, b| b.1.total_cmp(&a.1));
+        entries.truncate(count);
+        entries
+    }
 }
 
+pub struct TimingSpan<'a> {
+    timings: &'a mut SystemTimings,
+    name: &'static str,
+    started: Instant,
+}
+
+impl Drop for TimingSpan<'_> {
+    fn drop(&mut self) {
+        let ms = self.started.elapsed().as_secs_f64() * 1000.0;
+        self.timings.record(self.name, ms);
+    }
+}
+
+fn reset_culling_stats(mut stats: ResMut<CullingStats>) {
+    *stats = CullingStats::default();
+}
+
+// Chunks changing level go through the dirty queue so renderers rebuild them. While
+// the camera and settings stay the same only new chunks are evaluated.
 fn update_voxel_lod(
-    mut chunks: Query<(&mut VoxelChunk, &GlobalTransform)>,
-    camera: Query<&Transform, With<Camera>>,
+    mut chunks: Query<(Entity, &mut VoxelChunk, &GlobalTransform)>,
     settings: Res<LodSettings>,
+    motion: Res<CameraMotion>,
+    mut dirty: ResMut<DirtyChunks>,
+    mut timings: ResMut<SystemTimings>,
 ) {
-    if let Ok(camera_transform) = camera.get_single() {
-        let camera_pos = camera_transform.translation;
-        
-        for (mut chunk, transform) in chunks.iter_mut() {
-            let distance = (transform.translation() - camera_pos).length();
-            
-            // Update LOD level based on distance
-            for (i, (threshold, _)) in settings.distances.iter().enumerate() {
-                if distance <= *threshold {
-                    chunk.lod_level = i;
-                    break;
+    let _timing = timings.span("lod");
+    let evaluate_all = motion.moved || settings.is_changed();
+    if let Some(view) = motion.view() {
+        for (entity, mut chunk, transform) in chunks.iter_mut() {
+            if !evaluate_all && !chunk.is_added() {
+                continue;
+            }
+            let distance = view.distance_to(transform.translation());
+            let level = settings.level_for(distance, chunk.lod_level);
+            if level != chunk.lod_level {
+                chunk.lod_level = level;
+                dirty.mark_lod_change(entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Looking down -z from the origin with a 90° square view, so the side planes sit
+    // at |x| = -z and |y| = -z. Near and far are swapped for reversed depth, near at
+    // 0.1 and far at 100.
+    fn test_frustum() -> FrustumPlanes {
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 100.0, 0.1);
+        FrustumPlanes::from_view_projection(projection)
+    }
+
+    #[test]
+    fn frustum_keeps_boxes_straddling_each_plane() {
+        let frustum = test_frustum();
+        let straddling = [
+            ("left", Vec3::new(-12.0, -1.0, -11.0), Vec3::new(-8.0, 1.0, -9.0)),
+            ("right", Vec3::new(8.0, -1.0, -11.0), Vec3::new(12.0, 1.0, -9.0)),
+            ("bottom", Vec3::new(-1.0, -12.0, -11.0), Vec3::new(1.0, -8.0, -9.0)),
+            ("top", Vec3::new(-1.0, 8.0, -11.0), Vec3::new(1.0, 12.0, -9.0)),
+            ("near", Vec3::new(-0.01, -0.01, -1.0), Vec3::new(0.01, 0.01, 1.0)),
+            ("far", Vec3::new(-1.0, -1.0, -102.0), Vec3::new(1.0, 1.0, -98.0)),
+        ];
+        for (plane, min, max) in straddling {
+            assert!(frustum.intersects_aabb(min, max), "box across the {} plane was culled", plane);
+        }
+    }
+
+    #[test]
+    fn frustum_culls_boxes_outside_each_plane() {
+        let frustum = test_frustum();
+        let outside = [
+            ("left", Vec3::new(-14.0, -1.0, -11.0), Vec3::new(-12.0, 1.0, -9.0)),
+            ("right", Vec3::new(12.0, -1.0, -11.0), Vec3::new(14.0, 1.0, -9.0)),
+            ("bottom", Vec3::new(-1.0, -14.0, -11.0), Vec3::new(1.0, -12.0, -9.0)),
+            ("top", Vec3::new(-1.0, 12.0, -11.0), Vec3::new(1.0, 14.0, -9.0)),
+            ("near", Vec3::new(-1.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 1.0)),
+            ("far", Vec3::new(-1.0, -1.0, -110.0), Vec3::new(1.0, 1.0, -105.0)),
+        ];
+        for (plane, min, max) in outside {
+            assert!(!frustum.intersects_aabb(min, max), "box past the {} plane was kept", plane);
+        }
+        assert!(frustum.intersects_aabb(Vec3::new(-1.0, -1.0, -11.0), Vec3::new(1.0, 1.0, -9.0)));
+    }
+
+    // 10k chunks in a 25 × 16 × 25 block around the origin, indexed as the app does,
+    // with the flat list a full iteration would walk
+    fn index_10k_chunks() -> (ChunkSpatialIndex, Vec<(Entity, IVec3)>) {
+        let mut index = ChunkSpatialIndex {
+            chunk_extent: CHUNK_SIZE as f32,
+            ..default()
+        };
+        let mut all = Vec::new();
+        for i in 0..10_000 {
+            let position = IVec3::new(i % 25 - 12, i / 25 % 16 - 8, i / 400 - 12);
+            let entity = Entity::from_raw(i as u32);
+            index.insert(entity, position);
+            all.push((entity, position));
+        }
+        (index, all)
+    }
+
+    fn sphere_by_iteration(all: &[(Entity, IVec3)], center: Vec3, radius: f32) -> Vec<Entity> {
+        all.iter()
+            .filter(|(_, position)| ((position.as_vec3() + Vec3::splat(0.5)) * CHUNK_SIZE as f32).distance(center) <= radius)
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    fn frustum_by_iteration(all: &[(Entity, IVec3)], frustum: &FrustumPlanes) -> Vec<Entity> {
+        all.iter()
+            .filter(|(_, position)| {
+                let min = position.as_vec3() * CHUNK_SIZE as f32;
+                frustum.intersects_aabb(min, min + Vec3::splat(CHUNK_SIZE as f32))
+            })
+            .map(|(entity, _)| *entity)
+            .collect()
+    }
+
+    fn sorted(mut entities: Vec<Entity>) -> Vec<Entity> {
+        entities.sort_by_key(|entity| entity.to_bits());
+        entities
+    }
+
+    #[test]
+    fn indexed_queries_match_full_iteration_over_10k_chunks() {
+        let (index, all) = index_10k_chunks();
+        assert_eq!(index.len(), 10_000);
+
+        // Chunk (0, 0, 0) is centered on (8, 8, 8) and is entity 5012
+        let center = Vec3::splat(8.0);
+        assert_eq!(index.chunks_within_sphere(center, 0.0), [Entity::from_raw(5012)]);
+        for radius in [40.0, 100.0, 1000.0] {
+            let found = sorted(index.chunks_within_sphere(center, radius));
+            assert_eq!(found, sorted(sphere_by_iteration(&all, center, radius)), "radius {}", radius);
+        }
+        assert_eq!(index.chunks_within_sphere(center, 1000.0).len(), all.len());
+
+        let frustum = test_frustum();
+        let in_view = sorted(index.chunks_intersecting_frustum(&frustum));
+        assert_eq!(in_view, sorted(frustum_by_iteration(&all, &frustum)));
+        assert!(!in_view.is_empty() && in_view.len() < all.len());
+    }
+
+    // Full iteration against the index over 10k chunks, best of several runs each.
+    // Timing depends on the machine, so it only runs on demand:
+    // cargo test --release spatial_index_queries -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn spatial_index_queries_beat_full_iteration_over_10k_chunks() {
+        const RUNS: usize = 20;
+        let (index, all) = index_10k_chunks();
+        let (center, frustum) = (Vec3::splat(8.0), test_frustum());
+        let best = |query: &dyn Fn() -> Vec<Entity>| {
+            (0..RUNS)
+                .map(|_| {
+                    let started = Instant::now();
+                    std::hint::black_box(query());
+                    started.elapsed()
+                })
+                .min()
+                .unwrap()
+        };
+
+        let sphere_full = best(&|| sphere_by_iteration(&all, center, 100.0));
+        let sphere_indexed = best(&|| index.chunks_within_sphere(center, 100.0));
+        let frustum_full = best(&|| frustum_by_iteration(&all, &frustum));
+        let frustum_indexed = best(&|| index.chunks_intersecting_frustum(&frustum));
+        println!("10k chunks, sphere of 100: full {:.2?}, indexed {:.2?}", sphere_full, sphere_indexed);
+        println!("10k chunks, frustum: full {:.2?}, indexed {:.2?}", frustum_full, frustum_indexed);
+        assert!(sphere_indexed < sphere_full, "sphere query took {:.2?} against {:.2?}", sphere_indexed, sphere_full);
+        assert!(frustum_indexed < frustum_full, "frustum query took {:.2?} against {:.2?}", frustum_indexed, frustum_full);
+    }
+
+    #[test]
+    fn lod_levels_for_default_thresholds() {
+        let lod = LodSettings::default();
+        let expected = [(0.0, 0), (25.0, 0), (50.0, 1), (75.0, 1), (100.0, 2), (500.0, 2)];
+        for (distance, level) in expected {
+            assert_eq!(lod.band(distance), level, "band at {}", distance);
+            assert_eq!(lod.level_for(distance, level), level, "level kept at {}", distance);
+        }
+        // Away from the thresholds, the previous level doesn't matter
+        for (distance, level) in [(0.0, 0), (25.0, 0), (75.0, 1), (500.0, 2)] {
+            for current in 0..lod.distances.len() {
+                assert_eq!(lod.level_for(distance, current), level, "from level {} at {}", current, distance);
+            }
+        }
+    }
+
+    #[test]
+    fn lod_level_holds_inside_hysteresis_margin() {
+        let lod = LodSettings::default();
+        // 10% of the 0 to 50 band below the threshold at 50
+        let mut level = 0;
+        for (distance, expected) in [(48.0, 0), (53.0, 0), (47.0, 0), (54.0, 0), (56.0, 1), (47.0, 1), (53.0, 1), (46.0, 1), (44.0, 0)] {
+            level = lod.level_for(distance, level);
+            assert_eq!(level, expected, "at {}", distance);
+        }
+
+        // The impostor threshold at 150 is 50 past the last threshold, so its margin is 5
+        assert!(!lod.is_impostor(153.0, false));
+        assert!(lod.is_impostor(153.0, true));
+        assert!(lod.is_impostor(156.0, false));
+        assert!(!lod.is_impostor(144.0, true));
+    }
+
+    #[test]
+    fn last_lod_band_is_drawn_before_impostors_take_over() {
+        let lod = LodSettings::default();
+        let last = lod.distances.len() - 1;
+        // Walking a chunk outwards, it reaches the last band while still drawn as voxels
+        let (mut level, mut distance) = (0, 0.0);
+        while !lod.is_impostor(distance, false) {
+            level = lod.level_for(distance, level);
+            distance += 1.0;
+        }
+        assert_eq!(level, last, "impostors start at {} before the last band", distance);
+        assert_eq!(lod.factor(level), 4);
+    }
+
+    // A 4×4×4 block of chunks, mostly solid with scattered holes so border masks
+    // depend on the neighbors
+    fn stress_chunks() -> Vec<VoxelChunk> {
+        let mut chunks = Vec::new();
+        for position in (0..64).map(|i| IVec3::new(i % 4, i / 4 % 4, i / 16)) {
+            let mut voxels = Vec::new();
+            for z in 0..CHUNK_SIZE {
+                for y in 0..CHUNK_SIZE {
+                    for x in 0..CHUNK_SIZE {
+                        let cell = position * CHUNK_SIZE + IVec3::new(x, y, z);
+                        if (cell.x + cell.y * 3 + cell.z * 7) % 5 != 0 {
+                            voxels.push(Voxel {
+                                position: Vec3::new(x as f32, y as f32, z as f32),
+                                color: Color::rgb(0.5, 0.4, 0.3),
+                                kind: crate::voxel_types::KIND_PLAIN,
+                            });
+                        }
+                    }
                 }
             }
+            chunks.push(VoxelChunk::new(position, voxels));
+        }
+        chunks
+    }
+
+    // Visible cells with their masks and hidden cells, in a fixed order
+    fn mask_summary(chunk: &VoxelChunk) -> (Vec<(i32, i32, i32, FaceMask)>, Vec<(i32, i32, i32)>) {
+        let cell = |voxel: &Voxel| {
+            let pos = LocalPos::from_vec3(voxel.position);
+            (pos.x, pos.y, pos.z)
+        };
+        let mut visible: Vec<_> = chunk
+            .voxels
+            .iter()
+            .enumerate()
+            .map(|(index, voxel)| {
+                let (x, y, z) = cell(voxel);
+                (x, y, z, chunk.face_mask(index))
+            })
+            .collect();
+        let mut hidden: Vec<_> = chunk.hidden_voxels.iter().map(cell).collect();
+        visible.sort_unstable();
+        hidden.sort_unstable();
+        (visible, hidden)
+    }
+
+    // The serial pass, one chunk at a time with its neighbors gathered first
+    fn serial_face_masks(chunks: &mut [VoxelChunk]) {
+        let occupancies: HashMap<IVec3, ChunkOccupancy> =
+            chunks.iter().map(|chunk| (chunk.position, chunk.occupancy.clone())).collect();
+        for chunk in chunks {
+            let neighborhood = NeighborhoodOccupancy::gather(&chunk.occupancy, |offset| {
+                occupancies.get(&(chunk.position + offset))
+            });
+            chunk.update_face_masks(Some(&neighborhood), true);
         }
     }
-}
\ No newline at end of file
+
+    // An app running the parallel pass over the chunks, past the frame that loads them
+    fn dirty_chunk_app(chunks: Vec<VoxelChunk>) -> (App, Vec<Entity>) {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins)
+            .init_resource::<DirtyChunks>()
+            .init_resource::<LodSettings>()
+            .init_resource::<VoxelRenderSettings>()
+            .init_resource::<CullingStats>()
+            .init_resource::<SystemTimings>()
+            .add_systems(Update, process_dirty_chunks)
+            .add_systems(Last, clear_dirty_chunks);
+        let entities: Vec<Entity> = chunks.into_iter().map(|chunk| app.world.spawn(chunk).id()).collect();
+        app.update();
+        (app, entities)
+    }
+
+    fn mark_all_dirty(app: &mut App, entities: &[Entity]) {
+        for entity in entities {
+            app.world.resource_mut::<DirtyChunks>().mark(*entity);
+        }
+    }
+
+    // Dirties 64 chunks in one frame and checks the parallel pass matches a serial
+    // one chunk at a time
+    #[test]
+    fn parallel_face_masks_match_serial_for_64_dirty_chunks() {
+        let mut serial = stress_chunks();
+        serial_face_masks(&mut serial);
+        let expected: HashMap<IVec3, _> = serial.iter().map(|chunk| (chunk.position, mask_summary(chunk))).collect();
+
+        let (mut app, entities) = dirty_chunk_app(stress_chunks());
+        // A plain edit of all 64
+        mark_all_dirty(&mut app, &entities);
+        app.update();
+
+        for entity in entities {
+            let chunk = app.world.get::<VoxelChunk>(entity).unwrap();
+            assert_eq!(chunk.version, 1, "dirty chunk {:?} wasn't processed", chunk.position);
+            assert!(mask_summary(chunk) == expected[&chunk.position], "masks differ in chunk {:?}", chunk.position);
+        }
+    }
+
+    // Wall time of the serial pass against a frame that runs the parallel one, best
+    // of several runs each. Timing depends on the machine, so it only runs on demand:
+    // cargo test --release parallel_face_mask_frame -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn parallel_face_mask_frame_is_no_slower_than_serial() {
+        const RUNS: usize = 5;
+        let serial = (0..RUNS)
+            .map(|_| {
+                let mut chunks = stress_chunks();
+                let started = Instant::now();
+                serial_face_masks(&mut chunks);
+                started.elapsed()
+            })
+            .min()
+            .unwrap();
+
+        let (mut app, entities) = dirty_chunk_app(stress_chunks());
+        let parallel = (0..RUNS)
+            .map(|_| {
+                mark_all_dirty(&mut app, &entities);
+                let started = Instant::now();
+                app.update();
+                started.elapsed()
+            })
+            .min()
+            .unwrap();
+
+        println!("64 dirty chunks: serial {:.2?}, parallel frame {:.2?}", serial, parallel);
+        // The frame also runs the schedule around the pass, so it gets a quarter on top
+        assert!(
+            parallel <= serial + serial / 4,
+            "parallel frame took {:.2?} against {:.2?} serial",
+            parallel,
+            serial
+        );
+    }
+}
@@ -0,0 +1,179 @@
+// src/voxel/collision.rs
+use bevy::{prelude::*, render::primitives::Aabb};
+use super::{split_world, LocalPos, VoxelWorld, CHUNK_SIZE};
+
+// Boxes are shrunk by this fraction of a voxel, so one resting on a face or
+// touching a side doesn't count as inside the voxel there
+const CONTACT_EPSILON: f32 = 1e-3;
+
+pub(super) fn cells_between(min: IVec3, max: IVec3) -> impl Iterator<Item = IVec3> {
+    (min.x..=max.x).flat_map(move |x| (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z))))
+}
+
+// Box queries against solid voxels, in world units. Translucent voxels don't
+// collide, and unloaded chunks read as empty.
+impl VoxelWorld<'_, '_> {
+    // World voxel coordinates of the cells a box covers, inclusive
+    fn cell_range(&self, min: Vec3, max: Vec3) -> (IVec3, IVec3) {
+        let voxel_size = self.voxel_size();
+        let epsilon = CONTACT_EPSILON * voxel_size;
+        (((min + epsilon) / voxel_size).floor().as_ivec3(), ((max - epsilon) / voxel_size).floor().as_ivec3())
+    }
+
+    pub fn overlaps_solid(&self, aabb: &Aabb) -> bool {
+        self.solid_voxels_in(aabb).next().is_some()
+    }
+
+    // Chunk position and cell of each solid voxel inside the box. Each chunk is
+    // looked up once, so boxes spanning several chunks stay cheap.
+    pub fn solid_voxels_in(&self, aabb: &Aabb) -> impl Iterator<Item = (IVec3, LocalPos)> + '_ {
+        let (min, max) = self.cell_range(aabb.min().into(), aabb.max().into());
+        let (min_chunk, max_chunk) = (split_world(min).0, split_world(max).0);
+        cells_between(min_chunk, max_chunk)
+            .filter_map(move |position| self.chunk(position))
+            .flat_map(move |chunk| {
+                let origin = chunk.position * CHUNK_SIZE;
+                let low = (min - origin).max(IVec3::ZERO);
+                let high = (max - origin).min(IVec3::splat(CHUNK_SIZE - 1));
+                cells_between(low, high)
+                    .map(|cell| LocalPos::new(cell.x, cell.y, cell.z))
+                    .filter(move |local| chunk.occupancy.is_solid(*local))
+                    .map(move |local| (chunk.position, local))
+            })
+    }
+
+    // How far of velocity a box can move before touching solid voxels, resolving
+    // the vertical axis first and then each horizontal one from where the last
+    // left it, so a blocked axis slides along the others. Voxels the box already
+    // overlaps don't block it, so a box stuck inside rock can move out.
+    pub fn resolve_aabb(&self, aabb: &Aabb, velocity: Vec3) -> Vec3 {
+        let voxel_size = self.voxel_size();
+        let epsilon = CONTACT_EPSILON * voxel_size;
+        let (mut min, mut max): (Vec3, Vec3) = (aabb.min().into(), aabb.max().into());
+        let mut allowed = Vec3::ZERO;
+        for axis in [1, 0, 2] {
+            let delta = velocity[axis];
+            if delta == 0.0 {
+                continue;
+            }
+            // Layers of cells past the leading face, nearest first; the first with a
+            // solid voxel across the box's cross-section stops it at that layer
+            let (first, last, step) = if delta > 0.0 {
+                let first = ((max[axis] - epsilon) / voxel_size).floor() as i32 + 1;
+                (first, ((max[axis] + delta - epsilon) / voxel_size).floor() as i32, 1)
+            } else {
+                let first = ((min[axis] + epsilon) / voxel_size).floor() as i32 - 1;
+                (first, ((min[axis] + delta + epsilon) / voxel_size).floor() as i32, -1)
+            };
+            let (cross_min, cross_max) = self.cell_range(min, max);
+            let mut moved = delta;
+            let mut layer = first;
+            while (layer - last) * step <= 0 {
+                let (mut low, mut high) = (cross_min, cross_max);
+                low[axis] = layer;
+                high[axis] = layer;
+                if cells_between(low, high).any(|cell| self.is_solid(cell)) {
+                    moved = if step > 0 {
+                        (layer as f32 * voxel_size - max[axis]).clamp(0.0, delta)
+                    } else {
+                        ((layer + 1) as f32 * voxel_size - min[axis]).clamp(delta, 0.0)
+                    };
+                    break;
+                }
+                layer += step;
+            }
+            min[axis] += moved;
+            max[axis] += moved;
+            allowed[axis] = moved;
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::{ecs::system::SystemState, utils::HashSet};
+    use crate::voxel::{test_world, VoxelChunk};
+    use crate::voxel_types::Voxel;
+
+    fn chunk(position: IVec3, filled: impl Fn(i32, i32, i32) -> bool) -> VoxelChunk {
+        let voxels = cells_between(IVec3::ZERO, IVec3::splat(CHUNK_SIZE - 1))
+            .filter(|cell| filled(cell.x, cell.y, cell.z))
+            .map(|cell| Voxel {
+                position: cell.as_vec3(),
+                color: Color::rgb(0.5, 0.5, 0.52),
+                kind: 0,
+            })
+            .collect();
+        VoxelChunk::new(position, voxels)
+    }
+
+    // Chunk (-1, 0, 0) has a floor at y 0 and a wall at world x -3; chunk (0, 0, 0)
+    // has no floor and a wall at x 5. Both walls cover y 1..=3 and the chunks' z.
+    fn collision_world() -> World {
+        test_world(vec![
+            chunk(IVec3::new(-1, 0, 0), |x, y, _| y == 0 || (x == 13 && (1..=3).contains(&y))),
+            chunk(IVec3::ZERO, |x, y, _| x == 5 && (1..=3).contains(&y)),
+        ])
+    }
+
+    fn aabb(min: Vec3, max: Vec3) -> Aabb {
+        Aabb::from_min_max(min, max)
+    }
+
+    #[test]
+    fn boxes_straddling_a_chunk_border_find_voxels_on_both_sides() {
+        let mut world = collision_world();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let voxels = state.get(&world);
+
+        let found: HashSet<_> = voxels.solid_voxels_in(&aabb(Vec3::new(-4.5, 0.2, 2.2), Vec3::new(5.5, 1.8, 2.8))).collect();
+        let expected: HashSet<_> = [
+            (IVec3::new(-1, 0, 0), LocalPos::new(11, 0, 2)),
+            (IVec3::new(-1, 0, 0), LocalPos::new(12, 0, 2)),
+            (IVec3::new(-1, 0, 0), LocalPos::new(13, 0, 2)),
+            (IVec3::new(-1, 0, 0), LocalPos::new(14, 0, 2)),
+            (IVec3::new(-1, 0, 0), LocalPos::new(15, 0, 2)),
+            (IVec3::new(-1, 0, 0), LocalPos::new(13, 1, 2)),
+            (IVec3::new(0, 0, 0), LocalPos::new(5, 1, 2)),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(found, expected);
+        // Resting on the floor or against a wall isn't overlapping it
+        assert!(!voxels.overlaps_solid(&aabb(Vec3::new(-1.5, 1.0, 2.0), Vec3::new(1.5, 2.0, 3.0))));
+        assert!(!voxels.overlaps_solid(&aabb(Vec3::new(2.0, 1.0, 2.0), Vec3::new(5.0, 2.0, 3.0))));
+
+        // Falling across the border, the part over chunk -1 lands on its floor
+        let straddling = aabb(Vec3::new(-0.2, 3.0, 4.0), Vec3::new(0.8, 4.8, 5.0));
+        assert_eq!(voxels.resolve_aabb(&straddling, Vec3::new(0.0, -5.0, 0.0)), Vec3::new(0.0, -2.0, 0.0));
+        let over_chunk_0 = aabb(Vec3::new(0.1, 3.0, 4.0), Vec3::new(1.1, 4.8, 5.0));
+        assert_eq!(voxels.resolve_aabb(&over_chunk_0, Vec3::new(0.0, -5.0, 0.0)), Vec3::new(0.0, -5.0, 0.0));
+        // Sliding out of chunk 0 into chunk -1 stops at the wall there
+        let sliding = aabb(Vec3::new(1.0, 1.0, 4.0), Vec3::new(2.0, 2.5, 5.0));
+        assert_eq!(voxels.resolve_aabb(&sliding, Vec3::new(-10.0, 0.0, 0.0)), Vec3::new(-3.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn boxes_larger_than_a_chunk_see_every_chunk_they_cover() {
+        let mut world = collision_world();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let voxels = state.get(&world);
+
+        let everything = aabb(Vec3::new(-20.0, -1.0, -1.0), Vec3::new(20.0, 4.5, 20.0));
+        let found: Vec<_> = voxels.solid_voxels_in(&everything).collect();
+        // Floor, then both walls, each voxel once
+        assert_eq!(found.len(), 256 + 48 + 48);
+        assert_eq!(found.iter().collect::<HashSet<_>>().len(), found.len());
+        let chunks: HashSet<IVec3> = found.iter().map(|(chunk, _)| *chunk).collect();
+        assert_eq!(chunks, [IVec3::new(-1, 0, 0), IVec3::ZERO].into_iter().collect());
+
+        // Wider and deeper than a chunk, resting on the floor and already through
+        // the wall at x -3, which doesn't hold it back; the wall at x 5 does
+        let wide = aabb(Vec3::new(-18.0, 1.0, -2.0), Vec3::new(2.0, 3.0, 18.0));
+        assert!(voxels.overlaps_solid(&wide));
+        assert_eq!(voxels.resolve_aabb(&wide, Vec3::new(10.0, -4.0, 0.0)), Vec3::new(3.0, 0.0, 0.0));
+        assert_eq!(voxels.resolve_aabb(&wide, Vec3::new(-10.0, 0.0, 0.0)), Vec3::new(-10.0, 0.0, 0.0));
+    }
+}
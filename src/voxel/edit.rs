@@ -0,0 +1,309 @@
+// src/voxel/edit.rs
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
+use crate::voxel_types::{Voxel, VoxelRenderSettings};
+use super::collision::cells_between;
+use super::region::chunk_voxels;
+use super::{split_world, ChunkSpatialIndex, DirtyChunks, LocalPos, VoxelChunk, WorldRegion, ALL_FACES, CHUNK_SIZE, FACE_NEIGHBORS};
+
+// Oldest steps are dropped past this
+const MAX_UNDO_STEPS: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EditOp {
+    // Fills the cell, replacing whatever was there
+    Place { color: Color, kind: u16 },
+    // Fills the cell only if it's empty
+    PlaceEmpty { color: Color, kind: u16 },
+    Erase,
+    // Recolors a filled cell and leaves empty ones alone
+    Paint(Color),
+}
+
+// Sent for each chunk an edit, undo or redo changed, with the chunk's position
+#[derive(Event, Clone, Copy, Debug)]
+pub struct VoxelChanged {
+    pub chunk: IVec3,
+}
+
+// Cell changes batched by chunk, in world voxel coordinates. A later change to the
+// same cell replaces an earlier one.
+#[derive(Default)]
+pub struct VoxelEdits {
+    chunks: HashMap<IVec3, HashMap<LocalPos, EditOp>>,
+}
+
+impl VoxelEdits {
+    pub fn push(&mut self, world: IVec3, op: EditOp) {
+        let (chunk, local) = split_world(world);
+        self.chunks.entry(chunk).or_default().insert(local, op);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.values().map(HashMap::len).sum()
+    }
+
+    pub fn get(&self, world: IVec3) -> Option<&EditOp> {
+        let (chunk, local) = split_world(world);
+        self.chunks.get(&chunk)?.get(&local)
+    }
+}
+
+impl VoxelChunk {
+    // Applies edits to the voxel lists and occupancy. Face masks are left for
+    // process_dirty_chunks, so the chunk must be marked dirty when this returns Some.
+    // Solid cells a generator left without a voxel can be erased but not painted.
+    // Returns the previous contents of the changed cells, as edits that restore
+    // them; those voxel-less solid cells come back empty.
+    pub fn apply_edits(&mut self, edits: &HashMap<LocalPos, EditOp>) -> Option<HashMap<LocalPos, EditOp>> {
+        let mut pending = edits.clone();
+        let mut previous = HashMap::default();
+        // Cells whose opacity may have changed, and whether they're opaque now
+        let mut solidity = Vec::new();
+        let mut translucency_changed = false;
+
+        for list in [&mut self.voxels, &mut self.hidden_voxels] {
+            list.retain_mut(|voxel| {
+                let pos = LocalPos::from_vec3(voxel.position);
+                let Some(op) = pending.remove(&pos) else {
+                    return true;
+                };
+                let before = EditOp::Place {
+                    color: voxel.color,
+                    kind: voxel.kind,
+                };
+                let was_translucent = voxel.is_translucent();
+                match op {
+                    EditOp::Place { color, kind } => {
+                        voxel.color = color;
+                        voxel.kind = kind;
+                    }
+                    EditOp::Paint(color) => voxel.color = color,
+                    EditOp::PlaceEmpty { .. } => return true,
+                    EditOp::Erase => {
+                        solidity.push((pos, false));
+                        translucency_changed |= was_translucent;
+                        previous.insert(pos, before);
+                        return false;
+                    }
+                }
+                if (EditOp::Place { color: voxel.color, kind: voxel.kind }) != before {
+                    solidity.push((pos, !voxel.is_translucent()));
+                    translucency_changed |= was_translucent || voxel.is_translucent();
+                    previous.insert(pos, before);
+                }
+                true
+            });
+        }
+
+        // Cells with no voxel yet
+        for (pos, op) in pending {
+            match op {
+                // Solid without a voxel still counts as filled
+                EditOp::PlaceEmpty { .. } if self.occupancy.is_solid(pos) => {}
+                EditOp::Place { color, kind } | EditOp::PlaceEmpty { color, kind } => {
+                    let voxel = Voxel {
+                        position: Vec3::new(pos.x as f32, pos.y as f32, pos.z as f32),
+                        color,
+                        kind,
+                    };
+                    solidity.push((pos, !voxel.is_translucent()));
+                    translucency_changed |= voxel.is_translucent();
+                    previous.insert(pos, EditOp::Erase);
+                    self.voxels.push(voxel);
+                }
+                EditOp::Erase if self.occupancy.is_solid(pos) => solidity.push((pos, false)),
+                EditOp::Erase | EditOp::Paint(_) => {}
+            }
+        }
+        if solidity.is_empty() {
+            return None;
+        }
+
+        for (pos, solid) in solidity {
+            self.occupancy.set(pos, solid);
+        }
+        if translucency_changed {
+            self.occupancy.assign_translucent_groups(self.voxels.iter().chain(self.hidden_voxels.iter()));
+        }
+        self.face_masks = vec![ALL_FACES; self.voxels.len()];
+        Some(previous)
+    }
+}
+
+// Applied edits, each step kept as the edits that reverse it. A new edit clears
+// the redo steps.
+#[derive(Resource, Default)]
+pub struct EditHistory {
+    undo: Vec<VoxelEdits>,
+    redo: Vec<VoxelEdits>,
+}
+
+// Writes batched edits into the world, marking each touched chunk dirty once along
+// with loaded neighbors whose border faces the edits could change. Placing into a
+// chunk that doesn't exist spawns it. Each batch is one undo step.
+#[derive(SystemParam)]
+pub struct VoxelEditor<'w, 's> {
+    commands: Commands<'w, 's>,
+    index: Res<'w, ChunkSpatialIndex>,
+    chunks: Query<'w, 's, &'static mut VoxelChunk>,
+    dirty: ResMut<'w, DirtyChunks>,
+    settings: Res<'w, VoxelRenderSettings>,
+    history: ResMut<'w, EditHistory>,
+    changed: EventWriter<'w, VoxelChanged>,
+}
+
+impl VoxelEditor<'_, '_> {
+    // Returns how many chunks changed
+    pub fn apply(&mut self, edits: VoxelEdits) -> usize {
+        let (changed_chunks, reverse) = self.write(edits);
+        if !reverse.is_empty() {
+            self.history.undo.push(reverse);
+            if self.history.undo.len() > MAX_UNDO_STEPS {
+                self.history.undo.remove(0);
+            }
+            self.history.redo.clear();
+        }
+        changed_chunks
+    }
+
+    // Like apply, but folds the change into the latest undo step, so a stroke
+    // applied over several frames undoes in one go
+    pub fn apply_continuing(&mut self, edits: VoxelEdits) -> usize {
+        let (changed_chunks, reverse) = self.write(edits);
+        let Some(step) = self.history.undo.last_mut() else {
+            if !reverse.is_empty() {
+                self.history.undo.push(reverse);
+            }
+            return changed_chunks;
+        };
+        // Cells the step already restores keep their older contents
+        for (position, cells) in reverse.chunks {
+            let restores = step.chunks.entry(position).or_default();
+            for (pos, op) in cells {
+                restores.entry(pos).or_insert(op);
+            }
+        }
+        self.history.redo.clear();
+        changed_chunks
+    }
+
+    // Calls change for every cell in the region with its stored voxel, if any, and
+    // applies the edits it returns as one batch, so each touched chunk is rebuilt
+    // once and the whole change undoes in one step. Returns how many chunks changed.
+    pub fn modify_region(&mut self, region: WorldRegion, mut change: impl FnMut(IVec3, Option<&Voxel>) -> Option<EditOp>) -> usize {
+        let mut edits = VoxelEdits::default();
+        for position in cells_between(split_world(region.min).0, split_world(region.max).0) {
+            let chunk = self.index.chunk_at(position).and_then(|entity| self.chunks.get(entity).ok());
+            let stored: HashMap<IVec3, &Voxel> = chunk
+                .into_iter()
+                .flat_map(chunk_voxels)
+                .map(|(cell, _, voxel)| (cell, voxel))
+                .collect();
+            let origin = position * CHUNK_SIZE;
+            let low = region.min.max(origin);
+            let high = region.max.min(origin + IVec3::splat(CHUNK_SIZE - 1));
+            for cell in cells_between(low, high) {
+                if let Some(op) = change(cell, stored.get(&cell).copied()) {
+                    edits.push(cell, op);
+                }
+            }
+        }
+        self.apply(edits)
+    }
+
+    // Reverts the latest step. Returns false when there's nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(step) = self.history.undo.pop() else {
+            return false;
+        };
+        let (_, reverse) = self.write(step);
+        if !reverse.is_empty() {
+            self.history.redo.push(reverse);
+        }
+        true
+    }
+
+    // Reapplies the latest undone step. Returns false when there's nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(step) = self.history.redo.pop() else {
+            return false;
+        };
+        let (_, reverse) = self.write(step);
+        if !reverse.is_empty() {
+            self.history.undo.push(reverse);
+        }
+        true
+    }
+
+    // Returns how many chunks changed and the edits that reverse the change
+    fn write(&mut self, edits: VoxelEdits) -> (usize, VoxelEdits) {
+        let mut changed_chunks = 0;
+        let mut reverse = VoxelEdits::default();
+        for (position, cells) in edits.chunks {
+            let previous = match self.index.chunk_at(position) {
+                Some(entity) => {
+                    let Ok(mut chunk) = self.chunks.get_mut(entity) else {
+                        continue;
+                    };
+                    let Some(previous) = chunk.apply_edits(&cells) else {
+                        continue;
+                    };
+                    self.dirty.mark(entity);
+                    previous
+                }
+                None => match self.spawn_chunk(position, &cells) {
+                    Some(previous) => previous,
+                    None => continue,
+                },
+            };
+            changed_chunks += 1;
+            self.changed.send(VoxelChanged { chunk: position });
+            if !previous.is_empty() {
+                reverse.chunks.insert(position, previous);
+            }
+
+            // New chunks refresh their neighbors when they're added
+            for offset in FACE_NEIGHBORS {
+                let offset = IVec3::new(offset.x, offset.y, offset.z);
+                let touches_border = cells.keys().any(|pos| {
+                    let cell = IVec3::new(pos.x, pos.y, pos.z) + offset;
+                    cell.cmplt(IVec3::ZERO).any() || cell.cmpge(IVec3::splat(CHUNK_SIZE)).any()
+                });
+                if let Some(neighbor) = self.index.chunk_at(position + offset).filter(|_| touches_border) {
+                    self.dirty.mark(neighbor);
+                }
+            }
+        }
+        (changed_chunks, reverse)
+    }
+
+    // Returns edits that empty the placed cells again, or None if nothing was placed
+    fn spawn_chunk(&mut self, position: IVec3, cells: &HashMap<LocalPos, EditOp>) -> Option<HashMap<LocalPos, EditOp>> {
+        let voxels: Vec<Voxel> = cells
+            .iter()
+            .filter_map(|(pos, op)| match op {
+                EditOp::Place { color, kind } | EditOp::PlaceEmpty { color, kind } => Some(Voxel {
+                    position: Vec3::new(pos.x as f32, pos.y as f32, pos.z as f32),
+                    color: *color,
+                    kind: *kind,
+                }),
+                EditOp::Erase | EditOp::Paint(_) => None,
+            })
+            .collect();
+        if voxels.is_empty() {
+            return None;
+        }
+        let previous = voxels
+            .iter()
+            .map(|voxel| (LocalPos::from_vec3(voxel.position), EditOp::Erase))
+            .collect();
+        let chunk = VoxelChunk::new(position, voxels);
+        let transform = Transform::from_translation(chunk.world_center(self.settings.voxel_size));
+        self.commands.spawn((chunk, SpatialBundle::from_transform(transform)));
+        Some(previous)
+    }
+}
@@ -0,0 +1,61 @@
+// src/voxel/flood_fill.rs
+use bevy::{prelude::*, utils::{HashMap, HashSet}};
+use std::collections::VecDeque;
+use super::{split_world, LocalPos, VoxelWorld, FACE_NEIGHBORS};
+
+// Voxels a flood fill reached, in world voxel coordinates
+pub struct FloodFill {
+    pub cells: Vec<IVec3>,
+    // Stopped at the cap with connected voxels left over
+    pub capped: bool,
+}
+
+impl VoxelWorld<'_, '_> {
+    // Voxels joined to the start through shared faces that all have its color,
+    // hidden ones included. Breadth first with an explicit queue, so large regions
+    // can't overflow the stack, stopping after cap voxels. Unloaded chunks read as
+    // empty. None when there's no voxel at the start.
+    pub fn flood_fill_color(&self, start: IVec3, cap: usize) -> Option<FloodFill> {
+        // Colors by cell, built once per chunk the fill reaches
+        let mut chunk_colors: HashMap<IVec3, HashMap<LocalPos, Color>> = HashMap::default();
+        let mut color_at = |cell: IVec3| {
+            let (chunk, local) = split_world(cell);
+            chunk_colors
+                .entry(chunk)
+                .or_insert_with(|| self.voxel_colors(chunk))
+                .get(&local)
+                .copied()
+        };
+
+        let color = color_at(start)?;
+        let mut visited = HashSet::default();
+        visited.insert(start);
+        let mut queue = VecDeque::from([start]);
+        let mut cells = Vec::new();
+        while let Some(cell) = queue.pop_front() {
+            if cells.len() >= cap {
+                return Some(FloodFill { cells, capped: true });
+            }
+            cells.push(cell);
+            for offset in FACE_NEIGHBORS {
+                let neighbor = cell + IVec3::new(offset.x, offset.y, offset.z);
+                if color_at(neighbor) == Some(color) && visited.insert(neighbor) {
+                    queue.push_back(neighbor);
+                }
+            }
+        }
+        Some(FloodFill { cells, capped: false })
+    }
+
+    fn voxel_colors(&self, position: IVec3) -> HashMap<LocalPos, Color> {
+        let Some(chunk) = self.chunk(position) else {
+            return HashMap::default();
+        };
+        chunk
+            .voxels
+            .iter()
+            .chain(chunk.hidden_voxels.iter())
+            .map(|voxel| (LocalPos::from_vec3(voxel.position), voxel.color))
+            .collect()
+    }
+}
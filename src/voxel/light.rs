@@ -0,0 +1,293 @@
+// src/voxel/light.rs
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use std::collections::VecDeque;
+use crate::voxel_types::Voxel;
+use super::{ChunkOccupancy, ChunkSpatialIndex, DirtyChunks, LocalPos, SystemTimings, VoxelChanged, VoxelChunk, CHUNK_SIZE, FACE_NEIGHBORS};
+
+pub const MAX_LIGHT: u8 = 15;
+// Index of the face toward +y in FACE_NEIGHBORS
+const UP: usize = 2;
+// Chunks relit per frame; the rest wait for the next
+const MAX_RELIGHTS_PER_FRAME: usize = 32;
+
+// Baked sunlight per cell, 0 to MAX_LIGHT. Open cells hold the light reaching them;
+// solid cells hold the brightest open cell beside them, which is how lit the voxel
+// there looks. Chunks are fully lit until their first light pass.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChunkLight {
+    levels: Vec<u8>,
+}
+
+impl Default for ChunkLight {
+    fn default() -> Self {
+        Self {
+            levels: vec![MAX_LIGHT; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize],
+        }
+    }
+}
+
+impl ChunkLight {
+    // None unless there's a level, at most MAX_LIGHT, for every cell
+    pub fn from_levels(levels: Vec<u8>) -> Option<Self> {
+        let complete = levels.len() == (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+        (complete && levels.iter().all(|level| *level <= MAX_LIGHT)).then_some(Self { levels })
+    }
+
+    // In cell order, x fastest, then y, then z
+    pub fn levels(&self) -> &[u8] {
+        &self.levels
+    }
+
+    fn index(pos: LocalPos) -> usize {
+        ((pos.z * CHUNK_SIZE + pos.y) * CHUNK_SIZE + pos.x) as usize
+    }
+
+    pub fn level(&self, pos: LocalPos) -> u8 {
+        self.levels[Self::index(pos)]
+    }
+
+    // Heap bytes held, for diagnostics
+    pub fn memory_bytes(&self) -> usize {
+        self.levels.capacity()
+    }
+
+    // Whether the cells on one face of the chunk differ
+    fn border_differs(&self, other: &Self, face: usize) -> bool {
+        border_cells(face).any(|pos| self.level(pos) != other.level(pos))
+    }
+}
+
+// How bright a light level draws a voxel, never below ambient
+pub fn light_brightness(level: u8, ambient: f32) -> f32 {
+    let ambient = ambient.clamp(0.0, 1.0);
+    ambient + (1.0 - ambient) * level as f32 / MAX_LIGHT as f32
+}
+
+impl VoxelChunk {
+    // Color of a voxel, stored or downsampled, scaled by the sunlight on it
+    pub fn lit_color(&self, voxel: &Voxel, ambient: f32) -> Color {
+        let brightness = light_brightness(self.light.level(LocalPos::from_vec3(voxel.position)), ambient);
+        let [r, g, b, a] = voxel.color.as_linear_rgba_f32();
+        Color::rgba_linear(r * brightness, g * brightness, b * brightness, a)
+    }
+}
+
+// Cells of the chunk on one face
+fn border_cells(face: usize) -> impl Iterator<Item = LocalPos> {
+    let axis = face / 2;
+    let layer = if face % 2 == 0 { CHUNK_SIZE - 1 } else { 0 };
+    (0..CHUNK_SIZE).flat_map(move |u| {
+        (0..CHUNK_SIZE).map(move |v| {
+            let mut coords = [0; 3];
+            coords[axis] = layer;
+            coords[(axis + 1) % 3] = u;
+            coords[(axis + 2) % 3] = v;
+            LocalPos::new(coords[0], coords[1], coords[2])
+        })
+    })
+}
+
+fn wrap(pos: LocalPos) -> LocalPos {
+    let wrapped = IVec3::new(pos.x, pos.y, pos.z).rem_euclid(IVec3::splat(CHUNK_SIZE));
+    LocalPos::new(wrapped.x, wrapped.y, wrapped.z)
+}
+
+// Lights a chunk from its occupancy and the light already in its face neighbors.
+// `neighbor` returns the occupancy and light of the chunk across a face, if loaded;
+// unloaded neighbors count as open sky. Sunlight falls straight down at full
+// strength until something solid stops it, then spreads sideways, up and down,
+// losing a level per cell.
+fn compute_light<'a>(
+    occupancy: &ChunkOccupancy,
+    neighbor: impl Fn(usize) -> Option<(&'a ChunkOccupancy, &'a ChunkLight)>,
+) -> ChunkLight {
+    let neighbors: Vec<Option<(&ChunkOccupancy, &ChunkLight)>> = (0..FACE_NEIGHBORS.len()).map(neighbor).collect();
+    // Light of the open cell across a face from a border cell, None if it's solid
+    let across = |face: usize, pos: LocalPos| {
+        let offset = FACE_NEIGHBORS[face];
+        let cell = wrap(LocalPos::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z));
+        match neighbors[face] {
+            Some((occupancy, _)) if occupancy.is_solid(cell) => None,
+            Some((_, light)) => Some(light.level(cell)),
+            None => Some(MAX_LIGHT),
+        }
+    };
+
+    let mut light = ChunkLight { levels: vec![0; (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize] };
+    let mut queue = VecDeque::new();
+    for x in 0..CHUNK_SIZE {
+        for z in 0..CHUNK_SIZE {
+            if across(UP, LocalPos::new(x, CHUNK_SIZE - 1, z)) != Some(MAX_LIGHT) {
+                continue;
+            }
+            for y in (0..CHUNK_SIZE).rev() {
+                let pos = LocalPos::new(x, y, z);
+                if occupancy.is_solid(pos) {
+                    break;
+                }
+                light.levels[ChunkLight::index(pos)] = MAX_LIGHT;
+                queue.push_back(pos);
+            }
+        }
+    }
+    for face in 0..FACE_NEIGHBORS.len() {
+        for pos in border_cells(face) {
+            let Some(level) = across(face, pos).filter(|_| !occupancy.is_solid(pos)) else {
+                continue;
+            };
+            let cell = &mut light.levels[ChunkLight::index(pos)];
+            if level.saturating_sub(1) > *cell {
+                *cell = level - 1;
+                queue.push_back(pos);
+            }
+        }
+    }
+
+    while let Some(pos) = queue.pop_front() {
+        let level = light.level(pos);
+        if level <= 1 {
+            continue;
+        }
+        for offset in FACE_NEIGHBORS {
+            let next = LocalPos::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z);
+            if !ChunkOccupancy::in_bounds(next) || occupancy.is_solid(next) {
+                continue;
+            }
+            let cell = &mut light.levels[ChunkLight::index(next)];
+            if *cell < level - 1 {
+                *cell = level - 1;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    // Solid cells last, so they read the finished open cells
+    for z in 0..CHUNK_SIZE {
+        for y in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let pos = LocalPos::new(x, y, z);
+                if !occupancy.is_solid(pos) {
+                    continue;
+                }
+                let brightest = FACE_NEIGHBORS
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(face, offset)| {
+                        let next = LocalPos::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z);
+                        if !ChunkOccupancy::in_bounds(next) {
+                            across(face, pos)
+                        } else if occupancy.is_solid(next) {
+                            None
+                        } else {
+                            Some(light.level(next))
+                        }
+                    })
+                    .max()
+                    .unwrap_or(0);
+                light.levels[ChunkLight::index(pos)] = brightest;
+            }
+        }
+    }
+    light
+}
+
+// Chunk positions waiting for a light pass, and where each lit chunk was so its
+// neighbors can be relit when it's despawned
+#[derive(Resource, Default)]
+pub struct LightQueue {
+    pending: VecDeque<IVec3>,
+    queued: HashSet<IVec3>,
+    positions: HashMap<Entity, IVec3>,
+}
+
+impl LightQueue {
+    fn push(&mut self, position: IVec3) {
+        if self.queued.insert(position) {
+            self.pending.push_back(position);
+        }
+    }
+
+    // The chunk and its face neighbors, whose border light reads its cells
+    fn push_with_neighbors(&mut self, position: IVec3) {
+        self.push(position);
+        for offset in FACE_NEIGHBORS {
+            self.push(position + IVec3::new(offset.x, offset.y, offset.z));
+        }
+    }
+}
+
+// Relights chunks that were added, edited or lost a neighbor, a chunk at a time: each
+// pass reads only the chunk and the border cells of its neighbors. A chunk whose light
+// changed is marked dirty so it's redrawn, and neighbors across a border that changed
+// are queued, so light crosses chunk borders over successive passes until it settles.
+#[allow(clippy::too_many_arguments)]
+pub fn propagate_sunlight(
+    mut queue: ResMut<LightQueue>,
+    mut dirty: ResMut<DirtyChunks>,
+    mut changes: EventReader<VoxelChanged>,
+    added: Query<(Entity, &VoxelChunk), Added<VoxelChunk>>,
+    mut removed: RemovedComponents<VoxelChunk>,
+    mut chunks: Query<&mut VoxelChunk>,
+    index: Res<ChunkSpatialIndex>,
+    mut timings: ResMut<SystemTimings>,
+) {
+    let _timing = timings.span("sunlight");
+    for entity in removed.read() {
+        if let Some(position) = queue.positions.remove(&entity) {
+            queue.push_with_neighbors(position);
+        }
+    }
+    for (entity, chunk) in added.iter() {
+        queue.positions.insert(entity, chunk.position);
+        queue.push_with_neighbors(chunk.position);
+    }
+    for change in changes.read() {
+        queue.push_with_neighbors(change.chunk);
+    }
+    let edited: Vec<IVec3> = dirty
+        .iter()
+        .filter(|entity| dirty.voxels_changed(*entity))
+        .filter_map(|entity| chunks.get(entity).ok().map(|chunk| chunk.position))
+        .collect();
+    for position in edited {
+        queue.push_with_neighbors(position);
+    }
+
+    let mut relit = 0;
+    while relit < MAX_RELIGHTS_PER_FRAME {
+        let Some(position) = queue.pending.pop_front() else {
+            break;
+        };
+        queue.queued.remove(&position);
+        let Some(entity) = index.chunk_at(position) else {
+            continue;
+        };
+        let Ok(chunk) = chunks.get(entity) else {
+            continue;
+        };
+        relit += 1;
+        let light = compute_light(&chunk.occupancy, |face| {
+            let offset = FACE_NEIGHBORS[face];
+            let neighbor = index.chunk_at(position + IVec3::new(offset.x, offset.y, offset.z))?;
+            chunks.get(neighbor).ok().map(|neighbor| (&neighbor.occupancy, &neighbor.light))
+        });
+        if light == chunk.light {
+            continue;
+        }
+        let changed_faces: Vec<usize> = (0..FACE_NEIGHBORS.len())
+            .filter(|face| light.border_differs(&chunk.light, *face))
+            .collect();
+
+        if let Ok(mut chunk) = chunks.get_mut(entity) {
+            chunk.light = light;
+        }
+        dirty.mark(entity);
+        for face in changed_faces {
+            let offset = FACE_NEIGHBORS[face];
+            queue.push(position + IVec3::new(offset.x, offset.y, offset.z));
+        }
+    }
+}
@@ -0,0 +1,291 @@
+// src/voxel/model.rs
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    prelude::*,
+    reflect::TypePath,
+    utils::{BoxedFuture, HashMap, HashSet},
+};
+use std::fmt;
+use std::path::Path;
+use crate::bytes::{ByteError, ByteReader};
+use crate::generation::GenerationProgress;
+use super::{split_world, EditOp, VoxelEditor, VoxelEdits, VoxelWorld, WorldRegion, CHUNK_SIZE};
+
+const MAGIC: &[u8; 4] = b"WVOX";
+// Bumped whenever the layout changes; older builds refuse newer models
+const FORMAT_VERSION: u32 = 1;
+// Palette color as RGBA bytes, then the voxel kind
+const PALETTE_ENTRY_BYTES: usize = 4 + 2;
+// Cell as three i32s, then a palette index
+const VOXEL_BYTES: usize = 3 * 4 + 4;
+
+// A voxel model in .wvox files, loaded through the asset server so edits on disk
+// hot-reload. Cells are relative to the model's origin.
+#[derive(Asset, TypePath, Clone, Debug, Default)]
+pub struct VoxelModel {
+    pub voxels: Vec<ModelVoxel>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ModelVoxel {
+    pub cell: IVec3,
+    pub color: Color,
+    pub kind: u16,
+}
+
+#[derive(Debug)]
+pub enum WvoxError {
+    Io(std::io::Error),
+    NotAModel,
+    NewerVersion { found: u32, supported: u32 },
+    Corrupt(&'static str),
+    // No loaded voxels inside the region
+    Empty,
+}
+
+impl fmt::Display for WvoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WvoxError::Io(err) => write!(f, "could not access .wvox file: {}", err),
+            WvoxError::NotAModel => write!(f, "not a .wvox model"),
+            WvoxError::NewerVersion { found, supported } => write!(
+                f,
+                ".wvox model is version {}, but this build reads up to version {}",
+                found, supported
+            ),
+            WvoxError::Corrupt(reason) => write!(f, ".wvox model is corrupt: {}", reason),
+            WvoxError::Empty => write!(f, "no voxels to export"),
+        }
+    }
+}
+
+impl std::error::Error for WvoxError {}
+
+impl From<ByteError> for WvoxError {
+    fn from(err: ByteError) -> Self {
+        WvoxError::Corrupt(err.reason())
+    }
+}
+
+impl VoxelModel {
+    // Layout: magic, version, then a palette of colors and kinds, then each
+    // voxel's cell and palette index
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut palette: Vec<([u8; 4], u16)> = Vec::new();
+        let mut indices: HashMap<([u8; 4], u16), u32> = HashMap::default();
+        let mut body = Vec::with_capacity(self.voxels.len() * VOXEL_BYTES);
+        for voxel in &self.voxels {
+            let entry = (voxel.color.as_rgba_u8(), voxel.kind);
+            let index = *indices.entry(entry).or_insert_with(|| {
+                palette.push(entry);
+                palette.len() as u32 - 1
+            });
+            for axis in voxel.cell.to_array() {
+                body.extend_from_slice(&axis.to_le_bytes());
+            }
+            body.extend_from_slice(&index.to_le_bytes());
+        }
+
+        let mut out = Vec::with_capacity(16 + palette.len() * PALETTE_ENTRY_BYTES + body.len());
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        out.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+        for (rgba, kind) in &palette {
+            out.extend_from_slice(rgba);
+            out.extend_from_slice(&kind.to_le_bytes());
+        }
+        out.extend_from_slice(&(self.voxels.len() as u32).to_le_bytes());
+        out.extend_from_slice(&body);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, WvoxError> {
+        let mut reader = ByteReader { bytes };
+        if reader.take(MAGIC.len()).ok() != Some(MAGIC.as_slice()) {
+            return Err(WvoxError::NotAModel);
+        }
+        let version = reader.u32()?;
+        if version > FORMAT_VERSION {
+            return Err(WvoxError::NewerVersion {
+                found: version,
+                supported: FORMAT_VERSION,
+            });
+        }
+        let palette_len = reader.u32()? as usize;
+        // Checked against what's left so a corrupt count can't demand a huge buffer
+        if palette_len > reader.bytes.len() / PALETTE_ENTRY_BYTES {
+            return Err(WvoxError::Corrupt("palette is longer than the file"));
+        }
+        let mut palette = Vec::with_capacity(palette_len);
+        for _ in 0..palette_len {
+            let [r, g, b, a] = reader.array()?;
+            palette.push((Color::rgba_u8(r, g, b, a), reader.u16()?));
+        }
+        let count = reader.u32()? as usize;
+        if count > reader.bytes.len() / VOXEL_BYTES {
+            return Err(WvoxError::Corrupt("voxel count is larger than the file"));
+        }
+        let mut voxels = Vec::with_capacity(count);
+        for _ in 0..count {
+            let cell = IVec3::new(reader.i32()?, reader.i32()?, reader.i32()?);
+            let (color, kind) = *palette
+                .get(reader.u32()? as usize)
+                .ok_or(WvoxError::Corrupt("voxel refers past the palette"))?;
+            voxels.push(ModelVoxel { cell, color, kind });
+        }
+        Ok(Self { voxels })
+    }
+}
+
+// Writes the loaded voxels inside a region as a .wvox model, relative to the
+// region's min corner
+pub fn export_wvox(world: &VoxelWorld, region: WorldRegion, path: &Path) -> Result<(), WvoxError> {
+    let mut model = VoxelModel::default();
+    let (min_chunk, _) = split_world(region.min);
+    let (max_chunk, _) = split_world(region.max);
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                let Some(chunk) = world.chunk(IVec3::new(x, y, z)) else {
+                    continue;
+                };
+                let origin = chunk.position * CHUNK_SIZE;
+                for voxel in chunk.voxels.iter().chain(chunk.hidden_voxels.iter()) {
+                    let cell = origin + voxel.position.as_ivec3();
+                    if region.contains(cell) {
+                        model.voxels.push(ModelVoxel {
+                            cell: cell - region.min,
+                            color: voxel.color,
+                            kind: voxel.kind,
+                        });
+                    }
+                }
+            }
+        }
+    }
+    if model.voxels.is_empty() {
+        return Err(WvoxError::Empty);
+    }
+    std::fs::write(path, model.to_bytes()).map_err(WvoxError::Io)
+}
+
+#[derive(Default)]
+pub(super) struct VoxelModelLoader;
+
+impl AssetLoader for VoxelModelLoader {
+    type Asset = VoxelModel;
+    type Settings = ();
+    type Error = WvoxError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        _load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<VoxelModel, WvoxError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader.read_to_end(&mut bytes).await.map_err(WvoxError::Io)?;
+            VoxelModel::from_bytes(&bytes)
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["wvox"]
+    }
+}
+
+// A model placed into the world at origin. Its voxels are written as an undoable
+// edit once both the model and the world are ready, and rewritten whenever the
+// model reloads or the world regenerates. Despawning the entity leaves the voxels
+// in place.
+#[derive(Component)]
+pub struct VoxelModelInstance {
+    pub model: Handle<VoxelModel>,
+    pub origin: IVec3,
+    // World cells written last time; None until first placed
+    placed: Option<HashSet<IVec3>>,
+}
+
+pub trait SpawnVoxelModelExt {
+    fn spawn_voxel_model(&mut self, model: Handle<VoxelModel>, origin: IVec3) -> Entity;
+}
+
+impl SpawnVoxelModelExt for Commands<'_, '_> {
+    fn spawn_voxel_model(&mut self, model: Handle<VoxelModel>, origin: IVec3) -> Entity {
+        self.spawn(VoxelModelInstance {
+            model,
+            origin,
+            placed: None,
+        })
+        .id()
+    }
+}
+
+// Asset paths of models spawned at startup, with their origins, as for --spawn-model
+#[derive(Resource, Default)]
+pub struct StartupModels(pub Vec<(String, IVec3)>);
+
+pub(super) fn spawn_startup_models(mut commands: Commands, asset_server: Res<AssetServer>, startup: Res<StartupModels>) {
+    for (path, origin) in &startup.0 {
+        info!("Spawning {} at {}", path, origin);
+        commands.spawn_voxel_model(asset_server.load(path.clone()), *origin);
+    }
+}
+
+pub(super) fn place_voxel_models(
+    mut events: EventReader<AssetEvent<VoxelModel>>,
+    models: Res<Assets<VoxelModel>>,
+    mut instances: Query<&mut VoxelModelInstance>,
+    progress: Res<GenerationProgress>,
+    mut editor: VoxelEditor,
+) {
+    let reloaded: HashSet<AssetId<VoxelModel>> = events
+        .read()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { id } => Some(*id),
+            _ => None,
+        })
+        .collect();
+    // Generated chunks replace placed ones, so models are placed again once the
+    // world has finished generating
+    if !progress.is_complete() {
+        for mut instance in &mut instances {
+            if instance.placed.is_some() {
+                instance.placed = None;
+            }
+        }
+        return;
+    }
+    for mut instance in &mut instances {
+        if instance.placed.is_some() && !reloaded.contains(&instance.model.id()) {
+            continue;
+        }
+        let Some(model) = models.get(&instance.model) else {
+            continue;
+        };
+        let origin = instance.origin;
+        let cells: HashSet<IVec3> = model.voxels.iter().map(|voxel| origin + voxel.cell).collect();
+        let mut edits = VoxelEdits::default();
+        // Cells the previous version filled and this one doesn't
+        for cell in instance.placed.iter().flatten().filter(|cell| !cells.contains(*cell)) {
+            edits.push(*cell, EditOp::Erase);
+        }
+        for voxel in &model.voxels {
+            edits.push(origin + voxel.cell, EditOp::Place {
+                color: voxel.color,
+                kind: voxel.kind,
+            });
+        }
+        let reloading = instance.placed.is_some();
+        let chunks = editor.apply(edits);
+        info!(
+            "{} {} voxels of a model at {} in {} chunks",
+            if reloading { "Reloaded" } else { "Placed" },
+            cells.len(),
+            origin,
+            chunks,
+        );
+        instance.placed = Some(cells);
+    }
+}
@@ -0,0 +1,355 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use crate::voxel_types::Voxel;
+use super::{LocalPos, VoxelChunk, CHUNK_SIZE, FACE_NEIGHBORS};
+
+// Bit i is set when the face towards FACE_NEIGHBORS[i] is exposed
+pub type FaceMask = u8;
+
+pub const ALL_FACES: FaceMask = 0b11_1111;
+
+// Exposed faces of the voxel at pos. Past the chunk border the neighborhood decides
+// when there is one; without it, border faces count as exposed.
+pub(super) fn face_mask(occupancy: &ChunkOccupancy, pos: LocalPos, neighborhood: Option<&NeighborhoodOccupancy>) -> FaceMask {
+    FACE_NEIGHBORS.iter().enumerate().fold(0, |mask, (face, offset)| {
+        let neighbor = LocalPos::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z);
+        let exposed = match neighborhood {
+            Some(neighborhood) if !ChunkOccupancy::in_bounds(neighbor) => !neighborhood.is_solid(neighbor),
+            _ => occupancy.face_exposed(pos, *offset),
+        };
+        mask | ((exposed as FaceMask) << face)
+    })
+}
+
+// Average of the exposed face normals, for lighting a voxel drawn as a single sprite
+pub fn dominant_open_direction(mask: FaceMask) -> Vec3 {
+    FACE_NEIGHBORS
+        .iter()
+        .enumerate()
+        .filter(|(face, _)| mask & (1 << face) != 0)
+        .map(|(_, offset)| Vec3::new(offset.x as f32, offset.y as f32, offset.z as f32))
+        .sum::<Vec3>()
+        .try_normalize()
+        .unwrap_or(Vec3::Y)
+}
+
+// Which cells of a chunk are filled, including voxels removed by occlusion culling.
+// Solid cells are opaque. Translucent cells (water, glass) don't hide what's behind
+// them and carry a group id per distinct color, so water hides its own inner faces
+// but not the glass standing in it.
+#[derive(Clone, Debug)]
+pub struct ChunkOccupancy {
+    bits: Vec<u64>,
+    // 0 where the cell isn't translucent
+    translucent: Vec<u8>,
+}
+
+impl Default for ChunkOccupancy {
+    fn default() -> Self {
+        let cells = (CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE) as usize;
+        Self {
+            bits: vec![0; (cells + 63) / 64],
+            translucent: vec![0; cells],
+        }
+    }
+}
+
+impl ChunkOccupancy {
+    // Heap bytes held, for diagnostics
+    pub fn memory_bytes(&self) -> usize {
+        self.bits.capacity() * std::mem::size_of::<u64>() + self.translucent.capacity()
+    }
+
+    pub fn from_voxels(voxels: &[Voxel]) -> Self {
+        let mut occupancy = Self::default();
+        for voxel in voxels.iter().filter(|voxel| !voxel.is_translucent()) {
+            occupancy.set(LocalPos::from_vec3(voxel.position), true);
+        }
+        occupancy.assign_translucent_groups(voxels.iter());
+        occupancy
+    }
+
+    // Regroups every translucent cell from the given voxels, one group per color.
+    // Edits call this with all of a chunk's voxels after changing any translucent one.
+    pub fn assign_translucent_groups<'a>(&mut self, voxels: impl Iterator<Item = &'a Voxel>) {
+        self.translucent.fill(0);
+        let mut groups: HashMap<[u32; 4], u8> = HashMap::default();
+        for voxel in voxels.filter(|voxel| voxel.is_translucent()) {
+            // Colors past the 255th share the last group
+            let next = (groups.len() + 1).min(u8::MAX as usize) as u8;
+            let key = voxel.color.as_rgba_f32().map(f32::to_bits);
+            let group = *groups.entry(key).or_insert(next);
+            self.set_translucent(LocalPos::from_vec3(voxel.position), group);
+        }
+    }
+
+    pub fn in_bounds(pos: LocalPos) -> bool {
+        pos.x >= 0 && pos.x < CHUNK_SIZE &&
+        pos.y >= 0 && pos.y < CHUNK_SIZE &&
+        pos.z >= 0 && pos.z < CHUNK_SIZE
+    }
+
+    fn index(pos: LocalPos) -> usize {
+        ((pos.z * CHUNK_SIZE + pos.y) * CHUNK_SIZE + pos.x) as usize
+    }
+
+    // Marks an opaque cell
+    pub fn set(&mut self, pos: LocalPos, solid: bool) {
+        if !Self::in_bounds(pos) {
+            return;
+        }
+        let index = Self::index(pos);
+        if solid {
+            self.bits[index / 64] |= 1 << (index % 64);
+            self.translucent[index] = 0;
+        } else {
+            self.bits[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    // Group 0 clears the cell
+    pub fn set_translucent(&mut self, pos: LocalPos, group: u8) {
+        if !Self::in_bounds(pos) {
+            return;
+        }
+        let index = Self::index(pos);
+        self.translucent[index] = group;
+        if group != 0 {
+            self.bits[index / 64] &= !(1 << (index % 64));
+        }
+    }
+
+    // Opaque cells only. Positions outside the chunk always read as empty.
+    pub fn is_solid(&self, pos: LocalPos) -> bool {
+        if !Self::in_bounds(pos) {
+            return false;
+        }
+        let index = Self::index(pos);
+        self.bits[index / 64] & (1 << (index % 64)) != 0
+    }
+
+    fn translucent_group(&self, pos: LocalPos) -> u8 {
+        if !Self::in_bounds(pos) {
+            return 0;
+        }
+        self.translucent[Self::index(pos)]
+    }
+
+    pub fn is_translucent(&self, pos: LocalPos) -> bool {
+        self.translucent_group(pos) != 0
+    }
+
+    // Every cell opaque, so nothing behind the chunk shows through it
+    pub fn is_full(&self) -> bool {
+        self.bits.iter().all(|word| *word == u64::MAX)
+    }
+
+    // Whether the face of the voxel at pos towards offset is visible. Faces show
+    // through translucent neighbors unless both sides are the same translucent group.
+    pub fn face_exposed(&self, pos: LocalPos, offset: LocalPos) -> bool {
+        let neighbor = LocalPos::new(pos.x + offset.x, pos.y + offset.y, pos.z + offset.z);
+        if self.is_solid(neighbor) {
+            return false;
+        }
+        let group = self.translucent_group(neighbor);
+        group == 0 || group != self.translucent_group(pos)
+    }
+}
+
+const PADDED_SIZE: i32 = CHUNK_SIZE + 2;
+
+// Occupancy of a chunk plus the one voxel shell around it copied from its 26
+// neighbors, so lookups just past the chunk border see the neighboring voxels
+pub struct NeighborhoodOccupancy {
+    cells: Vec<bool>,
+}
+
+impl NeighborhoodOccupancy {
+    // `neighbor` returns the occupancy of the chunk at a relative chunk offset, if loaded
+    pub fn gather<'a>(
+        center: &'a ChunkOccupancy,
+        neighbor: impl Fn(IVec3) -> Option<&'a ChunkOccupancy>,
+    ) -> Self {
+        let mut chunks = [None; 27];
+        for (i, chunk) in chunks.iter_mut().enumerate() {
+            let offset = IVec3::new(i as i32 % 3, i as i32 / 3 % 3, i as i32 / 9) - IVec3::ONE;
+            *chunk = if offset == IVec3::ZERO { Some(center) } else { neighbor(offset) };
+        }
+
+        let mut cells = vec![false; (PADDED_SIZE * PADDED_SIZE * PADDED_SIZE) as usize];
+        for z in -1..=CHUNK_SIZE {
+            for y in -1..=CHUNK_SIZE {
+                for x in -1..=CHUNK_SIZE {
+                    let pos = IVec3::new(x, y, z);
+                    let offset = pos.div_euclid(IVec3::splat(CHUNK_SIZE)) + IVec3::ONE;
+                    let Some(chunk) = chunks[(offset.z * 9 + offset.y * 3 + offset.x) as usize] else {
+                        continue;
+                    };
+                    let local = pos.rem_euclid(IVec3::splat(CHUNK_SIZE));
+                    cells[Self::index(x, y, z)] = chunk.is_solid(LocalPos::new(local.x, local.y, local.z));
+                }
+            }
+        }
+
+        Self { cells }
+    }
+
+    fn index(x: i32, y: i32, z: i32) -> usize {
+        (((z + 1) * PADDED_SIZE + y + 1) * PADDED_SIZE + x + 1) as usize
+    }
+
+    // Valid from -1 to CHUNK_SIZE on every axis; anything further reads as empty
+    pub fn is_solid(&self, pos: LocalPos) -> bool {
+        let in_range = |v: i32| (-1..=CHUNK_SIZE).contains(&v);
+        in_range(pos.x) && in_range(pos.y) && in_range(pos.z) && self.cells[Self::index(pos.x, pos.y, pos.z)]
+    }
+}
+
+impl VoxelChunk {
+    pub fn filter_occluded_voxels(&mut self) {
+        self.update_face_masks(None, true);
+    }
+
+    // Moves voxels with no exposed face to hidden_voxels and recomputes the face
+    // masks of the rest, using the neighborhood for faces on the chunk border. Hiding
+    // only looks inside the chunk: a voxel hidden by a neighboring chunk stays with
+    // those faces masked off, since the neighbor can change. Without cull, every
+    // voxel is drawn with all its faces. Returns whether anything changed.
+    pub fn update_face_masks(&mut self, neighborhood: Option<&NeighborhoodOccupancy>, cull: bool) -> bool {
+        let voxel_count = self.voxels.len();
+        let mut all = std::mem::take(&mut self.voxels);
+        all.append(&mut self.hidden_voxels);
+        let mut masks = Vec::with_capacity(voxel_count);
+
+        for voxel in all {
+            let pos = LocalPos::from_vec3(voxel.position);
+            let local = face_mask(&self.occupancy, pos, None);
+            if cull && local == 0 {
+                self.hidden_voxels.push(voxel);
+                continue;
+            }
+            masks.push(match neighborhood {
+                _ if !cull => ALL_FACES,
+                Some(neighborhood) => face_mask(&self.occupancy, pos, Some(neighborhood)),
+                None => local,
+            });
+            self.voxels.push(voxel);
+        }
+
+        let changed = self.voxels.len() != voxel_count || masks != self.face_masks;
+        self.face_masks = masks;
+        if changed {
+            self.clear_lod_cache();
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::voxel_types::KIND_PLAIN;
+
+    const RIGHT: FaceMask = 1 << 0;
+    const LEFT: FaceMask = 1 << 1;
+
+    fn voxel(x: i32, y: i32, z: i32) -> Voxel {
+        Voxel {
+            position: Vec3::new(x as f32, y as f32, z as f32),
+            color: Color::rgb(0.5, 0.5, 0.5),
+            kind: KIND_PLAIN,
+        }
+    }
+
+    fn mask_at(chunk: &VoxelChunk, pos: LocalPos) -> FaceMask {
+        let index = chunk
+            .voxels
+            .iter()
+            .position(|voxel| LocalPos::from_vec3(voxel.position) == pos)
+            .expect("voxel is visible");
+        chunk.face_mask(index)
+    }
+
+    #[test]
+    fn pair_masks_off_shared_faces() {
+        let chunk = VoxelChunk::new(IVec3::ZERO, vec![voxel(3, 4, 5), voxel(4, 4, 5)]);
+        assert_eq!(mask_at(&chunk, LocalPos::new(3, 4, 5)), ALL_FACES & !RIGHT);
+        assert_eq!(mask_at(&chunk, LocalPos::new(4, 4, 5)), ALL_FACES & !LEFT);
+    }
+
+    #[test]
+    fn pair_across_chunk_border_masks_off_shared_faces() {
+        let last = CHUNK_SIZE - 1;
+        let mut chunk = VoxelChunk::new(IVec3::ZERO, vec![voxel(last, 4, 5)]);
+        let right = ChunkOccupancy::from_voxels(&[voxel(0, 4, 5)]);
+        // Without the neighbor, border faces count as exposed
+        assert_eq!(mask_at(&chunk, LocalPos::new(last, 4, 5)), ALL_FACES);
+
+        let neighborhood = NeighborhoodOccupancy::gather(&chunk.occupancy, |offset| (offset == IVec3::X).then_some(&right));
+        assert!(chunk.update_face_masks(Some(&neighborhood), true));
+        assert_eq!(mask_at(&chunk, LocalPos::new(last, 4, 5)), ALL_FACES & !RIGHT);
+    }
+
+    // Filters a chunk of the cells the predicate picks and returns how many voxels
+    // stay visible and how many are hidden
+    fn culled_counts(filled: impl Fn(i32, i32, i32) -> bool) -> (usize, usize) {
+        let mut voxels = Vec::new();
+        for z in 0..CHUNK_SIZE {
+            for y in 0..CHUNK_SIZE {
+                for x in 0..CHUNK_SIZE {
+                    if filled(x, y, z) {
+                        voxels.push(voxel(x, y, z));
+                    }
+                }
+            }
+        }
+        let mut chunk = VoxelChunk::new(IVec3::ZERO, voxels);
+        chunk.filter_occluded_voxels();
+        assert_eq!(chunk.face_masks.len(), chunk.voxels.len());
+        (chunk.voxels.len(), chunk.hidden_voxels.len())
+    }
+
+    fn on_border(x: i32, y: i32, z: i32) -> bool {
+        [x, y, z].iter().any(|v| *v == 0 || *v == CHUNK_SIZE - 1)
+    }
+
+    #[test]
+    fn full_chunk_shows_only_its_border() {
+        // 16³ less the 14³ interior
+        assert_eq!(culled_counts(|_, _, _| true), (1352, 2744));
+    }
+
+    #[test]
+    fn hollow_shell_is_all_visible() {
+        assert_eq!(culled_counts(on_border), (1352, 0));
+    }
+
+    #[test]
+    fn single_voxel_is_visible() {
+        assert_eq!(culled_counts(|x, y, z| (x, y, z) == (7, 8, 9)), (1, 0));
+    }
+
+    #[test]
+    fn checkerboard_is_all_visible() {
+        assert_eq!(culled_counts(|x, y, z| (x + y + z) % 2 == 0), (2048, 0));
+    }
+
+    // A 3x3x3 stone cube over 4..=6 with a water voxel against its +x face, from a
+    // glass pool world
+    mod captured {
+        include!("../../fixtures/chunk_stone_cube_by_water.rs");
+    }
+
+    #[test]
+    fn captured_cube_by_water_hides_only_its_core() {
+        let chunk = VoxelChunk::from_fixture(captured::CHUNK_2_0_M1).unwrap();
+        assert_eq!(chunk.position, IVec3::new(2, 0, -1));
+        assert_eq!((chunk.voxels.len(), chunk.hidden_voxels.len()), (27, 1));
+        assert_eq!(LocalPos::from_vec3(chunk.hidden_voxels[0].position), LocalPos::new(5, 5, 5));
+        // Water doesn't hide the stone behind it, but the stone hides the water's face
+        assert_eq!(mask_at(&chunk, LocalPos::new(6, 5, 5)), RIGHT);
+        assert_eq!(mask_at(&chunk, LocalPos::new(7, 5, 5)), ALL_FACES & !LEFT);
+        assert_eq!(chunk.light.level(LocalPos::new(5, 5, 5)), 0);
+        assert_eq!(chunk.light.level(LocalPos::new(7, 5, 5)), 15);
+    }
+}
@@ -0,0 +1,81 @@
+// src/voxel/ply.rs
+use bevy::prelude::*;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use crate::voxel_types::Voxel;
+use super::{split_world, VoxelWorld, WorldRegion, CHUNK_SIZE};
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(std::io::Error),
+    // No visible voxels inside the region
+    Empty,
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlyError::Io(err) => write!(f, "could not write .ply file: {}", err),
+            PlyError::Empty => write!(f, "no voxels to export"),
+        }
+    }
+}
+
+impl std::error::Error for PlyError {}
+
+// Calls visit with each visible voxel of the loaded chunks inside a region and
+// its world cell
+fn for_each_visible_voxel(world: &VoxelWorld, region: WorldRegion, mut visit: impl FnMut(IVec3, &Voxel)) {
+    let (min_chunk, _) = split_world(region.min);
+    let (max_chunk, _) = split_world(region.max);
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                let Some(chunk) = world.chunk(IVec3::new(x, y, z)) else {
+                    continue;
+                };
+                let origin = chunk.position * CHUNK_SIZE;
+                for voxel in &chunk.voxels {
+                    let cell = origin + voxel.position.as_ivec3();
+                    if region.contains(cell) {
+                        visit(cell, voxel);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Writes the centers of the visible voxels inside a region as a binary
+// little-endian PLY point cloud with sRGB colors, relative to the region's min
+// corner. The voxels are counted first so the header is exact, then streamed.
+pub fn export_ply(world: &VoxelWorld, region: WorldRegion, path: &Path) -> Result<(), PlyError> {
+    let mut count = 0;
+    for_each_visible_voxel(world, region, |_, _| count += 1);
+    if count == 0 {
+        return Err(PlyError::Empty);
+    }
+    let voxel_size = world.voxel_size();
+    let mut out = BufWriter::new(File::create(path).map_err(PlyError::Io)?);
+    write!(
+        out,
+        "ply\nformat binary_little_endian 1.0\ncomment Exported by bevy_voxel\nelement vertex {}\n\
+         property float x\nproperty float y\nproperty float z\n\
+         property uchar red\nproperty uchar green\nproperty uchar blue\nend_header\n",
+        count,
+    )
+    .map_err(PlyError::Io)?;
+    let mut result = Ok(());
+    for_each_visible_voxel(world, region, |cell, voxel| {
+        if result.is_err() {
+            return;
+        }
+        let center = ((cell - region.min).as_vec3() + Vec3::splat(0.5)) * voxel_size;
+        let [x, y, z] = center.to_array().map(f32::to_le_bytes);
+        let [r, g, b, _] = voxel.color.as_rgba_u8();
+        result = out.write_all(&[&x[..], &y, &z, &[r, g, b]].concat());
+    });
+    result.and_then(|()| out.flush()).map_err(PlyError::Io)
+}
@@ -0,0 +1,330 @@
+// src/voxel/raycast.rs
+use bevy::prelude::*;
+use super::{LocalPos, VoxelWorld, CHUNK_SIZE, FACE_NEIGHBORS};
+
+// First solid voxel along a ray
+#[derive(Clone, Copy, Debug)]
+pub struct VoxelHit {
+    // World voxel coordinate of the hit voxel
+    pub world: IVec3,
+    pub chunk: IVec3,
+    pub local: LocalPos,
+    // Where the ray entered the voxel, in world units
+    pub position: Vec3,
+    // Outward normal of the face the ray entered through; zero when the ray starts
+    // inside the voxel
+    pub normal: IVec3,
+    // Along the ray, in world units
+    pub distance: f32,
+}
+
+impl VoxelHit {
+    // Index into FACE_NEIGHBORS of the face the ray entered through
+    pub fn face(&self) -> Option<usize> {
+        FACE_NEIGHBORS
+            .iter()
+            .position(|offset| IVec3::new(offset.x, offset.y, offset.z) == self.normal)
+    }
+}
+
+// Chunk position and position within it of a world voxel coordinate
+pub fn split_world(world: IVec3) -> (IVec3, LocalPos) {
+    let chunk = world.div_euclid(IVec3::splat(CHUNK_SIZE));
+    let local = world.rem_euclid(IVec3::splat(CHUNK_SIZE));
+    (chunk, LocalPos::new(local.x, local.y, local.z))
+}
+
+// Steps through the voxel grid cell by cell (Amanatides & Woo), in voxel units.
+// Returns the first solid cell, the normal of the face entered through, and the
+// distance along the ray.
+pub fn raycast_grid(
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    is_solid: impl Fn(IVec3) -> bool,
+) -> Option<(IVec3, IVec3, f32)> {
+    let direction = direction.normalize_or_zero();
+    if direction == Vec3::ZERO {
+        return None;
+    }
+
+    let mut cell = origin.floor().as_ivec3();
+    // signum() maps 0.0 to 1.0, so axes the ray doesn't move along are zeroed
+    let axis_step = |v: f32| if v == 0.0 { 0 } else { v.signum() as i32 };
+    let step = IVec3::new(axis_step(direction.x), axis_step(direction.y), axis_step(direction.z));
+    let t_delta = direction.abs().recip();
+    let mut t_max = Vec3::ZERO;
+    for axis in 0..3 {
+        t_max[axis] = match step[axis] {
+            0 => f32::INFINITY,
+            s if s > 0 => (cell[axis] as f32 + 1.0 - origin[axis]) * t_delta[axis],
+            _ => (origin[axis] - cell[axis] as f32) * t_delta[axis],
+        };
+    }
+
+    let mut normal = IVec3::ZERO;
+    let mut distance = 0.0;
+    loop {
+        if is_solid(cell) {
+            return Some((cell, normal, distance));
+        }
+
+        let axis = if t_max.x < t_max.y && t_max.x < t_max.z {
+            0
+        } else if t_max.y < t_max.z {
+            1
+        } else {
+            2
+        };
+        distance = t_max[axis];
+        if distance > max_distance {
+            return None;
+        }
+        cell[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+}
+
+// Which voxels stop a ray. Translucent voxels such as water and glass let rays and
+// sight lines through unless blocks_translucent is set.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RayFilter {
+    pub blocks_translucent: bool,
+}
+
+// Queries for any system with a VoxelWorld; they only read chunks. Positions and
+// distances are in world units, and unloaded chunks read as empty.
+impl VoxelWorld<'_, '_> {
+    pub fn blocks_ray(&self, world: IVec3, filter: RayFilter) -> bool {
+        let (chunk, local) = split_world(world);
+        self.chunk(chunk).is_some_and(|chunk| {
+            chunk.occupancy.is_solid(local) || (filter.blocks_translucent && chunk.occupancy.is_translucent(local))
+        })
+    }
+
+    // First voxel the filter stops within max_distance, crossing chunk borders as
+    // it goes. A ray starting inside such a voxel hits it at distance zero.
+    pub fn raycast(&self, origin: Vec3, direction: Vec3, max_distance: f32, filter: RayFilter) -> Option<VoxelHit> {
+        let voxel_size = self.voxel_size();
+        let (world, normal, distance) =
+            raycast_grid(origin / voxel_size, direction, max_distance / voxel_size, |cell| self.blocks_ray(cell, filter))?;
+        let (chunk, local) = split_world(world);
+        let distance = distance * voxel_size;
+        Some(VoxelHit {
+            world,
+            chunk,
+            local,
+            position: origin + direction.normalize() * distance,
+            normal,
+            distance,
+        })
+    }
+
+    // Whether nothing solid lies between two points. A voxel holding either point
+    // blocks the view.
+    pub fn line_of_sight(&self, a: Vec3, b: Vec3) -> bool {
+        self.line_of_sight_with(a, b, RayFilter::default())
+    }
+
+    pub fn line_of_sight_with(&self, a: Vec3, b: Vec3, filter: RayFilter) -> bool {
+        let length = a.distance(b);
+        if length == 0.0 {
+            return !self.blocks_ray((a / self.voxel_size()).floor().as_ivec3(), filter);
+        }
+        self.raycast(a, b - a, length, filter).map_or(true, |hit| hit.distance >= length)
+    }
+
+    // First hit along a path through the points, such as a projectile's arc. The
+    // hit's distance is measured along the whole path.
+    pub fn first_hit_along(&self, points: &[Vec3], filter: RayFilter) -> Option<VoxelHit> {
+        let mut travelled = 0.0;
+        for segment in points.windows(2) {
+            let length = segment[0].distance(segment[1]);
+            if length == 0.0 {
+                continue;
+            }
+            if let Some(mut hit) = self.raycast(segment[0], segment[1] - segment[0], length, filter) {
+                hit.distance += travelled;
+                return Some(hit);
+            }
+            travelled += length;
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+    use std::cell::RefCell;
+    use crate::voxel::{test_world, VoxelChunk};
+    use crate::voxel_types::Voxel;
+
+    fn solid_at(cells: &[IVec3]) -> impl Fn(IVec3) -> bool + '_ {
+        move |cell| cells.contains(&cell)
+    }
+
+    #[test]
+    fn axis_aligned_rays_hit_the_facing_side() {
+        let cells = [IVec3::new(5, 0, 0), IVec3::new(0, 3, 0)];
+
+        let (cell, normal, distance) = raycast_grid(Vec3::splat(0.5), Vec3::X, 32.0, solid_at(&cells)).unwrap();
+        assert_eq!((cell, normal), (IVec3::new(5, 0, 0), IVec3::NEG_X));
+        assert!((distance - 4.5).abs() < 1e-5);
+
+        let (cell, normal, distance) =
+            raycast_grid(Vec3::new(0.5, 10.5, 0.5), Vec3::NEG_Y, 32.0, solid_at(&cells)).unwrap();
+        assert_eq!((cell, normal), (IVec3::new(0, 3, 0), IVec3::Y));
+        assert!((distance - 6.5).abs() < 1e-5);
+
+        // Short of the voxel, and pointing away from it
+        assert!(raycast_grid(Vec3::splat(0.5), Vec3::X, 4.0, solid_at(&cells)).is_none());
+        assert!(raycast_grid(Vec3::splat(0.5), Vec3::NEG_X, 32.0, solid_at(&cells)).is_none());
+    }
+
+    #[test]
+    fn diagonal_ray_steps_face_to_face_across_chunk_borders() {
+        let target = IVec3::new(17, 0, 17);
+        let visited = RefCell::new(Vec::new());
+        let origin = Vec3::new(14.2, 0.5, 14.7);
+        let (cell, normal, distance) = raycast_grid(origin, Vec3::new(1.0, 0.0, 1.0), 32.0, |cell| {
+            visited.borrow_mut().push(cell);
+            cell == target
+        })
+        .unwrap();
+
+        // z crosses 17 before x does, so the ray enters through the -X face
+        assert_eq!((cell, normal), (target, IVec3::NEG_X));
+        assert!((distance - 2.8 * std::f32::consts::SQRT_2).abs() < 1e-4);
+
+        // Every step moves one cell along one axis, so no voxel is skipped
+        let visited = visited.into_inner();
+        assert_eq!(visited.first(), Some(&IVec3::new(14, 0, 14)));
+        for pair in visited.windows(2) {
+            let step = (pair[1] - pair[0]).abs();
+            assert_eq!(step.x + step.y + step.z, 1, "{:?} -> {:?}", pair[0], pair[1]);
+        }
+        let chunks: Vec<IVec3> = visited.iter().map(|cell| split_world(*cell).0).collect();
+        assert!(chunks.contains(&IVec3::ZERO));
+        assert!(chunks.contains(&IVec3::new(0, 0, 1)));
+        assert_eq!(split_world(target), (IVec3::new(1, 0, 1), LocalPos::new(1, 0, 1)));
+    }
+
+    #[test]
+    fn ray_starting_inside_a_voxel_hits_it_at_zero() {
+        let cells = [IVec3::new(2, 1, -1)];
+        let (cell, normal, distance) =
+            raycast_grid(Vec3::new(2.3, 1.7, -0.4), Vec3::new(0.3, -1.0, 0.2), 32.0, solid_at(&cells)).unwrap();
+        assert_eq!((cell, normal), (IVec3::new(2, 1, -1), IVec3::ZERO));
+        assert_eq!(distance, 0.0);
+    }
+
+    #[test]
+    fn rays_reach_voxels_in_negative_chunks() {
+        let cells = [IVec3::new(-17, 0, 0), IVec3::new(-3, -20, -5)];
+
+        let (cell, normal, distance) =
+            raycast_grid(Vec3::new(-0.5, 0.5, 0.5), Vec3::NEG_X, 32.0, solid_at(&cells)).unwrap();
+        assert_eq!((cell, normal), (IVec3::new(-17, 0, 0), IVec3::X));
+        assert!((distance - 16.5).abs() < 1e-5);
+        assert_eq!(split_world(cell), (IVec3::new(-2, 0, 0), LocalPos::new(15, 0, 0)));
+
+        let (cell, normal, _) =
+            raycast_grid(Vec3::new(-2.5, 0.5, -4.5), Vec3::NEG_Y, 32.0, solid_at(&cells)).unwrap();
+        assert_eq!((cell, normal), (IVec3::new(-3, -20, -5), IVec3::Y));
+        assert_eq!(split_world(cell), (IVec3::new(-1, -2, -1), LocalPos::new(13, 12, 11)));
+        assert_eq!(split_world(IVec3::new(-1, -16, 16)), (IVec3::new(-1, -1, 1), LocalPos::new(15, 0, 0)));
+    }
+
+    // One chunk at the origin, so world and local coordinates match: a lone stone
+    // voxel at (2, 2, 2), a 4³ stone block over 8..=11 and a water voxel at (5, 9, 9)
+    // in front of the block's -x face
+    fn ray_world() -> World {
+        let voxel = |x: i32, y: i32, z: i32, color| Voxel {
+            position: Vec3::new(x as f32, y as f32, z as f32),
+            color,
+            kind: 0,
+        };
+        let stone = Color::rgb(0.5, 0.5, 0.52);
+        let mut voxels = vec![voxel(2, 2, 2, stone), voxel(5, 9, 9, Color::rgba(0.15, 0.35, 0.8, 0.55))];
+        for z in 8..12 {
+            for y in 8..12 {
+                for x in 8..12 {
+                    voxels.push(voxel(x, y, z, stone));
+                }
+            }
+        }
+        test_world(vec![VoxelChunk::new(IVec3::ZERO, voxels)])
+    }
+
+    #[test]
+    fn rays_grazing_a_voxel_follow_the_cell_they_run_in() {
+        let mut world = ray_world();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let voxels = state.get(&world);
+
+        // Along the bottom face the ray runs in the voxel's row; along the top face it
+        // runs in the row above
+        let hit = voxels.raycast(Vec3::new(0.5, 2.0, 2.5), Vec3::X, 8.0, RayFilter::default()).unwrap();
+        assert_eq!((hit.world, hit.normal), (IVec3::new(2, 2, 2), IVec3::NEG_X));
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+        assert!(voxels.raycast(Vec3::new(0.5, 3.0, 2.5), Vec3::X, 8.0, RayFilter::default()).is_none());
+        assert!(voxels.line_of_sight(Vec3::new(0.5, 3.0, 2.5), Vec3::new(5.5, 3.0, 2.5)));
+        assert!(!voxels.line_of_sight(Vec3::new(0.5, 2.999, 2.5), Vec3::new(5.5, 2.999, 2.5)));
+
+        // Through the voxel's vertical edge at x 2, z 3: the ray steps into the cell
+        // beside it first, then hits the voxel's -x face at the edge
+        let direction = Vec3::new(1.0, 0.0, -1.0);
+        let hit = voxels.raycast(Vec3::new(1.5, 2.5, 3.5), direction, 8.0, RayFilter::default()).unwrap();
+        assert_eq!((hit.world, hit.normal), (IVec3::new(2, 2, 2), IVec3::NEG_X));
+        assert!((hit.distance - 0.5 * std::f32::consts::SQRT_2).abs() < 1e-5);
+        assert!((hit.position - Vec3::new(2.0, 2.5, 3.0)).length() < 1e-5);
+    }
+
+    #[test]
+    fn rays_inside_solid_matter_hit_where_they_start() {
+        let mut world = ray_world();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let voxels = state.get(&world);
+        let (inside, also_inside) = (Vec3::new(9.5, 9.5, 9.5), Vec3::new(10.5, 10.2, 9.1));
+
+        let hit = voxels.raycast(inside, Vec3::new(0.3, 1.0, -0.2), 8.0, RayFilter::default()).unwrap();
+        assert_eq!((hit.world, hit.normal, hit.distance), (IVec3::new(9, 9, 9), IVec3::ZERO, 0.0));
+        assert!(!voxels.line_of_sight(inside, also_inside));
+        // A single point inside a voxel can't see itself either
+        assert!(!voxels.line_of_sight(inside, inside));
+
+        let hit = voxels.first_hit_along(&[inside, also_inside], RayFilter::default()).unwrap();
+        assert_eq!((hit.world, hit.distance), (IVec3::new(9, 9, 9), 0.0));
+        // From open air, the hit's distance counts the clear segment before it
+        let path = [Vec3::new(9.5, 14.5, 9.5), Vec3::new(9.5, 13.5, 9.5), Vec3::new(9.5, 9.5, 9.5)];
+        let hit = voxels.first_hit_along(&path, RayFilter::default()).unwrap();
+        assert_eq!((hit.world, hit.normal), (IVec3::new(9, 11, 9), IVec3::Y));
+        assert!((hit.distance - 2.5).abs() < 1e-5);
+    }
+
+    #[test]
+    fn water_only_blocks_rays_that_ask_it_to() {
+        let mut world = ray_world();
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let voxels = state.get(&world);
+        let (eye, beyond_water) = (Vec3::new(3.5, 9.5, 9.5), Vec3::new(7.5, 9.5, 9.5));
+        let blocks_water = RayFilter { blocks_translucent: true };
+
+        assert!(voxels.line_of_sight(eye, beyond_water));
+        assert!(!voxels.line_of_sight_with(eye, beyond_water, blocks_water));
+
+        let hit = voxels.raycast(eye, Vec3::X, 16.0, RayFilter::default()).unwrap();
+        assert_eq!(hit.world, IVec3::new(8, 9, 9));
+        assert!((hit.distance - 4.5).abs() < 1e-5);
+        let hit = voxels.raycast(eye, Vec3::X, 16.0, blocks_water).unwrap();
+        assert_eq!((hit.world, hit.normal), (IVec3::new(5, 9, 9), IVec3::NEG_X));
+        assert!((hit.distance - 1.5).abs() < 1e-5);
+
+        let hit = voxels.first_hit_along(&[eye, beyond_water, Vec3::new(7.5, 9.5, 12.5)], blocks_water).unwrap();
+        assert_eq!(hit.world, IVec3::new(5, 9, 9));
+    }
+}
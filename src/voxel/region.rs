@@ -0,0 +1,48 @@
+// src/voxel/region.rs
+use bevy::prelude::*;
+use crate::voxel_types::Voxel;
+use super::collision::cells_between;
+use super::{split_world, LocalPos, VoxelChunk, VoxelWorld, WorldRegion, CHUNK_SIZE};
+
+// Stored voxels of a chunk, exposed and hidden, with their world cells
+pub(super) fn chunk_voxels(chunk: &VoxelChunk) -> impl Iterator<Item = (IVec3, LocalPos, &Voxel)> {
+    let origin = chunk.position * CHUNK_SIZE;
+    chunk.voxels.iter().chain(chunk.hidden_voxels.iter()).map(move |voxel| {
+        let local = LocalPos::from_vec3(voxel.position);
+        (origin + IVec3::new(local.x, local.y, local.z), local, voxel)
+    })
+}
+
+// Region queries over stored voxels, in world units. Only chunks overlapping the
+// region are visited; voxel-less solid cells and unloaded chunks yield nothing.
+impl VoxelWorld<'_, '_> {
+    // World cell, chunk position, cell in the chunk and voxel of each stored voxel
+    // whose cell lies in the box
+    pub fn voxels_in_box(&self, min: Vec3, max: Vec3) -> impl Iterator<Item = (IVec3, IVec3, LocalPos, &Voxel)> + '_ {
+        let voxel_size = self.voxel_size();
+        let region = WorldRegion {
+            min: (min / voxel_size).floor().as_ivec3(),
+            max: (max / voxel_size).floor().as_ivec3(),
+        };
+        self.voxels_in_region(region)
+    }
+
+    // As voxels_in_box, for voxels whose centers lie in the sphere
+    pub fn voxels_in_sphere(&self, center: Vec3, radius: f32) -> impl Iterator<Item = (IVec3, IVec3, LocalPos, &Voxel)> + '_ {
+        let voxel_size = self.voxel_size();
+        self.voxels_in_box(center - radius, center + radius).filter(move |(cell, ..)| {
+            let voxel_center = (cell.as_vec3() + 0.5) * voxel_size;
+            voxel_center.distance_squared(center) <= radius * radius
+        })
+    }
+
+    fn voxels_in_region(&self, region: WorldRegion) -> impl Iterator<Item = (IVec3, IVec3, LocalPos, &Voxel)> + '_ {
+        cells_between(split_world(region.min).0, split_world(region.max).0)
+            .filter_map(move |position| self.chunk(position))
+            .flat_map(move |chunk| {
+                chunk_voxels(chunk)
+                    .filter(move |(cell, ..)| region.contains(*cell))
+                    .map(move |(cell, local, voxel)| (cell, chunk.position, local, voxel))
+            })
+    }
+}
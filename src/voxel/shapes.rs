@@ -0,0 +1,135 @@
+// src/voxel/shapes.rs
+use bevy::prelude::*;
+
+// Cells on a straight line from a to b, both ends included (3D Bresenham). Every
+// step moves one cell along the longest axis and at most one along the others, so
+// diagonal lines stay connected.
+pub fn line(a: IVec3, b: IVec3) -> Vec<IVec3> {
+    let delta = b - a;
+    let length = delta.abs();
+    let step = delta.signum();
+    let major = if length.x >= length.y && length.x >= length.z {
+        0
+    } else if length.y >= length.z {
+        1
+    } else {
+        2
+    };
+    let (minor_a, minor_b) = ((major + 1) % 3, (major + 2) % 3);
+    let steps = length[major];
+
+    let mut cells = Vec::with_capacity(steps as usize + 1);
+    let mut cell = a;
+    let mut error_a = 2 * length[minor_a] - steps;
+    let mut error_b = 2 * length[minor_b] - steps;
+    cells.push(cell);
+    for _ in 0..steps {
+        cell[major] += step[major];
+        if error_a > 0 {
+            cell[minor_a] += step[minor_a];
+            error_a -= 2 * steps;
+        }
+        if error_b > 0 {
+            cell[minor_b] += step[minor_b];
+            error_b -= 2 * steps;
+        }
+        error_a += 2 * length[minor_a];
+        error_b += 2 * length[minor_b];
+        cells.push(cell);
+    }
+    cells
+}
+
+// Axis-aligned rectangle with a and b at opposite corners, both included. When
+// they differ along all three axes, the rectangle lies in a's plane across the
+// axis they differ least along.
+pub fn plane(a: IVec3, b: IVec3) -> Vec<IVec3> {
+    let length = (b - a).abs();
+    let flat = if length.x <= length.y && length.x <= length.z {
+        0
+    } else if length.y <= length.z {
+        1
+    } else {
+        2
+    };
+    let mut b = b;
+    b[flat] = a[flat];
+    let (min, max) = (a.min(b), a.max(b));
+    (min.x..=max.x)
+        .flat_map(|x| (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| IVec3::new(x, y, z))))
+        .collect()
+}
+
+// Cells plane(a, b) covers, without building them
+pub fn plane_area(a: IVec3, b: IVec3) -> i64 {
+    let size = (b - a).abs() + IVec3::ONE;
+    size.x as i64 * size.y as i64 * size.z as i64 / size.min_element() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENDPOINTS: [(IVec3, IVec3); 6] = [
+        (IVec3::new(0, 0, 0), IVec3::new(7, 0, 0)),
+        (IVec3::new(0, 0, 0), IVec3::new(5, 2, -3)),
+        (IVec3::new(-4, 9, 1), IVec3::new(3, -2, 6)),
+        (IVec3::new(2, 2, 2), IVec3::new(-6, -6, -6)),
+        (IVec3::new(0, -8, 0), IVec3::new(1, 3, -1)),
+        (IVec3::new(-17, 0, 5), IVec3::new(-16, 1, 5)),
+    ];
+
+    #[test]
+    fn lines_include_both_ends() {
+        for (a, b) in ENDPOINTS {
+            for (from, to) in [(a, b), (b, a)] {
+                let cells = line(from, to);
+                assert_eq!(cells.first(), Some(&from));
+                assert_eq!(cells.last(), Some(&to));
+                assert_eq!(cells.len() as i32, (to - from).abs().max_element() + 1);
+            }
+        }
+        assert_eq!(line(IVec3::new(3, -1, 2), IVec3::new(3, -1, 2)), vec![IVec3::new(3, -1, 2)]);
+    }
+
+    #[test]
+    fn diagonal_lines_have_no_gaps() {
+        for (a, b) in ENDPOINTS {
+            let cells = line(a, b);
+            for pair in cells.windows(2) {
+                let step = (pair[1] - pair[0]).abs();
+                // One cell along the longest axis, and never more than one along any
+                assert_eq!(step.max_element(), 1, "{:?} -> {:?} on {:?} -> {:?}", pair[0], pair[1], a, b);
+            }
+        }
+        let diagonal: Vec<IVec3> = (0..=4).map(IVec3::splat).collect();
+        assert_eq!(line(IVec3::ZERO, IVec3::splat(4)), diagonal);
+    }
+
+    #[test]
+    fn planes_fill_the_rectangle_between_corners() {
+        let (a, b) = (IVec3::new(-2, 5, 1), IVec3::new(3, 5, -3));
+        let cells = plane(a, b);
+        assert_eq!(cells.len(), 6 * 5);
+        assert_eq!(cells.len() as i64, plane_area(a, b));
+        assert!(cells.contains(&a) && cells.contains(&b));
+        assert!(cells.contains(&IVec3::new(-2, 5, -3)) && cells.contains(&IVec3::new(3, 5, 1)));
+        assert!(cells.iter().all(|cell| cell.y == 5));
+        let mut unique = cells.clone();
+        unique.sort_by_key(|cell| (cell.x, cell.y, cell.z));
+        unique.dedup();
+        assert_eq!(unique.len(), cells.len());
+    }
+
+    #[test]
+    fn plane_between_offset_corners_lies_in_the_first_corners_plane() {
+        // Differs least along z, so the rectangle is flat at a.z
+        let (a, b) = (IVec3::new(0, 0, 4), IVec3::new(-3, 6, 5));
+        let cells = plane(a, b);
+        assert_eq!(cells.len(), 4 * 7);
+        assert_eq!(cells.len() as i64, plane_area(a, b));
+        assert!(cells.iter().all(|cell| cell.z == 4));
+        assert!(cells.contains(&a) && cells.contains(&IVec3::new(-3, 6, 4)));
+        assert_eq!(plane(a, a), vec![a]);
+    }
+}
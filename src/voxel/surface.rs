@@ -0,0 +1,38 @@
+// src/voxel/surface.rs
+use bevy::prelude::*;
+use super::{RayFilter, VoxelWorld, CHUNK_SIZE};
+
+// Terrain surface queries, in world units. The filter says whether translucent
+// voxels such as water count as ground; by default they're skipped.
+impl VoxelWorld<'_, '_> {
+    // Top of the highest voxel in the column holding (x, z), scanning the loaded
+    // chunks from the top down. None when no loaded chunk in the column has one.
+    pub fn surface_height_at(&self, x: f32, z: f32, filter: RayFilter) -> Option<f32> {
+        let voxel_size = self.voxel_size();
+        let (min_chunk_y, max_chunk_y) = self.index.chunk_y_range()?;
+        let column = IVec2::new((x / voxel_size).floor() as i32, (z / voxel_size).floor() as i32);
+        let chunk_column = column.div_euclid(IVec2::splat(CHUNK_SIZE));
+        for chunk_y in (min_chunk_y..=max_chunk_y).rev() {
+            if self.chunk(IVec3::new(chunk_column.x, chunk_y, chunk_column.y)).is_none() {
+                continue;
+            }
+            for y in (chunk_y * CHUNK_SIZE..(chunk_y + 1) * CHUNK_SIZE).rev() {
+                if self.blocks_ray(IVec3::new(column.x, y, column.y), filter) {
+                    return Some((y + 1) as f32 * voxel_size);
+                }
+            }
+        }
+        None
+    }
+
+    // Upward normal of the surface at (x, z), from the heights of the neighboring
+    // columns. A neighbor without a surface counts as level with the center.
+    pub fn surface_normal_at(&self, x: f32, z: f32, filter: RayFilter) -> Option<Vec3> {
+        let voxel_size = self.voxel_size();
+        let center = self.surface_height_at(x, z, filter)?;
+        let height = |dx: f32, dz: f32| self.surface_height_at(x + dx * voxel_size, z + dz * voxel_size, filter).unwrap_or(center);
+        let slope_x = (height(1.0, 0.0) - height(-1.0, 0.0)) / (2.0 * voxel_size);
+        let slope_z = (height(0.0, 1.0) - height(0.0, -1.0)) / (2.0 * voxel_size);
+        Some(Vec3::new(-slope_x, 1.0, -slope_z).normalize())
+    }
+}
@@ -0,0 +1,360 @@
+// src/voxel/vox.rs
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use std::fmt;
+use std::path::Path;
+use super::{split_world, VoxelWorld, CHUNK_SIZE};
+
+// MagicaVoxel 0.99 writes and reads this version
+const VOX_VERSION: i32 = 150;
+// Largest model along any axis; bigger regions are split into several
+const MAX_MODEL_SIZE: i32 = 256;
+// Palette index 0 means empty, leaving 255 colors
+const MAX_VOX_COLORS: usize = 255;
+
+// Box of world voxel coordinates to export, both corners inclusive
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct WorldRegion {
+    pub min: IVec3,
+    pub max: IVec3,
+}
+
+impl WorldRegion {
+    pub fn chunk(position: IVec3) -> Self {
+        let min = position * CHUNK_SIZE;
+        Self {
+            min,
+            max: min + IVec3::splat(CHUNK_SIZE - 1),
+        }
+    }
+
+    pub fn contains(&self, cell: IVec3) -> bool {
+        cell.cmpge(self.min).all() && cell.cmple(self.max).all()
+    }
+
+    fn size(&self) -> IVec3 {
+        self.max - self.min + IVec3::ONE
+    }
+}
+
+#[derive(Debug)]
+pub enum VoxError {
+    Io(std::io::Error),
+    // No loaded voxels inside the region
+    Empty,
+}
+
+impl fmt::Display for VoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VoxError::Io(err) => write!(f, "could not write .vox file: {}", err),
+            VoxError::Empty => write!(f, "no voxels to export"),
+        }
+    }
+}
+
+impl std::error::Error for VoxError {}
+
+// Writes the loaded voxels inside a region as a MagicaVoxel file. MagicaVoxel is
+// Z-up, so world y becomes vox z and world z is flipped into vox y to keep the
+// handedness. Each model of up to 256³ gets a transform node placing it in the scene.
+pub fn export_vox(world: &VoxelWorld, region: WorldRegion, path: &Path) -> Result<(), VoxError> {
+    let size = region.size();
+    // In vox axes, relative to the region
+    let vox_size = IVec3::new(size.x, size.z, size.y);
+
+    let mut colors: Vec<[u8; 4]> = Vec::new();
+    let mut color_counts: HashMap<[u8; 4], usize> = HashMap::default();
+    let mut models: HashMap<IVec3, Vec<([u8; 3], [u8; 4])>> = HashMap::default();
+    let (min_chunk, _) = split_world(region.min);
+    let (max_chunk, _) = split_world(region.max);
+    for x in min_chunk.x..=max_chunk.x {
+        for y in min_chunk.y..=max_chunk.y {
+            for z in min_chunk.z..=max_chunk.z {
+                let Some(chunk) = world.chunk(IVec3::new(x, y, z)) else {
+                    continue;
+                };
+                let chunk_origin = chunk.position * CHUNK_SIZE;
+                for voxel in chunk.voxels.iter().chain(chunk.hidden_voxels.iter()) {
+                    let cell = chunk_origin + voxel.position.as_ivec3();
+                    if cell.cmplt(region.min).any() || cell.cmpgt(region.max).any() {
+                        continue;
+                    }
+                    let offset = cell - region.min;
+                    let vox = IVec3::new(offset.x, size.z - 1 - offset.z, offset.y);
+                    let model = vox / MAX_MODEL_SIZE;
+                    let local = vox % MAX_MODEL_SIZE;
+                    let rgba = voxel.color.as_rgba_u8();
+                    let count = color_counts.entry(rgba).or_insert_with(|| {
+                        colors.push(rgba);
+                        0
+                    });
+                    *count += 1;
+                    let position = [local.x as u8, local.y as u8, local.z as u8];
+                    models.entry(model).or_default().push((position, rgba));
+                }
+            }
+        }
+    }
+    if models.is_empty() {
+        return Err(VoxError::Empty);
+    }
+
+    let palette = quantize(colors, &color_counts);
+    let nearest: HashMap<[u8; 4], u8> =
+        color_counts.keys().map(|color| (*color, nearest_index(&palette, *color))).collect();
+
+    let mut models: Vec<(IVec3, Vec<([u8; 3], [u8; 4])>)> = models.into_iter().collect();
+    models.sort_by_key(|(model, _)| (model.z, model.y, model.x));
+
+    let mut body = Vec::new();
+    for (model, voxels) in &models {
+        let model_size = (vox_size - *model * MAX_MODEL_SIZE).min(IVec3::splat(MAX_MODEL_SIZE));
+        write_chunk(&mut body, b"SIZE", |out| {
+            for axis in model_size.to_array() {
+                write_i32(out, axis);
+            }
+        });
+        write_chunk(&mut body, b"XYZI", |out| {
+            write_i32(out, voxels.len() as i32);
+            for (position, color) in voxels {
+                out.extend_from_slice(position);
+                // Palette indices in XYZI start at 1
+                out.push(nearest[color] + 1);
+            }
+        });
+    }
+
+    // Root transform, a group holding every model, then a transform and shape per model
+    write_chunk(&mut body, b"nTRN", |out| write_transform(out, 0, 1, -1, None));
+    write_chunk(&mut body, b"nGRP", |out| {
+        write_i32(out, 1);
+        write_i32(out, 0);
+        write_i32(out, models.len() as i32);
+        for index in 0..models.len() as i32 {
+            write_i32(out, 2 + 2 * index);
+        }
+    });
+    for (index, (model, _)) in models.iter().enumerate() {
+        let index = index as i32;
+        let model_min = *model * MAX_MODEL_SIZE;
+        let model_size = (vox_size - model_min).min(IVec3::splat(MAX_MODEL_SIZE));
+        // MagicaVoxel positions a model by its center, rounded down
+        let translation = model_min + model_size / 2 - vox_size / 2;
+        write_chunk(&mut body, b"nTRN", |out| write_transform(out, 2 + 2 * index, 3 + 2 * index, 0, Some(translation)));
+        write_chunk(&mut body, b"nSHP", |out| {
+            write_i32(out, 3 + 2 * index);
+            write_i32(out, 0);
+            write_i32(out, 1);
+            write_i32(out, index);
+            write_i32(out, 0);
+        });
+    }
+
+    write_chunk(&mut body, b"RGBA", |out| {
+        for index in 0..256 {
+            out.extend_from_slice(&palette.get(index).copied().unwrap_or([0, 0, 0, 255]));
+        }
+    });
+
+    let mut file = Vec::with_capacity(body.len() + 20);
+    file.extend_from_slice(b"VOX ");
+    write_i32(&mut file, VOX_VERSION);
+    file.extend_from_slice(b"MAIN");
+    write_i32(&mut file, 0);
+    write_i32(&mut file, body.len() as i32);
+    file.extend_from_slice(&body);
+    std::fs::write(path, file).map_err(VoxError::Io)
+}
+
+// Keeps every color when they fit; otherwise the most used ones, with the rest
+// mapped to their nearest
+fn quantize(mut colors: Vec<[u8; 4]>, counts: &HashMap<[u8; 4], usize>) -> Vec<[u8; 4]> {
+    if colors.len() > MAX_VOX_COLORS {
+        warn!("Exporting {} distinct colors; mapping them onto the {} most used", colors.len(), MAX_VOX_COLORS);
+        colors.sort_by_key(|color| std::cmp::Reverse(counts[color]));
+        colors.truncate(MAX_VOX_COLORS);
+    }
+    colors
+}
+
+fn nearest_index(palette: &[[u8; 4]], color: [u8; 4]) -> u8 {
+    let distance = |entry: &[u8; 4]| -> i32 {
+        entry.iter().zip(color).map(|(a, b)| (*a as i32 - b as i32).pow(2)).sum()
+    };
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, entry)| distance(entry))
+        .map_or(0, |(index, _)| index as u8)
+}
+
+fn write_i32(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) {
+    write_i32(out, value.len() as i32);
+    out.extend_from_slice(value.as_bytes());
+}
+
+// Chunk with content and no children
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], content: impl FnOnce(&mut Vec<u8>)) {
+    let mut bytes = Vec::new();
+    content(&mut bytes);
+    out.extend_from_slice(id);
+    write_i32(out, bytes.len() as i32);
+    write_i32(out, 0);
+    out.extend_from_slice(&bytes);
+}
+
+// Transform node with one frame, translated when given
+fn write_transform(out: &mut Vec<u8>, node: i32, child: i32, layer: i32, translation: Option<IVec3>) {
+    write_i32(out, node);
+    write_i32(out, 0);
+    write_i32(out, child);
+    // Reserved id
+    write_i32(out, -1);
+    write_i32(out, layer);
+    write_i32(out, 1);
+    match translation {
+        Some(translation) => {
+            write_i32(out, 1);
+            write_string(out, "_t");
+            write_string(out, &format!("{} {} {}", translation.x, translation.y, translation.z));
+        }
+        None => write_i32(out, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::SystemState;
+    use crate::voxel::{test_world, VoxelChunk};
+    use crate::voxel_types::Voxel;
+
+    struct ParsedVox {
+        sizes: Vec<IVec3>,
+        // Per model, each voxel's position and palette index
+        models: Vec<Vec<([u8; 3], u8)>>,
+        palette: Vec<[u8; 4]>,
+    }
+
+    fn read_i32(bytes: &[u8], at: usize) -> i32 {
+        i32::from_le_bytes(bytes[at..at + 4].try_into().unwrap())
+    }
+
+    // Walks the chunks under MAIN, keeping the ones describing models and colors
+    fn parse_vox(bytes: &[u8]) -> ParsedVox {
+        assert_eq!(&bytes[0..4], b"VOX ");
+        assert_eq!(read_i32(bytes, 4), VOX_VERSION);
+        assert_eq!(&bytes[8..12], b"MAIN");
+        assert_eq!(read_i32(bytes, 12), 0);
+        assert_eq!(read_i32(bytes, 16) as usize, bytes.len() - 20);
+
+        let mut parsed = ParsedVox {
+            sizes: Vec::new(),
+            models: Vec::new(),
+            palette: Vec::new(),
+        };
+        let mut at = 20;
+        while at < bytes.len() {
+            let id = &bytes[at..at + 4];
+            let length = read_i32(bytes, at + 4) as usize;
+            assert_eq!(read_i32(bytes, at + 8), 0, "chunks have no children");
+            let content = &bytes[at + 12..at + 12 + length];
+            match id {
+                b"SIZE" => parsed.sizes.push(IVec3::new(read_i32(content, 0), read_i32(content, 4), read_i32(content, 8))),
+                b"XYZI" => {
+                    let count = read_i32(content, 0) as usize;
+                    assert_eq!(length, 4 + count * 4);
+                    let voxels = content[4..]
+                        .chunks(4)
+                        .map(|entry| ([entry[0], entry[1], entry[2]], entry[3]))
+                        .collect();
+                    parsed.models.push(voxels);
+                }
+                b"RGBA" => {
+                    assert_eq!(length, 256 * 4);
+                    parsed.palette = content.chunks(4).map(|entry| entry.try_into().unwrap()).collect();
+                }
+                _ => {}
+            }
+            at += 12 + length;
+        }
+        assert_eq!(at, bytes.len());
+        parsed
+    }
+
+    #[test]
+    fn exported_region_reads_back_with_its_voxels() {
+        let (red, blue) = (Color::rgb(1.0, 0.0, 0.0), Color::rgb(0.0, 0.0, 1.0));
+        // A 4×2×4 block straddling the border between chunks -1 and 0 along x, red
+        // below and blue above, plus a voxel outside the region
+        let (mut left, mut right) = (Vec::new(), Vec::new());
+        for x in -1..=2 {
+            for y in 0..2 {
+                for z in 0..4 {
+                    let color = if y == 0 { red } else { blue };
+                    let local = IVec3::new(x, y, z).rem_euclid(IVec3::splat(CHUNK_SIZE));
+                    let chunk = if x < 0 { &mut left } else { &mut right };
+                    chunk.push(Voxel {
+                        position: local.as_vec3(),
+                        color,
+                        kind: 1,
+                    });
+                }
+            }
+        }
+        right.push(Voxel {
+            position: Vec3::new(8.0, 8.0, 8.0),
+            color: red,
+            kind: 1,
+        });
+        let mut world = test_world(vec![
+            VoxelChunk::new(IVec3::new(-1, 0, 0), left),
+            VoxelChunk::new(IVec3::ZERO, right),
+        ]);
+
+        let path = std::env::temp_dir().join(format!("worldvox-export-{}.vox", std::process::id()));
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let region = WorldRegion {
+            min: IVec3::new(-1, 0, 0),
+            max: IVec3::new(2, 1, 3),
+        };
+        export_vox(&state.get(&world), region, &path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let parsed = parse_vox(&bytes);
+
+        // World y is vox z
+        assert_eq!(parsed.sizes, vec![IVec3::new(4, 4, 2)]);
+        assert_eq!(parsed.models.len(), 1);
+        let voxels = &parsed.models[0];
+        assert_eq!(voxels.len(), 4 * 2 * 4);
+        let mut positions: Vec<[u8; 3]> = voxels.iter().map(|(position, _)| *position).collect();
+        positions.sort();
+        positions.dedup();
+        assert_eq!(positions.len(), voxels.len());
+
+        let color_of = |index: u8| parsed.palette[index as usize - 1];
+        for (position, index) in voxels {
+            assert!(position[0] < 4 && position[1] < 4 && position[2] < 2);
+            let expected = if position[2] == 0 { red } else { blue };
+            assert_eq!(color_of(*index), expected.as_rgba_u8());
+        }
+        // World (-1, 1, 0) sits at the region's min x and far vox y, one up
+        assert!(voxels.iter().any(|(position, index)| *position == [0, 3, 1] && color_of(*index) == blue.as_rgba_u8()));
+    }
+
+    #[test]
+    fn region_without_voxels_is_an_error() {
+        let mut world = test_world(Vec::new());
+        let mut state = SystemState::<VoxelWorld>::new(&mut world);
+        let path = std::env::temp_dir().join(format!("worldvox-empty-{}.vox", std::process::id()));
+        let result = export_vox(&state.get(&world), WorldRegion::chunk(IVec3::ZERO), &path);
+        assert!(matches!(result, Err(VoxError::Empty)));
+        assert!(!path.exists());
+    }
+}
@@ -1,29 +1,208 @@
 // src/voxel_types.rs
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
 #[derive(Component, Debug, Clone)]
 pub struct Voxel {
     pub position: Vec3,
     pub color: Color,
+    // Index into the VoxelTypeRegistry
+    pub kind: u16,
+}
+
+impl Voxel {
+    // Translucent voxels are drawn in the transparent pass and don't occlude
+    pub fn is_translucent(&self) -> bool {
+        self.color.a() < 1.0
+    }
+}
+
+pub const KIND_PLAIN: u16 = 0;
+pub const KIND_GRASS: u16 = 1;
+pub const KIND_DIRT: u16 = 2;
+pub const KIND_STONE: u16 = 3;
+
+// Atlas tile indices, counted row by row from the top left tile
+#[derive(Clone, Copy, Debug)]
+pub struct FaceTiles {
+    pub top: u32,
+    pub side: u32,
+    pub bottom: u32,
+}
+
+impl FaceTiles {
+    pub const fn uniform(tile: u32) -> Self {
+        Self { top: tile, side: tile, bottom: tile }
+    }
+
+    // Faces are ordered like FACE_NEIGHBORS
+    pub fn for_face(&self, face: usize) -> u32 {
+        match face {
+            2 => self.top,
+            3 => self.bottom,
+            _ => self.side,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VoxelType {
+    pub name: &'static str,
+    pub tiles: FaceTiles,
+}
+
+#[derive(Resource, Clone)]
+pub struct VoxelTypeRegistry {
+    types: Vec<VoxelType>,
+}
+
+impl Default for VoxelTypeRegistry {
+    fn default() -> Self {
+        Self {
+            types: vec![
+                VoxelType { name: "plain", tiles: FaceTiles::uniform(0) },
+                VoxelType { name: "grass", tiles: FaceTiles { top: 1, side: 2, bottom: 3 } },
+                VoxelType { name: "dirt", tiles: FaceTiles::uniform(3) },
+                VoxelType { name: "stone", tiles: FaceTiles::uniform(4) },
+            ],
+        }
+    }
+}
+
+impl VoxelTypeRegistry {
+    pub fn register(&mut self, voxel_type: VoxelType) -> u16 {
+        self.types.push(voxel_type);
+        (self.types.len() - 1) as u16
+    }
+
+    // Unknown kinds render as the plain type
+    pub fn get(&self, kind: u16) -> &VoxelType {
+        self.types.get(kind as usize).unwrap_or(&self.types[0])
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RenderMode {
+    Billboards,
+    // One camera-facing mesh per chunk, oriented in the vertex shader
+    BatchedBillboards,
+    CubeMesh,
+    Instanced,
+    // One dot per voxel from a point mesh per chunk
+    Points,
+    // Batched discs lying in each voxel's surface plane instead of facing the camera
+    Splats,
+}
+
+impl RenderMode {
+    // In cycling order
+    pub const ALL: [RenderMode; 6] = [
+        RenderMode::Billboards,
+        RenderMode::BatchedBillboards,
+        RenderMode::CubeMesh,
+        RenderMode::Instanced,
+        RenderMode::Points,
+        RenderMode::Splats,
+    ];
+
+    pub fn next(self) -> Self {
+        match self {
+            RenderMode::Billboards => RenderMode::BatchedBillboards,
+            RenderMode::BatchedBillboards => RenderMode::CubeMesh,
+            RenderMode::CubeMesh => RenderMode::Instanced,
+            RenderMode::Instanced => RenderMode::Points,
+            RenderMode::Points => RenderMode::Splats,
+            RenderMode::Splats => RenderMode::Billboards,
+        }
+    }
 }
 
 #[derive(Resource)]
 pub struct VoxelRenderSettings {
+    pub render_mode: RenderMode,
+    // Merge coplanar same-colored faces when building cube meshes
+    pub greedy_meshing: bool,
+    // How much baked ambient occlusion darkens cube mesh corners; 0 disables it
+    pub ao_strength: f32,
+    // Brightness of voxels baked sunlight doesn't reach, as a fraction of full
+    // sunlight; 1 draws everything fully lit
+    pub light_ambient: f32,
+    // Draw points as small quads; PointList topology is always one pixel in wgpu
+    pub point_quads: bool,
+    // Base level size of the billboard circle texture, rounded up to a power of two
+    pub circle_texture_size: u32,
+    // Width of the circle's feathered edge in base level pixels
+    pub circle_edge_softness: f32,
     pub debug_mode: bool,
     pub voxel_size: f32,
     pub render_distance: f32,
+    // Color voxels blend toward with distance
+    pub fog_color: Color,
+    // Fraction of the render distance where fog begins
+    pub fog_start: f32,
+    // Fraction of the render distance, at its far end, over which voxels dither out
+    pub fade_band: f32,
+    // Edge length of the directional light's shadow map in texels
+    pub shadow_map_size: usize,
+    pub shadow_cascades: usize,
+    // Distance from the camera where shadows end
+    pub shadow_distance: f32,
+    // Give billboard modes a coarse, invisible mesh per chunk that casts shadows
+    pub billboard_shadow_proxies: bool,
     pub show_chunk_bounds: bool,
+    // Draw cube meshes as wireframes
+    pub wireframe: bool,
+    // Diagnostics panel and crosshair target readout. F3 cycles hidden, compact
+    // and extended.
     pub show_diagnostics: bool,
+    // Adds culling, meshing, memory and per-mode timing sections to the panel, and
+    // the frame time graph
+    pub extended_diagnostics: bool,
+    // Texture atlas for cube meshes; flat vertex colors when None
+    pub atlas: Option<Handle<Image>>,
+    // Edge length of one square atlas tile in pixels
+    pub atlas_tile_size: u32,
+    // Hide chunks outside the view frustum; Ctrl+1 toggles it
+    pub frustum_culling: bool,
+    // Hide chunks past the render distance; Ctrl+2 toggles it
+    pub distance_culling: bool,
+    // Skip voxels with no exposed face; Ctrl+3 toggles it
+    pub voxel_occlusion: bool,
+    // Hide chunks the camera can't reach through empty space; Ctrl+4 toggles it
+    pub chunk_occlusion: bool,
 }
 
 impl Default for VoxelRenderSettings {
     fn default() -> Self {
         Self {
+            render_mode: RenderMode::Billboards,
+            greedy_meshing: true,
+            ao_strength: 0.5,
+            light_ambient: 0.2,
+            point_quads: true,
+            circle_texture_size: 256,
+            circle_edge_softness: 1.5,
             debug_mode: false,
             voxel_size: 1.0,
             render_distance: 100.0,
+            // Matches the default sky horizon
+            fog_color: Color::rgb(0.7, 0.8, 0.95),
+            fog_start: 0.5,
+            fade_band: 0.1,
+            shadow_map_size: 2048,
+            shadow_cascades: 4,
+            shadow_distance: 100.0,
+            billboard_shadow_proxies: true,
             show_chunk_bounds: false,
+            wireframe: false,
             show_diagnostics: true,
+            extended_diagnostics: false,
+            atlas: None,
+            atlas_tile_size: 8,
+            frustum_culling: true,
+            distance_culling: true,
+            voxel_occlusion: true,
+            chunk_occlusion: true,
         }
     }
 }
\ No newline at end of file